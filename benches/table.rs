@@ -0,0 +1,67 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
+
+use desynced_exchange::{
+    Exchange,
+    Str,
+    error::LoadError,
+    dumper::{dump_blueprint, dump_blueprint_with, DumpOptions},
+    loader::load_blueprint,
+    value::{Key, Value, Table},
+};
+
+type ValueExchange = Exchange<Option<Value>>;
+
+/// A table with `len` named keys and `len` sparse integer keys,
+/// all of them ending up in the assoc part.
+fn wide_table(len: i32) -> Value {
+    Value::Table(Table::from_iter((0 .. len).flat_map(|index| [
+        ( Key::Name(Str::from(format!("key_{index}").as_str())),
+            Value::Integer(index) ),
+        ( Key::Index(index * 7 - len),
+            Value::Boolean(index % 2 == 0) ),
+    ])))
+}
+
+fn bench_dump(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dump");
+    for len in [16, 256, 4096] {
+        let value = wide_table(len);
+        group.bench_with_input(BenchmarkId::from_parameter(len), &value,
+            |b, value| b.iter(|| dump_blueprint::<_, Value>(
+                ValueExchange::Blueprint(Some(value.clone())) ).unwrap()) );
+    }
+    group.finish();
+}
+
+fn bench_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+    for len in [16, 256, 4096] {
+        let exchange = dump_blueprint::<_, Value>(
+            ValueExchange::Blueprint(Some(wide_table(len))) ).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(len), &exchange,
+            |b, exchange| b.iter(||
+                load_blueprint::<Value, Value, LoadError>(exchange).unwrap() ));
+    }
+    group.finish();
+}
+
+/// Build with `--features parallel` to compress the chunks in parallel.
+fn bench_compress(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compress");
+    group.sample_size(10);
+    let value = wide_table(1 << 16);
+    for chunk_size in [None, Some(1 << 16), Some(1 << 18)] {
+        let mut options = DumpOptions::default();
+        options.compression_chunk_size = chunk_size;
+        let id = chunk_size.map_or_else(
+            || String::from("whole"), |size| size.to_string() );
+        group.bench_with_input(BenchmarkId::from_parameter(id), &value,
+            |b, value| b.iter(|| dump_blueprint_with::<_, Value>(
+                ValueExchange::Blueprint(Some(value.clone())), &options
+            ).unwrap()) );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_dump, bench_load, bench_compress);
+criterion_main!(benches);
@@ -0,0 +1,142 @@
+//! `#[derive(FromTable)]` for the `desynced-exchange` crate;
+//! see `desynced_exchange::value::TryFromTable`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, format_ident};
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, Field, LitStr,
+};
+
+/// Implement `TryFromTable` for a struct with named fields.
+///
+/// Fields are only matched with string keys;
+/// integer keys and the array part are not supported.
+///
+/// Field attributes:
+/// * `#[table(key = "name")]` — take the field from this key
+///   instead of the field name;
+/// * `#[table(extra)]` — collect the items not matching
+///   other fields instead of failing on them.
+#[proc_macro_derive(FromTable, attributes(table))]
+pub fn derive_from_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct FieldSpec<'f> {
+    field: &'f Field,
+    key: String,
+    extra: bool,
+}
+
+fn parse_field(field: &Field) -> syn::Result<FieldSpec<'_>> {
+    let Some(ident) = &field.ident else {
+        return Err(syn::Error::new_spanned(field,
+            "only named fields are supported" ));
+    };
+    let mut spec = FieldSpec {
+        field, key: ident.to_string(), extra: false };
+    for attr in &field.attrs {
+        if !attr.path().is_ident("table") { continue; }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                spec.key = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else if meta.path.is_ident("extra") {
+                spec.extra = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown table attribute"))
+            }
+        })?;
+    }
+    Ok(spec)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(input,
+            "only structs are supported" ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(input,
+            "only structs with named fields are supported" ));
+    };
+    let specs = fields.named.iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let mut extras = specs.iter().filter(|spec| spec.extra);
+    let extra = extras.next();
+    if let Some(spec) = extras.next() {
+        return Err(syn::Error::new_spanned(spec.field,
+            "only one field can collect extra items" ));
+    }
+
+    let krate = quote!(::desynced_exchange);
+    let regular = specs.iter()
+        .filter(|spec| !spec.extra)
+        .collect::<Vec<_>>();
+    let vars = regular.iter()
+        .map(|spec| format_ident!("field_{}",
+            spec.field.ident.as_ref().unwrap_or_else(|| unreachable!()) ))
+        .collect::<Vec<_>>();
+    let keys = regular.iter().map(|spec| &spec.key).collect::<Vec<_>>();
+    let idents = regular.iter().map(|spec| &spec.field.ident);
+    let types = regular.iter().map(|spec| &spec.field.ty);
+
+    let unmatched = if extra.is_some() {
+        quote!( extra.push((key, value)); )
+    } else {
+        quote!( return ::core::result::Result::Err(
+            #krate::error::LoadError::from(::std::format!(
+                "unexpected table key {:?}", key )) ); )
+    };
+    let extra_decl = extra.map(|_spec| quote!(
+        let mut extra = ::std::vec::Vec::<(
+            #krate::value::Key, #krate::value::Value )>::new();
+    ));
+    let extra_init = extra.map(|spec| {
+        let ident = &spec.field.ident;
+        quote!( #ident: ::core::iter::FromIterator::from_iter(extra), )
+    });
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::value::TryFromTable
+        for #name #type_generics #where_clause {
+            fn try_from_table(table: #krate::value::Table)
+            -> ::core::result::Result<Self, #krate::error::LoadError>
+            {
+                #( let mut #vars = ::core::option::Option::None; )*
+                #extra_decl
+                for (key, value) in table {
+                    match key.as_name() {
+                        #( ::core::option::Option::Some(#keys) => {
+                            #vars = ::core::option::Option::Some(
+                                #krate::value::TableField::from_value(value)
+                                .map_err(|error| #krate::value::error_in_field(
+                                    #keys, &error ))? );
+                        }, )*
+                        _ => { #unmatched },
+                    }
+                }
+                ::core::result::Result::Ok(Self {
+                    #( #idents: match #vars {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None =>
+                            <#types as #krate::value::TableField>::missing()
+                            .ok_or_else(|| #krate::error::LoadError::from(
+                                ::std::format!("missing table key {:?}", #keys)
+                            ))?,
+                    }, )*
+                    #extra_init
+                })
+            }
+        }
+    })
+}
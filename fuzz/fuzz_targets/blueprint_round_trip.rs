@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use desynced_exchange::{
+    Exchange,
+    blueprint::{
+        Blueprint, Behavior, LoadOptions,
+        dump_blueprint, load_blueprint_with,
+    },
+};
+
+fuzz_target!(|blueprint: Exchange<Blueprint, Behavior>| {
+    // An arbitrary structure does not necessarily survive the first
+    // round trip unchanged, but the result of loading should.
+    let Ok(exchange) = dump_blueprint(blueprint) else { return };
+    let Ok(reloaded) = load_blueprint_with(&exchange, LoadOptions::lenient())
+        else { return };
+    let exchange = dump_blueprint(reloaded)
+        .expect("loaded blueprint should dump");
+    let reloaded = load_blueprint_with(&exchange, LoadOptions::lenient())
+        .expect("dumped blueprint should load");
+    let redumped = dump_blueprint(reloaded)
+        .expect("loaded blueprint should dump");
+    assert_eq!(exchange, redumped);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use desynced_exchange::{
+    error::LoadError,
+    loader::load_blueprint,
+    value::Value,
+};
+
+fuzz_target!(|exchange: &str| {
+    let _ = load_blueprint::<Value, Value, LoadError>(exchange);
+});
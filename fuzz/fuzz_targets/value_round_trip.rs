@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use desynced_exchange::{
+    Exchange,
+    error::LoadError,
+    dumper::dump_blueprint,
+    loader::load_blueprint,
+    value::Value,
+};
+
+fuzz_target!(|value: Exchange<Option<Value>>| {
+    let Ok(exchange) = dump_blueprint(value) else { return };
+    let reloaded = load_blueprint::<Value, Value, LoadError>(&exchange)
+        .expect("dumped value should load");
+    let redumped = dump_blueprint(reloaded)
+        .expect("loaded value should dump");
+    // comparing strings rather than values to tolerate NaNs
+    assert_eq!(exchange, redumped);
+});
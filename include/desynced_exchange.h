@@ -0,0 +1,44 @@
+/* C interface of the desynced-exchange library
+ * (built with the `capi` feature, see `src/capi.rs`).
+ *
+ * Every function returns one of the DSE_* codes and,
+ * unless the code is DSE_NULL_ARGUMENT, stores either the result
+ * or an error message in *output. The stored string is owned
+ * by the caller and should be released with dse_free.
+ */
+
+#ifndef DESYNCED_EXCHANGE_H
+#define DESYNCED_EXCHANGE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DSE_OK 0
+#define DSE_NULL_ARGUMENT 1
+/* The input is not valid UTF-8. */
+#define DSE_INVALID_UTF8 2
+/* The exchange string cannot be decoded. */
+#define DSE_LOAD_ERROR 3
+/* The JSON cannot be parsed or produced. */
+#define DSE_JSON_ERROR 4
+/* The blueprint cannot be encoded. */
+#define DSE_DUMP_ERROR 5
+/* The library panicked; this is a bug. */
+#define DSE_PANIC 6
+
+/* Decode the exchange string into JSON. */
+int dse_decode(const char *exchange, char **output);
+
+/* Encode the JSON into an exchange string. */
+int dse_encode(const char *json, char **output);
+
+/* Release a string stored by the other functions.
+ * Null pointers are ignored. */
+void dse_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DESYNCED_EXCHANGE_H */
@@ -0,0 +1,151 @@
+//! Aggregate statistics over collections of exchange strings.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    value::Value,
+    loader::{decode, decompress::decompress_limited, Limits, Warnings},
+    blueprint::{build_value, Blueprint, Behavior, LoadOptions, OpRef},
+};
+
+/// Statistics gathered by [`corpus_report`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub struct CorpusReport {
+    /// Number of exchange strings examined.
+    pub exchanges: usize,
+    /// Number of exchange strings that could not be loaded;
+    /// they are not counted anywhere else.
+    pub failed: usize,
+    pub blueprints: usize,
+    /// Number of behaviors, including behaviors of blueprint components
+    /// and subroutines.
+    pub behaviors: usize,
+    /// How many instructions use each operation.
+    pub op_frequency: BTreeMap<OpRef, usize>,
+    /// Average number of instructions in a behavior.
+    pub mean_instruction_count: Option<f64>,
+    /// Number of exchange strings by their length,
+    /// rounded down to a power of two.
+    pub length_histogram: BTreeMap<usize, usize>,
+    /// Number of exchange strings with compressed data.
+    pub compressed: usize,
+    /// Average ratio of the length of the binary data
+    /// to the length of the exchange string.
+    pub mean_compression_ratio: Option<f64>,
+}
+
+/// Load every exchange string and collect statistics about them.
+#[must_use]
+pub fn corpus_report<'s, I>(exchanges: I) -> CorpusReport
+where I: IntoIterator<Item=&'s str>
+{
+    let mut report = CorpusReport::default();
+    let mut instruction_count: usize = 0;
+    let mut compression_ratio_sum: f64 = 0.0;
+    for exchange in exchanges {
+        report.exchanges += 1;
+        let Ok((loaded, data_len, compressed)) = load(exchange) else {
+            report.failed += 1;
+            continue;
+        };
+        let mut behaviors = Vec::new();
+        match loaded {
+            Exchange::Blueprint(blueprint) => {
+                report.blueprints += 1;
+                behaviors.extend( blueprint.components.into_iter()
+                    .filter_map(|component| component.behavior) );
+            },
+            Exchange::Behavior(behavior) => behaviors.push(behavior),
+        }
+        while let Some(behavior) = behaviors.pop() {
+            let Behavior { instructions, subroutines, .. } = behavior;
+            report.behaviors += 1;
+            instruction_count += instructions.len();
+            for instruction in instructions {
+                *report.op_frequency.entry(instruction.operation)
+                    .or_default() += 1;
+            }
+            behaviors.extend(subroutines);
+        }
+        let length_bucket = exchange.len().checked_ilog2()
+            .map_or(0, |log| 1 << log);
+        *report.length_histogram.entry(length_bucket).or_default() += 1;
+        if compressed {
+            report.compressed += 1;
+        }
+        compression_ratio_sum += data_len as f64 / exchange.len() as f64;
+    }
+    let loaded = report.exchanges - report.failed;
+    if report.behaviors > 0 {
+        report.mean_instruction_count = Some(
+            instruction_count as f64 / report.behaviors as f64 );
+    }
+    if loaded > 0 {
+        report.mean_compression_ratio = Some(
+            compression_ratio_sum / loaded as f64 );
+    }
+    report
+}
+
+/// Load the exchange string, decoding it only once.
+/// Returns the structure along with the length of the binary data
+/// and whether it was compressed.
+fn load(exchange: &str)
+-> Result<(Exchange<Blueprint, Behavior>, usize, bool), LoadError>
+{
+    let (data, compressed) = decompress_limited( exchange,
+        Limits::default().max_data_len, true )?;
+    let options = LoadOptions::default();
+    let decode_options = options.decode_options();
+    let mut warnings = Warnings::new();
+    let data_len = data.as_ref().map_mono(Vec::len).unwrap();
+    let value = match data.as_deref() {
+        Exchange::Blueprint(data) => Exchange::Blueprint(
+            decode::<Value>(data, decode_options, &mut warnings)? ),
+        Exchange::Behavior(data) => Exchange::Behavior(
+            decode::<Value>(data, decode_options, &mut warnings)? ),
+    };
+    Ok((build_value(value, options)?, data_len, compressed))
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::test::{EXCHANGE_BEHAVIOR_2, EXCHANGE_BEHAVIOR_4_SUB};
+
+use super::corpus_report;
+
+#[test]
+fn test_corpus_report() {
+    let report = corpus_report([
+        EXCHANGE_BEHAVIOR_2, EXCHANGE_BEHAVIOR_4_SUB, "DSB0garbage" ]);
+    assert_eq!(report.exchanges, 3);
+    assert_eq!(report.failed, 1);
+    assert_eq!(report.blueprints, 0);
+    assert!(report.behaviors >= 2);
+    assert_eq!(report.length_histogram.values().sum::<usize>(), 2);
+    let op_count: usize = report.op_frequency.values().sum();
+    let mean = report.mean_instruction_count.unwrap();
+    assert!((mean * report.behaviors as f64 - op_count as f64).abs() < 1e-9);
+    assert!(report.mean_compression_ratio.unwrap() > 0.0);
+    let info = crate::loader::verify(
+        EXCHANGE_BEHAVIOR_2, crate::loader::Limits::default() ).unwrap();
+    let report_2 = corpus_report([EXCHANGE_BEHAVIOR_2]);
+    assert_eq!(report_2.compressed, usize::from(info.compressed));
+    assert_eq!( report_2.mean_compression_ratio,
+        Some(info.data_len as f64 / EXCHANGE_BEHAVIOR_2.len() as f64) );
+    let json = serde_json::to_string(&report).unwrap();
+    assert!(json.contains("\"op_frequency\""));
+    let empty = corpus_report([]);
+    assert_eq!(empty.mean_instruction_count, None);
+    assert_eq!(empty.mean_compression_ratio, None);
+}
+
+}
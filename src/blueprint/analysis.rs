@@ -0,0 +1,254 @@
+//! Static checks of behavior instructions.
+//!
+//! The encoding does not tell inputs from outputs,
+//! so the direction of each argument is taken from an [`OpCatalog`].
+//! Arguments of operations that are not in the catalog
+//! are counted as reads.
+
+use std::collections::BTreeSet;
+
+use crate::Str;
+
+use super::{Behavior, Instruction, Operand, Jump, Place, OpCatalog, ArgKind};
+
+/// Instructions that read and write a variable or a register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PlaceUsage {
+    pub place: Place,
+    /// `0`-based positions of instructions reading the place.
+    pub reads: Vec<usize>,
+    /// `0`-based positions of instructions writing the place.
+    pub writes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UsageReport {
+    /// Variables and registers, in the order of their first use.
+    pub places: Vec<PlaceUsage>,
+    /// `1`-based indices of parameters that no instruction refers to.
+    pub unused_parameters: Vec<i32>,
+    /// Variables read by an instruction (given by its `0`-based position)
+    /// that can be reached from the start without writing them first.
+    pub undefined_reads: Vec<(usize, Str)>,
+}
+
+/// Collect the usage of places in the instructions of the behavior.
+///
+/// Subroutines are not included; they can be analyzed separately.
+#[must_use]
+pub fn variable_usage(behavior: &Behavior, catalog: &OpCatalog)
+-> UsageReport
+{
+    let instructions: Vec<InstructionUsage> = behavior.instructions.iter()
+        .enumerate()
+        .map(|(index, instruction)|
+            InstructionUsage::new(index, instruction, catalog) )
+        .collect();
+    let mut report = UsageReport::default();
+    for (index, usage) in instructions.iter().enumerate() {
+        for place in &usage.reads {
+            report.place_mut(place).reads.push(index);
+        }
+        for place in &usage.writes {
+            report.place_mut(place).writes.push(index);
+        }
+    }
+    let used: BTreeSet<i32> = instructions.iter()
+        .flat_map(|usage| usage.parameters.iter().copied())
+        .collect();
+    report.unused_parameters = (1 ..= behavior.parameters.len())
+        .filter_map(|index| i32::try_from(index).ok())
+        .filter(|index| !used.contains(index))
+        .collect();
+    let defined = defined_variables(&instructions);
+    for (index, usage) in instructions.iter().enumerate() {
+        let Some(ref defined) = defined[index] else { continue };
+        for place in &usage.reads {
+            if let Place::Variable(ref name) = *place {
+                if !defined.contains(name) {
+                    report.undefined_reads.push((index, name.clone()));
+                }
+            }
+        }
+    }
+    report
+}
+
+impl UsageReport {
+    fn place_mut(&mut self, place: &Place) -> &mut PlaceUsage {
+        let index = match self.places.iter()
+            .position(|usage| usage.place == *place)
+        {
+            Some(index) => index,
+            None => {
+                self.places.push(PlaceUsage {
+                    place: place.clone(),
+                    reads: Vec::new(), writes: Vec::new(),
+                });
+                self.places.len() - 1
+            },
+        };
+        &mut self.places[index]
+    }
+}
+
+struct InstructionUsage {
+    reads: Vec<Place>,
+    writes: Vec<Place>,
+    parameters: Vec<i32>,
+    /// `0`-based positions of the instructions that can follow.
+    successors: Vec<usize>,
+}
+
+impl InstructionUsage {
+
+    fn new(index: usize, instruction: &Instruction, catalog: &OpCatalog)
+    -> Self
+    {
+        let mut this = Self {
+            reads: Vec::new(), writes: Vec::new(),
+            parameters: Vec::new(), successors: Vec::new(),
+        };
+        this.add_jump(index, &instruction.next);
+        let info = catalog.get_ref(&instruction.operation);
+        for (arg_index, arg) in instruction.args.iter().enumerate() {
+            let kind = info.and_then(|info| info.args.get(arg_index))
+                .map(|arg_info| arg_info.kind);
+            let mut operand = arg.clone();
+            let _converted = match kind {
+                Some(ArgKind::Exec) => operand.make_jump(),
+                Some(ArgKind::In | ArgKind::Out) => operand.make_place(),
+                None => Ok(()),
+            };
+            match operand {
+                Operand::Jump(ref jump) => this.add_jump(index, jump),
+                // possibly a parameter
+                Operand::UnknownIndex(parameter) |
+                Operand::Place(Some(Place::Parameter(parameter))) =>
+                    this.parameters.push(parameter),
+                Operand::Place(Some(place)) => match kind {
+                    Some(ArgKind::Out) => this.writes.push(place),
+                    _ => this.reads.push(place),
+                },
+                _ => (),
+            }
+        }
+        this
+    }
+
+    fn add_jump(&mut self, index: usize, jump: &Jump) {
+        match *jump {
+            Jump::Next => self.successors.push(index + 1),
+            // unresolved labels (see `Behavior::resolve_labels`)
+            // are not followed
+            Jump::Return | Jump::Label(_) => (),
+            Jump::Jump(target) => {
+                if let Some(target) = target.checked_sub(1)
+                    .and_then(|target| usize::try_from(target).ok())
+                {
+                    self.successors.push(target);
+                }
+            },
+        }
+    }
+
+}
+
+/// Variables written on every path from the start
+/// to each instruction; `None` for unreachable instructions.
+fn defined_variables(instructions: &[InstructionUsage])
+-> Vec<Option<BTreeSet<Str>>>
+{
+    let mut defined: Vec<Option<BTreeSet<Str>>> =
+        vec![None; instructions.len()];
+    if instructions.is_empty() {
+        return defined;
+    }
+    defined[0] = Some(BTreeSet::new());
+    let mut queue = vec![0];
+    while let Some(index) = queue.pop() {
+        let Some(mut output) = defined[index].clone() else { continue };
+        let usage = &instructions[index];
+        output.extend(usage.writes.iter().filter_map(|place| match *place {
+            Place::Variable(ref name) => Some(name.clone()),
+            _ => None,
+        }));
+        for &successor in &usage.successors {
+            let Some(slot) = defined.get_mut(successor) else { continue };
+            let changed = match *slot {
+                None => {
+                    *slot = Some(output.clone());
+                    true
+                },
+                Some(ref mut input) => {
+                    let len = input.len();
+                    input.retain(|name| output.contains(name));
+                    input.len() != len
+                },
+            };
+            if changed {
+                queue.push(successor);
+            }
+        }
+    }
+    defined
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::Str;
+
+use super::{
+    super::{Behavior, Place, Register, OpCatalog, OpInfo, ArgInfo, ArgKind},
+    variable_usage,
+};
+
+#[test]
+fn test_variable_usage() {
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        parameters: [
+            (name: "target", is_output: false),
+            (name: "unused", is_output: true),
+        ],
+        instructions: [
+            (op: "check_number", args: [Jump(4), Variable("A")]),
+            (op: "set_reg", args: [Parameter(1), Variable("A")]),
+            (op: "set_reg", args: [Variable("A"), Register(Signal)],
+                next: Return),
+            (op: "set_reg", args: [Variable("B"), Variable("C")],
+                next: Jump(3)),
+        ],
+    )"#).unwrap();
+    let catalog = OpCatalog::from_iter([
+        ("check_number", OpInfo::new(vec![
+            ArgInfo::new("if_larger", ArgKind::Exec),
+            ArgInfo::new("value", ArgKind::In),
+        ])),
+        ("set_reg", OpInfo::new(vec![
+            ArgInfo::new("value", ArgKind::In),
+            ArgInfo::new("to", ArgKind::Out),
+        ])),
+    ]);
+    let report = variable_usage(&behavior, &catalog);
+    let usage: Vec<(Place, Vec<usize>, Vec<usize>)> = report.places.iter()
+        .map(|usage| (
+            usage.place.clone(), usage.reads.clone(), usage.writes.clone() ))
+        .collect();
+    assert_eq!(usage, [
+        (Place::Variable(Str::from("A")), vec![0, 2], vec![1]),
+        (Place::Register(Register::Signal), vec![], vec![2]),
+        (Place::Variable(Str::from("B")), vec![3], vec![]),
+        (Place::Variable(Str::from("C")), vec![], vec![3]),
+    ]);
+    assert_eq!(report.unused_parameters, [2]);
+    assert_eq!(report.undefined_reads, [
+        (0, Str::from("A")),
+        (2, Str::from("A")),
+        (3, Str::from("B")),
+    ]);
+}
+
+}
@@ -1,52 +1,102 @@
 #![allow(clippy::use_self)]
 
+#[cfg(feature = "serde")]
 use serde::{
     Serialize,
     Deserialize,
 };
+#[cfg(feature = "serde")]
+use crate::common::serde::option_some as serde_option_some;
 
 use crate::{
-    error::LoadError,
+    error::{LoadError, DumpError},
     Str,
-    common::{
-        u32_to_usize,
-        serde::option_some as serde_option_some,
+    common::u32_to_usize,
+    value::{
+        Key, Value, Table, ArrayBuilder as TableArrayBuilder,
+        FaithfulValue,
     },
-    value::{Key, Value, Table, ArrayBuilder as TableArrayBuilder},
 };
 
-use super::Instruction;
+use super::{
+    Instruction, LoadOptions, UnknownKeys, LabelError, migrate,
+};
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub struct Behavior {
 
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Option::is_none",
-        with="serde_option_some" )]
+        with="serde_option_some" ))]
     pub name: Option<Str>,
 
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Option::is_none",
-        with="serde_option_some" )]
+        with="serde_option_some" ))]
     pub description: Option<Str>,
 
-    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Vec::is_empty"))]
     pub parameters: Vec<Parameter>,
 
     pub instructions: Vec<Instruction>,
 
-    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Vec::is_empty"))]
     pub subroutines: Vec<Behavior>,
 
+    /// Keys not recognized on load (see [`super::UnknownKeys::Keep`]).
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Table::is_empty"))]
+    pub extra: Table,
+
+    /// Integer keys that do not continue the instruction array
+    /// (see [`LoadOptions::stray_indices`]).
+    /// Entries that would overwrite an instruction are not written back.
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Vec::is_empty"))]
+    pub extra_indexed: Vec<(i32, Value)>,
+
+    /// Names of parameters past the end of `parameters`
+    /// (see [`LoadOptions::stale_parameter_names`]).
+    /// Entries that would name an existing parameter
+    /// are not written back.
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Vec::is_empty"))]
+    pub stale_parameter_names: Vec<(i32, Str)>,
+
+    /// Empty tables that were present on load
+    /// (see [`LoadOptions::preserve_empty_tables`]).
+    /// They are written back if the corresponding collection
+    /// is still empty, instead of being omitted.
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Vec::is_empty"))]
+    pub empty_tables: Vec<EmptyTable>,
+
+}
+
+/// Keys of a behavior table that hold collections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum EmptyTable {
+    Parameters,
+    ParameterNames,
+    Subroutines,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub struct Parameter {
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Option::is_none",
-        with="serde_option_some" )]
+        with="serde_option_some" ))]
     pub name: Option<Str>,
     pub is_output: bool,
 }
@@ -54,51 +104,135 @@ pub struct Parameter {
 impl TryFrom<Value> for Behavior {
     type Error = LoadError;
     fn try_from(value: Value) -> Result<Behavior, Self::Error> {
-        let Value::Table(table) = value else {
-            return Err(LoadError::from(
-                "behavior should be represented by a table value" ));
-        };
-        Behavior::try_from(table)
+        Behavior::from_value_with(value, LoadOptions::default())
     }
 }
 
 impl TryFrom<Table> for Behavior {
     type Error = LoadError;
     fn try_from(table: Table) -> Result<Behavior, Self::Error> {
-        BehaviorBuilder::build_from(table)
+        BehaviorBuilder::build_from(table, LoadOptions::default())
     }
 }
 
+impl Behavior {
+
+    pub fn from_value_with(value: Value, options: LoadOptions)
+    -> Result<Behavior, LoadError>
+    {
+        let Value::Table(table) = value else {
+            return Err(LoadError::from(
+                "behavior should be represented by a table value" ));
+        };
+        Behavior::from_table_with(table, options)
+    }
+
+    pub fn from_table_with(table: Table, options: LoadOptions)
+    -> Result<Behavior, LoadError>
+    {
+        BehaviorBuilder::build_from(table, options)
+    }
+
+    /// The behavior as it would be encoded, with the table layout
+    /// (see [`FaithfulValue`]).
+    pub fn to_faithful(&self) -> Result<FaithfulValue, DumpError> {
+        FaithfulValue::from_dump(&Value::try_from(self.clone())?)
+    }
+
+    /// Load the behavior from its [`FaithfulValue`];
+    /// the layout itself is dropped.
+    pub fn from_faithful(value: &FaithfulValue)
+    -> Result<Behavior, LoadError>
+    {
+        Behavior::try_from(value.to_value()?)
+    }
+
+    /// The behavior and its subroutines, nested ones included,
+    /// each followed by its own subroutines.
+    ///
+    /// Only loading limits the nesting depth of subroutines
+    /// (see [`DecodeOptions::max_depth`]), and behaviors built
+    /// in memory can be nested arbitrarily deep,
+    /// so walkers go through this instead of recursing.
+    ///
+    /// [`DecodeOptions::max_depth`]: crate::loader::DecodeOptions::max_depth
+    pub(crate) fn with_subroutines(&self)
+    -> impl Iterator<Item=&Behavior>
+    {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let behavior = stack.pop()?;
+            stack.extend(behavior.subroutines.iter().rev());
+            Some(behavior)
+        })
+    }
+
+    /// Call `f` for the behavior and its subroutines,
+    /// in the order of [`with_subroutines`](Self::with_subroutines).
+    pub(crate) fn for_each_with_subroutines_mut<F>(&mut self, mut f: F)
+    where F: FnMut(&mut Behavior)
+    {
+        self.try_for_each_with_subroutines_mut(|behavior| {
+            f(behavior);
+            Ok::<(), std::convert::Infallible>(())
+        }).unwrap_or_else(|never| match never {});
+    }
+
+    /// Call `f` for the behavior and its subroutines,
+    /// in the order of [`with_subroutines`](Self::with_subroutines),
+    /// stopping at the first error.
+    pub(crate) fn try_for_each_with_subroutines_mut<F, E>(&mut self, mut f: F)
+    -> Result<(), E>
+    where F: FnMut(&mut Behavior) -> Result<(), E>
+    {
+        let mut stack = vec![self];
+        while let Some(behavior) = stack.pop() {
+            f(behavior)?;
+            stack.extend(behavior.subroutines.iter_mut().rev());
+        }
+        Ok(())
+    }
+
+}
+
 #[derive(Default)]
 struct BehaviorBuilder {
+    options: LoadOptions,
     name: Option<Str>,
     description: Option<Str>,
     parameters: Vec<Parameter>,
     parameter_names: Option<Table>,
     instructions: Vec<Instruction>,
     subroutines: Vec<Behavior>,
+    extra: Vec<(Key, Value)>,
+    extra_indexed: Vec<(i32, Value)>,
+    stale_parameter_names: Vec<(i32, Str)>,
+    empty_tables: Vec<EmptyTable>,
 }
 
 impl BehaviorBuilder {
 
-    fn build_from(table: Table) -> Result<Behavior, LoadError> {
-        let mut this = Self::default();
+    fn build_from(table: Table, options: LoadOptions)
+    -> Result<Behavior, LoadError> {
+        let table = options.migrations
+            .apply(migrate::Schema::Behavior, table)?;
+        let mut this = Self { options, ..Self::default() };
         let mut array = Vec::new();
         for (key, value) in table {
             match key {
                 Key::Index(index) if index > 0 &&
                     u32_to_usize((index - 1) as u32) == array.len()
                 => array.push(value),
-                Key::Index(index) =>
-                    return Err(Self::err_non_continuous(index)),
+                Key::Index(index) => this.set_stray(index, value)?,
                 Key::Name(name) => match name.as_ref() {
                     "name"       => this.set_name           (value)?,
                     "desc"       => this.set_description    (value)?,
                     "parameters" => this.set_parameters     (value)?,
                     "pnames"     => this.set_parameter_names(value)?,
                     "subs"       => this.set_subroutines    (value)?,
-                    _ => return Err(Self::err_unexpected_key(Key::Name(name))),
+                    _ => this.set_unknown(Key::Name(name), value)?,
                 },
+                Key::Bool(_) => this.set_unknown(key, value)?,
             }
         }
         this.instructions.reserve_exact(array.len());
@@ -112,6 +246,22 @@ impl BehaviorBuilder {
         "behavior representation should have \
          instruction indices in a continuous range `1..n`: {index:?}" )) }
 
+    fn set_stray(&mut self, index: i32, value: Value)
+    -> Result<(), LoadError> {
+        match self.options.stray_indices {
+            UnknownKeys::Error => return Err(Self::err_non_continuous(index)),
+            UnknownKeys::Keep => self.extra_indexed.push((index, value)),
+            UnknownKeys::Drop => (),
+        }
+        Ok(())
+    }
+
+    fn set_unknown(&mut self, key: Key, value: Value)
+    -> Result<(), LoadError> {
+        self.options.unknown_keys.handle( &mut self.extra,
+            key, value, Self::err_unexpected_key )
+    }
+
     fn err_unexpected_key(key: Key) -> LoadError { LoadError::from(format!(
         "behavior representation should not have {key:?} key" )) }
 
@@ -131,10 +281,17 @@ impl BehaviorBuilder {
         self.description = Some(value); Ok(())
     }
 
+    fn note_empty(&mut self, table: &Table, kind: EmptyTable) {
+        if table.is_empty() && self.options.preserve_empty_tables {
+            self.empty_tables.push(kind);
+        }
+    }
+
     fn set_parameters(&mut self, value: Value) -> Result<(), LoadError> {
         let Value::Table(table) = value else {
             return Err(Self::err_parameters());
         };
+        self.note_empty(&table, EmptyTable::Parameters);
         for item in table.into_continuous_iter() {
             let item = item.map_err(|_error| Self::err_parameters())?;
             let Value::Boolean(is_output) = item else {
@@ -153,6 +310,7 @@ impl BehaviorBuilder {
         let Value::Table(table) = value else {
             return Err(Self::err_param_names());
         };
+        self.note_empty(&table, EmptyTable::ParameterNames);
         self.parameter_names = Some(table);
         Ok(())
     }
@@ -160,24 +318,31 @@ impl BehaviorBuilder {
     fn reconcile_parameter_names(
         parameters: &mut [Parameter],
         parameter_names: Table,
+        stale_names: UnknownKeys,
+        stale_parameter_names: &mut Vec<(i32, Str)>,
     ) -> Result<(), LoadError> {
-        for (index, value) in parameter_names {
-            #[allow(clippy::unnecessary_lazy_evaluations)]
-            let Some(index) = index.as_index()
-                .filter(|&x| x > 0).map(|x| x - 1)
-                .map(usize::try_from).and_then(Result::ok)
+        for (key, value) in parameter_names {
+            let Some((number, index)) = key.as_index()
+                .filter(|&x| x > 0)
+                .and_then(|x| Some((x, usize::try_from(x - 1).ok()?)))
             else {
                 return Err(Self::err_param_names());
             };
-            if index >= parameters.len() {
-                return Err(LoadError::from(
-                    "the number of behavior's parameters is inconsistent with \
-                    the number of parameter names" ));
-            }
             let Value::String(value) = value else {
                 return Err(Self::err_param_names());
             };
-            parameters[index].name = Some(value);
+            if let Some(parameter) = parameters.get_mut(index) {
+                parameter.name = Some(value);
+                continue;
+            }
+            match stale_names {
+                UnknownKeys::Error => return Err(LoadError::from(
+                    "the number of behavior's parameters is inconsistent with \
+                    the number of parameter names" )),
+                UnknownKeys::Keep =>
+                    stale_parameter_names.push((number, value)),
+                UnknownKeys::Drop => (),
+            }
         }
         Ok(())
     }
@@ -190,9 +355,11 @@ impl BehaviorBuilder {
         let Value::Table(table) = value else {
             return Err(Self::err_subroutines());
         };
+        self.note_empty(&table, EmptyTable::Subroutines);
         for item in table.into_continuous_iter() {
             let item = item.map_err(|_error| Self::err_subroutines())?;
-            self.subroutines.push(Behavior::try_from(item)?);
+            self.subroutines.push(
+                Behavior::from_value_with(item, self.options.clone())? );
         }
         Ok(())
     }
@@ -202,67 +369,113 @@ impl BehaviorBuilder {
 
     fn build(self) -> Result<Behavior, LoadError> {
         let Self {
+            options,
             name, description,
             mut parameters, parameter_names,
             instructions,
             subroutines,
+            extra, extra_indexed, mut stale_parameter_names,
+            empty_tables,
         } = self;
         if let Some(parameter_names) = parameter_names {
-            Self::reconcile_parameter_names(&mut parameters, parameter_names)?;
+            Self::reconcile_parameter_names( &mut parameters,
+                parameter_names,
+                options.stale_parameter_names, &mut stale_parameter_names )?;
         }
         Ok(Behavior {
             name, description,
             parameters,
             instructions,
             subroutines,
+            extra: extra.into_iter().collect(),
+            extra_indexed,
+            stale_parameter_names,
+            empty_tables,
         })
     }
 
 }
 
-impl From<Behavior> for Value {
-    fn from(this: Behavior) -> Value {
+/// Resolves labels of the behavior and its subroutines
+/// (see [`Behavior::resolve_labels`]).
+impl TryFrom<Behavior> for Value {
+    type Error = LabelError;
+    fn try_from(mut this: Behavior) -> Result<Value, LabelError> {
+        this.resolve_own_labels()?;
         let Behavior {
             instructions,
             name: behavior_name, description,
             parameters,
             subroutines,
+            extra, extra_indexed, stale_parameter_names,
+            empty_tables,
         } = this;
+        let empty_table = |kind| empty_tables.contains(&kind)
+            .then(|| Value::Table(Table::new()));
+        let instruction_count = instructions.len();
         let mut table_array = TableArrayBuilder::new();
-        table_array.extend( instructions.into_iter()
-            .map(Value::from) );
+        for instruction in instructions {
+            table_array.push(Value::try_from(instruction)?);
+        }
         let mut table = table_array.build().into_builder();
+        table.extend(extra_indexed.into_iter()
+            .filter(|&(index, _)| usize::try_from(index)
+                .map_or(true, |index| index == 0 || index > instruction_count) )
+            .map(|(index, value)| (Key::Index(index), value)) );
         table.extend([
             ("name"      , behavior_name.map(Value::String)),
             ("desc"      , description.map(Value::String)),
-            ("parameters", if parameters.is_empty() { None } else {
+            ("parameters", if parameters.is_empty() {
+                empty_table(EmptyTable::Parameters)
+            } else {
                 Some(Value::Table( parameters.iter()
                     .map(|param| Some(Value::Boolean(param.is_output)))
                     .collect::<TableArrayBuilder<_>>().build() ))
             }),
-            ("pnames"    , if parameters.is_empty() { None } else {
-                Some(Value::Table( parameters.into_iter()
+            ("pnames"    , if parameters.is_empty() &&
+                stale_parameter_names.is_empty()
+            {
+                empty_table(EmptyTable::ParameterNames)
+            } else {
+                let parameter_count = parameters.len();
+                let mut names = parameters.into_iter()
                     .map(|param| param.name.map(Value::String))
-                    .collect::<TableArrayBuilder<_>>().build() ))
+                    .collect::<TableArrayBuilder<_>>().build().into_builder();
+                names.extend(stale_parameter_names.into_iter()
+                    .filter(|&(index, _)| usize::try_from(index)
+                        .is_ok_and(|index| index > parameter_count) )
+                    .map(|(index, name)|
+                        (Key::Index(index), Value::String(name)) ));
+                Some(Value::Table(names.build()))
             }),
-            ("subs"      , if subroutines.is_empty() { None } else {
+            ("subs"      , if subroutines.is_empty() {
+                empty_table(EmptyTable::Subroutines)
+            } else {
                 Some(Value::Table( subroutines.into_iter()
-                    .map(Value::from)
-                    .collect::<TableArrayBuilder<_>>().build() ))
+                    .map(Value::try_from)
+                    .collect::<Result<TableArrayBuilder<_>, _>>()?
+                    .build() ))
             }),
         ].into_iter().filter_map(|(name, value)| {
             let value = value?;
             Some((Key::from(name), value))
         }));
-        Value::Table(table.build())
+        table.extend(extra);
+        Ok(Value::Table(table.build()))
     }
 }
 
 #[cfg(test)]
 mod test {
 
-use super::Behavior;
+use crate::value::{Key, Value, Table};
 
+use super::{
+    super::{LoadOptions, UnknownKeys},
+    Behavior, EmptyTable,
+};
+
+#[cfg(feature = "serde")]
 #[test]
 fn test_map_1_de() {
     let s = r#"Behavior(
@@ -272,5 +485,93 @@ fn test_map_1_de() {
     let _: Behavior = ron::from_str(s).unwrap();
 }
 
+#[test]
+fn test_empty_tables() {
+    let table: Table = Table::from_iter([
+        (Key::from("name"), Value::String("Behavior".into())),
+        (Key::from("parameters"), Value::Table(Table::new())),
+        (Key::from("subs"), Value::Table(Table::new())),
+    ]);
+    let behavior = Behavior::try_from(table.clone()).unwrap();
+    assert!(behavior.empty_tables.is_empty());
+    assert_eq!( Value::try_from(behavior).unwrap(),
+        Value::Table(Table::from_iter([
+            (Key::from("name"), Value::String("Behavior".into())),
+        ])) );
+    let behavior = Behavior::from_table_with( table.clone(),
+        LoadOptions::lenient() ).unwrap();
+    assert_eq!( behavior.empty_tables,
+        [EmptyTable::Parameters, EmptyTable::Subroutines] );
+    assert_eq!(Value::try_from(behavior).unwrap(), Value::Table(table));
 }
 
+
+#[test]
+fn test_stray_indices() {
+    let instruction = || Value::Table(Table::from_iter([
+        (Key::from("op"), Value::String("nop".into())),
+    ]));
+    let table: Table = Table::from_iter([
+        (Key::Index(-1), Value::Integer(-1)),
+        (Key::Index(1), instruction()),
+        (Key::Index(2), instruction()),
+        (Key::Index(5), Value::Boolean(true)),
+    ]);
+    assert!(Behavior::try_from(table.clone()).is_err());
+    let mut behavior = Behavior::from_table_with( table.clone(),
+        LoadOptions::lenient() ).unwrap();
+    assert_eq!(behavior.instructions.len(), 2);
+    assert_eq!( behavior.extra_indexed,
+        [(-1, Value::Integer(-1)), (5, Value::Boolean(true))] );
+    assert_eq!( Value::try_from(behavior.clone()).unwrap(),
+        Value::Table(table) );
+    behavior.instructions.extend([
+        behavior.instructions[0].clone(),
+        behavior.instructions[0].clone(),
+        behavior.instructions[0].clone(),
+    ]);
+    let Value::Table(table) = Value::try_from(behavior).unwrap() else {
+        panic!("behavior should be a table");
+    };
+    assert_eq!(table.get(&Key::Index(-1)), Some(&Value::Integer(-1)));
+    assert_eq!(table.get(&Key::Index(5)), Some(&instruction()));
+}
+
+#[test]
+fn test_stale_parameter_names() {
+    let name = |name: &str| Value::String(name.into());
+    let table: Table = Table::from_iter([
+        (Key::from("parameters"), Value::Table(Table::from_iter([
+            (Key::Index(1), Value::Boolean(false)),
+        ]))),
+        (Key::from("pnames"), Value::Table(Table::from_iter([
+            (Key::Index(1), name("a")),
+            (Key::Index(3), name("c")),
+        ]))),
+    ]);
+    assert!(Behavior::try_from(table.clone()).is_err());
+    let options = LoadOptions {
+        stale_parameter_names: UnknownKeys::Drop,
+        ..LoadOptions::default() };
+    let behavior = Behavior::from_table_with(table.clone(), options).unwrap();
+    assert!(behavior.stale_parameter_names.is_empty());
+    assert_eq!(behavior.parameters[0].name.as_deref(), Some("a"));
+    let mut behavior = Behavior::from_table_with( table.clone(),
+        LoadOptions::lenient() ).unwrap();
+    assert_eq!( behavior.stale_parameter_names, [(3, "c".into())] );
+    assert_eq!( Value::try_from(behavior.clone()).unwrap(),
+        Value::Table(table) );
+    behavior.parameters.extend( std::iter::repeat(
+        behavior.parameters[0].clone() ).take(2) );
+    let Value::Table(table) = Value::try_from(behavior).unwrap() else {
+        panic!("behavior should be a table");
+    };
+    assert_eq!( table.get(&Key::from("pnames")),
+        Some(&Value::Table(Table::from_iter([
+            (Key::Index(1), name("a")),
+            (Key::Index(2), name("a")),
+            (Key::Index(3), name("a")),
+        ]))) );
+}
+
+}
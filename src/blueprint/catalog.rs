@@ -0,0 +1,281 @@
+//! Descriptions of behavior operations.
+//!
+//! The encoding does not say which arguments of an instruction
+//! are inputs, outputs or jumps, and the crate does not ship
+//! the list of the game's operations.
+//! An [`OpCatalog`] is filled by the caller
+//! (or deserialized, e.g. from a JSON file)
+//! and is used to label arguments in [`Behavior::pretty`].
+//!
+//! Some game versions refer to operations by numeric ids;
+//! the catalog maps them to names if [`OpInfo::id`] is filled.
+//!
+//! [`Behavior::pretty`]: super::Behavior::pretty
+
+use std::{collections::btree_map::BTreeMap as SortedMap, fmt};
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Str;
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct OpCatalog {
+    ops: SortedMap<Str, OpInfo>,
+}
+
+impl OpCatalog {
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the description of the operation.
+    pub fn insert(&mut self, op: impl Into<Str>, info: OpInfo) {
+        self.ops.insert(op.into(), info);
+    }
+
+    #[must_use]
+    pub fn get(&self, op: &str) -> Option<&OpInfo> {
+        self.ops.get(op)
+    }
+
+    /// Find the operation by its name or id.
+    #[must_use]
+    pub fn get_ref(&self, op: &OpRef) -> Option<&OpInfo> {
+        match *op {
+            OpRef::Name(ref name) => self.get(name),
+            OpRef::Id(id) => self.find_id(id).map(|(_, info)| info),
+        }
+    }
+
+    /// The name of the operation with the id.
+    #[must_use]
+    pub fn name_of(&self, id: i32) -> Option<&Str> {
+        self.find_id(id).map(|(name, _)| name)
+    }
+
+    /// The id of the operation with the name.
+    #[must_use]
+    pub fn id_of(&self, name: &str) -> Option<i32> {
+        self.get(name)?.id
+    }
+
+    fn find_id(&self, id: i32) -> Option<(&Str, &OpInfo)> {
+        self.ops.iter().find(|(_, info)| info.id == Some(id))
+    }
+
+}
+
+impl<S: Into<Str>> FromIterator<(S, OpInfo)> for OpCatalog {
+    fn from_iter<I: IntoIterator<Item=(S, OpInfo)>>(iter: I) -> Self {
+        Self { ops: iter.into_iter()
+            .map(|(op, info)| (op.into(), info)).collect() }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub struct OpInfo {
+    /// Arguments in the order of instruction arguments.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub args: Vec<ArgInfo>,
+    /// The numeric id some game versions encode
+    /// instead of the name.
+    #[cfg_attr(feature = "serde",
+        serde(default, skip_serializing_if="Option::is_none"))]
+    pub id: Option<i32>,
+}
+
+impl OpInfo {
+    #[must_use]
+    pub fn new(args: Vec<ArgInfo>) -> Self {
+        Self { args, id: None }
+    }
+    #[must_use]
+    pub fn with_id(self, id: i32) -> Self {
+        Self { id: Some(id), ..self }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub struct ArgInfo {
+    pub name: Str,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: ArgKind,
+}
+
+impl ArgInfo {
+    #[must_use]
+    pub fn new(name: impl Into<Str>, kind: ArgKind) -> Self {
+        Self { name: name.into(), kind }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub enum ArgKind {
+    /// A value or a place the operation reads.
+    #[default]
+    In,
+    /// A place the operation writes to.
+    Out,
+    /// An alternative next instruction.
+    Exec,
+}
+
+/// The operation of an instruction,
+/// encoded either by name or by numeric id.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum OpRef {
+    Name(Str),
+    Id(i32),
+}
+
+impl OpRef {
+
+    #[must_use]
+    pub fn as_name(&self) -> Option<&str> {
+        match *self {
+            Self::Name(ref name) => Some(name),
+            Self::Id(_) => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_id(&self) -> Option<i32> {
+        match *self {
+            Self::Name(_) => None,
+            Self::Id(id) => Some(id),
+        }
+    }
+
+    /// The name of the operation, looking ids up in the catalog.
+    #[must_use]
+    pub fn name<'s>(&'s self, catalog: &'s OpCatalog) -> Option<&'s str> {
+        match *self {
+            Self::Name(ref name) => Some(name),
+            Self::Id(id) => catalog.name_of(id).map(|name| &**name),
+        }
+    }
+
+    /// The id of the operation, looking names up in the catalog.
+    #[must_use]
+    pub fn id(&self, catalog: &OpCatalog) -> Option<i32> {
+        match *self {
+            Self::Name(ref name) => catalog.id_of(name),
+            Self::Id(id) => Some(id),
+        }
+    }
+
+    /// Replace an id with the name from the catalog, if there is one.
+    #[must_use]
+    pub fn into_named(self, catalog: &OpCatalog) -> Self {
+        match self {
+            Self::Id(id) => catalog.name_of(id)
+                .map_or(self, |name| Self::Name(name.clone())),
+            Self::Name(_) => self,
+        }
+    }
+
+}
+
+impl From<Str> for OpRef {
+    fn from(name: Str) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<&str> for OpRef {
+    fn from(name: &str) -> Self {
+        Self::Name(Str::from(name))
+    }
+}
+
+impl From<i32> for OpRef {
+    fn from(id: i32) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl PartialEq<str> for OpRef {
+    fn eq(&self, other: &str) -> bool {
+        self.as_name() == Some(other)
+    }
+}
+
+impl fmt::Debug for OpRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Name(ref name) => name.fmt(f),
+            Self::Id(id) => id.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for OpRef {
+    /// The name, or the id.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Name(ref name) => f.pad(name),
+            Self::Id(id) => f.pad(&id.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for OpRef {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where S: Serializer
+    {
+        match *self {
+            Self::Name(ref name) => ser.serialize_str(name),
+            Self::Id(id) => ser.serialize_i32(id),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for OpRef {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>
+    {
+        de.deserialize_any(OpRefVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct OpRefVisitor;
+
+#[cfg(feature = "serde")]
+impl de::Visitor<'_> for OpRefVisitor {
+    type Value = OpRef;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "an operation name or id")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<OpRef, E> {
+        Ok(OpRef::from(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<OpRef, E> {
+        i32::try_from(value).map(OpRef::Id).map_err(|_err|
+            E::invalid_value(de::Unexpected::Signed(value), &self) )
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<OpRef, E> {
+        i32::try_from(value).map(OpRef::Id).map_err(|_err|
+            E::invalid_value(de::Unexpected::Unsigned(value), &self) )
+    }
+
+}
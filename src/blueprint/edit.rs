@@ -0,0 +1,222 @@
+//! Editing operations on behaviors.
+//!
+//! An editor can express every change to a behavior as an [`EditOp`]
+//! and apply it with [`apply`], which returns the inverse operation.
+//! Applying the inverse restores the behavior exactly,
+//! so a stack of inverses is enough to implement undo.
+//!
+//! Instructions are addressed by their `0`-based position in
+//! [`Behavior::instructions`], while jumps keep referring to them by
+//! `1`-based indices (see [`Jump::Jump`]).
+//! Adding or removing an instruction renumbers `next` jumps
+//! and [`Operand::Jump`] arguments of the other instructions.
+//! Arguments that were not yet resolved to jumps
+//! ([`Operand::UnknownIndex`]) are left as is.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Str;
+
+use super::{Behavior, Instruction, Operand, Jump, Place};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub enum EditOp {
+    /// Insert the instruction before the `index`-th one
+    /// (or at the end, if `index` is the number of instructions).
+    /// Jumps of the inserted instruction are taken as is.
+    AddInstruction { index: usize, instruction: Instruction },
+    /// Remove the `index`-th instruction.
+    /// Fails if other instructions jump to it.
+    RemoveInstruction { index: usize },
+    /// Replace the `arg`-th argument of the `index`-th instruction.
+    SetOperand { index: usize, arg: usize, operand: Operand },
+    /// Replace the `next` jump of the `index`-th instruction.
+    SetNext { index: usize, next: Jump },
+    /// Rename the variable in all instruction arguments.
+    /// Fails if the new name is already in use.
+    RenameVariable { from: Str, to: Str },
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EditError {
+    #[error("There is no instruction at index {index}")]
+    NoInstruction { index: usize },
+    #[error("Instruction at index {index} has no argument {arg}")]
+    NoOperand { index: usize, arg: usize },
+    #[error("Instruction at index {index} is a target of another jump")]
+    JumpTarget { index: usize },
+    #[error("Variable {name:?} is already in use")]
+    VariableInUse { name: Str },
+}
+
+/// Apply the operation, returning its inverse.
+///
+/// On error the behavior is left unchanged.
+pub fn apply(behavior: &mut Behavior, op: EditOp)
+-> Result<EditOp, EditError>
+{
+    let instructions = &mut behavior.instructions;
+    Ok(match op {
+        EditOp::AddInstruction { index, instruction } => {
+            if index > instructions.len() {
+                return Err(EditError::NoInstruction { index });
+            }
+            let target = jump_target(index);
+            for other in instructions.iter_mut() {
+                for_each_jump(other, |jump| if *jump >= target {
+                    *jump += 1;
+                });
+            }
+            instructions.insert(index, instruction);
+            EditOp::RemoveInstruction { index }
+        },
+        EditOp::RemoveInstruction { index } => {
+            if index >= instructions.len() {
+                return Err(EditError::NoInstruction { index });
+            }
+            let target = jump_target(index);
+            let mut targeted = false;
+            for (other_index, other) in instructions.iter_mut().enumerate() {
+                if other_index == index { continue; }
+                for_each_jump(other, |jump| targeted |= *jump == target);
+            }
+            if targeted {
+                return Err(EditError::JumpTarget { index });
+            }
+            let instruction = instructions.remove(index);
+            for other in instructions.iter_mut() {
+                for_each_jump(other, |jump| if *jump > target {
+                    *jump -= 1;
+                });
+            }
+            EditOp::AddInstruction { index, instruction }
+        },
+        EditOp::SetOperand { index, arg, operand } => {
+            let instruction = instructions.get_mut(index)
+                .ok_or(EditError::NoInstruction { index })?;
+            let slot = instruction.args.get_mut(arg)
+                .ok_or(EditError::NoOperand { index, arg })?;
+            let operand = std::mem::replace(slot, operand);
+            EditOp::SetOperand { index, arg, operand }
+        },
+        EditOp::SetNext { index, next } => {
+            let instruction = instructions.get_mut(index)
+                .ok_or(EditError::NoInstruction { index })?;
+            let next = std::mem::replace(&mut instruction.next, next);
+            EditOp::SetNext { index, next }
+        },
+        EditOp::RenameVariable { from, to } => {
+            if from != to && instructions.iter()
+                .flat_map(|instruction| &instruction.args)
+                .any(|arg| is_variable(arg, &to))
+            {
+                return Err(EditError::VariableInUse { name: to });
+            }
+            for arg in instructions.iter_mut()
+                .flat_map(|instruction| &mut instruction.args)
+            {
+                if is_variable(arg, &from) {
+                    *arg = Operand::Place(Some(Place::Variable(to.clone())));
+                }
+            }
+            EditOp::RenameVariable { from: to, to: from }
+        },
+    })
+}
+
+/// `1`-based jump index of the `index`-th instruction.
+fn jump_target(index: usize) -> i32 {
+    let Ok(target) = i32::try_from(index + 1) else {
+        panic!("instruction index should fit into a jump");
+    };
+    target
+}
+
+pub(super) fn for_each_jump<F>(instruction: &mut Instruction, mut f: F)
+where F: FnMut(&mut i32)
+{
+    if let Jump::Jump(ref mut jump) = instruction.next {
+        f(jump);
+    }
+    for arg in &mut instruction.args {
+        if let Operand::Jump(Jump::Jump(ref mut jump)) = *arg {
+            f(jump);
+        }
+    }
+}
+
+pub(super) fn is_variable(operand: &Operand, name: &str) -> bool {
+    matches!( *operand,
+        Operand::Place(Some(Place::Variable(ref variable)))
+            if **variable == *name )
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use super::{super::Behavior, apply, EditOp, EditError};
+
+fn behavior() -> Behavior {
+    ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "check_number", args: [Jump(3), Variable("A")], next: Jump(2)),
+            (op: "set_reg", args: [Variable("A"), Variable("B")]),
+            (op: "jump", args: [Jump(1)], next: Return),
+        ],
+    )"#).unwrap()
+}
+
+fn to_ron(behavior: &Behavior) -> String {
+    ron::to_string(behavior).unwrap()
+}
+
+#[test]
+fn test_add_remove() {
+    let mut behavior = behavior();
+    let original = to_ron(&behavior);
+    let instruction = behavior.instructions[1].clone();
+    let inverse = apply( &mut behavior,
+        EditOp::AddInstruction { index: 1, instruction } ).unwrap();
+    assert_eq!(to_ron(&behavior), to_ron(&ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "check_number", args: [Jump(4), Variable("A")], next: Jump(3)),
+            (op: "set_reg", args: [Variable("A"), Variable("B")]),
+            (op: "set_reg", args: [Variable("A"), Variable("B")]),
+            (op: "jump", args: [Jump(1)], next: Return),
+        ],
+    )"#).unwrap()));
+    let redo = apply(&mut behavior, inverse).unwrap();
+    assert_eq!(to_ron(&behavior), original);
+    assert!(matches!(redo, EditOp::AddInstruction { index: 1, .. }));
+}
+
+#[test]
+fn test_remove_target() {
+    let mut behavior = behavior();
+    let original = to_ron(&behavior);
+    let error = apply( &mut behavior,
+        EditOp::RemoveInstruction { index: 2 } ).unwrap_err();
+    assert!(matches!(error, EditError::JumpTarget { index: 2 }));
+    apply(&mut behavior, EditOp::RemoveInstruction { index: 5 }).unwrap_err();
+    assert_eq!(to_ron(&behavior), original);
+}
+
+#[test]
+fn test_rename_variable() {
+    let mut behavior = behavior();
+    let original = to_ron(&behavior);
+    apply( &mut behavior, EditOp::RenameVariable {
+        from: "A".into(), to: "B".into() } ).unwrap_err();
+    let inverse = apply( &mut behavior, EditOp::RenameVariable {
+        from: "A".into(), to: "C".into() } ).unwrap();
+    assert!(!to_ron(&behavior).contains(r#"Variable("A")"#));
+    apply(&mut behavior, inverse).unwrap();
+    assert_eq!(to_ron(&behavior), original);
+}
+
+}
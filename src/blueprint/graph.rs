@@ -0,0 +1,234 @@
+//! Control flow graphs of behaviors in the DOT language of Graphviz,
+//! for visualizing complex behaviors.
+//!
+//! Instructions are drawn as nodes labeled with their operations,
+//! with edges to the next instruction and to the jump arguments.
+//! Subroutines are drawn as clusters, and calls (the `sub` key
+//! of an instruction) as dashed edges to the entry of the subroutine.
+
+use std::fmt;
+
+use crate::value::Value;
+
+use super::{Behavior, Jump, OpCatalog};
+
+/// Render the control flow graph of the behavior
+/// (see the [module documentation](self)).
+///
+/// Only arguments that are already [`Operand::Jump`]
+/// are drawn as edges; see [`to_dot_with`].
+///
+/// [`Operand::Jump`]: super::Operand::Jump
+#[must_use]
+pub fn to_dot(behavior: &Behavior) -> String {
+    to_dot_with(behavior, &OpCatalog::new())
+}
+
+/// Render the control flow graph of the behavior like [`to_dot`],
+/// taking the jump arguments and their names,
+/// and the names of operations encoded by id, from the catalog.
+#[must_use]
+pub fn to_dot_with(behavior: &Behavior, catalog: &OpCatalog) -> String {
+    Dot { behavior, catalog }.to_string()
+}
+
+struct Dot<'b> {
+    behavior: &'b Behavior,
+    catalog: &'b OpCatalog,
+}
+
+enum Item<'b> {
+    Open(Vec<usize>, &'b Behavior),
+    Close(usize),
+}
+
+impl fmt::Display for Dot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph behavior {{")?;
+        writeln!(f, "  node [shape=box];")?;
+        let mut stack = vec![Item::Open(Vec::new(), self.behavior)];
+        while let Some(item) = stack.pop() {
+            let (path, behavior) = match item {
+                Item::Open(path, behavior) => (path, behavior),
+                Item::Close(depth) => {
+                    writeln!(f, "{}}}", indent(depth))?;
+                    continue;
+                },
+            };
+            if !path.is_empty() {
+                let name = path.iter()
+                    .map(|position| (position + 1).to_string())
+                    .collect::<Vec<_>>().join(".");
+                writeln!(f, "{}subgraph cluster_{} {{",
+                    indent(path.len()), prefix(&path) )?;
+                writeln!(f, "{}label={};",
+                    indent(path.len() + 1), quote(&format!("sub {name}")) )?;
+                stack.push(Item::Close(path.len()));
+            }
+            self.write_behavior(f, &path, behavior)?;
+            stack.extend( behavior.subroutines.iter().enumerate().rev()
+                .map(|(position, subroutine)| {
+                    let mut path = path.clone();
+                    path.push(position);
+                    Item::Open(path, subroutine)
+                }) );
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Dot<'_> {
+
+    fn write_behavior( &self, f: &mut fmt::Formatter<'_>,
+        path: &[usize], behavior: &Behavior,
+    ) -> fmt::Result {
+        let indent = indent(path.len() + 1);
+        let prefix = prefix(path);
+        let len = behavior.instructions.len();
+        writeln!(f, "{indent}{prefix}_entry [shape=point];")?;
+        writeln!(f, "{indent}{prefix}_return \
+            [label=\"return\", shape=oval];")?;
+        for (index, instruction) in (1 ..).zip(&behavior.instructions) {
+            let op = instruction.operation.name(self.catalog)
+                .map_or_else(|| instruction.operation.to_string(),
+                    String::from);
+            let mut label = format!("{index}: {op}");
+            if let Some(ref name) = instruction.label {
+                label = format!("{}\n{label}", &**name);
+            }
+            writeln!(f, "{indent}{prefix}_{index} [label={}];",
+                quote(&label) )?;
+        }
+        let entry = if len == 0 { "return" } else { "1" };
+        writeln!(f, "{indent}{prefix}_entry -> {prefix}_{entry};")?;
+        for (index, instruction) in (1 ..).zip(&behavior.instructions) {
+            let from = format!("{prefix}_{index}");
+            if let Some(target) = target(behavior, index, &instruction.next)
+            {
+                writeln!(f, "{indent}{from} -> {prefix}_{target};")?;
+            }
+            let info = self.catalog.get_ref(&instruction.operation);
+            for (arg, jump) in instruction.jump_args(self.catalog) {
+                let Some(target) = target(behavior, index, &jump)
+                    else { continue };
+                let name = info.and_then(|info| info.args.get(arg))
+                    .map_or_else(|| format!("arg {}", arg + 1),
+                        |info| String::from(&*info.name));
+                writeln!(f, "{indent}{from} -> {prefix}_{target} \
+                    [label={}];", quote(&name) )?;
+            }
+            let Some(&Value::Integer(sub)) = instruction.extra.get("sub")
+                else { continue };
+            // calls refer to the subroutines of the outermost behavior
+            if usize::try_from(sub).ok().is_some_and(|sub|
+                sub >= 1 && sub <= self.behavior.subroutines.len() )
+            {
+                writeln!(f, "{indent}{from} -> b_{sub}_entry \
+                    [style=dashed];")?;
+            }
+        }
+        Ok(())
+    }
+
+}
+
+/// The node the jump leads to, as a suffix of the node name,
+/// or `None` if it leads nowhere.
+fn target(behavior: &Behavior, index: usize, jump: &Jump)
+-> Option<String>
+{
+    let len = behavior.instructions.len();
+    Some(match *jump {
+        Jump::Next if index < len => (index + 1).to_string(),
+        Jump::Next | Jump::Return => String::from("return"),
+        Jump::Jump(target) => usize::try_from(target).ok()
+            .filter(|&target| target >= 1 && target <= len)?
+            .to_string(),
+        Jump::Label(ref label) => (1 + behavior.instructions.iter()
+            .position(|instruction|
+                instruction.label.as_ref() == Some(label) )?)
+            .to_string(),
+    })
+}
+
+/// Prefix of the node names of the subroutine at the path.
+fn prefix(path: &[usize]) -> String {
+    let mut prefix = String::from("b");
+    for position in path {
+        prefix.push('_');
+        prefix.push_str(&(position + 1).to_string());
+    }
+    prefix
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
+
+/// A DOT string literal.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => { quoted.push('\\'); quoted.push(c); },
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use super::{
+    super::{Behavior, OpCatalog, OpInfo, ArgInfo, ArgKind},
+    to_dot, to_dot_with,
+};
+
+#[test]
+fn test_to_dot() {
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "check_number", args: [Index(3)], label: "top"),
+            (op: "call", next: Label("top"), extra: {"sub": 1}),
+            (op: "say \"hi\"", next: Jump(7)),
+        ],
+        subroutines: [Behavior(instructions: [
+            (op: "nop", next: Return),
+        ])],
+    )"#).unwrap();
+    let dot = to_dot(&behavior);
+    assert!(dot.starts_with("digraph behavior {\n"), "{dot}");
+    assert!(dot.ends_with("}\n"), "{dot}");
+    for line in [
+        "  b_entry -> b_1;",
+        "  b_1 [label=\"top\\n1: check_number\"];",
+        "  b_1 -> b_2;",
+        "  b_2 -> b_1;",
+        "  b_2 -> b_1_entry [style=dashed];",
+        "  b_3 [label=\"3: say \\\"hi\\\"\"];",
+        "  subgraph cluster_b_1 {",
+        "    label=\"sub 1\";",
+        "    b_1_1 -> b_1_return;",
+        "  }",
+    ] {
+        assert!(dot.lines().any(|l| l == line), "{line}\n{dot}");
+    }
+    // no jump arguments without a catalog, no edge for a bad jump
+    assert!(!dot.contains("b_1 -> b_3"), "{dot}");
+    assert!(!dot.lines().any(|l| l.starts_with("  b_3 ->")), "{dot}");
+
+    let catalog = OpCatalog::from_iter([
+        ("check_number", OpInfo::new(vec![
+            ArgInfo::new("if_larger", ArgKind::Exec) ])),
+    ]);
+    let named = to_dot_with(&behavior, &catalog);
+    assert!(named.contains("  b_1 -> b_3 [label=\"if_larger\"];\n"),
+        "{named}");
+    let empty = to_dot(&Behavior::default());
+    assert!(empty.contains("  b_entry -> b_return;\n"), "{empty}");
+}
+
+}
@@ -2,28 +2,128 @@
 
 use std::collections::btree_map::BTreeMap as SortedMap;
 
+#[cfg(feature = "serde")]
 use serde::{
     ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer
 };
+#[cfg(feature = "serde")]
+use crate::common::serde::Identifier;
 
 use crate::{
     error::LoadError,
     Str,
-    common::serde::Identifier,
     value::{Key, Value, Table, ArrayBuilder as TableArrayBuilder},
 };
 
-use super::{Operand, Jump};
+use super::{
+    Operand, Jump, Place, Value as OperandValue,
+    OpCatalog, OpRef, ArgKind, LabelError,
+};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub struct Instruction {
-    pub operation: Str,
+    pub operation: OpRef,
     pub args: Vec<Operand>,
+    /// Number of argument slots the encoded table declares,
+    /// if it is more than `args` (the slots past them are `nil`);
+    /// otherwise `0`.
+    /// Some operations take the number of slots into account.
+    pub arg_count: usize,
     pub next: Jump,
     pub extra: SortedMap<Str, Value>,
     pub comment: Option<Str>,
     pub offset: Option<(f64, f64)>,
+    /// Name for [`Jump::Label`] to refer to.
+    /// Not encoded, so it is always `None` on load.
+    pub label: Option<Str>,
+}
+
+impl Instruction {
+
+    /// Number of argument slots written on dump.
+    #[must_use]
+    pub fn arg_slots(&self) -> usize {
+        usize::max(self.args.len(), self.arg_count)
+    }
+
+    /// Declare `count` argument slots:
+    /// arguments past them are dropped,
+    /// and missing ones are written as `nil`.
+    pub fn set_arg_count(&mut self, count: usize) {
+        self.args.truncate(count);
+        self.arg_count = count;
+    }
+
+    /// Arguments that are alternative next instructions
+    /// (see [`ArgKind::Exec`]), with their `0`-based positions.
+    ///
+    /// Arguments the catalog does not describe are only included
+    /// if they are already jumps.
+    #[must_use]
+    pub fn jump_args(&self, catalog: &OpCatalog) -> Vec<(usize, Jump)> {
+        self.typed_args(catalog, |mut operand, kind| {
+            if kind == Some(ArgKind::Exec) {
+                operand.make_jump().ok()?;
+            }
+            match operand {
+                Operand::Jump(jump) => Some(jump),
+                _ => None,
+            }
+        })
+    }
+
+    /// Arguments that are places: all the outputs
+    /// and the inputs referring to a place,
+    /// with their `0`-based positions.
+    /// Skipped outputs are `None`.
+    ///
+    /// Arguments the catalog does not describe are only included
+    /// if they are already places.
+    #[must_use]
+    pub fn place_args(&self, catalog: &OpCatalog)
+    -> Vec<(usize, Option<Place>)>
+    {
+        self.typed_args(catalog, |mut operand, kind| {
+            match (kind, &operand) {
+                (Some(ArgKind::Out), _) |
+                (Some(ArgKind::In), &Operand::UnknownIndex(_)) =>
+                    operand.make_place().ok()?,
+                _ => (),
+            }
+            match operand {
+                Operand::Place(place) => Some(place),
+                _ => None,
+            }
+        })
+    }
+
+    /// Arguments that are values given in place,
+    /// with their `0`-based positions.
+    #[must_use]
+    pub fn value_args(&self, catalog: &OpCatalog)
+    -> Vec<(usize, OperandValue)>
+    {
+        self.typed_args(catalog, |operand, kind| match (kind, operand) {
+            (Some(ArgKind::Exec | ArgKind::Out), _) => None,
+            (_, Operand::Value(value)) => value,
+            (_, _) => None,
+        })
+    }
+
+    fn typed_args<T, F>(&self, catalog: &OpCatalog, mut f: F)
+    -> Vec<(usize, T)>
+    where F: FnMut(Operand, Option<ArgKind>) -> Option<T>
+    {
+        let info = catalog.get_ref(&self.operation);
+        self.args.iter().enumerate().filter_map(|(index, operand)| {
+            let kind = info.and_then(|info| info.args.get(index))
+                .map(|arg_info| arg_info.kind);
+            Some((index, f(operand.clone(), kind)?))
+        }).collect()
+    }
+
 }
 
 impl TryFrom<Value> for Instruction {
@@ -46,18 +146,21 @@ impl TryFrom<Table> for Instruction {
 
 #[derive(Default)]
 struct InstructionBuilder {
-    operation: Option<Str>,
+    operation: Option<OpRef>,
     args: Vec<Operand>,
+    arg_count: usize,
     next: Option<Jump>,
     extra: SortedMap<Str, Value>,
     comment: Option<Str>,
     offset: (Option<f64>, Option<f64>),
+    label: Option<Str>,
 }
 
 impl InstructionBuilder {
 
     fn build_from(table: Table) -> Result<Instruction, LoadError> {
         let mut this = Self::default();
+        let declared_len = table.declared_array_len();
         let mut array = Vec::new();
         // Technically, instructions can have unlimited number
         // of arguments, and all of them can be None. But if
@@ -95,8 +198,12 @@ impl InstructionBuilder {
                         };
                     },
                 },
+                Key::Bool(_) => return Err(Self::err_unexpected_key(key)),
             }
         }
+        if declared_len > array.len() {
+            this.arg_count = declared_len;
+        }
         this.args.reserve_exact(array.len());
         for value in array {
             this.args.push(Operand::try_from(value)?);
@@ -108,9 +215,11 @@ impl InstructionBuilder {
         "instruction representation should not have {key:?} key" )) }
 
     fn set_operation(&mut self, value: Value) -> Result<(), LoadError> {
-        let Value::String(value) = value else {
-            return Err(LoadError::from(
-                "instruction's operation should be a string" ));
+        let value = match value {
+            Value::String(name) => OpRef::Name(name),
+            Value::Integer(id) => OpRef::Id(id),
+            _ => return Err(LoadError::from(
+                "instruction's operation should be a string or an integer" )),
         };
         self.operation = Some(value); Ok(())
     }
@@ -146,25 +255,29 @@ impl InstructionBuilder {
 
     fn build(self) -> Result<Instruction, LoadError> {
         let Self {
-            operation, args, next,
+            operation, args, arg_count, next,
             extra,
             comment,
             offset,
+            label,
         } = self;
         let next = Jump::unwrap_option(next);
         let Some(operation) = operation else {
             return Err(LoadError::from(
                 "Operation must be represented with a non-nil `op` field" ));
         };
+        let arg_count = if arg_count > args.len() { arg_count } else { 0 };
         Ok(Instruction {
-            operation, args, next,
+            operation, args, arg_count, next,
             extra,
             comment,
             offset: Option::zip(offset.0, offset.1),
+            label,
         })
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> serde::de::Visitor<'de> for InstructionBuilder {
     type Value = Instruction;
 
@@ -181,10 +294,12 @@ impl<'de> serde::de::Visitor<'de> for InstructionBuilder {
             match name.as_ref() {
                 "op"      => self.operation = Some(map.next_value()?),
                 "args"    => self.args      = map.next_value()?,
+                "arg_count" => self.arg_count = map.next_value()?,
                 "next"    => self.next      = Some(map.next_value()?),
                 "comment" => self.comment   = Some(map.next_value()?),
                 "offset"  => self.offset    = Some(map.next_value::<>()?).unzip(),
                 "extra"   => self.extra     = map.next_value()?,
+                "label"   => self.label     = Some(map.next_value()?),
                 _ => return Err(A::Error::custom(
                     format!("instruction should not have “{name:?}” key") ))
             }
@@ -194,14 +309,34 @@ impl<'de> serde::de::Visitor<'de> for InstructionBuilder {
 
 }
 
-impl From<Instruction> for Value {
-    fn from(this: Instruction) -> Value {
+/// Fails on jumps to labels,
+/// which only a behavior can resolve (see [`Behavior::resolve_labels`]).
+///
+/// [`Behavior::resolve_labels`]: super::Behavior::resolve_labels
+impl TryFrom<Instruction> for Value {
+    type Error = LabelError;
+    fn try_from(this: Instruction) -> Result<Value, LabelError> {
+        let labels = std::iter::once(&this.next)
+            .chain(this.args.iter().filter_map(|arg| match *arg {
+                Operand::Jump(ref jump) => Some(jump),
+                _ => None,
+            }));
+        for jump in labels {
+            if let Jump::Label(ref label) = *jump {
+                return Err(LabelError::Unresolved { label: label.clone() });
+            }
+        }
         let mut table_array = TableArrayBuilder::new();
         table_array.extend( this.args.into_iter()
             .map(Option::<Value>::from) );
-        let mut table = table_array.build().into_builder();
+        let mut table_array = table_array.build();
+        table_array.set_declared_array_len(this.arg_count);
+        let mut table = table_array.into_builder();
         table.extend([
-            ("op"  , Some(Value::String(this.operation))),
+            ("op"  , Some(match this.operation {
+                OpRef::Name(name) => Value::String(name),
+                OpRef::Id(id) => Value::Integer(id),
+            })),
             ("next", Option::<Value>::from(this.next)),
             ("cmt" , this.comment.map(Value::String)),
             ("nx"  , this.offset.map(|(x,_)| Value::Float(x))),
@@ -215,10 +350,11 @@ impl From<Instruction> for Value {
             }
             (Key::Name(name), value)
         })));
-        Value::Table(table.build())
+        Ok(Value::Table(table.build()))
     }
 }
 
+#[cfg(feature = "serde")]
 impl Serialize for Instruction {
     fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
     where S: Serializer
@@ -227,9 +363,11 @@ impl Serialize for Instruction {
             "Instruction",
             2 // op and next
             + usize::from(!self.args.is_empty())
+            + usize::from(self.arg_count > self.args.len())
             + usize::from(!self.extra.is_empty())
             + usize::from(self.comment.is_some())
             + usize::from(self.offset.is_some())
+            + usize::from(self.label.is_some())
         )?;
 
         ser.serialize_field("op", &self.operation)?;
@@ -238,6 +376,10 @@ impl Serialize for Instruction {
             ser.serialize_field("args", &self.args)?;
         } else { ser.skip_field("args")?; }
 
+        if self.arg_count > self.args.len() {
+            ser.serialize_field("arg_count", &self.arg_count)?;
+        } else { ser.skip_field("arg_count")?; }
+
         ser.serialize_field("next", &self.next)?;
 
         if !self.extra.is_empty() {
@@ -252,10 +394,15 @@ impl Serialize for Instruction {
             ser.serialize_field("offset", offset)?;
         } else { ser.skip_field("offset")?; }
 
+        if let Some(ref label) = self.label {
+            ser.serialize_field("label", label)?;
+        } else { ser.skip_field("label")?; }
+
         ser.end()
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Instruction {
     fn deserialize<D>(de: D) -> Result<Self, D::Error>
     where D: Deserializer<'de>
@@ -265,3 +412,108 @@ impl<'de> Deserialize<'de> for Instruction {
     }
 }
 
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::value::{Key, Value as _Value, Table};
+
+use super::super::{
+    Instruction, Operand, Jump, Place, Register, Value,
+    OpCatalog, OpInfo, OpRef,
+};
+
+#[test]
+fn test_typed_args() {
+    let catalog: OpCatalog = ron::from_str(r#"{
+        "check_number": (args: [
+            (name: "if_larger", kind: Exec),
+            (name: "value", kind: In),
+            (name: "compare", kind: In),
+        ]),
+        "set_reg": (args: [
+            (name: "value", kind: In),
+            (name: "to", kind: Out),
+        ]),
+    }"#).unwrap();
+    let check: Instruction = ron::from_str(r#"(
+        op: "check_number",
+        args: [Index(3), Index(1), Number(5)],
+    )"#).unwrap();
+    assert_eq!(check.jump_args(&catalog), [(0, Jump::Jump(3))]);
+    assert_eq!( check.place_args(&catalog),
+        [(1, Some(Place::Parameter(1)))] );
+    assert_eq!(check.value_args(&catalog), [(2, Value::Number(5))]);
+    let set: Instruction = ron::from_str(r#"(
+        op: "set_reg", args: [Skipped, Register(Signal)],
+    )"#).unwrap();
+    assert!(set.jump_args(&catalog).is_empty());
+    assert_eq!( set.place_args(&catalog),
+        [(1, Some(Place::Register(Register::Signal)))] );
+    assert!(set.value_args(&catalog).is_empty());
+    let unknown: Instruction = ron::from_str(r#"(
+        op: "unknown", args: [Index(3), Jump(2)],
+    )"#).unwrap();
+    assert_eq!(unknown.jump_args(&catalog), [(1, Jump::Jump(2))]);
+    assert!(unknown.place_args(&catalog).is_empty());
+}
+
+#[test]
+fn test_arg_count() {
+    let mut table = Table::from_iter([
+        (Key::Index(1), _Value::Integer(1)),
+        (Key::from("op"), _Value::String("nop".into())),
+    ]);
+    table.set_declared_array_len(3);
+    let mut instruction = Instruction::try_from(table.clone()).unwrap();
+    assert_eq!(instruction.args.len(), 1);
+    assert_eq!((instruction.arg_count, instruction.arg_slots()), (3, 3));
+    let _Value::Table(dumped) = _Value::try_from(instruction.clone()).unwrap()
+        else { panic!("should be a table") };
+    assert_eq!(dumped.array_part_len(), 3);
+    assert_eq!(dumped, table);
+    let json = serde_json::to_string(&instruction).unwrap();
+    assert!(json.contains(r#""arg_count":3"#), "{json}");
+    let parsed: Instruction = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.arg_count, 3);
+
+    instruction.args.push(Operand::Value(Some(Value::Number(2))));
+    instruction.set_arg_count(1);
+    assert_eq!(instruction.args.len(), 1);
+    assert_eq!(instruction.arg_slots(), 1);
+    let _Value::Table(dumped) = _Value::try_from(instruction).unwrap()
+        else { panic!("should be a table") };
+    assert_eq!(dumped.array_part_len(), 1);
+    let table = Table::from_iter([
+        (Key::Index(1), _Value::Integer(1)),
+        (Key::from("op"), _Value::String("nop".into())),
+    ]);
+    assert_eq!(Instruction::try_from(table).unwrap().arg_count, 0);
+}
+
+#[test]
+fn test_op_id() {
+    let table = Table::from_iter([(Key::from("op"), _Value::Integer(7))]);
+    let instruction = Instruction::try_from(table.clone()).unwrap();
+    assert_eq!(instruction.operation, OpRef::Id(7));
+    assert_eq!( _Value::try_from(instruction.clone()).unwrap(),
+        _Value::Table(table) );
+    let catalog = OpCatalog::from_iter([
+        ("nop", OpInfo::new(Vec::new()).with_id(7)) ]);
+    assert_eq!(instruction.operation.name(&catalog), Some("nop"));
+    assert!(catalog.get_ref(&instruction.operation).is_some());
+    let named = instruction.operation.clone().into_named(&catalog);
+    assert_eq!(named, OpRef::from("nop"));
+    assert_eq!(named.id(&catalog), Some(7));
+    assert_eq!(OpRef::Id(8).into_named(&catalog), OpRef::Id(8));
+    assert_eq!(instruction.to_string(), "7");
+    let json = serde_json::to_string(&instruction).unwrap();
+    let parsed: Instruction = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.operation, OpRef::Id(7));
+    let parsed: Instruction = serde_json::from_str(r#"{"op":"nop"}"#)
+        .unwrap();
+    assert_eq!(parsed.operation, *"nop");
+    Instruction::try_from(Table::from_iter([
+        (Key::from("op"), _Value::Boolean(true)) ])).unwrap_err();
+}
+
+}
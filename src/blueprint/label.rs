@@ -0,0 +1,168 @@
+//! Symbolic jump targets (see [`Jump::Label`]).
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::Str;
+
+use super::{Blueprint, Behavior, Operand, Jump};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LabelError {
+    #[error("Label {label:?} is given to more than one instruction")]
+    Duplicate { label: Str },
+    #[error("Instruction {index} jumps to unknown label {label:?}")]
+    Unknown { index: usize, label: Str },
+    /// An instruction converted on its own jumps to a label.
+    #[error("Jump to label {label:?} is not resolved")]
+    Unresolved { label: Str },
+}
+
+impl Behavior {
+
+    /// Replace [`Jump::Label`] jumps of the behavior
+    /// and its subroutines with [`Jump::Jump`] to the instruction
+    /// with the label.
+    /// Each subroutine has its own labels.
+    ///
+    /// This is done on dump by [`dump_blueprint`]
+    /// and on conversion of the behavior into a [`Value`],
+    /// both of which fail on jumps to unknown labels.
+    /// Labels of instructions are kept.
+    ///
+    /// On error the behavior is left unchanged.
+    ///
+    /// [`dump_blueprint`]: super::dump_blueprint
+    /// [`Value`]: crate::value::Value
+    pub fn resolve_labels(&mut self) -> Result<(), LabelError> {
+        let mut resolved = self.clone();
+        resolved.try_for_each_with_subroutines_mut(Self::resolve_own_labels)?;
+        *self = resolved;
+        Ok(())
+    }
+
+    /// Resolve labels of the behavior itself, not of subroutines.
+    /// Labels that do resolve are resolved even on error.
+    pub(super) fn resolve_own_labels(&mut self)
+    -> Result<(), LabelError>
+    {
+        let targets = self.label_targets()?;
+        let mut result = Ok(());
+        let mut resolve = |index: usize, jump: &mut Jump| {
+            let Jump::Label(ref label) = *jump else { return };
+            match targets.get(label) {
+                Some(&target) => *jump = Jump::Jump(target),
+                None if result.is_ok() =>
+                    result = Err(LabelError::Unknown {
+                        index, label: label.clone() }),
+                None => (),
+            }
+        };
+        for (index, instruction) in
+            self.instructions.iter_mut().enumerate()
+        {
+            resolve(index, &mut instruction.next);
+            for arg in &mut instruction.args {
+                if let Operand::Jump(ref mut jump) = *arg {
+                    resolve(index, jump);
+                }
+            }
+        }
+        result
+    }
+
+    /// `1`-based indices of labeled instructions.
+    fn label_targets(&self) -> Result<HashMap<Str, i32>, LabelError> {
+        let mut targets = HashMap::new();
+        for (index, instruction) in (1_i32 ..).zip(&self.instructions) {
+            let Some(ref label) = instruction.label else { continue };
+            if targets.insert(label.clone(), index).is_some() {
+                return Err(LabelError::Duplicate { label: label.clone() });
+            }
+        }
+        Ok(targets)
+    }
+
+}
+
+impl Blueprint {
+
+    /// [`Behavior::resolve_labels`] for behaviors of the components.
+    ///
+    /// On error the blueprint is left unchanged.
+    pub fn resolve_labels(&mut self) -> Result<(), LabelError> {
+        let mut resolved = self.components.clone();
+        for component in &mut resolved {
+            if let Some(ref mut behavior) = component.behavior {
+                behavior.resolve_labels()?;
+            }
+        }
+        self.components = resolved;
+        Ok(())
+    }
+
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::{
+    Exchange,
+    value::Value,
+};
+
+use super::super::{
+    Behavior, Operand, Jump, LabelError,
+    dump_blueprint, load_blueprint,
+};
+
+#[test]
+fn test_labels() {
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "check_number", args: [Label("end")], next: Label("top"),
+                label: "top"),
+            (op: "nop", label: "end"),
+        ],
+        subroutines: [Behavior(instructions: [
+            (op: "nop", next: Label("end")),
+            (op: "nop", label: "end"),
+        ])],
+    )"#).unwrap();
+    let json = serde_json::to_string(&behavior).unwrap();
+    assert!(json.contains(r#""next":{"Label":"top"}"#), "{json}");
+    let mut resolved = behavior.clone();
+    resolved.resolve_labels().unwrap();
+    assert_eq!(resolved.instructions[0].next, Jump::Jump(1));
+    assert_eq!( resolved.instructions[0].args[0],
+        Operand::Jump(Jump::Jump(2)) );
+    assert_eq!(resolved.subroutines[0].instructions[0].next, Jump::Jump(2));
+    assert_eq!( Value::try_from(behavior.clone()).unwrap(),
+        Value::try_from(resolved.clone()).unwrap() );
+    let dumped = dump_blueprint(Exchange::Behavior(behavior)).unwrap();
+    let Exchange::Behavior(loaded) = load_blueprint(&dumped).unwrap()
+        else { panic!("should be a behavior") };
+    assert_eq!(loaded.instructions[0].next, Jump::Jump(1));
+    assert_eq!(loaded.instructions[1].label, None);
+
+    let mut unknown = resolved.clone();
+    unknown.instructions[1].next = Jump::Label("nowhere".into());
+    let Err(LabelError::Unknown { index: 1, .. }) =
+        unknown.resolve_labels()
+        else { panic!("should be an unknown label") };
+    let Err(LabelError::Unknown { index: 1, .. }) =
+        Value::try_from(unknown.clone())
+        else { panic!("should be an unknown label") };
+    unknown.to_faithful().unwrap_err();
+    dump_blueprint(Exchange::Behavior(unknown)).unwrap_err();
+    let mut duplicate = resolved;
+    duplicate.subroutines[0].instructions[0].label = Some("end".into());
+    duplicate.instructions[0].next = Jump::Label("top".into());
+    let Err(LabelError::Duplicate { .. }) = duplicate.resolve_labels()
+        else { panic!("should be a duplicate label") };
+    assert_eq!(duplicate.instructions[0].next, Jump::Label("top".into()));
+}
+
+}
@@ -0,0 +1,309 @@
+//! Checks of behaviors for likely mistakes, e.g. for editors.
+//!
+//! Unlike [`Blueprint::validate`](super::Blueprint::validate),
+//! these do not prevent the behavior from loading in the game;
+//! each rule can be given its [`Severity`] or turned off.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::value::Value;
+
+use super::{Behavior, Jump, OpCatalog, analysis::variable_usage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub enum LintRule {
+    /// A jump to an instruction that does not exist,
+    /// or to a label no instruction has (see [`Jump::Label`]).
+    InvalidJump,
+    /// A parameter that no instruction refers to.
+    UnusedParameter,
+    /// A `sub` reference that is not a `1`-based position
+    /// in the subroutines of the linted behavior.
+    InvalidSubroutine,
+    /// More argument slots than the operation takes
+    /// according to [`LintConfig::catalog`].
+    TooManyOperands,
+}
+
+impl LintRule {
+
+    /// Severity of the rule unless [`LintConfig::rules`] says otherwise.
+    #[must_use]
+    pub fn default_severity(self) -> Severity {
+        match self {
+            LintRule::InvalidJump | LintRule::InvalidSubroutine =>
+                Severity::Error,
+            LintRule::UnusedParameter | LintRule::TooManyOperands =>
+                Severity::Warning,
+        }
+    }
+
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LintConfig {
+    /// Operations, to check the number of operands
+    /// and to tell jumps and parameters among arguments.
+    /// Empty by default.
+    pub catalog: OpCatalog,
+    /// Severity of rules, `None` to turn a rule off;
+    /// rules not listed have their [`LintRule::default_severity`].
+    pub rules: BTreeMap<LintRule, Option<Severity>>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            catalog: OpCatalog::new(),
+            rules: BTreeMap::new(),
+        }
+    }
+}
+
+impl LintConfig {
+
+    /// Severity of the rule, or `None` if it is turned off.
+    #[must_use]
+    pub fn severity(&self, rule: LintRule) -> Option<Severity> {
+        match self.rules.get(&rule) {
+            Some(&severity) => severity,
+            None => Some(rule.default_severity()),
+        }
+    }
+
+}
+
+/// Where a [`Lint`] is found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub struct Span {
+    /// `0`-based positions of subroutines leading
+    /// from the linted behavior to the one the lint is in;
+    /// empty for the linted behavior itself.
+    pub subroutine: Vec<usize>,
+    /// `0`-based position of the instruction.
+    pub instruction: Option<usize>,
+    /// `0`-based position of the argument of the instruction.
+    pub operand: Option<usize>,
+    /// `0`-based position of the parameter.
+    pub parameter: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub struct Lint {
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+/// Check the behavior and its subroutines
+/// against the rules that are turned on in `config`.
+///
+/// Lints are ordered by subroutine, then by rule.
+#[must_use]
+pub fn lint(behavior: &Behavior, config: &LintConfig) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let subroutine_count = behavior.subroutines.len();
+    let mut behaviors = vec![(Vec::new(), behavior)];
+    while let Some((path, current)) = behaviors.pop() {
+        let mut linter = Linter { config, lints: &mut lints, path: &path };
+        linter.invalid_jumps(current);
+        linter.unused_parameters(current);
+        linter.invalid_subroutines(current, subroutine_count);
+        linter.too_many_operands(current);
+        behaviors.extend( current.subroutines.iter().enumerate().rev()
+            .map(|(index, subroutine)| {
+                let mut path = path.clone();
+                path.push(index);
+                (path, subroutine)
+            }) );
+    }
+    lints
+}
+
+struct Linter<'c, 'l, 'p> {
+    config: &'c LintConfig,
+    lints: &'l mut Vec<Lint>,
+    path: &'p [usize],
+}
+
+impl Linter<'_, '_, '_> {
+
+    fn push(&mut self, rule: LintRule, span: Span, message: String) {
+        let Some(severity) = self.config.severity(rule) else { return };
+        self.lints.push(Lint {
+            rule, severity,
+            span: Span { subroutine: self.path.to_vec(), ..span },
+            message,
+        });
+    }
+
+    fn invalid_jumps(&mut self, behavior: &Behavior) {
+        if self.config.severity(LintRule::InvalidJump).is_none() {
+            return;
+        }
+        let len = behavior.instructions.len();
+        for (index, instruction) in behavior.instructions.iter().enumerate()
+        {
+            let args = instruction.jump_args(&self.config.catalog)
+                .into_iter()
+                .map(|(arg, jump)| (Some(arg), jump));
+            for (operand, jump) in
+                std::iter::once((None, instruction.next.clone())).chain(args)
+            {
+                let message = match jump {
+                    Jump::Jump(target) if !usize::try_from(target).ok()
+                        .is_some_and(|target| target >= 1 && target <= len) =>
+                        format!("jump to nonexistent instruction {target}"),
+                    Jump::Label(ref label) if !behavior.instructions.iter()
+                        .any(|other| other.label.as_ref() == Some(label)) =>
+                        format!("jump to unknown label {:?}", &**label),
+                    _ => continue,
+                };
+                self.push( LintRule::InvalidJump, Span {
+                    instruction: Some(index), operand,
+                    ..Span::default()
+                }, message );
+            }
+        }
+    }
+
+    fn unused_parameters(&mut self, behavior: &Behavior) {
+        if self.config.severity(LintRule::UnusedParameter).is_none() {
+            return;
+        }
+        let report = variable_usage(behavior, &self.config.catalog);
+        for index in report.unused_parameters {
+            let Some(position) = index.checked_sub(1)
+                .and_then(|position| usize::try_from(position).ok())
+                else { continue };
+            self.push( LintRule::UnusedParameter, Span {
+                parameter: Some(position),
+                ..Span::default()
+            }, format!("parameter {index} is not used") );
+        }
+    }
+
+    fn invalid_subroutines(&mut self, behavior: &Behavior, count: usize) {
+        for (index, instruction) in behavior.instructions.iter().enumerate()
+        {
+            let Some(sub) = instruction.extra.get("sub") else { continue };
+            let message = match *sub {
+                Value::Integer(sub) if usize::try_from(sub).ok()
+                    .is_some_and(|sub| sub >= 1 && sub <= count) =>
+                    continue,
+                Value::Integer(sub) =>
+                    format!("call of nonexistent subroutine {sub}"),
+                _ => String::from("subroutine reference is not an integer"),
+            };
+            self.push( LintRule::InvalidSubroutine, Span {
+                instruction: Some(index),
+                ..Span::default()
+            }, message );
+        }
+    }
+
+    fn too_many_operands(&mut self, behavior: &Behavior) {
+        for (index, instruction) in behavior.instructions.iter().enumerate()
+        {
+            let Some(info) = self.config.catalog
+                .get_ref(&instruction.operation)
+                else { continue };
+            let arity = info.args.len();
+            if instruction.arg_slots() <= arity {
+                continue;
+            }
+            self.push( LintRule::TooManyOperands, Span {
+                instruction: Some(index), operand: Some(arity),
+                ..Span::default()
+            }, format!( "operation {} takes {arity} operands, \
+                but the instruction has {}",
+                instruction.operation, instruction.arg_slots() ) );
+        }
+    }
+
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use super::{
+    super::{Behavior, OpCatalog, OpInfo, ArgInfo, ArgKind},
+    lint, LintConfig, LintRule, Severity,
+};
+
+#[test]
+fn test_lint() {
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        parameters: [
+            (name: "used", is_output: false),
+            (name: "unused", is_output: false),
+        ],
+        instructions: [
+            (op: "check_number", args: [Index(5), Parameter(1)]),
+            (op: "call", next: Jump(3), extra: {"sub": 2}),
+            (op: "nop", args: [Skipped], next: Label("nowhere")),
+        ],
+        subroutines: [Behavior(instructions: [
+            (op: "call", next: Jump(0), extra: {"sub": 1}),
+        ])],
+    )"#).unwrap();
+    let mut config = LintConfig::default();
+    config.catalog = OpCatalog::from_iter([
+        ("check_number", OpInfo::new(vec![
+            ArgInfo::new("if_larger", ArgKind::Exec),
+            ArgInfo::new("value", ArgKind::In),
+        ])),
+        ("nop", OpInfo::new(Vec::new())),
+    ]);
+    let lints = lint(&behavior, &config);
+    let found: Vec<_> = lints.iter()
+        .map(|lint| (
+            lint.rule, lint.severity, lint.span.subroutine.clone(),
+            lint.span.instruction, lint.span.operand, lint.span.parameter,
+        ))
+        .collect();
+    assert_eq!(found, [
+        (LintRule::InvalidJump, Severity::Error, vec![],
+            Some(0), Some(0), None),
+        (LintRule::InvalidJump, Severity::Error, vec![],
+            Some(2), None, None),
+        (LintRule::UnusedParameter, Severity::Warning, vec![],
+            None, None, Some(1)),
+        (LintRule::InvalidSubroutine, Severity::Error, vec![],
+            Some(1), None, None),
+        (LintRule::TooManyOperands, Severity::Warning, vec![],
+            Some(2), Some(0), None),
+        (LintRule::InvalidJump, Severity::Error, vec![0],
+            Some(0), None, None),
+    ]);
+    assert!(lints[0].message.contains('5'), "{}", lints[0].message);
+
+    config.rules.insert(LintRule::InvalidJump, None);
+    config.rules.insert(LintRule::UnusedParameter, Some(Severity::Error));
+    let lints = lint(&behavior, &config);
+    assert!(lints.iter().all(|lint| lint.rule != LintRule::InvalidJump));
+    assert_eq!( lints[0].rule, LintRule::UnusedParameter);
+    assert_eq!( lints[0].severity, Severity::Error);
+    assert!(lint(&Behavior::default(), &config).is_empty());
+}
+
+}
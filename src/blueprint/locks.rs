@@ -0,0 +1,86 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use crate::common::serde::vec_option_wrap as serde_vec_option_wrap;
+
+use crate::Str;
+
+/// Items that the inventory slots of a blueprint are locked to.
+///
+/// Slots are numbered from `0`; unlocked slots are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub struct Locks {
+    #[cfg_attr(feature = "serde", serde(with="serde_vec_option_wrap"))]
+    pub slots: Vec<Option<Str>>,
+}
+
+impl Locks {
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// The item the slot is locked to.
+    #[must_use]
+    pub fn get(&self, slot: usize) -> Option<&Str> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    /// Lock the slot to the item, or unlock it with `None`.
+    /// Trailing unlocked slots are dropped.
+    pub fn set(&mut self, slot: usize, item: Option<Str>) {
+        if slot >= self.slots.len() {
+            if item.is_none() {
+                return;
+            }
+            self.slots.resize(slot + 1, None);
+        }
+        self.slots[slot] = item;
+        while self.slots.last() == Some(&None) {
+            self.slots.pop();
+        }
+    }
+
+    /// Locked slots with their items.
+    pub fn iter(&self) -> impl Iterator<Item=(usize, &Str)> + '_ {
+        self.slots.iter().enumerate()
+            .filter_map(|(slot, item)| Some((slot, item.as_ref()?)))
+    }
+
+}
+
+impl From<Vec<Option<Str>>> for Locks {
+    fn from(slots: Vec<Option<Str>>) -> Self {
+        Self { slots }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::Str;
+
+use super::Locks;
+
+#[test]
+fn test_locks() {
+    let mut locks = Locks::default();
+    locks.set(2, Some(Str::from("metalore")));
+    locks.set(0, Some(Str::from("crystal")));
+    assert_eq!(locks.slots.len(), 3);
+    assert_eq!(locks.get(1), None);
+    assert_eq!(locks.get(2), Some(&Str::from("metalore")));
+    assert_eq!(locks.iter().map(|(slot, _)| slot).collect::<Vec<_>>(), [0, 2]);
+    locks.set(5, None);
+    locks.set(2, None);
+    assert_eq!(locks.slots, [Some(Str::from("crystal"))]);
+    locks.set(0, None);
+    assert!(locks.is_empty());
+}
+
+}
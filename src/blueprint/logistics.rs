@@ -0,0 +1,203 @@
+use std::collections::btree_map::BTreeMap as SortedMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Str, value::Value};
+
+/// Blueprint logistics settings.
+///
+/// The known flags are fields; a flag that is absent
+/// (which the game treats as its default) is `None`.
+/// Settings with other names, and known names with non-boolean values,
+/// are kept in `other`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde( from="SortedMap<Str, LogisticsValue>",
+    into="SortedMap<Str, LogisticsValue>" ))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub struct Logistics {
+    pub carrier: Option<bool>,
+    pub requester: Option<bool>,
+    pub supplier: Option<bool>,
+    pub channel_1: Option<bool>,
+    pub channel_2: Option<bool>,
+    pub channel_3: Option<bool>,
+    pub channel_4: Option<bool>,
+    pub high_priority: Option<bool>,
+    pub crane_only: Option<bool>,
+    /// E.g. `transport_route`.
+    pub other: SortedMap<Str, LogisticsValue>,
+}
+
+const FLAG_NAMES: [&str; 9] = [
+    "carrier", "requester", "supplier",
+    "channel_1", "channel_2", "channel_3", "channel_4",
+    "high_priority", "crane_only",
+];
+
+impl Logistics {
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.other.is_empty() &&
+            FLAG_NAMES.iter().all(|&name| self.flag(name).is_none())
+    }
+
+    fn flag(&self, name: &str) -> Option<bool> {
+        match name {
+            "carrier"       => self.carrier,
+            "requester"     => self.requester,
+            "supplier"      => self.supplier,
+            "channel_1"     => self.channel_1,
+            "channel_2"     => self.channel_2,
+            "channel_3"     => self.channel_3,
+            "channel_4"     => self.channel_4,
+            "high_priority" => self.high_priority,
+            "crane_only"    => self.crane_only,
+            _ => None,
+        }
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut Option<bool>> {
+        Some(match name {
+            "carrier"       => &mut self.carrier,
+            "requester"     => &mut self.requester,
+            "supplier"      => &mut self.supplier,
+            "channel_1"     => &mut self.channel_1,
+            "channel_2"     => &mut self.channel_2,
+            "channel_3"     => &mut self.channel_3,
+            "channel_4"     => &mut self.channel_4,
+            "high_priority" => &mut self.high_priority,
+            "crane_only"    => &mut self.crane_only,
+            _ => return None,
+        })
+    }
+
+    /// Set a setting by its name, as it is encoded.
+    pub fn insert(&mut self, name: Str, value: LogisticsValue) {
+        if let LogisticsValue::Flag(flag) = value {
+            if let Some(field) = self.flag_mut(&name) {
+                *field = Some(flag);
+                self.other.remove(&name);
+                return;
+            }
+        }
+        if let Some(field) = self.flag_mut(&name) {
+            *field = None;
+        }
+        self.other.insert(name, value);
+    }
+
+}
+
+impl FromIterator<(Str, LogisticsValue)> for Logistics {
+    fn from_iter<I>(iter: I) -> Self
+    where I: IntoIterator<Item=(Str, LogisticsValue)>
+    {
+        let mut this = Self::default();
+        for (name, value) in iter {
+            this.insert(name, value);
+        }
+        this
+    }
+}
+
+impl From<SortedMap<Str, LogisticsValue>> for Logistics {
+    fn from(map: SortedMap<Str, LogisticsValue>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl IntoIterator for Logistics {
+    type Item = (Str, LogisticsValue);
+    type IntoIter = std::collections::btree_map::IntoIter<Str, LogisticsValue>;
+    /// Settings by their names, in the order of names.
+    fn into_iter(self) -> Self::IntoIter {
+        let flags: Vec<(Str, LogisticsValue)> = FLAG_NAMES.into_iter()
+            .filter_map(|name| Some((
+                Str::known(name), LogisticsValue::Flag(self.flag(name)?) )))
+            .collect();
+        let mut map = self.other;
+        map.extend(flags);
+        map.into_iter()
+    }
+}
+
+impl From<Logistics> for SortedMap<Str, LogisticsValue> {
+    fn from(this: Logistics) -> Self {
+        this.into_iter().collect()
+    }
+}
+
+/// Value of a blueprint logistics setting.
+///
+/// Most settings (`carrier`, `requester`, `channel_1`, …)
+/// are boolean flags.
+/// Some (notably `transport_route`) may hold values
+/// of other types; these are carried as is.
+/// No exported blueprint with a `transport_route` was at hand,
+/// so its shape is not known; any value is accepted.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum LogisticsValue {
+    Flag(bool),
+    Other(Value),
+}
+
+impl LogisticsValue {
+    #[must_use]
+    pub fn as_flag(&self) -> Option<bool> {
+        match *self {
+            Self::Flag(flag) => Some(flag),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl From<bool> for LogisticsValue {
+    fn from(flag: bool) -> Self {
+        Self::Flag(flag)
+    }
+}
+
+impl From<Value> for LogisticsValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Boolean(flag) => Self::Flag(flag),
+            value => Self::Other(value),
+        }
+    }
+}
+
+impl From<LogisticsValue> for Value {
+    fn from(this: LogisticsValue) -> Self {
+        match this {
+            LogisticsValue::Flag(flag) => Value::Boolean(flag),
+            LogisticsValue::Other(value) => value,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for LogisticsValue {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>
+    {
+        Ok(Self::from(Value::deserialize(de)?))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for LogisticsValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where S: Serializer
+    {
+        match self {
+            Self::Flag(flag) => flag.serialize(ser),
+            Self::Other(value) => value.serialize(ser),
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Combining two blueprints or two behaviors into one.
+
+use thiserror::Error;
+
+use crate::value::{Key, Value as _Value};
+
+use super::{
+    Blueprint, Component, Behavior, Instruction, Operand, Jump, Place,
+    RegisterAddress, RegisterLayout,
+    edit::for_each_jump,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MergeError {
+    /// Integer operands are not resolved to jumps or parameters on load
+    /// (see [`Operand::make_jump`] and [`Operand::make_place`]),
+    /// and they would need different renumbering.
+    #[error( "Argument {arg} of instruction {index} \
+              can be either a jump or a parameter" )]
+    AmbiguousOperand { index: usize, arg: usize },
+    #[error("Register link {link:?} does not fit the register layout")]
+    InvalidLink { link: (i32, i32) },
+    #[error("There are more registers than the register layout allows")]
+    ExcessRegisters,
+    /// A renumbered index (of a component, a jump, a parameter
+    /// or a subroutine) does not fit into an `i32`.
+    #[error("Renumbered index does not fit into an integer")]
+    IndexOverflow,
+    /// Both behaviors keep the key in [`Behavior::extra`],
+    /// with different values.
+    #[error("Both behaviors have different values for {key:?} key")]
+    ConflictingExtra { key: Key },
+}
+
+impl Blueprint {
+
+    /// Append the components of `other`, along with their registers
+    /// and the register links of `other`.
+    ///
+    /// Component indices of `other` are shifted past the largest
+    /// component index of `self`.
+    /// Links to the frame registers of `other`
+    /// are attached to the frame registers of `self`;
+    /// the values of the frame registers of `other` are dropped.
+    /// `register_count` should return the number of registers
+    /// of the component (see [`Blueprint::register_layout`]).
+    ///
+    /// On error the blueprint is left unchanged.
+    pub fn merge_components<F>(&mut self, other: &Blueprint,
+        mut register_count: F,
+    ) -> Result<(), MergeError>
+    where F: FnMut(&Component) -> usize
+    {
+        let self_counts: Vec<usize> = self.components.iter()
+            .map(&mut register_count).collect();
+        let other_counts: Vec<usize> = other.components.iter()
+            .map(&mut register_count).collect();
+        let self_layout = RegisterLayout::new(self_counts.iter().copied());
+        let other_layout = RegisterLayout::new(other_counts.iter().copied());
+        if self.registers.len() > self_layout.len() ||
+            other.registers.len() > other_layout.len()
+        {
+            return Err(MergeError::ExcessRegisters);
+        }
+        let layout = RegisterLayout::new(
+            self_counts.iter().chain(&other_counts).copied() );
+        let component_offset = self.components.len();
+        let links = other.links.iter().map(|&link| {
+            let (x, y) = other_layout.resolve_link(link)
+                .ok_or(MergeError::InvalidLink { link })?;
+            let reindex = |address| layout.index(match address {
+                RegisterAddress::Component { component, register } =>
+                    RegisterAddress::Component {
+                        component: component + component_offset,
+                        register },
+                address @ RegisterAddress::Frame(_) => address,
+            }).ok_or(MergeError::InvalidLink { link });
+            Ok((reindex(x)?, reindex(y)?))
+        }).collect::<Result<Vec<_>, MergeError>>()?;
+
+        let index_offset = self.components.iter()
+            .map(|component| component.index).max().unwrap_or(0);
+        let components = other.components.iter().map(|component| {
+            let mut component = component.clone();
+            shift(&mut component.index, index_offset)?;
+            Ok(component)
+        }).collect::<Result<Vec<_>, MergeError>>()?;
+        self.components.extend(components);
+        let other_registers = other.registers.get(
+            usize::min(super::FRAME_REGISTER_COUNT, other.registers.len()) ..
+        ).unwrap_or(&[]);
+        if !other_registers.is_empty() {
+            self.registers.resize_with(self_layout.len(), || None);
+            self.registers.extend_from_slice(other_registers);
+        }
+        self.links.extend(links);
+        Ok(())
+    }
+
+}
+
+impl Behavior {
+
+    /// Append the parameters, instructions and subroutines of `other`.
+    ///
+    /// Jumps, parameter references and subroutine references
+    /// (the `sub` key of instructions, at any subroutine depth) of `other`
+    /// are renumbered to point to the appended items.
+    /// Execution that would fall off the end of `self`
+    /// returns instead of continuing with the instructions of `other`.
+    /// The kept keys of `other` are merged into those of `self`
+    /// (see [`Behavior::extra`] and [`Behavior::empty_tables`]);
+    /// stray indices and stale parameter names of `other`
+    /// are renumbered as well.
+    ///
+    /// On error the behavior is left unchanged.
+    pub fn append(&mut self, other: Behavior) -> Result<(), MergeError> {
+        let jump_offset = offset(self.instructions.len());
+        let parameter_offset = offset(self.parameters.len());
+        let subroutine_offset = offset(self.subroutines.len());
+        if jump_offset != parameter_offset {
+            for (index, instruction) in other.instructions.iter().enumerate() {
+                if let Some(arg) = instruction.args.iter().position(
+                    |arg| matches!(*arg, Operand::UnknownIndex(_)) )
+                {
+                    return Err(MergeError::AmbiguousOperand { index, arg });
+                }
+            }
+        }
+        for (key, value) in &other.extra {
+            if self.extra.get(&key).is_some_and(|kept| kept != value) {
+                return Err(MergeError::ConflictingExtra { key });
+            }
+        }
+        let Behavior {
+            parameters, mut instructions, mut subroutines,
+            extra, extra_indexed, stale_parameter_names, empty_tables,
+            ..
+        } = other;
+        for instruction in &mut instructions {
+            let mut result = Ok(());
+            for_each_jump(instruction, |jump| if result.is_ok() {
+                result = shift(jump, jump_offset);
+            });
+            result?;
+            for arg in &mut instruction.args {
+                match *arg {
+                    Operand::Place(Some(Place::Parameter(ref mut index))) |
+                    Operand::UnknownIndex(ref mut index) =>
+                        shift(index, parameter_offset)?,
+                    _ => (),
+                }
+            }
+        }
+        for instruction in &mut instructions {
+            shift_subroutine(instruction, subroutine_offset)?;
+        }
+        let mut stack = subroutines.iter_mut().collect::<Vec<_>>();
+        while let Some(subroutine) = stack.pop() {
+            for instruction in &mut subroutine.instructions {
+                shift_subroutine(instruction, subroutine_offset)?;
+            }
+            stack.extend(&mut subroutine.subroutines);
+        }
+        let extra_indexed = extra_indexed.into_iter()
+            .map(|(mut index, value)| {
+                if index > 0 { shift(&mut index, jump_offset)?; }
+                Ok((index, value))
+            }).collect::<Result<Vec<_>, MergeError>>()?;
+        let stale_parameter_names = stale_parameter_names.into_iter()
+            .map(|(mut index, name)| {
+                shift(&mut index, parameter_offset)?;
+                Ok((index, name))
+            }).collect::<Result<Vec<_>, MergeError>>()?;
+        if !instructions.is_empty() {
+            let end = jump_offset + 1;
+            for instruction in &mut self.instructions {
+                return_at(&mut instruction.next, end);
+                for arg in &mut instruction.args {
+                    if let Operand::Jump(ref mut jump) = *arg {
+                        return_at(jump, end);
+                    }
+                }
+            }
+            if let Some(last) = self.instructions.last_mut() {
+                if last.next == Jump::Next { last.next = Jump::Return; }
+            }
+        }
+        self.parameters.extend(parameters);
+        self.instructions.extend(instructions);
+        self.subroutines.extend(subroutines);
+        for (key, value) in extra {
+            self.extra.insert(key, value);
+        }
+        self.extra_indexed.extend(extra_indexed);
+        self.stale_parameter_names.extend(stale_parameter_names);
+        for kind in empty_tables {
+            if !self.empty_tables.contains(&kind) {
+                self.empty_tables.push(kind);
+            }
+        }
+        Ok(())
+    }
+
+}
+
+fn offset(len: usize) -> i32 {
+    let Ok(offset) = i32::try_from(len) else {
+        panic!("the number of items should fit into an index");
+    };
+    offset
+}
+
+fn shift(index: &mut i32, offset: i32) -> Result<(), MergeError> {
+    *index = index.checked_add(offset).ok_or(MergeError::IndexOverflow)?;
+    Ok(())
+}
+
+/// Replace a jump past the last instruction with a return.
+fn return_at(jump: &mut Jump, end: i32) {
+    if *jump == Jump::Jump(end) { *jump = Jump::Return; }
+}
+
+fn shift_subroutine(instruction: &mut Instruction, offset: i32)
+-> Result<(), MergeError>
+{
+    if let Some(&mut _Value::Integer(ref mut index)) =
+        instruction.extra.get_mut("sub")
+    {
+        shift(index, offset)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::Str;
+
+use super::{
+    super::{Blueprint, Component, Value, RegisterValue},
+    MergeError,
+};
+
+#[test]
+fn test_merge_components() {
+    let number = |number| Some(RegisterValue::from(Value::Number(number)));
+    let component = |item: &str, index| Component {
+        item: Str::from(item), index, ..Component::default() };
+    let mut blueprint = Blueprint {
+        components: vec![component("c_small_storage", 1)],
+        registers: vec![number(1)],
+        links: vec![(1, 5)],
+        ..Blueprint::default()
+    };
+    let other = Blueprint {
+        components: vec![
+            component("c_assembler", 1), component("c_small_storage", 2) ],
+        registers: vec![None, None, None, number(2),
+            None, number(3)],
+        links: vec![(6, 2), (7, 5)],
+        ..Blueprint::default()
+    };
+    let register_count = |component: &Component|
+        if &*component.item == "c_assembler" { 2 } else { 1 };
+    blueprint.merge_components(&other, register_count).unwrap();
+    let indices: Vec<i32> = blueprint.components.iter()
+        .map(|component| component.index).collect();
+    assert_eq!(indices, [1, 2, 3]);
+    assert_eq!(blueprint.registers, [
+        number(1), None, None, None, None,
+        None, number(3) ]);
+    assert_eq!(blueprint.links, [(1, 5), (7, 2), (8, 6)]);
+    let invalid = Blueprint { links: vec![(1, 5)], ..Blueprint::default() };
+    assert!(matches!( blueprint.merge_components(&invalid, register_count),
+        Err(MergeError::InvalidLink { link: (1, 5) }) ));
+    assert_eq!(blueprint.components.len(), 3);
+    let overflowing = Blueprint {
+        components: vec![component("c_small_storage", i32::MAX)],
+        ..Blueprint::default()
+    };
+    assert!(matches!( blueprint.merge_components(&overflowing, register_count),
+        Err(MergeError::IndexOverflow) ));
+    assert_eq!(blueprint.components.len(), 3);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_append() {
+    use super::super::Behavior;
+    let mut behavior: Behavior = ron::from_str(r#"Behavior(
+        parameters: [(is_output: false)],
+        instructions: [
+            (op: "check_number", args: [Jump(3), Parameter(1)],
+                next: Jump(2)),
+            (op: "call", args: [Parameter(1)], extra: {"sub": 1}),
+        ],
+        subroutines: [(instructions: [(op: "nop")])],
+        extra: {"shared": 1},
+    )"#).unwrap();
+    let other: Behavior = ron::from_str(r#"Behavior(
+        parameters: [(name: "x", is_output: true)],
+        instructions: [
+            (op: "set_reg", args: [Parameter(1), Variable("A")]),
+            (op: "call", args: [], next: Jump(1), extra: {"sub": 1}),
+        ],
+        subroutines: [(
+            instructions: [(op: "call", extra: {"sub": 2})],
+            subroutines: [(instructions: [(op: "call", extra: {"sub": 1})])],
+        ), (instructions: [(op: "nop")])],
+        extra: {"shared": 1, "other": true},
+        empty_tables: [ParameterNames],
+    )"#).unwrap();
+    let ambiguous: Behavior = ron::from_str(r#"Behavior(
+        instructions: [(op: "jump", args: [Index(1)])],
+    )"#).unwrap();
+    assert!(matches!( behavior.clone().append(ambiguous),
+        Err(MergeError::AmbiguousOperand { index: 0, arg: 0 }) ));
+    let overflowing: Behavior = ron::from_str(r#"Behavior(
+        instructions: [(op: "nop", next: Jump(2147483647))],
+    )"#).unwrap();
+    assert!(matches!( behavior.clone().append(overflowing),
+        Err(MergeError::IndexOverflow) ));
+    let conflicting: Behavior = ron::from_str(r#"Behavior(
+        instructions: [(op: "nop")],
+        extra: {"shared": 2},
+    )"#).unwrap();
+    assert!(matches!( behavior.clone().append(conflicting),
+        Err(MergeError::ConflictingExtra { .. }) ));
+    behavior.append(other).unwrap();
+    assert_eq!( behavior.instructions[0].args[0],
+        super::Operand::Jump(super::Jump::Return) );
+    let expected: Behavior = ron::from_str(r#"Behavior(
+        parameters: [(is_output: false), (name: "x", is_output: true)],
+        instructions: [
+            (op: "check_number", args: [Return, Parameter(1)],
+                next: Jump(2)),
+            (op: "call", args: [Parameter(1)], next: Return,
+                extra: {"sub": 1}),
+            (op: "set_reg", args: [Parameter(2), Variable("A")]),
+            (op: "call", args: [], next: Jump(3), extra: {"sub": 2}),
+        ],
+        subroutines: [
+            (instructions: [(op: "nop")]),
+            (
+                instructions: [(op: "call", extra: {"sub": 3})],
+                subroutines: [
+                    (instructions: [(op: "call", extra: {"sub": 2})]) ],
+            ),
+            (instructions: [(op: "nop")]),
+        ],
+        extra: {"shared": 1, "other": true},
+        empty_tables: [ParameterNames],
+    )"#).unwrap();
+    assert_eq!( ron::to_string(&behavior).unwrap(),
+        ron::to_string(&expected).unwrap() );
+}
+
+}
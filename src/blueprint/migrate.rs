@@ -0,0 +1,172 @@
+//! Upgrading tables exported by other versions of the game.
+//!
+//! A [`Migration`] recognizes a quirk of some export format
+//! (e.g. an old name of a field) in the raw table
+//! and rewrites the table into the form this crate understands.
+//! [`Migrations`] given in [`LoadOptions`](super::LoadOptions)
+//! are applied before every conversion of a table
+//! into a [`Blueprint`](super::Blueprint) or a [`Behavior`](super::Behavior),
+//! including the nested ones.
+//! The [`TryFrom`] conversions use the default options,
+//! which have no migrations.
+
+use std::sync::Arc;
+
+use crate::{
+    Str,
+    error::LoadError,
+    value::{Key, Table},
+};
+
+/// Which kind of structure the table is going to be converted into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Schema {
+    Blueprint,
+    Behavior,
+}
+
+pub trait Migration: Send + Sync {
+    /// A short description of the quirk, reported by [`Migrations::detect`].
+    fn name(&self) -> &str;
+    /// Whether the table has the quirk.
+    fn detect(&self, schema: Schema, table: &Table) -> bool;
+    /// Rewrite the table; only called if [`Migration::detect`]
+    /// returned `true`.
+    fn migrate(&self, schema: Schema, table: Table)
+    -> Result<Table, LoadError>;
+}
+
+/// Migrations to apply, in order, before every conversion of a table
+/// (see the [module documentation](self));
+/// given through [`LoadOptions::migrations`].
+///
+/// [`LoadOptions::migrations`]: super::LoadOptions::migrations
+#[derive(Clone, Default)]
+pub struct Migrations {
+    list: Vec<Arc<dyn Migration>>,
+}
+
+impl std::fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.list.iter().map(|migration| migration.name()))
+            .finish()
+    }
+}
+
+impl Migrations {
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the migration after the ones already added.
+    pub fn push<M: Migration + 'static>(&mut self, migration: M) {
+        self.list.push(Arc::new(migration));
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Names of the migrations that would apply to the table.
+    #[must_use]
+    pub fn detect(&self, schema: Schema, table: &Table) -> Vec<String> {
+        self.list.iter()
+            .filter(|migration| migration.detect(schema, table))
+            .map(|migration| String::from(migration.name()))
+            .collect()
+    }
+
+    pub(super) fn apply(&self, schema: Schema, mut table: Table)
+    -> Result<Table, LoadError>
+    {
+        for migration in &self.list {
+            if migration.detect(schema, &table) {
+                table = migration.migrate(schema, table)?;
+            }
+        }
+        Ok(table)
+    }
+
+}
+
+/// Rename a key that was renamed in the export format.
+///
+/// Detected if the table has the old key but not the new one.
+#[derive(Debug, Clone)]
+pub struct RenameKey {
+    schema: Schema,
+    // `Str` is not `Send`
+    from: String,
+    to: String,
+    name: String,
+}
+
+impl RenameKey {
+    #[must_use]
+    pub fn new(schema: Schema, from: &str, to: &str) -> Self {
+        let name = format!("{schema:?} key {from:?} renamed to {to:?}");
+        Self { schema, from: from.into(), to: to.into(), name }
+    }
+    fn key(name: &str) -> Key {
+        Key::Name(Str::from(name))
+    }
+}
+
+impl Migration for RenameKey {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn detect(&self, schema: Schema, table: &Table) -> bool {
+        schema == self.schema &&
+            table.get(&Self::key(&self.from)).is_some() &&
+            table.get(&Self::key(&self.to)).is_none()
+    }
+    fn migrate(&self, _schema: Schema, table: Table)
+    -> Result<Table, LoadError>
+    {
+        Ok(table.into_iter().map(|(key, value)| match key {
+            Key::Name(name) if *name == *self.from =>
+                (Self::key(&self.to), value),
+            key => (key, value),
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::value::{Key, Value, Table};
+
+use super::{
+    super::{Blueprint, LoadOptions},
+    Schema, RenameKey, Migrations,
+};
+
+#[test]
+fn test_rename_key() {
+    let table = Table::from_iter([
+        ( Key::from("frame_test_old"),
+            Value::String("f_building1x1a".into()) ),
+    ]);
+    let mut migrations = Migrations::new();
+    migrations.push(
+        RenameKey::new(Schema::Blueprint, "frame_test_old", "frame") );
+    assert_eq!(migrations.detect(Schema::Blueprint, &table).len(), 1);
+    assert!(migrations.detect(Schema::Behavior, &table).is_empty());
+    Blueprint::try_from(table.clone()).unwrap_err();
+    let options = LoadOptions { migrations, ..LoadOptions::default() };
+    let blueprint = Blueprint::from_table_with(table, options).unwrap();
+    assert_eq!(&*blueprint.frame, "f_building1x1a");
+}
+
+}
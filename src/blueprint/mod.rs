@@ -1,73 +1,154 @@
 #![allow(clippy::use_self)]
 
-use std::collections::btree_map::BTreeMap as SortedMap;
-
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use crate::common::serde::{
+    option_some as serde_option_some,
+    vec_option_wrap as serde_vec_option_wrap,
+};
 
 use crate::{
     error::{LoadError, DumpError},
-    Str,
-    common::serde::{
-        option_some as serde_option_some,
-        vec_option_wrap as serde_vec_option_wrap,
+    Str, Progress,
+    value::{
+        Key, Value as _Value, Table, ArrayBuilder as TableArrayBuilder,
+        FaithfulValue,
     },
-    value::{Key, Value as _Value, Table, ArrayBuilder as TableArrayBuilder},
+    loader::{Warning, Warnings, MAX_INSTRUCTION_OFFSET},
 };
 
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::{Observer, PhaseMetrics};
+
 pub use crate::Exchange;
 
 mod behavior;
-pub use behavior::{Behavior, Parameter};
+pub use behavior::{Behavior, Parameter, EmptyTable};
+
+mod catalog;
+pub use catalog::{OpCatalog, OpInfo, ArgInfo, ArgKind, OpRef};
+
+mod pretty;
+
+mod merge;
+pub use merge::MergeError;
+
+mod patch;
+pub use patch::Patcher;
 
 mod instruction;
 pub use instruction::Instruction;
 
 mod operand;
-pub use operand::{Operand, Jump, Place, Value};
+pub use operand::{Operand, Jump, Place, Register, Value, RegisterValue};
+
+mod logistics;
+pub use logistics::{Logistics, LogisticsValue};
+
+mod locks;
+pub use locks::Locks;
+
+mod options;
+pub use options::{LoadOptions, UnknownKeys};
+
+mod validate;
+pub use validate::ValidationError;
+
+mod stats;
+pub use stats::BehaviorStats;
+
+mod search;
+
+mod rename;
+
+mod strip;
+pub use strip::{StripOptions, StripReport};
+
+mod label;
+pub use label::LabelError;
+
+pub mod pin;
+
+pub mod edit;
 
+pub mod analysis;
+
+pub mod lint;
+
+#[cfg(feature = "graph")]
+pub mod graph;
+
+pub mod text;
+
+pub mod migrate;
+
+pub mod optimize;
+
+mod register;
+pub use register::{
+    RegisterLayout, RegisterAddress, InvalidRegisterAddress,
+    ReorderError,
+    FRAME_REGISTER_COUNT,
+};
+
+#[cfg(feature = "serde")]
 fn bool_true() -> bool { true }
 
+#[cfg(feature = "serde")]
 #[allow(clippy::trivially_copy_pass_by_ref)]
 fn bool_is_true(&b: &bool) -> bool { b }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub struct Blueprint {
 
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Option::is_none",
-        with="serde_option_some" )]
+        with="serde_option_some" ))]
     pub name: Option<Str>,
 
     pub frame: Str,
 
-    #[serde( default="bool_true",
-        skip_serializing_if="bool_is_true" )]
+    #[cfg_attr(feature = "serde", serde( default="bool_true",
+        skip_serializing_if="bool_is_true" ))]
     pub powered: bool,
 
-    #[serde( default="bool_true",
-        skip_serializing_if="bool_is_true" )]
+    #[cfg_attr(feature = "serde", serde( default="bool_true",
+        skip_serializing_if="bool_is_true" ))]
     pub connected: bool,
 
-    #[serde( default,
-        skip_serializing_if="SortedMap::is_empty" )]
-    pub logistics: SortedMap<Str, bool>,
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Logistics::is_empty" ))]
+    pub logistics: Logistics,
 
+    /// Empty for a bare frame (see [`Blueprint::frame_only`]).
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Vec::is_empty" ))]
     pub components: Vec<Component>,
 
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Vec::is_empty",
-        with="serde_vec_option_wrap" )]
-    pub registers: Vec<Option<Value>>,
+        with="serde_vec_option_wrap" ))]
+    pub registers: Vec<Option<RegisterValue>>,
 
-    #[serde( default,
-        skip_serializing_if="Vec::is_empty" )]
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Vec::is_empty" ))]
     pub links: Vec<(i32, i32)>,
 
-    #[serde( default,
-        skip_serializing_if="Vec::is_empty",
-        with="serde_vec_option_wrap" )]
-    pub locks: Vec<Option<Str>>,
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Locks::is_empty" ))]
+    pub locks: Locks,
+
+    /// Keys not recognized on load (see [`UnknownKeys::Keep`]).
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Table::is_empty" ))]
+    pub extra: Table,
 
 }
 
@@ -78,11 +159,12 @@ impl Default for Blueprint {
             frame: Str::default(),
             powered: true,
             connected: true,
-            locks: Vec::new(),
-            logistics: SortedMap::new(),
+            locks: Locks::default(),
+            logistics: Logistics::default(),
             components: Vec::new(),
             registers: Vec::new(),
             links: Vec::new(),
+            extra: Table::new(),
         }
     }
 }
@@ -90,41 +172,93 @@ impl Default for Blueprint {
 impl TryFrom<_Value> for Blueprint {
     type Error = LoadError;
     fn try_from(value: _Value) -> Result<Blueprint, Self::Error> {
-        let _Value::Table(table) = value else {
-            return Err(LoadError::from(
-                "blueprint should be represented by a table value" ));
-        };
-        Blueprint::try_from(table)
+        Blueprint::from_value_with(value, LoadOptions::default())
     }
 }
 
 impl TryFrom<Table> for Blueprint {
     type Error = LoadError;
     fn try_from(table: Table) -> Result<Blueprint, Self::Error> {
-        BlueprintBuilder::build_from(table)
+        BlueprintBuilder::build_from(table, LoadOptions::default())
     }
 }
 
+impl Blueprint {
+
+    /// A bare frame with no components, as the game exports
+    /// ghost and ordered buildings
+    /// (e.g. for tools generating placement plans).
+    #[must_use]
+    pub fn frame_only(frame: Str) -> Self {
+        Self { frame, ..Self::default() }
+    }
+
+    /// Whether the blueprint is a bare frame with no components.
+    /// Settings of the frame itself (registers, logistics and such)
+    /// may still be present.
+    #[must_use]
+    pub fn is_frame_only(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    pub fn from_value_with(value: _Value, options: LoadOptions)
+    -> Result<Blueprint, LoadError>
+    {
+        let _Value::Table(table) = value else {
+            return Err(LoadError::from(
+                "blueprint should be represented by a table value" ));
+        };
+        Blueprint::from_table_with(table, options)
+    }
+
+    pub fn from_table_with(table: Table, options: LoadOptions)
+    -> Result<Blueprint, LoadError>
+    {
+        BlueprintBuilder::build_from(table, options)
+    }
+
+    /// The blueprint as it would be encoded, with the table layout
+    /// (see [`FaithfulValue`]).
+    pub fn to_faithful(&self) -> Result<FaithfulValue, DumpError> {
+        FaithfulValue::from_dump(&_Value::try_from(self.clone())?)
+    }
+
+    /// Load the blueprint from its [`FaithfulValue`];
+    /// the layout itself is dropped.
+    pub fn from_faithful(value: &FaithfulValue)
+    -> Result<Blueprint, LoadError>
+    {
+        Blueprint::try_from(value.to_value()?)
+    }
+
+}
+
 #[derive(Default)]
 struct BlueprintBuilder {
+    options: LoadOptions,
     name: Option<Str>,
     frame: Option<Str>,
     powered: Option<bool>,
     connected: Option<bool>,
-    logistics: SortedMap<Str, bool>,
+    logistics: Logistics,
     components: Vec<Component>,
-    registers: Vec<Option<Value>>,
+    registers: Vec<Option<RegisterValue>>,
     links: Vec<(i32, i32)>,
-    locks: Vec<Option<Str>>,
+    locks: Locks,
+    extra: Vec<(Key, _Value)>,
 }
 
 impl BlueprintBuilder {
 
-    fn build_from(table: Table) -> Result<Blueprint, LoadError> {
-        let mut this = Self::default();
+    fn build_from(table: Table, options: LoadOptions)
+    -> Result<Blueprint, LoadError> {
+        let table = options.migrations
+            .apply(migrate::Schema::Blueprint, table)?;
+        let mut this = Self { options, ..Self::default() };
         for (key, value) in table {
             let Key::Name(name) = key else {
-                return Err(Self::err_unexpected_key(key));
+                this.set_unknown(key, value)?;
+                continue;
             };
             match name.as_ref() {
                 "name"         => this.set_name        (value)?,
@@ -136,12 +270,18 @@ impl BlueprintBuilder {
                 "regs"         => this.set_registers   (value)?,
                 "links"        => this.set_links       (value)?,
                 "locks"        => this.set_locks       (value)?,
-                _ => return Err(Self::err_unexpected_key(Key::Name(name))),
+                _ => this.set_unknown(Key::Name(name), value)?,
             }
         }
         this.build()
     }
 
+    fn set_unknown(&mut self, key: Key, value: _Value)
+    -> Result<(), LoadError> {
+        self.options.unknown_keys.handle( &mut self.extra,
+            key, value, Self::err_unexpected_key )
+    }
+
     fn err_unexpected_key(key: Key) -> LoadError { LoadError::from(format!(
         "blueprint representation should not have {key:?} key" )) }
 
@@ -186,16 +326,12 @@ impl BlueprintBuilder {
             let Key::Name(name) = key else {
                 return Err(Self::err_logistics());
             };
-            let _Value::Boolean(value) = value else {
-                return Err(Self::err_logistics());
-            };
-            self.logistics.insert(name, value);
+            self.logistics.insert(name, LogisticsValue::from(value));
         }
         Ok(())
     }
     fn err_logistics() -> LoadError { LoadError::from(
-        "blueprints's `logistics` should be a table mapping string keys \
-         to boolean values" ) }
+        "blueprints's `logistics` should be a table with string keys" ) }
 
     fn set_components(&mut self, value: _Value) -> Result<(), LoadError> {
         let _Value::Table(table) = value else {
@@ -203,7 +339,8 @@ impl BlueprintBuilder {
         };
         for item in table.into_continuous_iter() {
             let item = item.map_err(|_error| Self::err_components())?;
-            self.components.push(Component::try_from(item)?);
+            self.components.push(
+                Component::from_value_with(item, self.options.clone())? );
         }
         Ok(())
     }
@@ -221,7 +358,10 @@ impl BlueprintBuilder {
                 "unrealistically large number of blueprint registers"));
         }
         for item in table {
-            self.registers.push(item.map(Value::try_from).transpose()?);
+            self.registers.push(item
+                .map(|item| RegisterValue::from_value_with(
+                    item, self.options.clone() ))
+                .transpose()?);
         }
         Ok(())
     }
@@ -270,7 +410,7 @@ impl BlueprintBuilder {
                 Some(_Value::String(name)) => Some(name),
                 _ => return Err(Self::err_locks()),
             };
-            self.locks.push(item);
+            self.locks.slots.push(item);
         }
         Ok(())
     }
@@ -279,24 +419,18 @@ impl BlueprintBuilder {
 
     fn build(self) -> Result<Blueprint, LoadError> {
         let Self {
+            options: _options,
             name,
             frame, powered, connected, logistics,
             components, registers, links,
             locks,
+            extra,
         } = self;
         let Some(frame) = frame else {
             return Err(LoadError::from(
                 "Blueprint must have a `frame` defined" ));
         };
-        for (i, j) in links.iter().copied() {
-            for x in [i, j] {
-                if x <= 0 || x as usize > registers.len() {
-                    return Err(LoadError::from(
-                        "Link index is incorrect" ));
-                }
-            }
-        }
-        Ok(Blueprint {
+        let blueprint = Blueprint {
             name,
             frame,
             powered: powered.unwrap_or(true),
@@ -306,21 +440,30 @@ impl BlueprintBuilder {
             registers,
             links,
             locks,
-        })
+            extra: extra.into_iter().collect(),
+        };
+        if let Some(error) = blueprint.link_errors().next() {
+            return Err(LoadError::from(error.to_string()));
+        }
+        Ok(blueprint)
     }
 }
 
-impl From<Blueprint> for _Value {
-    fn from(this: Blueprint) -> _Value {
+/// Resolves labels of the behaviors of the components
+/// (see [`Blueprint::resolve_labels`]).
+impl TryFrom<Blueprint> for _Value {
+    type Error = LabelError;
+    fn try_from(this: Blueprint) -> Result<_Value, LabelError> {
         use TableArrayBuilder as ArrayBuilder;
         let Blueprint {
             name: blueprint_name,
             frame, powered, connected, logistics,
             components, registers, links,
             locks,
+            extra,
         } = this;
         #[allow(clippy::from_iter_instead_of_collect)]
-        _Value::Table(Table::from_iter([
+        let mut table = Table::from_iter([
             ("name"        , blueprint_name.map(_Value::String)),
             ("frame"       , Some(_Value::String(frame))),
             ("powered_down", (!powered).then_some(_Value::Boolean(true))),
@@ -328,14 +471,15 @@ impl From<Blueprint> for _Value {
             ("logistics"   , if logistics.is_empty() { None } else { Some(
                 _Value::Table(Table::from_iter(
                     logistics.into_iter().map( |(key, setting)|
-                        (Key::Name(key), _Value::Boolean(setting))
+                        (Key::Name(key), _Value::from(setting))
                     )
                 ))
             ) }),
             ("components"  , if components.is_empty() { None } else { Some(
-                _Value::Table(ArrayBuilder::from_iter(
-                    components.into_iter().map(_Value::from)
-                ).build())
+                _Value::Table( components.into_iter()
+                    .map(_Value::try_from)
+                    .collect::<Result<ArrayBuilder<_>, _>>()?
+                    .build() )
             ) }),
             ("regs"        , if registers.is_empty() { None } else { Some(
                 _Value::Table(ArrayBuilder::from_iter(
@@ -354,17 +498,22 @@ impl From<Blueprint> for _Value {
             ) }),
             ("locks"       , if locks.is_empty() { None } else { Some(
                 _Value::Table(ArrayBuilder::from_iter(
-                    locks.into_iter().map(|value| value.map(_Value::String))
+                    locks.slots.into_iter()
+                        .map(|value| value.map(_Value::String))
                 ).build())
             ) }),
         ].into_iter().filter_map(|(name, value)| {
             let value = value?;
             Some((Key::from(name), value))
-        })))
+        })).into_builder();
+        table.extend(extra);
+        Ok(_Value::Table(table.build()))
     }
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub struct Component {
 
@@ -372,109 +521,259 @@ pub struct Component {
 
     pub index: i32,
 
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Option::is_none",
-        with="serde_option_some" )]
+        with="serde_option_some" ))]
     pub behavior: Option<Behavior>,
 
-    #[serde( default,
+    #[cfg_attr(feature = "serde", serde( default,
         skip_serializing_if="Vec::is_empty",
-        with="serde_vec_option_wrap" )]
-    pub registers: Vec<Option<Value>>,
+        with="serde_vec_option_wrap" ))]
+    pub registers: Vec<Option<RegisterValue>>,
+
+    /// Keys not recognized on load (see [`UnknownKeys::Keep`]).
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Table::is_empty" ))]
+    pub extra: Table,
 
 }
 
 impl TryFrom<_Value> for Component {
     type Error = LoadError;
     fn try_from(value: _Value) -> Result<Self, Self::Error> {
-        let _Value::Table(table) = value else {
-            return Err(LoadError::from(
-                "component should be represented by a table value" ));
-        };
-        Component::try_from(table)
+        Component::from_value_with(value, LoadOptions::default())
     }
 }
 
 impl TryFrom<Table> for Component {
     type Error = LoadError;
     fn try_from(table: Table) -> Result<Component, Self::Error> {
+        Component::from_table_with(table, LoadOptions::default())
+    }
+}
+
+impl Component {
+
+    pub fn from_value_with(value: _Value, options: LoadOptions)
+    -> Result<Component, LoadError>
+    {
+        let _Value::Table(table) = value else {
+            return Err(LoadError::from(
+                "component should be represented by a table value" ));
+        };
+        Component::from_table_with(table, options)
+    }
+
+    pub fn from_table_with(table: Table, options: LoadOptions)
+    -> Result<Component, LoadError>
+    {
         let mut this = Self::default();
-        let mut table = table.into_continuous_iter();
-        let _Value::String(item) = table.next()
-            .and_then(Result::ok)
-            .ok_or_else(|| Self::Error::from(
+        let (mut item, mut index, mut behavior) = (None, None, None);
+        let mut extra = Vec::new();
+        for (key, value) in table {
+            match key {
+                Key::Index(1) => item = Some(value),
+                Key::Index(2) => index = Some(value),
+                Key::Index(3) => behavior = Some(value),
+                _ => options.unknown_component_keys.handle( &mut extra,
+                    key, value, Self::err_unexpected_key )?,
+            }
+        }
+        let _Value::String(item) = item
+            .ok_or_else(|| LoadError::from(
                 "component should have an item id" ))?
         else {
-            return Err(Self::Error::from(
+            return Err(LoadError::from(
                 "component's item id should be a string" ));
         };
         this.item = item;
-        let _Value::Integer(index) = table.next()
-            .and_then(Result::ok)
-            .ok_or_else(|| Self::Error::from(
+        let _Value::Integer(index) = index
+            .ok_or_else(|| LoadError::from(
                 "component should have an index" ))?
         else {
-            return Err(Self::Error::from(
+            return Err(LoadError::from(
                 "component's index should be an integer" ));
         };
         this.index = index;
-        if let Some(behavior) = table.next()
-            .transpose()
-            .map_err(|_error| Self::Error::from(
-                "component should either have a behavior \
-                 or no third parameter at all" ))?
-        {
+        if let Some(behavior) = behavior {
             let _Value::Table(behavior) = behavior else {
-                return Err(Self::Error::from(
+                return Err(LoadError::from(
                     "component's behavior should be represented \
                      by a table value" ));
             };
-            this.behavior = Some(Behavior::try_from(behavior)?);
+            this.behavior = Some(Behavior::from_table_with(behavior, options)?);
         }
+        this.extra = extra.into_iter().collect();
         Ok(this)
     }
+
+    fn err_unexpected_key(key: Key) -> LoadError { LoadError::from(format!(
+        "component representation should not have {key:?} key" )) }
+
 }
 
-impl From<Component> for _Value {
-    fn from(this: Component) -> _Value {
+impl TryFrom<Component> for _Value {
+    type Error = LabelError;
+    fn try_from(this: Component) -> Result<_Value, LabelError> {
         let Component {
             item,
             index,
             behavior,
             registers: _registers,
+            extra,
         } = this;
-        _Value::Table(TableArrayBuilder::from_iter([
+        let mut table = TableArrayBuilder::from_iter([
             Some(_Value::String(item)),
             Some(_Value::Integer(index)),
-            behavior.map(_Value::from),
-        ]).build())
+            behavior.map(_Value::try_from).transpose()?,
+        ]).build().into_builder();
+        table.extend(extra);
+        Ok(_Value::Table(table.build()))
     }
 }
 
 pub fn load_blueprint(exchange: &str)
 -> Result<Exchange<Blueprint, Behavior>, LoadError>
+{
+    load_blueprint_with(exchange, LoadOptions::default())
+}
+
+pub fn load_blueprint_with(exchange: &str, options: LoadOptions)
+-> Result<Exchange<Blueprint, Behavior>, LoadError>
+{
+    load_blueprint_with_warnings(exchange, options, None, &mut Warnings::new())
+}
+
+/// Load the exchange string, recording non-fatal oddities
+/// of the encoding and of the behaviors in `warnings`.
+///
+/// Operations are reported as unknown only if a catalog is given.
+pub fn load_blueprint_with_warnings( exchange: &str,
+    options: LoadOptions, catalog: Option<&OpCatalog>,
+    warnings: &mut Warnings,
+) -> Result<Exchange<Blueprint, Behavior>, LoadError>
+{
+    load_with(exchange, options, catalog, warnings, |_| ())
+}
+
+/// Load the exchange string, calling `progress` every now and then
+/// while the binary data is decoded
+/// (see [`crate::loader::load_blueprint_with_progress`]).
+pub fn load_blueprint_with_progress<F>( exchange: &str,
+    options: LoadOptions, progress: F,
+) -> Result<Exchange<Blueprint, Behavior>, LoadError>
+where F: FnMut(Progress)
+{
+    load_with(exchange, options, None, &mut Warnings::new(), progress)
+}
+
+fn load_with<F>( exchange: &str,
+    options: LoadOptions, catalog: Option<&OpCatalog>,
+    warnings: &mut Warnings, progress: F,
+) -> Result<Exchange<Blueprint, Behavior>, LoadError>
+where F: FnMut(Progress)
+{
+    type V = _Value;
+    let value = crate::loader::load_blueprint_with_progress::<V, V, F>(
+        exchange, options.decode_options(), warnings, progress )?;
+    let value = build_value(value, options)?;
+    let roots: Vec<&Behavior> = match value {
+        Exchange::Blueprint(ref blueprint) => blueprint.components.iter()
+            .filter_map(|component| component.behavior.as_ref()).collect(),
+        Exchange::Behavior(ref behavior) => vec![behavior],
+    };
+    for behavior in roots.into_iter().flat_map(Behavior::with_subroutines) {
+        for instruction in &behavior.instructions {
+            if catalog.is_some_and(|catalog|
+                catalog.get_ref(&instruction.operation).is_none() )
+            {
+                warnings.push(Warning::UnknownOperation {
+                    op: instruction.operation.clone() });
+            }
+            if let Some((x, y)) = instruction.offset {
+                if ![x, y].iter().all(|v|
+                    v.abs() <= MAX_INSTRUCTION_OFFSET )
+                {
+                    warnings.push(Warning::OffsetOutOfRange { x, y });
+                }
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Load the exchange string, reporting how long each phase
+/// of loading takes to `observer`
+/// (see [`crate::loader::load_blueprint_with_observer`]).
+#[cfg(feature = "metrics")]
+pub fn load_blueprint_with_observer( exchange: &str,
+    options: LoadOptions, observer: &mut dyn Observer,
+) -> Result<Exchange<Blueprint, Behavior>, LoadError>
 {
     type V = _Value;
-    let value = crate::loader::load_blueprint::<V, V, LoadError>(exchange)?;
+    let value = crate::loader::load_blueprint_with_observer::<V, V>(
+        exchange, options.decode_options(), &mut Warnings::new(),
+        observer )?;
+    let start = Instant::now();
+    let value = build_value(value, options)?;
+    observer.build(PhaseMetrics::new(start.elapsed()));
+    Ok(value)
+}
+
+pub(crate) fn build_value(
+    value: Exchange<Option<_Value>, Option<_Value>>,
+    options: LoadOptions,
+) -> Result<Exchange<Blueprint, Behavior>, LoadError>
+{
     let value = value.transpose().ok_or_else(|| LoadError::from(
         "Blueprint or behavior should not be represented with nil" ))?;
-    value.map(Blueprint::try_from, Behavior::try_from).transpose()
+    value.map(
+        |value| Blueprint::from_value_with(value, options.clone()),
+        |value| Behavior::from_value_with(value, options.clone()),
+    ).transpose()
 }
 
 pub fn dump_blueprint(blueprint: Exchange<Blueprint, Behavior>)
 -> Result<String, DumpError>
 {
     type V = _Value;
-    let value = blueprint.map(Blueprint::into, Behavior::into)
-        .map(Some, Some);
-    crate::dumper::dump_blueprint::<V, V>(value)
+    crate::dumper::dump_blueprint::<V, V>(into_values(blueprint)?)
+}
+
+/// Convert into the values to be dumped, resolving labels.
+fn into_values(blueprint: Exchange<Blueprint, Behavior>)
+-> Result<Exchange<Option<_Value>, Option<_Value>>, DumpError>
+{
+    Ok(blueprint.map(_Value::try_from, _Value::try_from)
+        .transpose()?.map(Some, Some))
+}
+
+/// Dump the exchange like [`dump_blueprint`],
+/// failing if the blueprint does not pass [`Blueprint::validate`]
+/// (the game would reject the string).
+/// Behavior exchanges are dumped unchecked.
+pub fn dump_blueprint_validated(blueprint: Exchange<Blueprint, Behavior>)
+-> Result<String, DumpError>
+{
+    if let Exchange::Blueprint(ref blueprint) = blueprint {
+        blueprint.validate().map_err(|errors| DumpError::from(
+            errors.iter().map(ToString::to_string)
+                .collect::<Vec<_>>().join("; ") ))?;
+    }
+    dump_blueprint(blueprint)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::Exchange;
+    use crate::{
+        Exchange,
+        value::{Key, Value, Table},
+    };
 
-    use super::{load_blueprint, dump_blueprint};
+    use super::{
+        load_blueprint, dump_blueprint,
+        Blueprint, Behavior, LoadOptions, UnknownKeys, LogisticsValue,
+    };
 
     #[test]
     fn test_load_error() {
@@ -516,5 +815,221 @@ mod test {
             else { panic!("should be a behavior") };
     }
 
-}
+    #[test]
+    fn test_load_unknown_keys() {
+        let table: Table = Table::from_iter([
+            (Key::from("name"), Value::String("Behavior".into())),
+            (Key::from("future_field"), Value::Integer(42)),
+        ]);
+        let Err(_) = Behavior::try_from(table.clone())
+            else { panic!("should be an error") };
+        let options = LoadOptions {
+            unknown_keys: UnknownKeys::Drop, ..LoadOptions::default() };
+        let dropped = Behavior::from_table_with(table.clone(), options)
+            .unwrap();
+        assert!(dropped.extra.is_empty());
+        let kept = Behavior::from_table_with(
+            table.clone(), LoadOptions::lenient() ).unwrap();
+        assert_eq!( kept.extra.get(&Key::from("future_field")),
+            Some(&Value::Integer(42)) );
+        assert_eq!(Value::try_from(kept).unwrap(), Value::Table(table));
+    }
 
+    #[test]
+    fn test_load_register_extra_keys() {
+        let register = Value::Table(Table::from_iter([
+            (Key::from("id"), Value::String("metalore".into())),
+            (Key::from("num"), Value::Integer(5)),
+            (Key::from("unknown"), Value::Boolean(true)),
+        ]));
+        let table = Value::Table(Table::from_iter([
+            (Key::from("frame"), Value::String("f_building1x1a".into())),
+            (Key::from("regs"), Value::Table(Table::from_iter([
+                (Key::Index(1), register) ]))),
+        ]));
+        let exchange = crate::dumper::dump_blueprint::<Value, Value>(
+            Exchange::Blueprint(Some(table.clone())) ).unwrap();
+        let Exchange::Blueprint(blueprint) = load_blueprint(&exchange)
+            .unwrap() else { panic!("should be a blueprint") };
+        let Some(Some(register)) = blueprint.registers.first()
+            else { panic!("should have a register") };
+        assert_eq!( register.value,
+            Some(super::Value::ItemCount("metalore".into(), 5)) );
+        assert_eq!( register.extra.get(&Key::from("unknown")),
+            Some(&Value::Boolean(true)) );
+        assert_eq!(Value::try_from(blueprint).unwrap(), table);
+        let Err(_) = super::load_blueprint_with(
+            &exchange, LoadOptions::strict() )
+            else { panic!("should be an error") };
+        #[cfg(feature = "json")] {
+            let blueprint: Blueprint = serde_json::from_str(r#"{
+                "frame": "f_building1x1a",
+                "components": [{
+                    "item": "c_portable_radar",
+                    "index": 1,
+                    "registers": [{ "Register": {
+                        "value": { "Number": 5 },
+                        "extra": { "unknown": true }
+                    } }]
+                }]
+            }"#).unwrap();
+            let Some(Some(register)) = blueprint.components[0]
+                .registers.first()
+                else { panic!("should have a register") };
+            assert_eq!(register.value, Some(super::Value::Number(5)));
+            assert_eq!( register.extra.get(&Key::from("unknown")),
+                Some(&Value::Boolean(true)) );
+        }
+    }
+
+    #[test]
+    fn test_logistics_transport_route() {
+        let route = Value::Table(Table::from_iter([
+            (Key::from("x"), Value::Integer(3)),
+            (Key::from("y"), Value::Integer(-7)),
+        ]));
+        let table: Table = Table::from_iter([
+            (Key::from("frame"), Value::String("f_building1x1a".into())),
+            (Key::from("logistics"), Value::Table(Table::from_iter([
+                (Key::from("carrier"), Value::Boolean(true)),
+                (Key::from("transport_route"), route.clone()),
+            ]))),
+        ]);
+        let blueprint = Blueprint::try_from(table.clone()).unwrap();
+        assert_eq!(blueprint.logistics.carrier, Some(true));
+        assert_eq!(blueprint.logistics.requester, None);
+        assert_eq!( blueprint.logistics.other.get("transport_route"),
+            Some(&LogisticsValue::Other(route)) );
+        #[cfg(feature = "serde")] {
+            let ron = ron::to_string(&blueprint).unwrap();
+            let blueprint_again: Blueprint = ron::from_str(&ron).unwrap();
+            assert_eq!(blueprint_again.logistics, blueprint.logistics);
+        }
+        assert_eq!( Value::try_from(blueprint).unwrap(),
+            Value::Table(table.clone()) );
+        // the same through an exchange string
+        let exchange = crate::dumper::dump_blueprint::<Value, Value>(
+            Exchange::Blueprint(Some(Value::Table(table))) ).unwrap();
+        let Exchange::Blueprint(blueprint) = load_blueprint(&exchange)
+            .unwrap() else { panic!("should be a blueprint") };
+        assert_eq!( dump_blueprint(Exchange::Blueprint(blueprint)).unwrap(),
+            exchange );
+    }
+
+    #[test]
+    fn test_frame_only() {
+        let blueprint = Blueprint::frame_only("f_building1x1a".into());
+        assert!(blueprint.is_frame_only());
+        blueprint.validate().unwrap();
+        assert_eq!( Value::try_from(blueprint.clone()).unwrap(),
+            Value::Table(Table::from_iter([(
+                Key::from("frame"),
+                Value::String("f_building1x1a".into()),
+            )])) );
+        let exchange = dump_blueprint(Exchange::Blueprint(blueprint.clone()))
+            .unwrap();
+        let Exchange::Blueprint(loaded) = load_blueprint(&exchange).unwrap()
+            else { panic!("should be a blueprint") };
+        assert!(loaded.is_frame_only());
+        assert_eq!(loaded.frame, blueprint.frame);
+        #[cfg(feature = "serde")] {
+            let loaded: Blueprint = ron::from_str(
+                r#"Blueprint(frame: "f_building1x1a")"# ).unwrap();
+            assert!(loaded.is_frame_only());
+            assert_eq!(ron::to_string(&loaded).unwrap(),
+                r#"(frame:"f_building1x1a")"#);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_extra() {
+        let instruction = Value::Table(Table::from_iter([
+            (Key::from("op"), Value::String("nop".into())),
+            (Key::from("future_op_field"), Value::Boolean(true)),
+        ]));
+        let behavior = Value::Table(Table::from_iter([
+            (Key::Index(1), instruction),
+            (Key::from("future_behavior_field"), Value::Integer(1)),
+        ]));
+        let component = Value::Table(Table::from_iter([
+            (Key::Index(1), Value::String("c_behavior".into())),
+            (Key::Index(2), Value::Integer(1)),
+            (Key::Index(3), behavior),
+            (Key::Index(4), Value::String("future_component_field".into())),
+        ]));
+        let table: Table = Table::from_iter([
+            (Key::from("frame"), Value::String("f_building1x1a".into())),
+            (Key::from("components"), Value::Table(Table::from_iter([
+                (Key::Index(1), component),
+            ]))),
+            (Key::from("future_blueprint_field"), Value::Float(0.5)),
+        ]);
+        let Err(_) = Blueprint::try_from(table.clone())
+            else { panic!("should be an error") };
+        let blueprint = Blueprint::from_table_with(
+            table.clone(), LoadOptions::lenient() ).unwrap();
+        assert_eq!(blueprint.extra.len(), 1);
+        assert_eq!(blueprint.components[0].extra.len(), 1);
+        assert_eq!(Value::try_from(blueprint).unwrap(), Value::Table(table));
+    }
+
+    #[test]
+    fn test_load_component_extra_entries() {
+        let component = Value::Table(Table::from_iter([
+            (Key::Index(1), Value::String("c_portable_radar".into())),
+            (Key::Index(2), Value::Integer(1)),
+            (Key::Index(4), Value::Integer(0)),
+            (Key::Index(5), Value::String("old".into())),
+        ]));
+        let table = Value::Table(Table::from_iter([
+            (Key::from("frame"), Value::String("f_building1x1a".into())),
+            (Key::from("components"), Value::Table(Table::from_iter([
+                (Key::Index(1), component) ]))),
+        ]));
+        let exchange = crate::dumper::dump_blueprint::<Value, Value>(
+            Exchange::Blueprint(Some(table.clone())) ).unwrap();
+        let Exchange::Blueprint(blueprint) = load_blueprint(&exchange)
+            .unwrap() else { panic!("should be a blueprint") };
+        assert_eq!(blueprint.components[0].extra.len(), 2);
+        assert_eq!(Value::try_from(blueprint).unwrap(), table);
+        let options = LoadOptions {
+            unknown_component_keys: UnknownKeys::Drop,
+            ..LoadOptions::default() };
+        let Exchange::Blueprint(blueprint) = super::load_blueprint_with(
+            &exchange, options ).unwrap()
+            else { panic!("should be a blueprint") };
+        assert!(blueprint.components[0].extra.is_empty());
+        let Err(_) = super::load_blueprint_with(
+            &exchange, LoadOptions::strict() )
+            else { panic!("should be an error") };
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_load_warnings() {
+        use crate::loader::{Warning, Warnings};
+        use super::{load_blueprint_with_warnings, OpCatalog, OpInfo};
+        let behavior: Behavior = ron::from_str(r#"Behavior(
+            instructions: [
+                (op: "nop", offset: (1.5, -2.0)),
+                (op: "frobnicate", offset: (1e9, 0.0)),
+            ],
+        )"#).unwrap();
+        let exchange = dump_blueprint(Exchange::Behavior(behavior)).unwrap();
+        let catalog = OpCatalog::from_iter([("nop", OpInfo::default())]);
+        let mut warnings = Warnings::new();
+        load_blueprint_with_warnings( &exchange,
+            LoadOptions::default(), Some(&catalog), &mut warnings,
+        ).unwrap();
+        assert_eq!(warnings.into_iter().collect::<Vec<_>>(), [
+            Warning::UnknownOperation { op: "frobnicate".into() },
+            Warning::OffsetOutOfRange { x: 1e9, y: 0.0 },
+        ]);
+        let mut warnings = Warnings::new();
+        load_blueprint_with_warnings( &exchange,
+            LoadOptions::default(), None, &mut warnings,
+        ).unwrap();
+        assert_eq!(warnings.len(), 1);
+    }
+
+}
@@ -1,25 +1,34 @@
 #![allow(clippy::use_self)]
 
+#[cfg(feature = "serde")]
 use serde::{
     Deserialize, de, Serialize,
 };
+#[cfg(feature = "serde")]
+use crate::common::serde::{
+    Identifier, PairVisitor,
+    DeserializeOption, forward_de_to_de_option,
+    SerializeOption,
+};
 
 use crate::{
     error::LoadError,
     Str,
-    common::serde::{
-        Identifier, PairVisitor,
-        DeserializeOption, forward_de_to_de_option,
-        SerializeOption,
+    value::{
+        Key, Value as _Value, Table,
+        ArrayBuilder as TableArrayBuilder,
     },
-    value::{Key, Value as _Value, Table},
 };
 
+use super::LoadOptions;
+
+#[cfg(feature = "serde")]
 enum EnumMatchError<'de, E, V> {
     DeErr(E),
     NoMatch(Identifier<'de>, V),
 }
 
+#[cfg(feature = "serde")]
 trait EnumTryVisitor<'de> : de::Visitor<'de> {
     fn visit_enum_match<V>(self, id: Identifier<'de>, contents: V)
     -> Result<Self::Value, EnumMatchError<'de, V::Error, V>>
@@ -29,6 +38,7 @@ trait EnumTryVisitor<'de> : de::Visitor<'de> {
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum Operand {
 
@@ -59,6 +69,10 @@ impl Operand {
         if let Some(this) = this { return this; }
         Self::UnknownUnset
     }
+    #[must_use]
+    pub fn is_unset(&self) -> bool {
+        matches!(self, Self::UnknownUnset)
+    }
     pub fn make_jump(&mut self) -> Result<(), LoadError> {
         match *self {
             Self::Jump(_) => (),
@@ -120,6 +134,8 @@ impl TryFrom<_Value> for Operand {
                 Value::try_from(table)? )),
             _Value::Float(_) => return Err(LoadError::from(
                 "operand cannot be a float" )),
+            _Value::Bytes(_) => return Err(LoadError::from(
+                "operand cannot be a string that is not valid UTF-8" )),
             _Value::Boolean(true) => return Err(LoadError::from(
                 "operand cannot be `true`" )),
             _Value::Integer(i32::MIN ..= 0) =>
@@ -145,6 +161,7 @@ impl From<Operand> for Option<_Value> {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Operand {
     fn deserialize<D>(de: D) -> Result<Operand, D::Error>
     where D: de::Deserializer<'de>
@@ -153,8 +170,10 @@ impl<'de> Deserialize<'de> for Operand {
     }
 }
 
+#[cfg(feature = "serde")]
 struct OperandVisitor;
 
+#[cfg(feature = "serde")]
 impl<'de> EnumTryVisitor<'de> for OperandVisitor {
     fn visit_enum_match<V>(self, mut id: Identifier<'de>, mut contents: V)
     -> Result<Self::Value, EnumMatchError<'de, V::Error, V>>
@@ -184,6 +203,7 @@ impl<'de> EnumTryVisitor<'de> for OperandVisitor {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> de::Visitor<'de> for OperandVisitor {
     type Value = Operand;
     fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result
@@ -204,6 +224,7 @@ impl<'de> de::Visitor<'de> for OperandVisitor {
     }
 }
 
+#[cfg(feature = "serde")]
 impl Serialize for Operand {
     fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
     where S: serde::Serializer {
@@ -222,13 +243,23 @@ impl Serialize for Operand {
 }
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum Jump {
     Return,
     Next,
     /// `1`-based index in the instruction list.
     Jump(i32),
+    /// The instruction with this [`Instruction::label`]
+    /// in the same behavior.
+    /// Labels are not encoded: they are resolved
+    /// into [`Jump::Jump`] on dump (see [`Behavior::resolve_labels`]).
+    ///
+    /// [`Instruction::label`]: super::Instruction::label
+    /// [`Behavior::resolve_labels`]: super::Behavior::resolve_labels
+    Label(Str),
 }
 
 impl Jump {
@@ -264,12 +295,14 @@ impl From<Jump> for Option<_Value> {
     fn from(this: Jump) -> Option<_Value> {
         match this {
             Jump::Jump(index) => Some(_Value::Integer(index)),
-            Jump::Next => None,
+            // unresolved labels (see `Behavior::resolve_labels`)
+            Jump::Next | Jump::Label(_) => None,
             Jump::Return => Some(_Value::Boolean(false)),
         }
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Jump {
     fn deserialize<D>(de: D) -> Result<Self, D::Error>
     where D: de::Deserializer<'de>
@@ -278,8 +311,10 @@ impl<'de> Deserialize<'de> for Jump {
     }
 }
 
+#[cfg(feature = "serde")]
 struct JumpVisitor;
 
+#[cfg(feature = "serde")]
 impl<'de> EnumTryVisitor<'de> for JumpVisitor {
     fn visit_enum_match<V>(self, id: Identifier<'de>, contents: V)
     -> Result<Self::Value, EnumMatchError<'de, V::Error, V>>
@@ -291,11 +326,14 @@ impl<'de> EnumTryVisitor<'de> for JumpVisitor {
             "Next"   => Jump::Next,
             "Jump"   => Jump::Jump(
                 contents.newtype_variant().map_err(DeErr)? ),
+            "Label"  => Jump::Label(
+                contents.newtype_variant().map_err(DeErr)? ),
             _ => return Err(NoMatch(id, contents)),
         })
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> de::Visitor<'de> for JumpVisitor {
     type Value = Jump;
     fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result
@@ -318,7 +356,9 @@ impl<'de> de::Visitor<'de> for JumpVisitor {
 
 
 /// Place arguments to instructions
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum Place {
     Parameter(i32),
@@ -362,6 +402,7 @@ impl From<Place> for _Value {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> DeserializeOption<'de> for Place {
     fn deserialize_option<D>(de: D)
     -> Result<Option<Self>, D::Error>
@@ -371,10 +412,13 @@ impl<'de> DeserializeOption<'de> for Place {
     }
 }
 
+#[cfg(feature = "serde")]
 forward_de_to_de_option!(Place);
 
+#[cfg(feature = "serde")]
 struct PlaceVisitor;
 
+#[cfg(feature = "serde")]
 impl<'de> EnumTryVisitor<'de> for PlaceVisitor {
     fn visit_enum_match<V>(self, id: Identifier<'de>, contents: V)
     -> Result<Self::Value, EnumMatchError<'de, V::Error, V>>
@@ -394,6 +438,7 @@ impl<'de> EnumTryVisitor<'de> for PlaceVisitor {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> de::Visitor<'de> for PlaceVisitor {
     type Value = Option<Place>;
     fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result
@@ -419,6 +464,7 @@ impl<'de> de::Visitor<'de> for PlaceVisitor {
     }
 }
 
+#[cfg(feature = "serde")]
 impl SerializeOption for Place {
     fn serialize_option<S>(this: Option<&Self>, ser: S)
     -> Result<S::Ok, S::Error>
@@ -432,7 +478,10 @@ impl SerializeOption for Place {
 }
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum Register {
     Goto,
     Store,
@@ -482,7 +531,9 @@ impl From<Register> for _Value {
 
 
 /// Value arguments to operations
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum Value {
     Number(i32),
@@ -490,6 +541,29 @@ pub enum Value {
     ItemCount(Str, i32),
     Coord(Coord),
     CoordCount(Coord, i32),
+    /// Represented by a non-empty array of coords
+    /// (an empty list would be an empty table, which does not load).
+    ///
+    /// Not checked against exported blueprints yet.
+    CoordList(Vec<Coord>),
+    /// A rectangular area, represented by the `coord` of one corner
+    /// and the coord of the other one at index `1`.
+    /// Corners are kept as they are stored.
+    ///
+    /// Not checked against exported blueprints yet.
+    Area { min: Coord, max: Coord },
+}
+
+impl Value {
+    /// The item id of `Item` and `ItemCount` values.
+    #[must_use]
+    pub fn item(&self) -> Option<&Str> {
+        match *self {
+            Self::Item(ref id) | Self::ItemCount(ref id, _) => Some(id),
+            Self::Number(_) | Self::Coord(_) | Self::CoordCount(..) |
+            Self::CoordList(_) | Self::Area { .. } => None,
+        }
+    }
 }
 
 impl TryFrom<_Value> for Value {
@@ -521,14 +595,33 @@ impl TryFrom<Table> for Value {
             }
         }
         let (mut id, mut coord, mut num) = (None, None, None);
+        let mut coords = Vec::new();
         for (key, value) in table {
-            match key.as_name() {
-                Some("id")    => id = Some(id_ok(value)?),
-                Some("coord") => coord = Some(Coord::try_from(value)?),
-                Some("num")   => num = Some(num_ok(value)?),
-                _ => return Err(err_unexpected_key(key)),
+            match key {
+                Key::Name(ref name) => match name.as_ref() {
+                    "id"    => id = Some(id_ok(value)?),
+                    "coord" => coord = Some(Coord::try_from(value)?),
+                    "num"   => num = Some(num_ok(value)?),
+                    _ => return Err(err_unexpected_key(key)),
+                },
+                // indices go first, in order
+                Key::Index(index) if usize::try_from(index)
+                    .is_ok_and(|index| index == coords.len() + 1) =>
+                    coords.push(Coord::try_from(value)?),
+                Key::Index(_) | Key::Bool(_) =>
+                    return Err(err_unexpected_key(key)),
             }
         }
+        if !coords.is_empty() {
+            return Ok(match (id, coord, num, coords.len()) {
+                (None, None, None, _) => Value::CoordList(coords),
+                (None, Some(min), None, 1) =>
+                    Value::Area { min, max: coords.remove(0) },
+                _ => return Err(LoadError::from(
+                    "value representation cannot have both \
+                     indices and fields other than `coord` of an area" )),
+            });
+        }
         Ok(match (id, coord, num) {
             (None, None, Some(num)) => Value::Number(num),
             (Some(id), None, None) => Value::Item(id),
@@ -536,8 +629,8 @@ impl TryFrom<Table> for Value {
             (None, Some(coord), None) => Value::Coord(coord),
             (None, Some(coord), Some(num)) => Value::CoordCount(coord, num),
             (None, None, None) => return Err(LoadError::from(
-                "value representation should have at least one of the fields\
-                 `id`, `coord`, `num`" )),
+                "value representation should have at least one of the fields \
+                 `id`, `coord`, `num` or coords at indices" )),
             (Some(_), Some(_), _) => return Err(LoadError::from(
                 "value representation cannot have both\
                  `id` and `coord` fields" )),
@@ -570,10 +663,19 @@ impl From<Value> for _Value {
                     ("id"   , _Value::String(id)),
                     ("num"  , _Value::Integer(num)),
                 ])),
+            Value::CoordList(coords) =>
+                _Value::Table(coords.into_iter().map(_Value::from)
+                    .collect::<TableArrayBuilder<_>>().build()),
+            Value::Area { min, max } =>
+                _Value::Table(Table::from_iter([
+                    (Key::from("coord"), _Value::from(min)),
+                    (Key::Index(1)     , _Value::from(max)),
+                ])),
         }
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> DeserializeOption<'de> for Value {
     fn deserialize_option<D>(de: D)
     -> Result<Option<Self>, D::Error>
@@ -583,10 +685,13 @@ impl<'de> DeserializeOption<'de> for Value {
     }
 }
 
+#[cfg(feature = "serde")]
 forward_de_to_de_option!(Value);
 
+#[cfg(feature = "serde")]
 struct ValueVisitor;
 
+#[cfg(feature = "serde")]
 impl<'de> EnumTryVisitor<'de> for ValueVisitor {
     fn visit_enum_match<V>(self, id: Identifier<'de>, contents: V)
     -> Result<Self::Value, EnumMatchError<'de, V::Error, V>>
@@ -611,11 +716,23 @@ impl<'de> EnumTryVisitor<'de> for ValueVisitor {
                     contents.tuple_variant(2, PairVisitor::new()).map_err(DeErr)?;
                 Some(Value::CoordCount(coord, count))
             },
+            "CoordList" => {
+                let coords: Vec<Coord> =
+                    contents.newtype_variant().map_err(DeErr)?;
+                if coords.is_empty() {
+                    return Err(DeErr(de::Error::custom(
+                        "coord list should not be empty" )));
+                }
+                Some(Value::CoordList(coords))
+            },
+            "Area" => Some( contents.struct_variant(&["min", "max"],
+                AreaVisitor ).map_err(DeErr)? ),
             _ => return Err(NoMatch(id, contents)),
         })
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> de::Visitor<'de> for ValueVisitor {
     type Value = Option<Value>;
     fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result
@@ -641,6 +758,42 @@ impl<'de> de::Visitor<'de> for ValueVisitor {
     }
 }
 
+#[cfg(feature = "serde")]
+struct AreaVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> de::Visitor<'de> for AreaVisitor {
+    type Value = Value;
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        write!(fmt, "an area with `min` and `max` corners")
+    }
+    fn visit_seq<S>(self, seq: S) -> Result<Self::Value, S::Error>
+    where S: de::SeqAccess<'de>
+    {
+        let (min, max) = PairVisitor::new().visit_seq(seq)?;
+        Ok(Value::Area { min, max })
+    }
+    fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+    where M: de::MapAccess<'de>
+    {
+        use serde::de::Error as _;
+        let (mut min, mut max) = (None, None);
+        while let Some(key) = map.next_key::<Identifier>()? {
+            match key.as_ref() {
+                "min" => min = Some(map.next_value()?),
+                "max" => max = Some(map.next_value()?),
+                _ => return Err(M::Error::unknown_field(
+                    &key, &["min", "max"] )),
+            }
+        }
+        let min = min.ok_or_else(|| M::Error::missing_field("min"))?;
+        let max = max.ok_or_else(|| M::Error::missing_field("max"))?;
+        Ok(Value::Area { min, max })
+    }
+}
+
+#[cfg(feature = "serde")]
 impl SerializeOption for Value {
     fn serialize_option<S>(this: Option<&Self>, ser: S)
     -> Result<S::Ok, S::Error>
@@ -654,7 +807,202 @@ impl SerializeOption for Value {
 }
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+/// Value of a blueprint or component register
+/// (see [`Blueprint::registers`] and [`Component::registers`]).
+///
+/// Registers hold the same values as value operands do;
+/// exports may add keys of their own to the register tables,
+/// which are handled according to
+/// [`LoadOptions::unknown_register_keys`] (kept by default).
+///
+/// [`Blueprint::registers`]: super::Blueprint::registers
+/// [`Component::registers`]: super::Component::registers
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub struct RegisterValue {
+    /// `None` if the register table has none of the keys
+    /// of [`Value`] (`id`, `coord`, `num` and indices),
+    /// only unknown ones.
+    pub value: Option<Value>,
+    /// Keys not recognized on load
+    /// (see [`LoadOptions::unknown_register_keys`]).
+    pub extra: Table,
+}
+
+impl From<Value> for RegisterValue {
+    fn from(value: Value) -> Self {
+        Self { value: Some(value), extra: Table::new() }
+    }
+}
+
+impl TryFrom<_Value> for RegisterValue {
+    type Error = LoadError;
+    fn try_from(value: _Value) -> Result<Self, Self::Error> {
+        Self::from_value_with(value, LoadOptions::default())
+    }
+}
+
+impl RegisterValue {
+
+    pub fn from_value_with(value: _Value, options: LoadOptions)
+    -> Result<Self, LoadError>
+    {
+        fn err_unexpected_key(key: Key) -> LoadError { LoadError::from(
+            format!("register representation should not have {key:?} key") )}
+        let _Value::Table(table) = value else {
+            return Err(LoadError::from(
+                "register value should be represented by a table value" ));
+        };
+        let mut known = Vec::new();
+        let mut extra = Vec::new();
+        let mut has_unknown = false;
+        for (key, item) in table {
+            match key {
+                Key::Index(_) => known.push((key, item)),
+                Key::Name(ref name)
+                    if matches!(name.as_ref(), "id" | "coord" | "num") =>
+                    known.push((key, item)),
+                Key::Name(_) | Key::Bool(_) => {
+                    has_unknown = true;
+                    options.unknown_register_keys.handle( &mut extra,
+                        key, item, err_unexpected_key )?;
+                },
+            }
+        }
+        let register = if known.is_empty() && has_unknown {
+            None
+        } else {
+            Some(Value::try_from(known.into_iter().collect::<Table>())?)
+        };
+        Ok(Self { value: register, extra: extra.into_iter().collect() })
+    }
+
+}
+
+impl From<RegisterValue> for _Value {
+    fn from(this: RegisterValue) -> _Value {
+        let RegisterValue { value, extra } = this;
+        let mut table = match value.map(_Value::from) {
+            Some(_Value::Table(table)) => table.into_builder(),
+            _ => Table::new().into_builder(),
+        };
+        table.extend(extra);
+        _Value::Table(table.build())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RegisterRepr {
+    #[serde(default)]
+    value: Option<Value>,
+    #[serde(default)]
+    extra: Table,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct RegisterReprRef<'r> {
+    #[serde(skip_serializing_if="Option::is_none")]
+    value: Option<&'r Value>,
+    #[serde(skip_serializing_if="Table::is_empty")]
+    extra: &'r Table,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> DeserializeOption<'de> for RegisterValue {
+    fn deserialize_option<D>(de: D)
+    -> Result<Option<Self>, D::Error>
+    where D: de::Deserializer<'de>
+    {
+        de.deserialize_enum("Value", &[], RegisterValueVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+forward_de_to_de_option!(RegisterValue);
+
+#[cfg(feature = "serde")]
+struct RegisterValueVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> EnumTryVisitor<'de> for RegisterValueVisitor {
+    fn visit_enum_match<V>(self, id: Identifier<'de>, contents: V)
+    -> Result<Self::Value, EnumMatchError<'de, V::Error, V>>
+    where V: de::VariantAccess<'de>
+    {
+        use EnumMatchError::DeErr;
+        if id.as_ref() == "Register" {
+            let RegisterRepr { value, extra } =
+                contents.newtype_variant().map_err(DeErr)?;
+            return Ok(Some(RegisterValue { value, extra }));
+        }
+        Ok(ValueVisitor.visit_enum_match(id, contents)?
+            .map(RegisterValue::from))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> de::Visitor<'de> for RegisterValueVisitor {
+    type Value = Option<RegisterValue>;
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        write!(fmt, "a register value")
+    }
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        Ok(None)
+    }
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where A: de::EnumAccess<'de>
+    {
+        use serde::de::Error as _;
+        let (id, contents) = data.variant()?;
+        match self.visit_enum_match(id, contents) {
+            Ok(value) => Ok(value),
+            Err(EnumMatchError::DeErr(error)) => Err(error),
+            Err(EnumMatchError::NoMatch(id, _)) => Err(A::Error::custom(
+                format!("name {id:?} is not a known Value variant") )),
+        }
+    }
+}
+
+/// Values without unknown keys are represented as [`Value`] is,
+/// others as a `Register` variant with `value` and `extra` fields.
+#[cfg(feature = "serde")]
+impl Serialize for RegisterValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+    {
+        match self.value {
+            Some(ref value) if self.extra.is_empty() => value.serialize(ser),
+            _ => ser.serialize_newtype_variant("Value", 0, "Register",
+                &RegisterReprRef {
+                    value: self.value.as_ref(),
+                    extra: &self.extra,
+                } ),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SerializeOption for RegisterValue {
+    fn serialize_option<S>(this: Option<&Self>, ser: S)
+    -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+    {
+        let Some(this) = this else {
+            return ser.serialize_unit_variant("Value", 0, "SkippedValue");
+        };
+        this.serialize(ser)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 pub struct Coord {
     pub x: i32,
     pub y: i32,
@@ -708,12 +1056,18 @@ impl From<Coord> for _Value {
 }
 
 
-#[cfg(test)]
+#[cfg(all(test, feature = "serde"))]
 mod test {
 
-use crate::Str;
+use crate::{
+    Str,
+    value::{Key, Value as _Value, Table},
+};
 
-use super::{Coord, Operand, Place, Register, Value};
+use super::{
+    super::{LoadOptions, UnknownKeys},
+    Coord, Operand, Place, Register, Value, RegisterValue,
+};
 
 #[test]
 fn test_operand_serde_ron() {
@@ -747,5 +1101,89 @@ fn test_operand_serde_ron() {
     }
 }
 
+#[test]
+fn test_register_value() {
+    let table = _Value::Table(Table::from_iter([
+        (Key::from("num"), _Value::Integer(5)),
+        (Key::from("unknown"), _Value::Boolean(true)),
+    ]));
+    let options = |unknown_register_keys| LoadOptions {
+        unknown_register_keys, ..LoadOptions::default() };
+    RegisterValue::from_value_with(table.clone(), options(UnknownKeys::Error))
+        .unwrap_err();
+    let register = RegisterValue::try_from(table.clone()).unwrap();
+    assert_eq!(register.value, Some(Value::Number(5)));
+    assert_eq!(register.extra.len(), 1);
+    assert_eq!(_Value::from(register.clone()), table);
+    assert_eq!( RegisterValue::from_value_with(
+            table, options(UnknownKeys::Drop) ).unwrap(),
+        RegisterValue::from(Value::Number(5)) );
+    let unknown_only = RegisterValue::try_from(_Value::Table(Table::from_iter([
+        (Key::from("unknown"), _Value::Boolean(true)),
+    ]))).unwrap();
+    assert_eq!(unknown_only.value, None);
+    assert_eq!( RegisterValue::from_value_with(
+            _Value::from(unknown_only.clone()), options(UnknownKeys::Drop) )
+            .unwrap(),
+        RegisterValue { value: None, extra: Table::new() } );
+    RegisterValue::try_from(_Value::Table(Table::new())).unwrap_err();
+
+    for (register, s) in [
+        (RegisterValue::from(Value::Number(5)), "Number(5)"),
+        (register,
+            "Register((value:Some(Number(5)),extra:{\"unknown\":true}))"),
+        (unknown_only, "Register((extra:{\"unknown\":true}))"),
+    ] {
+        assert_eq!(ron::to_string(&register).unwrap(), s);
+        assert_eq!(ron::from_str::<RegisterValue>(s).unwrap(), register);
+    }
 }
 
+#[test]
+fn test_coord_list_and_area() {
+    let coord = |x, y| _Value::from(Coord { x, y });
+    let list = _Value::Table(Table::from_iter([
+        (Key::Index(1), coord(1, 2)),
+        (Key::Index(2), coord(3, 4)),
+    ]));
+    let list_value = Value::try_from(list.clone()).unwrap();
+    assert_eq!( list_value, Value::CoordList(vec![
+        Coord { x: 1, y: 2 }, Coord { x: 3, y: 4 } ]) );
+    assert_eq!(_Value::from(list_value.clone()), list);
+    let area = _Value::Table(Table::from_iter([
+        (Key::from("coord"), coord(-1, -2)),
+        (Key::Index(1), coord(5, 6)),
+    ]));
+    let area_value = Value::try_from(area.clone()).unwrap();
+    assert_eq!( area_value, Value::Area {
+        min: Coord { x: -1, y: -2 }, max: Coord { x: 5, y: 6 } } );
+    assert_eq!(_Value::from(area_value.clone()), area);
+    for bad in [
+        vec![],
+        vec![(Key::Index(2), coord(1, 2))],
+        vec![
+            (Key::Index(1), coord(1, 2)),
+            (Key::from("num"), _Value::Integer(1)),
+        ],
+        vec![(Key::Index(1), _Value::Integer(1))],
+        vec![
+            (Key::from("coord"), coord(1, 2)),
+            (Key::Index(1), coord(1, 2)), (Key::Index(2), coord(1, 2)),
+        ],
+    ] {
+        Value::try_from(_Value::Table(Table::from_iter(bad))).unwrap_err();
+    }
+
+    for (value, s) in [
+        (list_value, "CoordList([(x:1,y:2),(x:3,y:4)])"),
+        (area_value, "Area(min:(x:-1,y:-2),max:(x:5,y:6))"),
+    ] {
+        assert_eq!(ron::to_string(&value).unwrap(), s);
+        assert_eq!(ron::from_str::<Value>(s).unwrap(), value);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+    }
+    ron::from_str::<Value>("CoordList([])").unwrap_err();
+}
+
+}
@@ -0,0 +1,339 @@
+//! Rewriting behaviors into smaller ones that run the same way.
+//!
+//! The encoding does not tell jumps from other arguments,
+//! so jump arguments are recognized with an [`OpCatalog`]
+//! (see [`ArgKind::Exec`]).
+//! Subroutines are not included; they can be optimized separately.
+
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+
+use crate::Str;
+
+use super::{Behavior, Instruction, Operand, Jump, OpCatalog, ArgKind};
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OptimizeOptions {
+    /// Remove instructions that cannot be reached from the first one.
+    pub remove_unreachable: bool,
+    /// Replace jumps to the following instruction with [`Jump::Next`],
+    /// and redirect jumps leading to a pass-through instruction
+    /// (see `pass_through`) to wherever it continues.
+    pub collapse_jumps: bool,
+    /// Drop empty comments and zero offsets.
+    pub drop_empty: bool,
+    /// Operations that do nothing but continue to `next`
+    /// when they have no arguments.
+    /// None by default, as the crate does not know the game's operations:
+    /// without them `collapse_jumps` only replaces jumps
+    /// to the following instruction.
+    pub pass_through: BTreeSet<Str>,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            remove_unreachable: true,
+            collapse_jumps: true,
+            drop_empty: true,
+            pass_through: BTreeSet::new(),
+        }
+    }
+}
+
+/// What [`optimize`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OptimizeReport {
+    /// `0`-based positions of the removed instructions,
+    /// as they were before the optimization.
+    pub removed: Vec<usize>,
+    /// Number of jumps that were redirected or replaced.
+    pub collapsed_jumps: usize,
+    pub dropped_comments: usize,
+    pub dropped_offsets: usize,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum OptimizeError {
+    /// Removing instructions renumbers jumps, and an argument
+    /// that the catalog does not describe may be a jump.
+    #[error( "Argument {arg} of instruction {index} \
+              can be either a jump or a parameter" )]
+    AmbiguousOperand { index: usize, arg: usize },
+}
+
+/// Apply the optimizations enabled in the options.
+///
+/// On error the behavior is left unchanged.
+pub fn optimize( behavior: &mut Behavior,
+    catalog: &OpCatalog, options: &OptimizeOptions,
+) -> Result<OptimizeReport, OptimizeError> {
+    let mut report = OptimizeReport::default();
+    let mut instructions = behavior.instructions.clone();
+    if options.collapse_jumps {
+        report.collapsed_jumps = collapse_jumps(
+            &mut instructions, catalog, &options.pass_through );
+    }
+    if options.remove_unreachable {
+        report.removed = remove_unreachable(&mut instructions, catalog)?;
+        // renumbered jumps may now lead to the following instruction
+        if options.collapse_jumps && !report.removed.is_empty() {
+            report.collapsed_jumps += collapse_jumps(
+                &mut instructions, catalog, &options.pass_through );
+        }
+    }
+    if options.drop_empty {
+        for instruction in &mut instructions {
+            if instruction.comment.as_deref() == Some("") {
+                instruction.comment = None;
+                report.dropped_comments += 1;
+            }
+            if instruction.offset == Some((0.0, 0.0)) {
+                instruction.offset = None;
+                report.dropped_offsets += 1;
+            }
+        }
+    }
+    behavior.instructions = instructions;
+    Ok(report)
+}
+
+fn collapse_jumps( instructions: &mut [Instruction],
+    catalog: &OpCatalog, pass_through: &BTreeSet<Str>,
+) -> usize {
+    // where execution continues after each pass-through instruction
+    let continuations: Vec<Option<Jump>> = instructions.iter().enumerate()
+        .map(|(index, instruction)| (
+            instruction.args.is_empty() &&
+            instruction.operation.as_name()
+                .is_some_and(|name| pass_through.contains(name))
+        ).then(|| absolute(index, &instruction.next)) )
+        .collect();
+    let mut count = 0;
+    for (index, instruction) in instructions.iter_mut().enumerate() {
+        visit_jumps(instruction, catalog, |jump| {
+            let mut target = absolute(index, &jump);
+            // the chain may loop, but it cannot be longer than that
+            for _ in 0 .. continuations.len() {
+                let Some(Some(continuation)) = position(&target)
+                    .and_then(|position| continuations.get(position))
+                else { break };
+                target = continuation.clone();
+            }
+            if target == absolute(index, &Jump::Next) {
+                target = Jump::Next;
+            }
+            if target == jump { return None; }
+            count += 1;
+            Some(target)
+        });
+    }
+    count
+}
+
+fn remove_unreachable( instructions: &mut Vec<Instruction>,
+    catalog: &OpCatalog,
+) -> Result<Vec<usize>, OptimizeError> {
+    let mut reachable = vec![false; instructions.len()];
+    let mut queue = Vec::from_iter(
+        (!instructions.is_empty()).then_some(0) );
+    while let Some(index) = queue.pop() {
+        if std::mem::replace(&mut reachable[index], true) { continue; }
+        let instruction = &instructions[index];
+        let mut targets = vec![absolute(index, &instruction.next)];
+        for (arg, operand) in instruction.args.iter().enumerate() {
+            match (as_jump(operand, arg_kind(catalog, instruction, arg)),
+                operand)
+            {
+                (Some(jump), _) => targets.push(absolute(index, &jump)),
+                // possibly a jump
+                (None, &Operand::UnknownIndex(target)) =>
+                    targets.push(Jump::Jump(target)),
+                (None, _) => (),
+            }
+        }
+        queue.extend( targets.iter().filter_map(position)
+            .filter(|&target| target < reachable.len()) );
+    }
+    let removed: Vec<usize> = reachable.iter().enumerate()
+        .filter_map(|(index, &reachable)| (!reachable).then_some(index))
+        .collect();
+    if removed.is_empty() {
+        return Ok(removed);
+    }
+    for (index, instruction) in instructions.iter().enumerate() {
+        if !reachable[index] { continue; }
+        for (arg, operand) in instruction.args.iter().enumerate() {
+            if matches!(*operand, Operand::UnknownIndex(_)) &&
+                arg_kind(catalog, instruction, arg).is_none()
+            {
+                return Err(OptimizeError::AmbiguousOperand { index, arg });
+            }
+        }
+    }
+    // `1`-based jump targets past the end keep their distance to the end
+    let renumber = |target: i32| {
+        let Some(position) = position(&Jump::Jump(target)) else {
+            return target;
+        };
+        let shift = removed.partition_point(|&index| index < position);
+        target - i32::try_from(shift).unwrap_or(i32::MAX)
+    };
+    let mut index = 0;
+    instructions.retain(|_| {
+        index += 1;
+        reachable[index - 1]
+    });
+    for instruction in instructions.iter_mut() {
+        visit_jumps(instruction, catalog, |jump| match jump {
+            Jump::Jump(target) if renumber(target) != target =>
+                Some(Jump::Jump(renumber(target))),
+            _ => None,
+        });
+    }
+    Ok(removed)
+}
+
+/// Call `f` on the `next` jump and jump arguments of the instruction,
+/// replacing them with the returned jump, if any.
+fn visit_jumps<F>(instruction: &mut Instruction, catalog: &OpCatalog, mut f: F)
+where F: FnMut(Jump) -> Option<Jump>
+{
+    if let Some(jump) = f(instruction.next.clone()) {
+        instruction.next = jump;
+    }
+    let kinds: Vec<Option<ArgKind>> = (0 .. instruction.args.len())
+        .map(|arg| arg_kind(catalog, instruction, arg))
+        .collect();
+    for (operand, kind) in instruction.args.iter_mut().zip(kinds) {
+        let Some(jump) = as_jump(operand, kind) else { continue };
+        if let Some(jump) = f(jump) {
+            *operand = Operand::Jump(jump);
+        }
+    }
+}
+
+fn arg_kind(catalog: &OpCatalog, instruction: &Instruction, arg: usize)
+-> Option<ArgKind>
+{
+    catalog.get_ref(&instruction.operation)
+        .and_then(|info| info.args.get(arg))
+        .map(|arg_info| arg_info.kind)
+}
+
+fn as_jump(operand: &Operand, kind: Option<ArgKind>) -> Option<Jump> {
+    let mut operand = operand.clone();
+    if kind == Some(ArgKind::Exec) {
+        operand.make_jump().ok()?;
+    }
+    match operand {
+        Operand::Jump(jump) => Some(jump),
+        _ => None,
+    }
+}
+
+/// Replace [`Jump::Next`] of the `index`-th instruction
+/// with the explicit jump.
+fn absolute(index: usize, jump: &Jump) -> Jump {
+    match *jump {
+        Jump::Next => Jump::Jump(
+            i32::try_from(index + 2).unwrap_or(i32::MAX) ),
+        ref jump => jump.clone(),
+    }
+}
+
+/// `0`-based position of the target of an explicit jump.
+fn position(jump: &Jump) -> Option<usize> {
+    match *jump {
+        Jump::Jump(target) => usize::try_from(target.checked_sub(1)?).ok(),
+        Jump::Next | Jump::Return | Jump::Label(_) => None,
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::Str;
+
+use super::{
+    super::{Behavior, Jump, Operand, OpCatalog, OpInfo, ArgInfo, ArgKind},
+    optimize, OptimizeOptions, OptimizeReport, OptimizeError,
+};
+
+#[test]
+fn test_optimize() {
+    let mut behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "check_number", args: [Index(4), Variable("A")],
+                next: Jump(2)),
+            (op: "nop", next: Jump(6), comment: ""),
+            (op: "set_reg", args: [Variable("B"), Variable("C")]),
+            (op: "nop", offset: (0.0, 0.0)),
+            (op: "set_reg", args: [Variable("A"), Variable("C")],
+                next: Return),
+            (op: "set_reg", args: [Variable("C"), Variable("A")],
+                next: Jump(5)),
+        ],
+    )"#).unwrap();
+    let catalog = OpCatalog::from_iter([
+        ("check_number", OpInfo::new(vec![
+            ArgInfo::new("if_larger", ArgKind::Exec),
+            ArgInfo::new("value", ArgKind::In),
+        ])),
+        ("set_reg", OpInfo::new(vec![
+            ArgInfo::new("value", ArgKind::In),
+            ArgInfo::new("to", ArgKind::Out),
+        ])),
+    ]);
+    let mut by_default = behavior.clone();
+    let report = optimize( &mut by_default, &catalog,
+        &OptimizeOptions::default() ).unwrap();
+    // only the jump to the following instruction is collapsed,
+    // and the `nop`s stay
+    assert_eq!((report.removed, report.collapsed_jumps), (vec![2], 1));
+    assert_eq!(by_default.instructions[0].next, Jump::Next);
+    assert_eq!(by_default.instructions[1].next, Jump::Jump(5));
+    assert_eq!(by_default.instructions.len(), 5);
+    let mut options = OptimizeOptions::default();
+    options.pass_through.insert(Str::from("nop"));
+    let report = optimize(&mut behavior, &catalog, &options).unwrap();
+    assert_eq!(report, OptimizeReport {
+        removed: vec![1, 2, 3],
+        collapsed_jumps: 4,
+        dropped_comments: 0,
+        dropped_offsets: 0,
+    });
+    let jumps: Vec<(&str, Jump)> = behavior.instructions.iter()
+        .map(|instruction| (
+            instruction.operation.as_name().unwrap(),
+            instruction.next.clone() ))
+        .collect();
+    assert_eq!(jumps, [
+        ("check_number", Jump::Jump(3)),
+        ("set_reg", Jump::Return),
+        ("set_reg", Jump::Jump(2)),
+    ]);
+    assert_eq!(behavior.instructions[0].args[0], Operand::Jump(Jump::Next));
+
+    let mut behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "unknown", args: [Index(1)], next: Return, comment: ""),
+            (op: "set_reg", offset: (0.0, 0.0)),
+        ],
+    )"#).unwrap();
+    let unchanged = format!("{behavior:?}");
+    assert!(matches!(
+        optimize(&mut behavior, &catalog, &OptimizeOptions::default()),
+        Err(OptimizeError::AmbiguousOperand { index: 0, arg: 0 }) ));
+    assert_eq!(format!("{behavior:?}"), unchanged);
+    options.remove_unreachable = false;
+    let report = optimize(&mut behavior, &catalog, &options).unwrap();
+    assert_eq!((report.dropped_comments, report.dropped_offsets), (1, 1));
+    assert_eq!(behavior.instructions[1].offset, None);
+}
+
+}
@@ -0,0 +1,169 @@
+use crate::{
+    error::LoadError,
+    loader::{
+        DecodeOptions, DuplicateKeys, InvalidUtf8, KeyMap,
+        DEFAULT_MAX_DEPTH,
+    },
+    value::{Key, Value},
+};
+
+use super::migrate::Migrations;
+
+/// Options for converting tables into blueprint structures.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LoadOptions {
+    pub unknown_keys: UnknownKeys,
+    /// Check the checksum digit of the exchange string
+    /// (the default).
+    /// Only affects loading from exchange strings.
+    pub verify_checksum: bool,
+    /// Reject non-minimal integer encodings,
+    /// see [`DecodeOptions::canonical_varints`].
+    /// Only affects loading from exchange strings.
+    pub canonical_varints: bool,
+    /// Remember which collections were represented by empty tables
+    /// (instead of being omitted),
+    /// so that they are written back the same way.
+    pub preserve_empty_tables: bool,
+    /// What to do with integer keys of behavior tables
+    /// that do not continue the instruction array
+    /// (the game accepts them);
+    /// kept ones go to [`Behavior::extra_indexed`].
+    ///
+    /// [`Behavior::extra_indexed`]: super::Behavior::extra_indexed
+    pub stray_indices: UnknownKeys,
+    /// What to do with names in `pnames` past the number
+    /// of behavior parameters
+    /// (exports may keep names of removed parameters);
+    /// kept ones go to [`Behavior::stale_parameter_names`].
+    ///
+    /// [`Behavior::stale_parameter_names`]:
+    ///     super::Behavior::stale_parameter_names
+    pub stale_parameter_names: UnknownKeys,
+    /// What to do with keys of register tables other than those
+    /// of [`Value`](super::Value) (exports add keys of their own);
+    /// kept ones go to [`RegisterValue::extra`].
+    /// Kept by default.
+    ///
+    /// [`RegisterValue::extra`]: super::RegisterValue::extra
+    pub unknown_register_keys: UnknownKeys,
+    /// What to do with entries of component tables past the item id,
+    /// the index and the behavior
+    /// (older versions of the crate ignored them);
+    /// kept ones go to [`Component::extra`].
+    /// Kept by default.
+    ///
+    /// [`Component::extra`]: super::Component::extra
+    pub unknown_component_keys: UnknownKeys,
+    /// See [`DecodeOptions::key_map`].
+    /// Only affects loading from exchange strings.
+    pub key_map: Option<KeyMap>,
+    /// See [`DecodeOptions::max_depth`].
+    /// Only affects loading from exchange strings.
+    pub max_depth: u32,
+    /// See [`DecodeOptions::duplicate_keys`].
+    /// Only affects loading from exchange strings.
+    pub duplicate_keys: DuplicateKeys,
+    /// See [`DecodeOptions::utf8`].
+    /// Fields of blueprint structures are text,
+    /// so [`InvalidUtf8::Bytes`] only helps the values
+    /// that are kept as they are (e.g. with unknown keys).
+    /// Only affects loading from exchange strings.
+    pub utf8: InvalidUtf8,
+    /// Applied to the tables of blueprints and behaviors
+    /// before they are converted. None by default.
+    pub migrations: Migrations,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            unknown_keys: UnknownKeys::default(),
+            verify_checksum: true,
+            canonical_varints: false,
+            preserve_empty_tables: false,
+            stray_indices: UnknownKeys::default(),
+            stale_parameter_names: UnknownKeys::default(),
+            unknown_register_keys: UnknownKeys::Keep,
+            unknown_component_keys: UnknownKeys::Keep,
+            key_map: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            duplicate_keys: DuplicateKeys::default(),
+            utf8: InvalidUtf8::default(),
+            migrations: Migrations::new(),
+        }
+    }
+}
+
+impl LoadOptions {
+
+    /// Reject anything unexpected.
+    /// This is the default, except that the default keeps
+    /// unknown keys of register and component tables.
+    #[must_use]
+    pub fn strict() -> Self {
+        Self {
+            unknown_register_keys: UnknownKeys::Error,
+            unknown_component_keys: UnknownKeys::Error,
+            ..Self::default()
+        }
+    }
+
+    /// Keep unexpected keys, stray indices, stale parameter names
+    /// and empty tables for round-tripping.
+    #[must_use]
+    pub fn lenient() -> Self {
+        Self {
+            unknown_keys: UnknownKeys::Keep,
+            preserve_empty_tables: true,
+            stray_indices: UnknownKeys::Keep,
+            stale_parameter_names: UnknownKeys::Keep,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions {
+            verify_checksum: self.verify_checksum,
+            canonical_varints: self.canonical_varints,
+            key_map: self.key_map,
+            max_depth: self.max_depth,
+            duplicate_keys: self.duplicate_keys,
+            utf8: self.utf8,
+        }
+    }
+
+}
+
+/// What to do with keys of blueprint and behavior tables
+/// that do not correspond to any known field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnknownKeys {
+    /// Fail the conversion.
+    #[default]
+    Error,
+    /// Store them in the `extra` table of the structure,
+    /// so that they are written back on dump.
+    Keep,
+    /// Silently discard them.
+    Drop,
+}
+
+impl UnknownKeys {
+    pub(super) fn handle<F>( self,
+        extra: &mut Vec<(Key, Value)>,
+        key: Key, value: Value,
+        err_unexpected_key: F,
+    ) -> Result<(), LoadError>
+    where F: FnOnce(Key) -> LoadError
+    {
+        match self {
+            Self::Error => return Err(err_unexpected_key(key)),
+            Self::Keep => extra.push((key, value)),
+            Self::Drop => (),
+        }
+        Ok(())
+    }
+}
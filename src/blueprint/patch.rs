@@ -0,0 +1,233 @@
+//! Re-encoding a behavior after small edits.
+//!
+//! Encoding a large behavior anew on every keystroke of an editor
+//! is wasteful, since an edit usually touches a few instructions.
+//! A [`Patcher`] remembers where each instruction is encoded
+//! in the binary data of the original exchange string
+//! and replaces only the instructions that were changed,
+//! leaving the rest of the data byte-for-byte intact.
+//! Edits that add or remove instructions shift the instruction list,
+//! so they fall back to encoding the whole behavior.
+
+use std::{collections::BTreeSet, ops::Range};
+
+use crate::{
+    Exchange,
+    error::{LoadError, DumpError},
+    value::Value as _Value,
+    loader::{decode, decompress::decompress, array_item_ranges, Warnings},
+    dumper::{DumpOptions, encode, compress::compress},
+};
+
+use super::{
+    Behavior, Instruction, Operand, Jump, LoadOptions,
+    edit::{apply, is_variable, EditOp, EditError},
+};
+
+pub struct Patcher {
+    behavior: Behavior,
+    data: Vec<u8>,
+    // Encoded instructions in `data`;
+    // `None` if the whole behavior should be encoded anew.
+    ranges: Option<Vec<Range<usize>>>,
+    dirty: BTreeSet<usize>,
+    options: DumpOptions,
+}
+
+impl Patcher {
+
+    /// Only behaviors can be patched.
+    pub fn new(exchange: &str, options: DumpOptions)
+    -> Result<Self, LoadError>
+    {
+        Self::with_options(exchange, options, LoadOptions::default())
+    }
+
+    /// Load the behavior with the options
+    /// (see [`load_blueprint_with`](super::load_blueprint_with)).
+    /// With [`LoadOptions::key_map`] the first patch encodes
+    /// the whole behavior, since the original keys are not kept.
+    pub fn with_options( exchange: &str,
+        options: DumpOptions, load_options: LoadOptions,
+    ) -> Result<Self, LoadError>
+    {
+        let Exchange::Behavior(data) =
+            decompress(exchange, load_options.verify_checksum)?
+        else {
+            return Err(LoadError::from("only behaviors can be patched"));
+        };
+        let key_map = load_options.key_map.is_some();
+        let value = decode::<_Value>( &data,
+            load_options.decode_options(), &mut Warnings::new() )?
+            .ok_or_else(|| LoadError::from(
+                "behavior should not be represented with nil" ))?;
+        let behavior = Behavior::from_value_with(value, load_options)?;
+        let ranges = if key_map { None } else {
+            instruction_ranges(&data, &behavior)
+        };
+        Ok(Self {
+            behavior, data, ranges,
+            dirty: BTreeSet::new(),
+            options,
+        })
+    }
+
+    #[must_use]
+    pub fn behavior(&self) -> &Behavior {
+        &self.behavior
+    }
+
+    /// Apply the edit (see [`apply`]), returning its inverse.
+    pub fn apply(&mut self, op: EditOp) -> Result<EditOp, EditError> {
+        let touched: Option<Vec<usize>> = match op {
+            EditOp::SetOperand { index, .. } |
+            EditOp::SetNext { index, .. } => Some(vec![index]),
+            EditOp::RenameVariable { ref from, .. } => Some(
+                self.behavior.instructions.iter().enumerate()
+                    .filter(|(_, instruction)| instruction.args.iter()
+                        .any(|arg| is_variable(arg, from)) )
+                    .map(|(index, _)| index)
+                    .collect() ),
+            EditOp::AddInstruction { .. } |
+            EditOp::RemoveInstruction { .. } => None,
+        };
+        let inverse = apply(&mut self.behavior, op)?;
+        match touched {
+            Some(touched) => self.dirty.extend(touched),
+            None => self.ranges = None,
+        }
+        Ok(inverse)
+    }
+
+    /// The exchange string of the edited behavior.
+    pub fn exchange(&mut self) -> Result<String, DumpError> {
+        // jumps to labels are resolved by encoding the whole behavior
+        if self.dirty.iter().any( |&index|
+            has_label_jumps(&self.behavior.instructions[index]) )
+        {
+            self.ranges = None;
+        }
+        match self.ranges {
+            Some(ref mut ranges) => {
+                let mut encoded = Vec::with_capacity(self.dirty.len());
+                for &index in &self.dirty {
+                    encoded.push((index, encode(Some(_Value::try_from(
+                        self.behavior.instructions[index].clone() )?))?));
+                }
+                let mut data = Vec::with_capacity(self.data.len());
+                let mut copied = 0;
+                let mut encoded = encoded.into_iter().peekable();
+                for (index, range) in ranges.iter_mut().enumerate() {
+                    data.extend_from_slice(
+                        &self.data[copied .. range.start] );
+                    let start = data.len();
+                    match encoded.next_if(|&(dirty, _)| dirty == index) {
+                        Some((_, instruction)) =>
+                            data.extend_from_slice(&instruction),
+                        None => data.extend_from_slice(
+                            &self.data[range.clone()] ),
+                    }
+                    copied = range.end;
+                    *range = start .. data.len();
+                }
+                data.extend_from_slice(&self.data[copied ..]);
+                self.data = data;
+            },
+            None => {
+                self.data = encode(Some(
+                    _Value::try_from(self.behavior.clone())? ))?;
+                self.ranges = instruction_ranges(&self.data, &self.behavior);
+            },
+        }
+        self.dirty.clear();
+        Ok(compress(Exchange::Behavior(&self.data), &self.options))
+    }
+
+}
+
+fn has_label_jumps(instruction: &Instruction) -> bool {
+    std::iter::once(&instruction.next)
+        .chain(instruction.args.iter().filter_map(|arg| match *arg {
+            Operand::Jump(ref jump) => Some(jump),
+            _ => None,
+        }))
+        .any(|jump| matches!(*jump, Jump::Label(_)))
+}
+
+/// `None` unless every instruction is encoded in the array part
+/// of the behavior table.
+fn instruction_ranges(data: &[u8], behavior: &Behavior)
+-> Option<Vec<Range<usize>>>
+{
+    let ranges = array_item_ranges(data).ok()?;
+    if ranges.len() != behavior.instructions.len() {
+        return None;
+    }
+    ranges.into_iter().collect()
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::{Exchange, dumper::DumpOptions};
+
+use super::{
+    super::{load_blueprint, Behavior, Jump, LoadOptions, edit::EditOp},
+    Patcher,
+};
+
+fn load_behavior(exchange: &str) -> Behavior {
+    let Exchange::Behavior(behavior) = load_blueprint(exchange).unwrap()
+        else { panic!("should be a behavior") };
+    behavior
+}
+
+fn to_ron(behavior: &Behavior) -> String {
+    ron::to_string(behavior).unwrap()
+}
+
+#[test]
+fn test_patcher() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let mut patcher = Patcher::new(exchange, DumpOptions::default()).unwrap();
+    assert!(patcher.ranges.is_some());
+    let instruction = patcher.behavior().instructions[1].clone();
+    patcher.apply(EditOp::SetNext { index: 0, next: Jump::Return })
+        .unwrap();
+    patcher.apply(EditOp::SetNext { index: 2, next: Jump::Jump(1) })
+        .unwrap();
+    let patched = patcher.exchange().unwrap();
+    assert!(patcher.ranges.is_some());
+    assert_eq!(to_ron(&load_behavior(&patched)), to_ron(patcher.behavior()));
+    patcher.apply(EditOp::AddInstruction { index: 1, instruction })
+        .unwrap();
+    let patched = patcher.exchange().unwrap();
+    assert!(patcher.ranges.is_some());
+    assert_eq!(to_ron(&load_behavior(&patched)), to_ron(patcher.behavior()));
+    patcher.apply(EditOp::SetNext { index: 3, next: Jump::Next }).unwrap();
+    let patched = patcher.exchange().unwrap();
+    assert_eq!(to_ron(&load_behavior(&patched)), to_ron(patcher.behavior()));
+    patcher.apply(EditOp::SetNext { index: 0,
+        next: Jump::Label("nowhere".into()) }).unwrap();
+    patcher.exchange().unwrap_err();
+}
+
+#[test]
+fn test_patcher_with_options() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let mut corrupted = String::from(exchange);
+    let last = corrupted.pop().unwrap();
+    corrupted.push(if last == 'A' { 'B' } else { 'A' });
+    assert!(Patcher::new(&corrupted, DumpOptions::default()).is_err());
+    let mut load_options = LoadOptions::default();
+    load_options.verify_checksum = false;
+    let mut patcher = Patcher::with_options( &corrupted,
+        DumpOptions::default(), load_options ).unwrap();
+    assert!(patcher.ranges.is_some());
+    patcher.apply(EditOp::SetNext { index: 0, next: Jump::Return })
+        .unwrap();
+    let patched = patcher.exchange().unwrap();
+    assert_eq!(to_ron(&load_behavior(&patched)), to_ron(patcher.behavior()));
+}
+
+}
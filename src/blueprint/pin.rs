@@ -0,0 +1,166 @@
+//! Header block for text (RON, JSON, …) exports of blueprints.
+//!
+//! A blueprint exported as [`Pinned`] carries the version of
+//! the text representation it was written with,
+//! and a hash of its content.
+//! On import, [`Pinned::check`] reports whether the content
+//! was produced by a different format version or edited since.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use crate::common::serde::option_some as serde_option_some;
+
+use crate::{
+    error::DumpError,
+    dumper::{DumpOptions, Compression, encode_raw},
+    Str,
+};
+
+use super::{Exchange, Blueprint, Behavior, into_values};
+
+/// Version of the text representation of blueprint structures.
+/// Incremented whenever the representation changes incompatibly.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub struct PinHeader {
+
+    /// Version of this crate that produced the export.
+    pub crate_version: Str,
+
+    /// See [`FORMAT_VERSION`].
+    pub format_version: u32,
+
+    /// Hash of the encoded data, before compression
+    /// (hexadecimal FNV-1a),
+    /// so that it does not depend on the zlib backend or level.
+    pub content_hash: Str,
+
+    /// Free-form version of the game the content is meant for.
+    #[cfg_attr(feature = "serde", serde( default,
+        skip_serializing_if="Option::is_none",
+        with="serde_option_some" ))]
+    pub game_version: Option<Str>,
+
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PinWarning {
+    /// The content was written with a different representation format.
+    FormatVersion { found: u32 },
+    /// The content does not match the hash in the header.
+    ContentModified,
+}
+
+impl std::fmt::Display for PinWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FormatVersion { found } => write!( f,
+                "content was written with format version {found}, \
+                 current format version is {FORMAT_VERSION}" ),
+            Self::ContentModified => write!( f,
+                "content does not match the hash in the header" ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub struct Pinned<T = Exchange<Blueprint, Behavior>> {
+    pub header: PinHeader,
+    pub content: T,
+}
+
+impl Pinned {
+
+    pub fn new( content: Exchange<Blueprint, Behavior>,
+        game_version: Option<Str>,
+    ) -> Result<Self, DumpError> {
+        let content_hash = content_hash(&content)?;
+        Ok(Self {
+            header: PinHeader {
+                crate_version: Str::known(env!("CARGO_PKG_VERSION")),
+                format_version: FORMAT_VERSION,
+                content_hash,
+                game_version,
+            },
+            content,
+        })
+    }
+
+    /// Compare the header against the current format version
+    /// and the actual content.
+    pub fn check(&self) -> Result<Vec<PinWarning>, DumpError> {
+        let mut warnings = Vec::new();
+        if self.header.format_version != FORMAT_VERSION {
+            warnings.push(PinWarning::FormatVersion {
+                found: self.header.format_version });
+        }
+        if content_hash(&self.content)? != self.header.content_hash {
+            warnings.push(PinWarning::ContentModified);
+        }
+        Ok(warnings)
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> Exchange<Blueprint, Behavior> {
+        self.content
+    }
+
+}
+
+fn content_hash(content: &Exchange<Blueprint, Behavior>)
+-> Result<Str, DumpError>
+{
+    type V = crate::value::Value;
+    let options = DumpOptions {
+        compression: Compression::Never,
+        ..DumpOptions::default()
+    };
+    let (kind, data) = match encode_raw::<V, V>(
+        into_values(content.clone())?, &options )?
+    {
+        Exchange::Blueprint(data) => (b'B', data),
+        Exchange::Behavior(data) => (b'C', data),
+    };
+    let hash = fnv1a(fnv1a(OFFSET, &[kind]), &data);
+    Ok(Str::from(format!("{hash:016x}").as_str()))
+}
+
+const OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    const PRIME: u64 = 0x0100_0000_01B3;
+    data.iter().fold(hash, |hash, &byte|
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME) )
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::Exchange;
+
+use super::{super::load_blueprint, Pinned, PinWarning};
+
+#[test]
+fn test_pin_check() {
+    let content = load_blueprint(crate::test::EXCHANGE_BEHAVIOR_2).unwrap();
+    let ron = ron::to_string(&Pinned::new(content, None).unwrap()).unwrap();
+    let mut pinned: Pinned = ron::from_str(&ron).unwrap();
+    assert_eq!(pinned.check().unwrap(), []);
+    pinned.header.format_version = 0;
+    let Exchange::Behavior(behavior) = &mut pinned.content
+        else { panic!("should be a behavior") };
+    behavior.name = Some("renamed".into());
+    assert_eq!( pinned.check().unwrap(), [
+        PinWarning::FormatVersion { found: 0 },
+        PinWarning::ContentModified,
+    ] );
+}
+
+}
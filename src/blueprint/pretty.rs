@@ -0,0 +1,293 @@
+//! Human-readable listing of behavior instructions.
+//!
+//! The `Display` implementations use the same listing
+//! without a catalog, so arguments are labeled by their numbers.
+
+use std::fmt::{self, Write as _};
+
+use super::{
+    Blueprint, Component, Behavior, Parameter, Instruction,
+    Operand, Jump, Place, Register, Value,
+    OpCatalog, ArgKind,
+};
+
+impl Behavior {
+
+    /// List the instructions one per line, e.g.
+    /// `3: mine  item=metal  -> 5`.
+    ///
+    /// Arguments are labeled with names from the catalog
+    /// (`#1`, `#2`, … for operations not in the catalog);
+    /// outputs are written as `name:=place`,
+    /// and the `next` jump is omitted if it is the next instruction.
+    /// Subroutines follow the main listing.
+    #[must_use]
+    pub fn pretty(&self, catalog: &OpCatalog) -> String {
+        let mut output = String::new();
+        self.write_pretty(catalog, &mut output);
+        for (index, subroutine) in self.subroutines.iter().enumerate() {
+            output.push('\n');
+            match subroutine.name {
+                Some(ref name) => writeln!(output, "{}:", &**name),
+                None => writeln!(output, "subroutine {}:", index + 1),
+            }.unwrap();
+            subroutine.write_pretty(catalog, &mut output);
+        }
+        output
+    }
+
+    fn write_pretty(&self, catalog: &OpCatalog, output: &mut String) {
+        let index_width = self.instructions.len().to_string().len();
+        let op_width = self.instructions.iter()
+            .map(|instruction|
+                instruction.operation.to_string().chars().count() )
+            .max().unwrap_or(0);
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let mut line = format!( "{:>index_width$}: {:<op_width$}",
+                index + 1, instruction.operation );
+            write_instruction_tail( &mut line,
+                &self.parameters, catalog, instruction );
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+    }
+
+}
+
+/// Arguments, the jump and the comment of the instruction.
+fn write_instruction_tail( line: &mut String,
+    parameters: &[Parameter], catalog: &OpCatalog,
+    instruction: &Instruction,
+) {
+    for arg in pretty_args(parameters, catalog, instruction) {
+        line.push_str("  ");
+        line.push_str(&arg);
+    }
+    match instruction.next {
+        Jump::Next => (),
+        Jump::Return => line.push_str("  -> return"),
+        Jump::Jump(target) =>
+            write!(line, "  -> {target}").unwrap(),
+        Jump::Label(ref label) =>
+            write!(line, "  -> {}", &**label).unwrap(),
+    }
+    if let Some(ref comment) = instruction.comment {
+        write!(line, "  -- {}", &**comment).unwrap();
+    }
+}
+
+fn pretty_args( parameters: &[Parameter], catalog: &OpCatalog,
+    instruction: &Instruction,
+) -> Vec<String> {
+    let info = catalog.get_ref(&instruction.operation);
+    instruction.args.iter().enumerate().filter_map(|(index, arg)| {
+        let arg_info = info.and_then(|info| info.args.get(index));
+        let (label, kind) = match arg_info {
+            Some(arg_info) =>
+                (String::from(&*arg_info.name), Some(arg_info.kind)),
+            None => (format!("#{}", index + 1), None),
+        };
+        let text = pretty_operand(parameters, arg, kind)?;
+        Some(match kind {
+            Some(ArgKind::Out) => format!("{label}:={text}"),
+            _ => format!("{label}={text}"),
+        })
+    }).collect()
+}
+
+fn pretty_operand( parameters: &[Parameter],
+    operand: &Operand, kind: Option<ArgKind>,
+) -> Option<String> {
+    let mut operand = operand.clone();
+    // operands that do not fit the kind are shown as they are
+    let _converted = match kind {
+        Some(ArgKind::Exec) => operand.make_jump(),
+        Some(ArgKind::In | ArgKind::Out) if !operand.is_unset() =>
+            operand.make_place(),
+        Some(_) | None => Ok(()),
+    };
+    Some(match operand {
+        Operand::UnknownUnset => return None,
+        Operand::UnknownSkipped |
+        Operand::Place(None) | Operand::Value(None) => String::from("-"),
+        Operand::UnknownIndex(index) |
+        Operand::Jump(Jump::Jump(index)) => index.to_string(),
+        Operand::Jump(Jump::Next) => String::from("next"),
+        Operand::Jump(Jump::Return) => String::from("return"),
+        Operand::Jump(Jump::Label(ref label)) => String::from(&**label),
+        Operand::Place(Some(ref place)) => pretty_place(parameters, place),
+        Operand::Value(Some(ref value)) => pretty_value(value),
+    })
+}
+
+fn pretty_place(parameters: &[Parameter], place: &Place) -> String {
+    match *place {
+        Place::Parameter(index) => index.checked_sub(1)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| parameters.get(index))
+            .and_then(|param| param.name.as_deref())
+            .map_or_else(|| format!("P{index}"), String::from),
+        Place::Register(Register::Goto) => String::from("goto"),
+        Place::Register(Register::Store) => String::from("store"),
+        Place::Register(Register::Visual) => String::from("visual"),
+        Place::Register(Register::Signal) => String::from("signal"),
+        Place::Variable(ref name) => String::from(&**name),
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// A line like `mine  #1=metal  #2=5  -> return`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut line = self.operation.to_string();
+        write_instruction_tail(&mut line, &[], &OpCatalog::new(), self);
+        f.write_str(&line)
+    }
+}
+
+impl fmt::Display for Behavior {
+    /// The name, the description and the parameters (if any),
+    /// followed by the [listing](Behavior::pretty) of the instructions.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref name) = self.name {
+            writeln!(f, "behavior {:?}", &**name)?;
+        }
+        if let Some(ref description) = self.description {
+            writeln!(f, "-- {}", &**description)?;
+        }
+        if !self.parameters.is_empty() {
+            let parameters: Vec<String> = (1 ..)
+                .zip(&self.parameters)
+                .map(|(index, parameter)| {
+                    let name = pretty_place(
+                        &self.parameters, &Place::Parameter(index) );
+                    if parameter.is_output { format!("{name}:=") }
+                    else { name }
+                })
+                .collect();
+            writeln!(f, "parameters: {}", parameters.join(", "))?;
+        }
+        f.write_str(&self.pretty(&OpCatalog::new()))
+    }
+}
+
+impl fmt::Display for Blueprint {
+    /// The frame and the settings of the blueprint,
+    /// followed by the components with their behaviors indented.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "blueprint {}", &*self.frame)?;
+        if let Some(ref name) = self.name {
+            write!(f, " {:?}", &**name)?;
+        }
+        f.write_str("\n")?;
+        if !self.powered {
+            writeln!(f, "powered down")?;
+        }
+        if !self.connected {
+            writeln!(f, "disconnected")?;
+        }
+        if !self.logistics.is_empty() {
+            let settings: Vec<String> = self.logistics.clone().into_iter()
+                .map(|(name, value)| match value.as_flag() {
+                    Some(flag) => format!("{}={flag}", &*name),
+                    None => format!("{}=…", &*name),
+                })
+                .collect();
+            writeln!(f, "logistics: {}", settings.join(" "))?;
+        }
+        for component in &self.components {
+            write!(f, "{component}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "component {} at {}", &*self.item, self.index)?;
+        if let Some(ref behavior) = self.behavior {
+            for line in behavior.to_string().lines() {
+                writeln!(f, "  {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pretty_value(value: &Value) -> String {
+    match *value {
+        Value::Number(number) => number.to_string(),
+        Value::Item(ref item) => String::from(&**item),
+        Value::ItemCount(ref item, count) => format!("{}:{count}", &**item),
+        Value::Coord(ref coord) => format!("({},{})", coord.x, coord.y),
+        Value::CoordCount(ref coord, count) =>
+            format!("({},{}):{count}", coord.x, coord.y),
+        Value::CoordList(ref coords) => coords.iter()
+            .map(|coord| format!("({},{})", coord.x, coord.y))
+            .collect::<Vec<_>>().join(";"),
+        Value::Area { ref min, ref max } =>
+            format!("({},{})..({},{})", min.x, min.y, max.x, max.y),
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use super::super::{
+    Blueprint, Behavior, Place, OpCatalog, OpInfo, ArgInfo, ArgKind };
+use super::pretty_place;
+
+#[test]
+fn test_pretty() {
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        parameters: [
+            (name: "target", is_output: false),
+        ],
+        instructions: [
+            (op: "mine", args: [Item("metal"), Index(5)]),
+            (op: "set_reg", args: [Parameter(1), Variable("A")],
+                next: Jump(1), comment: "loop"),
+            (op: "unknown_op", args: [Coord((x: 1, y: -2))],
+                next: Return),
+        ],
+    )"#).unwrap();
+    let catalog = OpCatalog::from_iter([
+        ("mine", OpInfo::new(vec![
+            ArgInfo::new("item", ArgKind::In),
+            ArgInfo::new("full", ArgKind::Exec),
+        ])),
+        ("set_reg", OpInfo::new(vec![
+            ArgInfo::new("value", ArgKind::In),
+            ArgInfo::new("to", ArgKind::Out),
+        ])),
+    ]);
+    assert_eq!(behavior.pretty(&catalog), "\
+        1: mine        item=metal  full=5\n\
+        2: set_reg     value=target  to:=A  -> 1  -- loop\n\
+        3: unknown_op  #1=(1,-2)  -> return\n" );
+    assert_eq!( behavior.instructions[1].to_string(),
+        "set_reg  #1=P1  #2=A  -> 1  -- loop" );
+    assert_eq!( pretty_place(&behavior.parameters, &Place::Parameter(0)),
+        "P0" );
+    assert_eq!(
+        pretty_place(&behavior.parameters, &Place::Parameter(i32::MIN)),
+        "P-2147483648" );
+    let mut blueprint: Blueprint = ron::from_str(r#"Blueprint(
+        name: "Miner",
+        frame: "f_bot_1s_a",
+        powered: false,
+        logistics: {"carrier": true},
+        components: [(item: "c_behavior", index: 1)],
+    )"#).unwrap();
+    let mut behavior = behavior;
+    behavior.instructions.truncate(1);
+    blueprint.components[0].behavior = Some(behavior);
+    assert_eq!(blueprint.to_string(), "\
+        blueprint f_bot_1s_a \"Miner\"\n\
+        powered down\n\
+        logistics: carrier=true\n\
+        component c_behavior at 1\n\
+        \x20 parameters: target\n\
+        \x20 1: mine  #1=metal  #2=5\n" );
+}
+
+}
@@ -0,0 +1,413 @@
+//! Mapping between flat register indices and logical register addresses.
+//!
+//! Blueprint registers (the `regs` table) and register links
+//! (the `links` table) address registers by a single `1`-based index.
+//! The first four indices belong to the frame
+//! (goto, store, visual, signal — in the same order as
+//! the `-1 ..= -4` register codes of instruction operands).
+//! They are followed by the registers of each component,
+//! in the order the components are listed in the blueprint.
+//! The number of registers of a component depends on its item
+//! and is not recorded in the blueprint itself,
+//! so it has to be supplied by the caller.
+
+use std::ops::Range;
+
+use thiserror::Error;
+
+use super::{Blueprint, Component, Register, RegisterValue};
+
+/// The number of registers every frame has.
+pub const FRAME_REGISTER_COUNT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RegisterAddress {
+    Frame(Register),
+    /// `component` is the position of the component
+    /// in `Blueprint::components`, and `register` is `0`-based
+    /// index among the registers of that component.
+    Component { component: usize, register: usize },
+}
+
+#[derive(Debug, Error)]
+#[error("The register address does not fit the register layout")]
+#[non_exhaustive]
+pub struct InvalidRegisterAddress;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ReorderError {
+    #[error("The order is not a permutation of the components")]
+    InvalidOrder,
+    #[error("Register link {link:?} does not fit the register layout")]
+    InvalidLink { link: (i32, i32) },
+    #[error("There are more registers than the register layout allows")]
+    ExcessRegisters,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterLayout {
+    // `offsets[i] .. offsets[i+1]` are the (`0`-based) positions
+    // of the registers of `i`-th component.
+    offsets: Vec<usize>,
+}
+
+impl RegisterLayout {
+
+    /// Build the layout from the numbers of registers of components.
+    #[must_use]
+    pub fn new<I>(component_register_counts: I) -> Self
+    where I: IntoIterator<Item=usize>
+    {
+        let mut offset = FRAME_REGISTER_COUNT;
+        let mut offsets = vec![offset];
+        for count in component_register_counts {
+            offset += count;
+            offsets.push(offset);
+        }
+        Self { offsets }
+    }
+
+    /// The total number of registers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let Some(&len) = self.offsets.last() else {
+            unreachable!("struct invariant");
+        };
+        len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    pub fn component_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Positions of the component's registers in `Blueprint::registers`.
+    #[must_use]
+    pub fn component_range(&self, component: usize) -> Option<Range<usize>> {
+        let start = *self.offsets.get(component)?;
+        let end = *self.offsets.get(component + 1)?;
+        Some(start .. end)
+    }
+
+    fn position(&self, address: RegisterAddress) -> Option<usize> {
+        Some(match address {
+            RegisterAddress::Frame(register) => match register {
+                Register::Goto   => 0,
+                Register::Store  => 1,
+                Register::Visual => 2,
+                Register::Signal => 3,
+            },
+            RegisterAddress::Component { component, register } => {
+                let range = self.component_range(component)?;
+                let position = range.start.checked_add(register)?;
+                if !range.contains(&position) { return None; }
+                position
+            },
+        })
+    }
+
+    fn address(&self, position: usize) -> Option<RegisterAddress> {
+        Some(match position {
+            0 => RegisterAddress::Frame(Register::Goto),
+            1 => RegisterAddress::Frame(Register::Store),
+            2 => RegisterAddress::Frame(Register::Visual),
+            3 => RegisterAddress::Frame(Register::Signal),
+            _ => {
+                if position >= self.len() { return None; }
+                let component = self.offsets
+                    .partition_point(|&offset| offset <= position) - 1;
+                RegisterAddress::Component {
+                    component,
+                    register: position - self.offsets[component],
+                }
+            },
+        })
+    }
+
+    /// Convert a `1`-based flat index (as used in `links`)
+    /// into a logical address.
+    #[must_use]
+    pub fn resolve(&self, index: i32) -> Option<RegisterAddress> {
+        let position = usize::try_from(index.checked_sub(1)?).ok()?;
+        self.address(position)
+    }
+
+    /// Convert a logical address into a `1`-based flat index
+    /// (as used in `links`).
+    #[must_use]
+    pub fn index(&self, address: RegisterAddress) -> Option<i32> {
+        i32::try_from(self.position(address)? + 1).ok()
+    }
+
+    #[must_use]
+    pub fn resolve_link(&self, (x, y): (i32, i32))
+    -> Option<(RegisterAddress, RegisterAddress)>
+    {
+        Some((self.resolve(x)?, self.resolve(y)?))
+    }
+
+}
+
+impl Blueprint {
+
+    /// Compute the register layout of the blueprint.
+    /// `register_count` should return the number of registers
+    /// of the component (which is determined by its item).
+    pub fn register_layout<F>(&self, register_count: F) -> RegisterLayout
+    where F: FnMut(&Component) -> usize
+    {
+        RegisterLayout::new(self.components.iter().map(register_count))
+    }
+
+    #[must_use]
+    pub fn register(&self, layout: &RegisterLayout, address: RegisterAddress)
+    -> Option<&RegisterValue>
+    {
+        self.registers.get(layout.position(address)?)?.as_ref()
+    }
+
+    /// Set the register value, returning the old one.
+    pub fn set_register( &mut self,
+        layout: &RegisterLayout, address: RegisterAddress,
+        value: Option<RegisterValue>,
+    ) -> Result<Option<RegisterValue>, InvalidRegisterAddress> {
+        let position = layout.position(address)
+            .ok_or(InvalidRegisterAddress)?;
+        if position >= self.registers.len() {
+            if value.is_none() { return Ok(None); }
+            self.registers.resize_with(position + 1, || None);
+        }
+        let old_value = std::mem::replace(
+            &mut self.registers[position], value );
+        while matches!(self.registers.last(), Some(None)) {
+            self.registers.pop();
+        }
+        Ok(old_value)
+    }
+
+    /// Registers of the component.
+    /// The slice may be shorter than the number of registers of the
+    /// component, since trailing empty registers are not stored.
+    #[must_use]
+    pub fn component_registers(&self, layout: &RegisterLayout, component: usize)
+    -> &[Option<RegisterValue>]
+    {
+        let Some(range) = layout.component_range(component) else {
+            return &[];
+        };
+        let len = self.registers.len();
+        &self.registers[usize::min(range.start, len) .. usize::min(range.end, len)]
+    }
+
+    /// Links with both ends resolved to logical addresses.
+    /// Links that do not fit the layout are reported as `Err`.
+    pub fn resolved_links<'s>(&'s self, layout: &'s RegisterLayout)
+    -> impl Iterator<Item=Result<
+        (RegisterAddress, RegisterAddress),
+        InvalidRegisterAddress
+    >> + 's
+    {
+        self.links.iter().map(|&link|
+            layout.resolve_link(link).ok_or(InvalidRegisterAddress) )
+    }
+
+    /// Rearrange the components so that `order[i]` is the old position
+    /// of the component that ends up at position `i`.
+    ///
+    /// Component indices stay with the positions in the list,
+    /// so the components trade places on the frame.
+    /// Component registers move along with their components,
+    /// and register links are renumbered to point to the same registers.
+    /// `register_count` should return the number of registers
+    /// of the component (see [`Blueprint::register_layout`]).
+    ///
+    /// On error the blueprint is left unchanged.
+    pub fn reorder_components<F>(&mut self, order: &[usize],
+        register_count: F,
+    ) -> Result<(), ReorderError>
+    where F: FnMut(&Component) -> usize
+    {
+        let mut new_positions = vec![None; self.components.len()];
+        if order.len() != new_positions.len() {
+            return Err(ReorderError::InvalidOrder);
+        }
+        for (new_position, &old_position) in order.iter().enumerate() {
+            match new_positions.get_mut(old_position) {
+                Some(position @ None) => *position = Some(new_position),
+                _ => return Err(ReorderError::InvalidOrder),
+            }
+        }
+        let old_layout = self.register_layout(register_count);
+        if self.registers.len() > old_layout.len() {
+            return Err(ReorderError::ExcessRegisters);
+        }
+        let new_layout = RegisterLayout::new(order.iter().map(|&position| {
+            old_layout.component_range(position).map_or(0, |range| range.len())
+        }));
+        let links = self.links.iter().map(|&link| {
+            let (x, y) = old_layout.resolve_link(link)
+                .ok_or(ReorderError::InvalidLink { link })?;
+            let reindex = |address| new_layout.index(match address {
+                RegisterAddress::Component { component, register } =>
+                    RegisterAddress::Component {
+                        component: new_positions[component]
+                            .unwrap_or(component),
+                        register },
+                address @ RegisterAddress::Frame(_) => address,
+            }).ok_or(ReorderError::InvalidLink { link });
+            Ok((reindex(x)?, reindex(y)?))
+        }).collect::<Result<Vec<_>, ReorderError>>()?;
+
+        let mut registers = self.registers.get(
+            .. usize::min(FRAME_REGISTER_COUNT, self.registers.len())
+        ).unwrap_or(&[]).to_vec();
+        let mut components = Vec::with_capacity(order.len());
+        for (new_position, &old_position) in order.iter().enumerate() {
+            let mut component = self.components[old_position].clone();
+            component.index = self.components[new_position].index;
+            components.push(component);
+            let moved = self.component_registers(&old_layout, old_position);
+            if moved.iter().any(Option::is_some) {
+                let Some(range) = new_layout.component_range(new_position)
+                else { unreachable!("the layout should fit the components") };
+                registers.resize_with(range.start, || None);
+                registers.extend_from_slice(moved);
+            }
+        }
+        while registers.last() == Some(&None) {
+            registers.pop();
+        }
+        self.components = components;
+        self.registers = registers;
+        self.links = links;
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::Str;
+
+use super::{
+    super::Value,
+    Blueprint, Component, Register, RegisterValue,
+    RegisterAddress, RegisterLayout, ReorderError,
+};
+
+#[test]
+fn test_register_layout() {
+    let layout = RegisterLayout::new([2, 0, 3]);
+    assert_eq!(layout.len(), 9);
+    assert_eq!(layout.component_range(2), Some(6 .. 9));
+    assert_eq!(layout.resolve(0), None);
+    assert_eq!(layout.resolve(2),
+        Some(RegisterAddress::Frame(Register::Store)) );
+    assert_eq!(layout.resolve(5),
+        Some(RegisterAddress::Component { component: 0, register: 0 }) );
+    assert_eq!(layout.resolve(7),
+        Some(RegisterAddress::Component { component: 2, register: 0 }) );
+    assert_eq!(layout.resolve(10), None);
+    for index in 1 ..= 9 {
+        let address = layout.resolve(index).unwrap();
+        assert_eq!(layout.index(address), Some(index));
+    }
+    assert_eq!(layout.index(
+        RegisterAddress::Component { component: 1, register: 0 } ), None);
+}
+
+#[test]
+fn test_set_register() {
+    let mut blueprint = Blueprint::default();
+    for item in ["c_small_storage", "c_assembler"] {
+        blueprint.components.push(Component {
+            item: Str::known(item),
+            ..Component::default()
+        });
+    }
+    let layout = blueprint.register_layout(
+        |component| if component.item.as_ref() == "c_assembler" { 2 } else { 0 } );
+    let address = RegisterAddress::Component { component: 1, register: 1 };
+    let value = RegisterValue::from(Value::Number(42));
+    assert!(blueprint.set_register(&layout, address, Some(value.clone()))
+        .unwrap().is_none());
+    assert_eq!(blueprint.registers.len(), 6);
+    assert_eq!(blueprint.register(&layout, address), Some(&value));
+    assert_eq!(blueprint.component_registers(&layout, 1),
+        &[None, Some(value.clone())] );
+    blueprint.links.push((1, 6));
+    assert_eq!(
+        blueprint.resolved_links(&layout).next().unwrap().unwrap(),
+        (RegisterAddress::Frame(Register::Goto), address) );
+    assert_eq!(blueprint.set_register(&layout, address, None).unwrap(),
+        Some(value) );
+    assert!(blueprint.registers.is_empty());
+}
+
+#[test]
+fn test_reorder_components() {
+    let mut blueprint = Blueprint::default();
+    for (index, item) in ["c_small_storage", "c_assembler", "c_radar"]
+        .into_iter().enumerate()
+    {
+        blueprint.components.push(Component {
+            item: Str::known(item),
+            index: i32::try_from(index).unwrap() + 1,
+            ..Component::default()
+        });
+    }
+    let register_count = |component: &Component|
+        match component.item.as_ref() {
+            "c_assembler" => 2,
+            "c_radar" => 1,
+            _ => 0,
+        };
+    let number = |number| Some(RegisterValue::from(Value::Number(number)));
+    // frame goto, assembler registers, radar register
+    blueprint.registers = vec![
+        number(1), None, None, None,
+        number(5), number(6),
+        number(7),
+    ];
+    blueprint.links = vec![(1, 7), (5, 6)];
+    blueprint.reorder_components(&[2, 0, 1], register_count).unwrap();
+    let items: Vec<(&str, i32)> = blueprint.components.iter()
+        .map(|component| (component.item.as_ref(), component.index))
+        .collect();
+    assert_eq!(items,
+        [("c_radar", 1), ("c_small_storage", 2), ("c_assembler", 3)] );
+    assert_eq!(blueprint.registers, [
+        number(1), None, None, None,
+        number(7),
+        number(5), number(6),
+    ]);
+    assert_eq!(blueprint.links, [(1, 5), (6, 7)]);
+    let layout = blueprint.register_layout(register_count);
+    assert_eq!(
+        blueprint.register(&layout,
+            RegisterAddress::Component { component: 2, register: 1 }),
+        number(6).as_ref() );
+
+    let unchanged = format!("{blueprint:?}");
+    for order in [&[0, 1][..], &[0, 0, 1], &[0, 1, 3]] {
+        assert!(matches!(
+            blueprint.reorder_components(order, register_count),
+            Err(ReorderError::InvalidOrder) ));
+    }
+    blueprint.links.push((1, 9));
+    assert!(matches!(
+        blueprint.reorder_components(&[1, 2, 0], register_count),
+        Err(ReorderError::InvalidLink { link: (1, 9) }) ));
+    blueprint.links.pop();
+    assert_eq!(format!("{blueprint:?}"), unchanged);
+}
+
+}
@@ -0,0 +1,118 @@
+//! Migrating item ids renamed between versions of the game.
+
+use std::collections::HashMap;
+
+use crate::Str;
+
+use super::{Blueprint, Behavior, Operand, Value};
+
+impl Blueprint {
+
+    /// Replace item ids found in the map keys with the map values:
+    /// the frame, component items, locked slots,
+    /// register values, and everything [`Behavior::rename_item_ids`]
+    /// replaces in component behaviors.
+    ///
+    /// Returns the number of replaced ids.
+    pub fn rename_item_ids(&mut self, map: &HashMap<Str, Str>) -> usize {
+        let mut count = rename(&mut self.frame, map);
+        for slot in self.locks.slots.iter_mut().flatten() {
+            count += rename(slot, map);
+        }
+        for value in self.registers.iter_mut().flatten()
+            .filter_map(|register| register.value.as_mut())
+        {
+            count += rename_value(value, map);
+        }
+        for component in &mut self.components {
+            count += rename(&mut component.item, map);
+            for value in component.registers.iter_mut().flatten()
+                .filter_map(|register| register.value.as_mut())
+            {
+                count += rename_value(value, map);
+            }
+            if let Some(ref mut behavior) = component.behavior {
+                count += behavior.rename_item_ids(map);
+            }
+        }
+        count
+    }
+
+}
+
+impl Behavior {
+
+    /// Replace item ids found in the map keys with the map values
+    /// in instruction arguments, of subroutines as well.
+    ///
+    /// Returns the number of replaced ids.
+    pub fn rename_item_ids(&mut self, map: &HashMap<Str, Str>) -> usize {
+        let mut count = 0;
+        self.for_each_with_subroutines_mut(|behavior| {
+            let operands = behavior.instructions.iter_mut()
+                .flat_map(|instruction| &mut instruction.args);
+            for operand in operands {
+                if let Operand::Value(Some(ref mut value)) = *operand {
+                    count += rename_value(value, map);
+                }
+            }
+        });
+        count
+    }
+
+}
+
+fn rename_value(value: &mut Value, map: &HashMap<Str, Str>) -> usize {
+    match *value {
+        Value::Item(ref mut id) | Value::ItemCount(ref mut id, _) =>
+            rename(id, map),
+        Value::Number(_) | Value::Coord(_) | Value::CoordCount(..) |
+        Value::CoordList(_) | Value::Area { .. } => 0,
+    }
+}
+
+fn rename(id: &mut Str, map: &HashMap<Str, Str>) -> usize {
+    let Some(new_id) = map.get(id) else { return 0 };
+    id.clone_from(new_id);
+    1
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use std::collections::HashMap;
+
+use crate::{Str, blueprint::{Blueprint, Operand, Value}};
+
+#[test]
+fn test_rename_item_ids() {
+    let mut blueprint: Blueprint = ron::from_str(r#"Blueprint(
+        frame: "f_bot_1s_a",
+        components: [
+            (item: "c_fabricator", index: 1, behavior: Behavior(
+                instructions: [
+                    (op: "produce", args: [ItemCount("metalore", 2)]),
+                ],
+                subroutines: [Behavior(instructions: [
+                    (op: "produce", args: [Item("metalore")]),
+                ])],
+            )),
+        ],
+        locks: [Some("metalore"), None, Some("crystal")],
+    )"#).unwrap();
+    let map = HashMap::from([
+        (Str::from("metalore"), Str::from("metal_ore")),
+        (Str::from("f_bot_1s_a"), Str::from("f_bot_1s_b")),
+    ]);
+    assert_eq!(blueprint.rename_item_ids(&map), 4);
+    assert_eq!(&*blueprint.frame, "f_bot_1s_b");
+    assert_eq!( blueprint.locks.slots,
+        [Some(Str::from("metal_ore")), None, Some(Str::from("crystal"))] );
+    let behavior = blueprint.components[0].behavior.as_ref().unwrap();
+    assert!(matches!( behavior.instructions[0].args[0],
+        Operand::Value(Some(Value::ItemCount(ref id, 2)))
+            if &**id == "metal_ore" ));
+    assert_eq!(blueprint.rename_item_ids(&map), 0);
+}
+
+}
@@ -0,0 +1,99 @@
+//! Looking up instruction arguments without walking the behavior by hand.
+
+use super::{Behavior, Operand, lint::Span};
+
+impl Behavior {
+
+    /// Instruction arguments accepted by the predicate,
+    /// of subroutines as well
+    /// (the same operands [`Blueprint::rename_item_ids`] goes through),
+    /// with where each of them is.
+    ///
+    /// The span has `instruction` and `operand` set;
+    /// the operands of a behavior come before those of its subroutines.
+    ///
+    /// [`Blueprint::rename_item_ids`]: super::Blueprint::rename_item_ids
+    pub fn find_operands<'s, P>(&'s self, mut predicate: P)
+    -> impl Iterator<Item=(Span, &'s Operand)> + 's
+    where P: FnMut(&Operand) -> bool + 's
+    {
+        let mut found = Vec::new();
+        let mut stack = vec![(Vec::new(), self)];
+        while let Some((path, behavior)) = stack.pop() {
+            let args = behavior.instructions.iter().enumerate()
+                .flat_map(|(index, instruction)| instruction.args.iter()
+                    .enumerate()
+                    .map(move |(arg, operand)| (Span {
+                        instruction: Some(index),
+                        operand: Some(arg),
+                        ..Span::default()
+                    }, operand)) );
+            found.extend( args
+                .filter(|&(_, operand)| predicate(operand))
+                .map(|(span, operand)| (Span {
+                    subroutine: path.clone(), ..span }, operand)) );
+            stack.extend( behavior.subroutines.iter().enumerate().rev()
+                .map(|(index, subroutine)| {
+                    let mut path = path.clone();
+                    path.push(index);
+                    (path, subroutine)
+                }) );
+        }
+        found.into_iter()
+    }
+
+    /// Instruction arguments that are values
+    /// of the item (with or without a count), e.g. `"c_coconut"`
+    /// (see [`find_operands`](Self::find_operands)).
+    pub fn find_item_references<'s>(&'s self, item: &'s str)
+    -> impl Iterator<Item=(Span, &'s Operand)> + 's
+    {
+        self.find_operands(move |operand| match *operand {
+            Operand::Value(Some(ref value)) =>
+                value.item().is_some_and(|id| &**id == item),
+            _ => false,
+        })
+    }
+
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::blueprint::{Behavior, Operand, lint::Span};
+
+#[test]
+fn test_find_operands() {
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "nop"),
+            (op: "check_item", args: [
+                Item("c_coconut"), Variable("A"), ItemCount("c_coconut", 3),
+            ]),
+        ],
+        subroutines: [Behavior(instructions: [
+            (op: "nop"),
+            (op: "produce", args: [Item("metalore")]),
+        ])],
+    )"#).unwrap();
+    let positions = |found: Vec<(Span, &Operand)>| found.into_iter()
+        .map(|(span, _)| (span.subroutine, span.instruction, span.operand))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        positions(behavior.find_item_references("c_coconut").collect()),
+        [
+            (vec![], Some(1), Some(0)),
+            (vec![], Some(1), Some(2)),
+        ] );
+    // used only inside a subroutine
+    assert_eq!(
+        positions(behavior.find_item_references("metalore").collect()),
+        [(vec![0], Some(1), Some(0))] );
+    assert_eq!(behavior.find_item_references("crystal").count(), 0);
+    let places = behavior.find_operands(|operand|
+        matches!(*operand, Operand::Place(_)) );
+    assert_eq!( places.map(|(span, _)| span.operand).collect::<Vec<_>>(),
+        [Some(1)] );
+}
+
+}
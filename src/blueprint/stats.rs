@@ -0,0 +1,112 @@
+//! Summary numbers about a behavior, e.g. for listing shared behaviors.
+
+use std::collections::BTreeSet;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::value::Value;
+
+use super::{Behavior, Operand, Jump, OpRef};
+
+/// Returned by [`Behavior::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub struct BehaviorStats {
+    /// Number of instructions, including those of subroutines
+    /// (nested ones as well).
+    pub instructions: usize,
+    /// Number of subroutines, nested ones included.
+    pub subroutines: usize,
+    /// Operations used by the instructions (of subroutines as well).
+    pub operations: BTreeSet<OpRef>,
+    /// Number of parameters of the behavior itself.
+    pub parameters: usize,
+    /// How many of the parameters are outputs.
+    pub output_parameters: usize,
+    /// The largest `1`-based instruction index the behavior
+    /// itself jumps to, or `None` if it has no explicit jumps.
+    /// Only `next` jumps and arguments that are already
+    /// [`Operand::Jump`] are taken into account.
+    pub max_jump_target: Option<i32>,
+    /// Total length of instruction comments in bytes
+    /// (of subroutines as well).
+    pub comment_bytes: usize,
+    /// See [`Value::estimated_encoded_len`];
+    /// `None` if the behavior cannot be converted into a value
+    /// (see [`Behavior::resolve_labels`]).
+    pub estimated_encoded_len: Option<usize>,
+}
+
+impl Behavior {
+
+    /// Gather [`BehaviorStats`] of the behavior.
+    #[must_use]
+    pub fn stats(&self) -> BehaviorStats {
+        let mut stats = BehaviorStats {
+            parameters: self.parameters.len(),
+            output_parameters: self.parameters.iter()
+                .filter(|param| param.is_output)
+                .count(),
+            max_jump_target: self.instructions.iter()
+                .flat_map(|instruction| {
+                    let args = instruction.args.iter()
+                        .filter_map(|arg| match *arg {
+                            Operand::Jump(ref jump) => Some(jump),
+                            _ => None,
+                        });
+                    std::iter::once(&instruction.next).chain(args)
+                })
+                .filter_map(|jump| match *jump {
+                    Jump::Jump(index) => Some(index),
+                    Jump::Return | Jump::Next | Jump::Label(_) => None,
+                })
+                .max(),
+            estimated_encoded_len: Value::try_from(self.clone()).ok()
+                .map(|value| value.estimated_encoded_len()),
+            ..BehaviorStats::default()
+        };
+        for behavior in self.with_subroutines() {
+            stats.instructions += behavior.instructions.len();
+            for instruction in &behavior.instructions {
+                stats.operations.insert(instruction.operation.clone());
+                stats.comment_bytes += instruction.comment.as_ref()
+                    .map_or(0, |comment| comment.len());
+            }
+            stats.subroutines += behavior.subroutines.len();
+        }
+        stats
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    blueprint::{load_blueprint, Behavior},
+    value::Value,
+};
+
+#[test]
+fn test_stats() {
+    let Exchange::Behavior(behavior) =
+        load_blueprint(crate::test::EXCHANGE_BEHAVIOR_4_SUB).unwrap()
+        else { panic!("should be a behavior") };
+    let stats = behavior.stats();
+    let nested: usize = behavior.subroutines.iter()
+        .map(|sub| sub.stats().instructions)
+        .sum();
+    assert_eq!(stats.instructions, behavior.instructions.len() + nested);
+    assert_eq!(stats.parameters, behavior.parameters.len());
+    assert!(!stats.operations.is_empty());
+    assert!(stats.operations.len() <= stats.instructions);
+    assert_eq!( stats.estimated_encoded_len, Some(
+        Value::try_from(behavior.clone()).unwrap().estimated_encoded_len() ));
+    let empty = Behavior::default().stats();
+    assert_eq!((empty.instructions, empty.max_jump_target), (0, None));
+}
+
+}
@@ -0,0 +1,112 @@
+//! Removing what only the editor needs, to make exchange strings shorter.
+
+use crate::Exchange;
+
+use super::{Behavior, dump_blueprint, text::TEXT_KEY};
+
+/// Options for [`Behavior::strip_metadata`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct StripOptions {
+    /// Remove instruction comments (`cmt`).
+    pub comments: bool,
+    /// Remove instruction positions in the editor (`nx` and `ny`).
+    pub offsets: bool,
+    /// Remove instruction texts (`txt`).
+    /// The game shows them, so they are kept by default.
+    pub texts: bool,
+}
+
+impl Default for StripOptions {
+    fn default() -> Self {
+        Self { comments: true, offsets: true, texts: false }
+    }
+}
+
+/// What [`Behavior::strip_metadata`] removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StripReport {
+    pub comments: usize,
+    pub offsets: usize,
+    pub texts: usize,
+    /// How much shorter the exchange string of the behavior
+    /// (as written by [`dump_blueprint`]) has become;
+    /// `None` if the behavior cannot be dumped
+    /// (e.g. it has jumps to unknown labels).
+    pub bytes_saved: Option<usize>,
+}
+
+impl Behavior {
+
+    /// Remove the metadata enabled in the options
+    /// from all instructions, of subroutines as well.
+    pub fn strip_metadata(&mut self, options: &StripOptions)
+    -> StripReport
+    {
+        let len_before = dumped_len(self);
+        let mut report = StripReport::default();
+        self.for_each_with_subroutines_mut(|behavior| {
+            for instruction in &mut behavior.instructions {
+                if options.comments && instruction.comment.take().is_some() {
+                    report.comments += 1;
+                }
+                if options.offsets && instruction.offset.take().is_some() {
+                    report.offsets += 1;
+                }
+                if options.texts &&
+                    instruction.extra.remove(TEXT_KEY).is_some()
+                {
+                    report.texts += 1;
+                }
+            }
+        });
+        report.bytes_saved = Option::zip(len_before, dumped_len(self))
+            .map(|(before, after)| before.saturating_sub(after));
+        report
+    }
+
+}
+
+fn dumped_len(behavior: &Behavior) -> Option<usize> {
+    dump_blueprint(Exchange::Behavior(behavior.clone())).ok()
+        .map(|exchange| exchange.len())
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::{
+    Exchange,
+    blueprint::{Behavior, dump_blueprint},
+};
+
+use super::{StripOptions, StripReport};
+
+#[test]
+fn test_strip_metadata() {
+    let mut behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [
+            (op: "nop", comment: "first", offset: (1.5, 2.0)),
+            (op: "notify", extra: {"txt": String("hello")}),
+        ],
+        subroutines: [Behavior(instructions: [(op: "nop", comment: "sub")])],
+    )"#).unwrap();
+    let dumped_len = |behavior: &Behavior| dump_blueprint(
+        Exchange::Behavior(behavior.clone()) ).unwrap().len();
+    let mut options = StripOptions::default();
+    options.texts = true;
+    let len_before = dumped_len(&behavior);
+    let report = behavior.strip_metadata(&options);
+    assert_eq!( (report.comments, report.offsets, report.texts),
+        (2, 1, 1) );
+    assert_eq!(report.bytes_saved, Some(len_before - dumped_len(&behavior)));
+    assert!(behavior.instructions.iter().all(|instruction|
+        instruction.comment.is_none() && instruction.offset.is_none()
+        && instruction.extra.is_empty() ));
+    assert!(dumped_len(&behavior) < len_before);
+    assert_eq!( behavior.strip_metadata(&options),
+        StripReport { bytes_saved: Some(0), ..StripReport::default() } );
+}
+
+}
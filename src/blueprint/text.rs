@@ -0,0 +1,196 @@
+//! Placeholders in instruction texts.
+//!
+//! Some operations carry a text shown by the game
+//! (under the `txt` key, kept in [`Instruction::extra`]).
+//! Besides plain text, it can refer to items as `[metalore]`
+//! and to behavior parameters as `{1}`.
+//! Anything that does not form a valid reference is plain text,
+//! so parsing never fails and rendering a parsed text
+//! gives back the original string.
+//!
+//! The references accepted by [`Text::parse`] are:
+//! * `[id]`, where `id` is a non-empty run of ASCII letters,
+//!   digits and underscores;
+//! * `{index}`, where `index` is a decimal number
+//!   without leading zeros that fits in an `i32`.
+//!
+//! References do not nest, and there is no escaping:
+//! a bracket that does not start a valid reference is plain text.
+
+use crate::{Str, value::Value};
+
+use super::Instruction;
+
+pub(super) const TEXT_KEY: &str = "txt";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Segment {
+    Plain(String),
+    /// An item (or another game object) by its id, `[id]`.
+    Item(Str),
+    /// A `1`-based index of a behavior parameter, `{index}`.
+    Parameter(i32),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Text {
+    /// Adjacent plain segments are merged by [`Text::parse`].
+    pub segments: Vec<Segment>,
+}
+
+impl Text {
+
+    /// Split the text into references and plain text
+    /// (see the [module documentation](self) for the syntax).
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let mut this = Self::default();
+        let mut rest = text;
+        while let Some(start) = rest.find(['[', '{']) {
+            let (plain, tail) = rest.split_at(start);
+            this.push_plain(plain);
+            if let Some((segment, len)) = parse_reference(tail) {
+                this.segments.push(segment);
+                (_, rest) = tail.split_at(len);
+            } else {
+                let (bracket, tail) = tail.split_at(1);
+                this.push_plain(bracket);
+                rest = tail;
+            }
+        }
+        this.push_plain(rest);
+        this
+    }
+
+    fn push_plain(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(Segment::Plain(last)) = self.segments.last_mut() {
+            last.push_str(text);
+        } else {
+            self.segments.push(Segment::Plain(String::from(text)));
+        }
+    }
+
+    /// Plain segments are written as is, so a plain segment
+    /// that looks like a reference reads back as a reference.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match *segment {
+                Segment::Plain(ref text) => output.push_str(text),
+                Segment::Item(ref id) => {
+                    output.push('[');
+                    output.push_str(id);
+                    output.push(']');
+                },
+                Segment::Parameter(index) => {
+                    output.push('{');
+                    output.push_str(&index.to_string());
+                    output.push('}');
+                },
+            }
+        }
+        output
+    }
+
+    /// Parameters referred to, in the order of appearance.
+    pub fn parameters(&self) -> impl Iterator<Item=i32> + '_ {
+        self.segments.iter().filter_map(|segment| match *segment {
+            Segment::Parameter(index) => Some(index),
+            _ => None,
+        })
+    }
+
+}
+
+fn parse_reference(text: &str) -> Option<(Segment, usize)> {
+    let (close, is_item) = match text.as_bytes().first() {
+        Some(b'[') => (']', true),
+        Some(b'{') => ('}', false),
+        _ => return None,
+    };
+    let (_, text) = text.split_at(1);
+    let end = text.find(close)?;
+    let (inner, _) = text.split_at(end);
+    let segment = if is_item {
+        if inner.is_empty() || !inner.bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'_')
+        {
+            return None;
+        }
+        Segment::Item(Str::from(inner))
+    } else {
+        // leading zeros would not survive rendering
+        if inner.starts_with('0') ||
+            !inner.bytes().all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        Segment::Parameter(inner.parse().ok()?)
+    };
+    Some((segment, end + 2))
+}
+
+impl Instruction {
+
+    /// The text of the instruction, if it has one.
+    #[must_use]
+    pub fn text(&self) -> Option<Text> {
+        match self.extra.get(TEXT_KEY)? {
+            Value::String(text) => Some(Text::parse(text)),
+            _ => None,
+        }
+    }
+
+    pub fn set_text(&mut self, text: &Text) {
+        self.extra.insert(
+            Str::known(TEXT_KEY),
+            Value::String(Str::from(text.render().as_str())),
+        );
+    }
+
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+
+use crate::Str;
+
+use super::{
+    super::Instruction,
+    Text, Segment,
+};
+
+#[test]
+fn test_text() {
+    let source = "Mine [metalore] x{2}, {0}{x} [] [a b] [[c]] {{1}} {";
+    let text = Text::parse(source);
+    assert_eq!(text.segments, [
+        Segment::Plain(String::from("Mine ")),
+        Segment::Item(Str::from("metalore")),
+        Segment::Plain(String::from(" x")),
+        Segment::Parameter(2),
+        Segment::Plain(String::from(", {0}{x} [] [a b] [")),
+        Segment::Item(Str::from("c")),
+        Segment::Plain(String::from("] {")),
+        Segment::Parameter(1),
+        Segment::Plain(String::from("} {")),
+    ]);
+    assert_eq!(text.render(), source);
+    assert_eq!(text.parameters().collect::<Vec<_>>(), [2, 1]);
+    assert_eq!(Text::parse("").segments, []);
+    assert_eq!(Text::parse("{99999999999}").segments, [
+        Segment::Plain(String::from("{99999999999}")) ]);
+    let mut instruction: Instruction =
+        ron::from_str(r#"(op: "notify", args: [])"#).unwrap();
+    assert_eq!(instruction.text(), None);
+    instruction.set_text(&text);
+    assert_eq!(instruction.text(), Some(text));
+}
+
+}
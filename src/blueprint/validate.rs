@@ -0,0 +1,161 @@
+//! Checking the invariants that loading a blueprint enforces,
+//! so that they can be checked before dumping as well.
+
+use thiserror::Error;
+
+use super::{Blueprint, Behavior};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// Link indices are `1`-based positions in [`Blueprint::registers`];
+    /// trailing empty registers are not encoded
+    /// and cannot be linked to.
+    #[error("Register link {link:?} refers to a missing register")]
+    InvalidLink { link: (i32, i32) },
+    /// The behavior of the component at this `0`-based position
+    /// in [`Blueprint::components`] (or one of its subroutines)
+    /// has an instruction offset that is NaN or infinite
+    /// (see [`FloatPolicy`](crate::dumper::FloatPolicy)).
+    /// Such blueprints still load.
+    #[error( "Behavior of component {component} has \
+        a non-finite instruction offset" )]
+    NonFiniteOffset { component: usize },
+}
+
+impl Blueprint {
+
+    /// Check that the blueprint would load back
+    /// and be accepted by the game, reporting every problem found.
+    ///
+    /// Loading only checks the links
+    /// ([`ValidationError::InvalidLink`]).
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors: Vec<_> = self.link_errors().collect();
+        for (component, behavior) in self.components.iter().enumerate()
+            .filter_map(|(index, component)|
+                Some((index, component.behavior.as_ref()?)) )
+        {
+            if has_non_finite_offset(behavior) {
+                errors.push(ValidationError::NonFiniteOffset { component });
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Links to missing registers, which fail the loading.
+    pub(super) fn link_errors(&self)
+    -> impl Iterator<Item=ValidationError> + '_
+    {
+        let register_count = self.registers.iter()
+            .rposition(Option::is_some)
+            .map_or(0, |position| position + 1);
+        let is_valid = move |index: i32| usize::try_from(index).ok()
+            .is_some_and(|index| index >= 1 && index <= register_count);
+        self.links.iter()
+            .filter(move |&&(x, y)| !is_valid(x) || !is_valid(y))
+            .map(|&link| ValidationError::InvalidLink { link })
+    }
+
+}
+
+fn has_non_finite_offset(root: &Behavior) -> bool {
+    root.with_subroutines()
+        .flat_map(|behavior| &behavior.instructions)
+        .any(|instruction| instruction.offset.is_some_and(|(x, y)|
+            !x.is_finite() || !y.is_finite() ))
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    blueprint::{
+        Blueprint, Value, RegisterValue,
+        dump_blueprint_validated, load_blueprint,
+    },
+};
+
+use super::ValidationError;
+
+#[test]
+fn test_validate() {
+    let mut blueprint = Blueprint {
+        frame: "f_building1x1a".into(),
+        registers: vec![
+            None, Some(RegisterValue::from(Value::Number(1))), None ],
+        links: vec![(1, 2)],
+        ..Blueprint::default()
+    };
+    blueprint.validate().unwrap();
+    let exchange = dump_blueprint_validated(
+        Exchange::Blueprint(blueprint.clone()) ).unwrap();
+    load_blueprint(&exchange).unwrap();
+
+    blueprint.links.extend([(2, 3), (0, 1)]);
+    assert_eq!( blueprint.validate().unwrap_err(), [
+        ValidationError::InvalidLink { link: (2, 3) },
+        ValidationError::InvalidLink { link: (0, 1) },
+    ] );
+    let error = dump_blueprint_validated(Exchange::Blueprint(blueprint))
+        .unwrap_err();
+    assert!(error.to_string().contains("(2, 3)"), "{error}");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_non_finite_offset() {
+    use crate::{
+        blueprint::{Behavior, Component},
+        dumper::{DumpOptions, FloatPolicy, dump_blueprint_with},
+    };
+    let behavior: Behavior = ron::from_str(r#"Behavior(
+        instructions: [(op: "nop")],
+        subroutines: [Behavior(instructions: [
+            (op: "nop", offset: (1.0, NaN)),
+        ])],
+    )"#).unwrap();
+    let mut blueprint = Blueprint {
+        frame: "f_building1x1a".into(),
+        components: vec![
+            Component { item: "c_behavior".into(), index: 1,
+                ..Component::default() },
+            Component { item: "c_behavior".into(), index: 2,
+                behavior: Some(behavior.clone()),
+                ..Component::default() },
+        ],
+        ..Blueprint::default()
+    };
+    assert_eq!( blueprint.validate().unwrap_err(),
+        [ValidationError::NonFiniteOffset { component: 1 }] );
+    type V = crate::value::Value;
+    let dump = |behavior, float_policy| dump_blueprint_with::<V, V>(
+        Exchange::Behavior(Some(V::try_from(behavior).unwrap())),
+        &DumpOptions { float_policy, ..DumpOptions::default() } );
+    dump(behavior.clone(), FloatPolicy::Error).unwrap_err();
+    let clamped = dump(behavior.clone(), FloatPolicy::ClampToZero).unwrap();
+    let Exchange::Behavior(clamped) = load_blueprint(&clamped).unwrap()
+        else { panic!("should be a behavior") };
+    assert_eq!(clamped.subroutines[0].instructions[0].offset, Some((1.0, 0.0)));
+    let kept = dump(behavior, FloatPolicy::AllowNonFinite).unwrap();
+    let Exchange::Behavior(kept) = load_blueprint(&kept).unwrap()
+        else { panic!("should be a behavior") };
+    assert!(kept.subroutines[0].instructions[0].offset
+        .is_some_and(|(_, y)| y.is_nan()));
+    // loading does not validate the offsets of blueprints either
+    let kept = dump_blueprint_with::<V, V>(
+        Exchange::Blueprint(Some(V::try_from(blueprint.clone()).unwrap())),
+        &DumpOptions {
+            float_policy: FloatPolicy::AllowNonFinite,
+            ..DumpOptions::default()
+        } ).unwrap();
+    let Exchange::Blueprint(kept) = load_blueprint(&kept).unwrap()
+        else { panic!("should be a blueprint") };
+    assert_eq!( kept.validate().unwrap_err(),
+        [ValidationError::NonFiniteOffset { component: 1 }] );
+    blueprint.components.pop();
+    blueprint.validate().unwrap();
+}
+
+}
@@ -0,0 +1,187 @@
+//! C interface, exchanging blueprints as JSON strings.
+//!
+//! The JSON is the serde representation of `Exchange<Blueprint, Behavior>`
+//! (see [`crate::schema`]).
+//! The declarations are in `include/desynced_exchange.h`.
+//! The crate is not built as a C library by default; use e.g.
+//! `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Every function returns one of the `DSE_*` codes
+//! and, unless the code is [`DSE_NULL_ARGUMENT`], stores
+//! either the result or an error message in `*output`.
+//! The stored string is owned by the caller
+//! and should be released with [`dse_free`].
+
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use crate::blueprint::{load_blueprint, dump_blueprint};
+
+pub const DSE_OK: c_int = 0;
+pub const DSE_NULL_ARGUMENT: c_int = 1;
+/// The input is not valid UTF-8.
+pub const DSE_INVALID_UTF8: c_int = 2;
+/// The exchange string cannot be decoded.
+pub const DSE_LOAD_ERROR: c_int = 3;
+/// The JSON cannot be parsed or produced.
+pub const DSE_JSON_ERROR: c_int = 4;
+/// The blueprint cannot be encoded.
+pub const DSE_DUMP_ERROR: c_int = 5;
+/// The library panicked; this is a bug.
+pub const DSE_PANIC: c_int = 6;
+
+/// Decode the exchange string into JSON.
+///
+/// # Safety
+///
+/// `exchange` should be a null-terminated string
+/// and `output` should be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn dse_decode(
+    exchange: *const c_char, output: *mut *mut c_char,
+) -> c_int {
+    // SAFETY: the caller upholds the requirements
+    unsafe { call(exchange, output, |exchange| {
+        let exchange = load_blueprint(exchange)
+            .map_err(|error| (DSE_LOAD_ERROR, error.to_string()))?;
+        serde_json::to_string(&exchange)
+            .map_err(|error| (DSE_JSON_ERROR, error.to_string()))
+    }) }
+}
+
+/// Encode the JSON into an exchange string.
+///
+/// # Safety
+///
+/// `json` should be a null-terminated string
+/// and `output` should be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn dse_encode(
+    json: *const c_char, output: *mut *mut c_char,
+) -> c_int {
+    // SAFETY: the caller upholds the requirements
+    unsafe { call(json, output, |json| {
+        let exchange = serde_json::from_str(json)
+            .map_err(|error| (DSE_JSON_ERROR, error.to_string()))?;
+        dump_blueprint(exchange)
+            .map_err(|error| (DSE_DUMP_ERROR, error.to_string()))
+    }) }
+}
+
+/// Release a string stored by the other functions.
+/// Null pointers are ignored.
+///
+/// # Safety
+///
+/// `string` should be null or a string stored by this library
+/// that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn dse_free(string: *mut c_char) {
+    if string.is_null() { return; }
+    // SAFETY: the string was made by `CString::into_raw`
+    drop(unsafe { CString::from_raw(string) });
+}
+
+/// Run `f` on the input, storing its result or error in `output`.
+///
+/// # Safety
+///
+/// `input` should be a null-terminated string
+/// and `output` should be valid for writes.
+unsafe fn call<F>(input: *const c_char, output: *mut *mut c_char, f: F)
+-> c_int
+where F: FnOnce(&str) -> Result<String, (c_int, String)>
+{
+    if input.is_null() || output.is_null() {
+        return DSE_NULL_ARGUMENT;
+    }
+    // SAFETY: the input is a null-terminated string
+    let input = unsafe { CStr::from_ptr(input) };
+    let result = match input.to_str() {
+        Ok(input) => catch_unwind(AssertUnwindSafe(|| f(input)))
+            .unwrap_or_else(|_panic| Err((
+                DSE_PANIC, String::from("the library panicked") ))),
+        Err(error) => Err((DSE_INVALID_UTF8, error.to_string())),
+    };
+    let (code, string) = match result {
+        Ok(string) => (DSE_OK, string),
+        Err((code, message)) => (code, message),
+    };
+    // JSON escapes nulls and exchange strings are alphanumeric,
+    // but error messages may quote the input
+    let string = CString::new(string.replace('\0', "\u{FFFD}"))
+        .unwrap_or_default();
+    // SAFETY: the output is valid for writes
+    unsafe { output.write(string.into_raw()); }
+    code
+}
+
+#[cfg(test)]
+mod test {
+
+use std::ffi::{CStr, CString, c_char};
+
+use super::{
+    dse_decode, dse_encode, dse_free,
+    DSE_OK, DSE_NULL_ARGUMENT, DSE_INVALID_UTF8, DSE_LOAD_ERROR,
+    DSE_JSON_ERROR, DSE_DUMP_ERROR, DSE_PANIC,
+};
+
+/// Call the function, returning the code and the stored string.
+fn call( f: unsafe extern "C" fn(*const c_char, *mut *mut c_char) -> i32,
+    input: &[u8],
+) -> (i32, String) {
+    let input = CString::new(input).unwrap();
+    let mut output = std::ptr::null_mut();
+    // SAFETY: the pointers are valid
+    let code = unsafe { f(input.as_ptr(), &mut output) };
+    // SAFETY: the output was stored by the function
+    let string = unsafe { CStr::from_ptr(output) }
+        .to_str().unwrap().to_owned();
+    // SAFETY: the output was stored by the function
+    unsafe { dse_free(output); }
+    (code, string)
+}
+
+#[test]
+fn test_capi() {
+    let (code, json) = call( dse_decode,
+        crate::test::EXCHANGE_BEHAVIOR_2.as_bytes() );
+    assert_eq!(code, DSE_OK, "{json}");
+    let (code, exchange) = call(dse_encode, json.as_bytes());
+    assert_eq!(code, DSE_OK, "{exchange}");
+    assert_eq!(call(dse_decode, exchange.as_bytes()), (DSE_OK, json));
+    assert_eq!(call(dse_decode, b"DSB").0, DSE_LOAD_ERROR);
+    assert_eq!(call(dse_decode, b"DSB\xFF").0, DSE_INVALID_UTF8);
+    assert_eq!(call(dse_encode, b"{").0, DSE_JSON_ERROR);
+    let mut output = std::ptr::null_mut();
+    // SAFETY: null input is allowed
+    let code = unsafe { dse_decode(std::ptr::null(), &mut output) };
+    assert_eq!((code, output.is_null()), (DSE_NULL_ARGUMENT, true));
+    // SAFETY: null is allowed
+    unsafe { dse_free(std::ptr::null_mut()); }
+}
+
+#[test]
+fn test_header() {
+    let header = include_str!("../include/desynced_exchange.h");
+    for name in ["dse_decode", "dse_encode", "dse_free"] {
+        assert!(header.contains(&format!(" {name}(")), "{name}");
+    }
+    for (name, code) in [
+        ("DSE_OK", DSE_OK),
+        ("DSE_NULL_ARGUMENT", DSE_NULL_ARGUMENT),
+        ("DSE_INVALID_UTF8", DSE_INVALID_UTF8),
+        ("DSE_LOAD_ERROR", DSE_LOAD_ERROR),
+        ("DSE_JSON_ERROR", DSE_JSON_ERROR),
+        ("DSE_DUMP_ERROR", DSE_DUMP_ERROR),
+        ("DSE_PANIC", DSE_PANIC),
+    ] {
+        assert!( header.contains(&format!("#define {name} {code}\n")),
+            "{name}" );
+    }
+}
+
+}
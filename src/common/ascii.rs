@@ -383,10 +383,11 @@ impl From<AsciiString> for String {
 macro_rules! ascii_char {
     ($value:literal) => { {
         const VALUE: $crate::common::ascii::Ascii = {
-            assert!($crate::common::ascii::char_is_ascii($value));
+            let value: char = $value;
+            assert!($crate::common::ascii::char_is_ascii(value));
             // SAFETY: we have just checked for ASCII value
             unsafe { $crate::common::ascii::Ascii::from_byte_unchecked(
-                $value as u8
+                value as u8
             ) }
         };
         VALUE
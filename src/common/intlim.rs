@@ -181,6 +181,54 @@ pub(crate) fn decode_base62(value: Ascii) -> Result<Int62, IntLimError> {
     Ok(unsafe { Int62::new_unchecked(decoded) })
 }
 
+/// Digit values of base62 characters, `INVALID_DIGIT` for other bytes.
+const BASE62_DIGITS: [u8; 256] = {
+    let mut table = [INVALID_DIGIT; 256];
+    let mut value = 0;
+    while value < 62 {
+        let encoded = match value {
+             0 ..=  9 => b'0' +  value      ,
+            10 ..= 35 => b'A' + (value - 10),
+            _         => b'a' + (value - 36),
+        };
+        table[encoded as usize] = value;
+        value += 1;
+    }
+    table
+};
+
+const INVALID_DIGIT: u8 = u8::MAX;
+
+/// `62^5, 62^4, ..., 62^0`
+const BASE62_WORD_POWERS: [u64; U32_ENCODED_LEN] = {
+    let mut powers = [1; U32_ENCODED_LEN];
+    let mut index = U32_ENCODED_LEN - 1;
+    while index > 0 {
+        powers[index - 1] = powers[index] * 62;
+        index -= 1;
+    }
+    powers
+};
+
+/// Decode a full-length word with a single range check
+/// instead of checked arithmetic on every digit.
+#[inline]
+fn decode_base62_word(word: [Ascii; U32_ENCODED_LEN])
+-> Result<u32, IntLimError>
+{
+    let mut result: u64 = 0;
+    let mut invalid = false;
+    for (c, power) in word.into_iter().zip(BASE62_WORD_POWERS) {
+        let digit = BASE62_DIGITS[u8::from(c) as usize];
+        invalid |= digit == INVALID_DIGIT;
+        result += u64::from(digit) * power;
+    }
+    if invalid {
+        return Err(IntLimError);
+    }
+    u32::try_from(result).map_err(|_err| IntLimError)
+}
+
 impl Int31 {
     #[inline]
     #[must_use]
@@ -224,6 +272,20 @@ impl CheckSum<u32> for std::num::Wrapping<u32> {
 const U32_LEN: usize = (u32::BITS / u8::BITS) as usize;
 const U32_ENCODED_LEN: usize = Int62::u32_sufficient_digits();
 
+/// Number of digits [`Base62Encode`] writes for `len` bytes.
+pub(crate) const fn base62_encoded_len(len: usize) -> usize {
+    #[allow(clippy::assertions_on_constants)]
+    { assert!(U32_ENCODED_LEN == 6); }
+    let tail_len = match len % U32_LEN {
+        0 => 0,
+        1 => 2,
+        2 => 3,
+        3 => 5,
+        _ => 6,
+    };
+    len / U32_LEN * U32_ENCODED_LEN + tail_len
+}
+
 pub(crate) struct Base62Encode<W, CS>
 where W: Write<Ascii>, CS: CheckSum<u32>
 {
@@ -360,6 +422,22 @@ where W: Write<u8>, CS: CheckSum<u32>
     }
     pub(crate) fn write_slice(&mut self, mut slice: &[Ascii])
     -> Result<(), LoadError> {
+        while self.buffer_len > 0 && !slice.is_empty() {
+            let written_len = self.write_part(slice)?;
+            slice = &slice[written_len..];
+        }
+        // Full words bypass the buffer.
+        // The last (possibly partial) word still goes through it,
+        // since only `end` knows whether it is the last one.
+        let mut words = slice.chunks_exact(U32_ENCODED_LEN);
+        for word in &mut words {
+            let Ok(&word) = <&[Ascii; U32_ENCODED_LEN]>::try_from(word)
+                else { unreachable!() };
+            let word = decode_base62_word(word)?;
+            self.checksum.add(word);
+            self.writer.write_slice(&word.to_le_bytes());
+        }
+        slice = words.remainder();
         while !slice.is_empty() {
             let written_len = self.write_part(slice)?;
             slice = &slice[written_len..];
@@ -375,7 +453,10 @@ mod test {
         ascii::{Ascii, AsciiStr},
         intlim::IntLimError,
     };
-    use super::{IntLim, Int62, encode_base62, decode_base62};
+    use super::{
+        IntLim, Int62, encode_base62, decode_base62,
+        decode_base62_word, Base62Decode,
+    };
 
     #[test]
     fn test_divrem() {
@@ -491,5 +572,43 @@ mod test {
         assert_eq!(decode("000000"),             0);
     }
 
+    #[test]
+    fn test_decode_base62_word() {
+        fn decode(value: &str) -> Option<u32> {
+            let word = <&AsciiStr>::try_from(value).unwrap();
+            decode_base62_word(<[Ascii; 6]>::try_from(&**word).unwrap()).ok()
+        }
+        assert_eq!(decode("4gfFC3"), Some(u32::MAX));
+        assert_eq!(decode("15ftgG"), Some(1_000_000_000));
+        assert_eq!(decode("000010"), Some(62));
+        assert_eq!(decode("000000"), Some(0));
+        assert_eq!(decode("4gfFC4"), None);
+        assert_eq!(decode("zzzzzz"), None);
+        assert_eq!(decode("00-000"), None);
+    }
+
+    #[test]
+    fn test_decode_split() {
+        let encoded = <&AsciiStr>::try_from("4gfFC315ftgG004C9240").unwrap();
+        let decode = |splits: &[usize]| {
+            let mut decoder = Base62Decode::new(
+                Vec::new(), std::num::Wrapping(0) );
+            let mut rest: &[Ascii] = encoded;
+            for &split in splits {
+                let (head, tail) = rest.split_at(split);
+                decoder.write_slice(head).unwrap();
+                rest = tail;
+            }
+            decoder.write_slice(rest).unwrap();
+            let (decoded, checksum) = decoder.end().unwrap();
+            (decoded, checksum.0)
+        };
+        let expected = decode(&[]);
+        assert_eq!(expected.0.len(), 4 + 4 + 4 + 1);
+        for splits in [&[1][..], &[5, 1], &[6, 6], &[7, 10], &[19]] {
+            assert_eq!(decode(splits), expected);
+        }
+    }
+
 }
 
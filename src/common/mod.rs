@@ -6,8 +6,14 @@ pub(crate) mod string;
 pub(crate) mod ascii;
 pub(crate) mod byteseq;
 pub(crate) mod intlim;
+#[cfg(feature = "serde")]
 pub(crate) mod serde;
 
+/// Supertrait of public traits that only this crate implements.
+/// It is not reachable from outside the crate,
+/// so the traits cannot be implemented elsewhere.
+pub trait Sealed {}
+
 #[must_use]
 #[inline]
 pub(crate) const fn u32_to_usize(len: u32) -> usize {
@@ -69,6 +75,7 @@ pub(crate) const fn ilog2_exact(len: usize)
 /// `Self` is `repr(transparent)` over `Self::Target` and
 /// there are no additional safety-bearing invariants for
 /// the contained value.
+#[cfg(feature = "serde")]
 pub(crate) unsafe trait TransparentRef : AsRef<Self::Target> + Sized {
     type Target : Sized;
     #[must_use]
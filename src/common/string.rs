@@ -1,10 +1,4 @@
-use std::rc::Rc;
-
-use serde::{Deserialize, de, Serialize};
-
-use crate::common::serde::DeserializeOption;
-
-use super::serde::impl_flat_se_option;
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 pub type SharedStr = Rc<str>;
 
@@ -68,6 +62,98 @@ impl Str {
     }
 }
 
+/// A pool of strings, so that equal strings made while it is installed
+/// (see [`Interner::install`]) share one allocation.
+///
+/// Only strings of decoded values and keys are pooled.
+/// [`Str`] is not thread-safe, so the pool is installed
+/// for the current thread only; other threads are not affected
+/// and may install pools of their own.
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<SharedStr>,
+}
+
+thread_local! {
+    static CURRENT_INTERNER: RefCell<Option<Interner>> =
+        const { RefCell::new(None) };
+}
+
+impl Interner {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Number of distinct strings in the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.strings.clear();
+    }
+    /// Get the pooled copy of the string, adding it if there is none.
+    pub fn intern(&mut self, string: &str) -> Str {
+        if let Some(shared) = self.strings.get(string) {
+            return Str::Shared(SharedStr::clone(shared));
+        }
+        let shared = SharedStr::from(string);
+        self.strings.insert(SharedStr::clone(&shared));
+        Str::Shared(shared)
+    }
+    /// Get the pooled copy of the string, adding this one
+    /// (not a copy of it) if there is none.
+    pub(crate) fn intern_shared(&mut self, string: &SharedStr)
+    -> SharedStr
+    {
+        if let Some(shared) = self.strings.get(string) {
+            return SharedStr::clone(shared);
+        }
+        self.strings.insert(SharedStr::clone(string));
+        SharedStr::clone(string)
+    }
+    /// Call `f` with the pool installed for the current thread,
+    /// so that decoding inside it takes strings from the pool.
+    ///
+    /// Strings added meanwhile stay in the pool afterwards.
+    /// Nested installations take turns: the innermost pool is used,
+    /// and the outer one is reinstalled after it.
+    pub fn install<R, F>(&mut self, f: F) -> R
+    where F: FnOnce() -> R
+    {
+        /// Put the pool back even if `f` panics.
+        struct Restore<'i> {
+            interner: &'i mut Interner,
+            previous: Option<Interner>,
+        }
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                let previous = self.previous.take();
+                *self.interner = CURRENT_INTERNER
+                    .with(|current| current.replace(previous))
+                    .unwrap_or_default();
+            }
+        }
+        let previous = CURRENT_INTERNER.with(|current| {
+            current.replace(Some(std::mem::take(self))) });
+        let _restore = Restore { interner: self, previous };
+        f()
+    }
+}
+
+/// Make a string, taking it from the installed pool if there is one.
+#[must_use]
+pub(crate) fn intern(string: &str) -> Str {
+    CURRENT_INTERNER.with(|current| match *current.borrow_mut() {
+        Some(ref mut interner) => interner.intern(string),
+        None => Str::new(string),
+    })
+}
+
 impl Default for Str {
     fn default() -> Self {
         Self::Static(<&str>::default())
@@ -104,21 +190,33 @@ impl std::hash::Hash for Str {
     }
 }
 
-impl<'de> Deserialize<'de> for Str {
-    #[inline]
-    fn deserialize<D>(de: D) -> Result<Self, D::Error>
-    where D: serde::Deserializer<'de>
+#[cfg(feature = "fuzz")]
+impl<'a> arbitrary::Arbitrary<'a> for Str {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>)
+    -> arbitrary::Result<Self>
     {
-        Ok(Self::new(<&'de str as Deserialize>::deserialize(de)?))
+        Ok(Self::new(<&'a str>::arbitrary(u)?))
+    }
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <&'a str>::size_hint(depth)
     }
 }
 
-impl Serialize for Str {
+#[cfg(feature = "serde")]
+mod de {
+
+use serde::{Deserialize, de};
+
+use crate::common::serde::DeserializeOption;
+
+use super::Str;
+
+impl<'de> Deserialize<'de> for Str {
     #[inline]
-    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
-    where S: serde::Serializer
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where D: serde::Deserializer<'de>
     {
-        ser.serialize_str(self)
+        Ok(Self::new(<&'de str as Deserialize>::deserialize(de)?))
     }
 }
 
@@ -160,18 +258,72 @@ impl<'de> de::Visitor<'de> for StrVisitor {
     }
 }
 
+}
+
+#[cfg(feature = "serde")]
+mod ser {
+
+use serde::Serialize;
+
+use crate::common::serde::impl_flat_se_option;
+
+use super::Str;
+
+impl Serialize for Str {
+    #[inline]
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer
+    {
+        ser.serialize_str(self)
+    }
+}
+
 impl_flat_se_option!(Str);
 
+}
+
 #[cfg(test)]
 mod test {
 
+#[cfg(feature = "serde")]
 use crate::common::{
     serde::OptionSerdeWrap,
     TransparentRef as _,
 };
 
-use super::Str;
+use super::{Str, Interner, intern};
+
+#[test]
+fn test_interner() {
+    let same = |one: &Str, other: &Str| match (one, other) {
+        (Str::Shared(one), Str::Shared(other)) =>
+            std::rc::Rc::ptr_eq(one, other),
+        _ => false,
+    };
+    assert!(!same(&intern("c_robot"), &intern("c_robot")));
+    let mut interner = Interner::new();
+    let (one, other) = interner.install(|| {
+        let mut inner = Interner::new();
+        inner.install(|| intern("c_scout"));
+        assert_eq!(inner.len(), 1);
+        (intern("c_robot"), intern("c_robot"))
+    });
+    assert!(same(&one, &other));
+    assert_eq!(interner.len(), 1);
+    assert!(same(&interner.intern("c_robot"), &one));
+    assert!(!same(&intern("c_robot"), &one));
+    interner.clear();
+    interner.install(|| crate::blueprint::load_blueprint(
+        crate::test::EXCHANGE_BEHAVIOR_2 )).unwrap();
+    assert!(!interner.is_empty());
+    std::thread::spawn(|| {
+        let mut interner = Interner::new();
+        interner.install(|| intern("c_robot"));
+        assert_eq!(interner.len(), 1);
+    }).join().unwrap();
+}
 
+#[cfg(feature = "serde")]
 #[test]
 fn str_option_flat_serde_ron() {
     for (s, s1) in [
@@ -185,6 +337,7 @@ fn str_option_flat_serde_ron() {
     }
 }
 
+#[cfg(feature = "serde")]
 #[test]
 fn str_option_flat_serde_json() {
     for (s, s1) in [
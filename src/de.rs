@@ -283,6 +283,8 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
             Some(Value::Float  (value)) => visitor.visit_f64(value),
             Some(Value::String (value)) =>
                 visitor.visit_str(value.as_ref()),
+            Some(Value::Bytes  (value)) =>
+                visitor.visit_byte_buf(value),
             Some(Value::Table  (table)) =>
                 visitor.visit_map(TableMapDeserializer::new(table)),
         }
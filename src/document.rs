@@ -0,0 +1,244 @@
+//! An exchange held in any of its representations at once.
+//!
+//! The same blueprint can be held as an exchange string,
+//! as a generic [`Value`], or as a [`Blueprint`] (or [`Behavior`]).
+//! A [`Document`] starts from any of these and produces the others
+//! on demand, keeping them for later calls.
+//! Mutable access to one representation drops the others,
+//! so they are produced again from the mutated one.
+
+use std::cell::OnceCell;
+
+use crate::{
+    Exchange,
+    error::{LoadError, DumpError},
+    dumper::DumpOptions,
+    value::Value,
+    blueprint::{Blueprint, Behavior, LoadOptions},
+};
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    // at least one of the representations is always present
+    exchange: OnceCell<String>,
+    value: OnceCell<Exchange<Value>>,
+    blueprint: OnceCell<Exchange<Blueprint, Behavior>>,
+    load_options: LoadOptions,
+    dump_options: DumpOptions,
+}
+
+impl Document {
+
+    fn empty() -> Self {
+        Self {
+            exchange: OnceCell::new(),
+            value: OnceCell::new(),
+            blueprint: OnceCell::new(),
+            load_options: LoadOptions::default(),
+            dump_options: DumpOptions::default(),
+        }
+    }
+
+    /// The string is not checked until another representation
+    /// is requested.
+    #[must_use]
+    pub fn from_exchange(exchange: String) -> Self {
+        Self { exchange: OnceCell::from(exchange), ..Self::empty() }
+    }
+
+    #[must_use]
+    pub fn from_value(value: Exchange<Value>) -> Self {
+        Self { value: OnceCell::from(value), ..Self::empty() }
+    }
+
+    #[must_use]
+    pub fn from_blueprint(blueprint: Exchange<Blueprint, Behavior>) -> Self {
+        Self { blueprint: OnceCell::from(blueprint), ..Self::empty() }
+    }
+
+    /// Options for producing a blueprint from the value.
+    /// Representations already produced are not affected.
+    #[must_use]
+    pub fn with_load_options(mut self, options: LoadOptions) -> Self {
+        self.load_options = options;
+        self
+    }
+
+    /// Options for producing the exchange string.
+    /// Representations already produced are not affected.
+    #[must_use]
+    pub fn with_dump_options(mut self, options: DumpOptions) -> Self {
+        self.dump_options = options;
+        self
+    }
+
+    /// Whether this is a blueprint or a behavior.
+    ///
+    /// Nothing is decoded for this; if only the exchange string
+    /// is present, just its header is read.
+    pub fn kind(&self) -> Result<Exchange<()>, LoadError> {
+        if let Some(blueprint) = self.blueprint.get() {
+            return Ok(blueprint.as_ref().map(|_| (), |_| ()));
+        }
+        if let Some(value) = self.value.get() {
+            return Ok(value.as_ref().map_mono(|_| ()));
+        }
+        crate::exchange::kind(self.exchange()?)
+    }
+
+    /// The exchange string, encoding it if needed.
+    ///
+    /// Encoding errors are reported as [`LoadError`]s,
+    /// since producing the value to encode may fail as well.
+    pub fn exchange(&self) -> Result<&str, LoadError> {
+        if let Some(exchange) = self.exchange.get() {
+            return Ok(exchange);
+        }
+        let value = self.value()?.clone();
+        let exchange = crate::dumper::dump_blueprint_with(
+            value.map(Some, Some), &self.dump_options,
+        ).map_err(dump_error)?;
+        Ok(self.exchange.get_or_init(|| exchange))
+    }
+
+    /// The generic value, decoding or converting it if needed.
+    pub fn value(&self) -> Result<&Exchange<Value>, LoadError> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = if let Some(blueprint) = self.blueprint.get() {
+            blueprint.clone().map(Value::try_from, Value::try_from)
+                .transpose()
+                .map_err(|error| LoadError::from(error.to_string()))?
+        } else {
+            let Some(exchange) = self.exchange.get() else {
+                unreachable!("struct invariant");
+            };
+            crate::loader::load_blueprint_with::<Value, Value>(
+                exchange, self.load_options.decode_options(),
+            )?.transpose().ok_or_else(|| LoadError::from(
+                "Blueprint or behavior should not be represented with nil" ))?
+        };
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// The blueprint or behavior, converting it from the value
+    /// (and decoding that) if needed.
+    pub fn blueprint(&self)
+    -> Result<&Exchange<Blueprint, Behavior>, LoadError>
+    {
+        if let Some(blueprint) = self.blueprint.get() {
+            return Ok(blueprint);
+        }
+        let options = &self.load_options;
+        let blueprint = self.value()?.clone().map(
+            |value| Blueprint::from_value_with(value, options.clone()),
+            |value| Behavior::from_value_with(value, options.clone()),
+        ).transpose()?;
+        Ok(self.blueprint.get_or_init(|| blueprint))
+    }
+
+    /// Replace the exchange string, dropping the other representations.
+    pub fn set_exchange(&mut self, exchange: String) {
+        *self = Self::from_exchange(exchange)
+            .with_load_options(self.load_options.clone())
+            .with_dump_options(self.dump_options.clone());
+    }
+
+    /// Mutable access to the value.
+    /// The exchange string and the blueprint are dropped.
+    pub fn value_mut(&mut self) -> Result<&mut Exchange<Value>, LoadError> {
+        self.value()?;
+        self.exchange.take();
+        self.blueprint.take();
+        let Some(value) = self.value.get_mut() else { unreachable!() };
+        Ok(value)
+    }
+
+    /// Mutable access to the blueprint.
+    /// The exchange string and the value are dropped.
+    pub fn blueprint_mut(&mut self)
+    -> Result<&mut Exchange<Blueprint, Behavior>, LoadError>
+    {
+        self.blueprint()?;
+        self.exchange.take();
+        self.value.take();
+        let Some(blueprint) = self.blueprint.get_mut() else { unreachable!() };
+        Ok(blueprint)
+    }
+
+    pub fn into_exchange(self) -> Result<String, LoadError> {
+        self.exchange()?;
+        let Some(exchange) = self.exchange.into_inner() else { unreachable!() };
+        Ok(exchange)
+    }
+
+    pub fn into_value(self) -> Result<Exchange<Value>, LoadError> {
+        self.value()?;
+        let Some(value) = self.value.into_inner() else { unreachable!() };
+        Ok(value)
+    }
+
+    pub fn into_blueprint(self)
+    -> Result<Exchange<Blueprint, Behavior>, LoadError>
+    {
+        self.blueprint()?;
+        let Some(blueprint) = self.blueprint.into_inner() else {
+            unreachable!() };
+        Ok(blueprint)
+    }
+
+}
+
+fn dump_error(error: DumpError) -> LoadError {
+    LoadError::from(error.to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    value::{Key, Value},
+};
+
+use super::Document;
+
+#[test]
+fn test_document() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let document = Document::from_exchange(String::from(exchange));
+    assert_eq!(document.kind().unwrap(), Exchange::Behavior(()));
+    let Exchange::Behavior(behavior) = document.blueprint().unwrap() else {
+        panic!("should be a behavior") };
+    let instruction_count = behavior.instructions.len();
+    assert_eq!(document.exchange().unwrap(), exchange);
+
+    let mut document = Document::from_blueprint(
+        document.into_blueprint().unwrap() );
+    let Exchange::Behavior(behavior) = document.blueprint_mut().unwrap() else {
+        unreachable!() };
+    behavior.instructions.pop();
+    let Exchange::Behavior(Value::Table(table)) = document.value().unwrap()
+        else { panic!("should be a behavior table") };
+    assert!(table.get(&Key::Index(
+        i32::try_from(instruction_count).unwrap() )).is_none());
+
+    let mut document =
+        Document::from_exchange(document.into_exchange().unwrap());
+    let Exchange::Behavior(Value::Table(table)) = document.value_mut().unwrap()
+        else { unreachable!() };
+    table.clone_from(&crate::value::Table::new());
+    let Exchange::Behavior(behavior) = document.blueprint().unwrap() else {
+        unreachable!() };
+    assert!(behavior.instructions.is_empty());
+    assert_ne!(document.exchange().unwrap(), exchange);
+
+    let mut document = Document::from_exchange(String::from("DSX"));
+    assert!(document.kind().is_err());
+    assert!(document.value().is_err());
+    document.set_exchange(String::from(exchange));
+    assert!(document.blueprint().is_ok());
+}
+
+}
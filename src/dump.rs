@@ -1,7 +1,12 @@
 //! A specialized imitation of `serde::ser`.
 //! Due to the nature of serialization format, it is more serializer-driven.
 
-use crate::table_iter::{TableItem, TableSize};
+use crate::{
+    common::Sealed,
+    error::DumpError,
+    dumper::DynDumper,
+    table_iter::{TableItem, TableSize},
+};
 
 pub trait Error : std::error::Error + for<'s> From<&'s str> {}
 
@@ -21,6 +26,41 @@ pub trait Dump {
     }
 }
 
+impl<T: Dump + ?Sized> Dump for &T {
+    fn dump<DD: Dumper>(&self, dumper: DD) -> Result<DD::Ok, DD::Error> {
+        T::dump(self, dumper)
+    }
+}
+
+impl<T: Dump + ?Sized> Dump for Box<T> {
+    fn dump<DD: Dumper>(&self, dumper: DD) -> Result<DD::Ok, DD::Error> {
+        T::dump(self, dumper)
+    }
+}
+
+/// An object-safe counterpart of [`Dump`],
+/// implemented for every type implementing it.
+///
+/// `dyn DynDump` implements [`Dump`] in turn,
+/// so values of types chosen at runtime (e.g. `Box<dyn DynDump>`)
+/// can be passed to the usual dumping functions.
+pub trait DynDump {
+    /// Called by the encoder; the argument cannot be made elsewhere.
+    fn dump_dyn(&self, dumper: &mut DynDumper<'_>) -> Result<(), DumpError>;
+}
+
+impl<T: Dump> DynDump for T {
+    fn dump_dyn(&self, dumper: &mut DynDumper<'_>) -> Result<(), DumpError> {
+        dumper.dump(self)
+    }
+}
+
+impl Dump for dyn DynDump + '_ {
+    fn dump<DD: Dumper>(&self, dumper: DD) -> Result<DD::Ok, DD::Error> {
+        dumper.dump_dyn(self)
+    }
+}
+
 pub trait TableDumpIter<'v> : TableSize + Iterator<
     Item = Option<TableItem<Self::Key, &'v Self::Value>> >
 {
@@ -28,26 +68,34 @@ pub trait TableDumpIter<'v> : TableSize + Iterator<
     type Value: Dump + 'v;
 }
 
-pub trait KeyDumper : Sized {
+/// Implemented by the encoder; sealed.
+pub trait KeyDumper : Sealed + Sized {
     type Ok;
     type Error;
     fn dump_integer(self, value: i32) -> Result<Self::Ok, Self::Error>;
     fn dump_string(self, value: &str) -> Result<Self::Ok, Self::Error>;
+    fn dump_boolean(self, value: bool) -> Result<Self::Ok, Self::Error>;
 }
 
-pub trait Dumper : Sized {
+/// Implemented by the encoder; sealed.
+pub trait Dumper : Sealed + Sized {
     type Ok;
-    type Error;
+    type Error: Error;
     fn dump_nil(self) -> Result<Self::Ok, Self::Error>;
     fn dump_boolean(self, value: bool) -> Result<Self::Ok, Self::Error>;
     fn dump_integer(self, value: i32) -> Result<Self::Ok, Self::Error>;
     fn dump_float(self, value: f64) -> Result<Self::Ok, Self::Error>;
     fn dump_string(self, value: &str) -> Result<Self::Ok, Self::Error>;
+    /// Dump a string that is not necessarily valid UTF-8.
+    fn dump_bytes(self, value: &[u8]) -> Result<Self::Ok, Self::Error>;
     fn dump_table<'v, T>(self, table: T) -> Result<Self::Ok, Self::Error>
     where
         T: TableDumpIter<'v>,
         T::Key: KeyDump,
         T::Value: Dump,
     ;
+    /// Dump the value through [`DynDump`].
+    fn dump_dyn(self, value: &dyn DynDump)
+    -> Result<Self::Ok, Self::Error>;
 }
 
@@ -3,30 +3,43 @@ use flate2::write::ZlibEncoder as ZippingWriter;
 use crate::{
     common::{
         ConstSlice,
-        ascii::{self, Ascii},
-        byteseq::Write as _,
-        intlim::{Int62, Int31, encode_base62, Base62Encode},
+        ascii::{self, Ascii, AsciiStr},
+        byteseq::Write,
+        intlim::{
+            Int62, Int31, encode_base62, Base62Encode,
+            base62_encoded_len,
+        },
     },
     Exchange,
 };
 
+use super::{DumpOptions, Compression};
+
 pub(crate) fn compress(
     body: Exchange<&[u8]>,
+    options: &DumpOptions,
 ) -> String {
+    let writer = compress_into(
+        body, options, Vec::<Ascii>::with_capacity(128) );
+    ascii::AsciiString(writer).into()
+}
+
+/// Write the exchange string like [`compress`] does,
+/// piece by piece as it is encoded.
+pub(crate) fn compress_into<W: Write<Ascii>>(
+    body: Exchange<&[u8]>,
+    options: &DumpOptions,
+    mut writer: W,
+) -> W {
     let (prefix, body) = match body {
         Exchange::Blueprint(body) => (ascii::str!("DSB"), body),
         Exchange::Behavior (body) => (ascii::str!("DSC"), body),
     };
-    let mut writer = Vec::<Ascii>::with_capacity(128);
     writer.write_slice(prefix);
-    let mut zipped = None;
-    let (len, body) = {
-        let zipped: &_ = zipped.insert(zip(body));
-        if body.len() <= zipped.len() {
-            (0, body)
-        } else {
-            (body.len(), zipped.as_ref())
-        }
+    let zipped = zip_with(body, options);
+    let (len, body) = match zipped.as_deref() {
+        Some(zipped) => (body.len(), zipped),
+        None => (0, body),
     };
     writer.write_slice(&encode_base31(len));
     let mut encoder = Base62Encode::new(writer, std::num::Wrapping(0));
@@ -34,10 +47,94 @@ pub(crate) fn compress(
     #[allow(clippy::shadow_unrelated)]
     let (mut writer, checksum) = encoder.end();
     writer.write_byte(encode_base62(Int62::divrem(checksum.0).1));
-    ascii::AsciiString(writer).into()
+    writer
+}
+
+/// Adapter of [`std::io::Write`] for [`compress_into`],
+/// keeping the first error until [`IoWriter::finish`].
+pub(crate) struct IoWriter<W: std::io::Write> {
+    writer: std::io::BufWriter<W>,
+    error: Option<std::io::Error>,
 }
 
-pub(super) fn encode_base31(len: usize) -> impl std::ops::Deref<Target=[Ascii]> {
+impl<W: std::io::Write> IoWriter<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self { writer: std::io::BufWriter::new(writer), error: None }
+    }
+    pub(crate) fn finish(mut self) -> Result<(), std::io::Error> {
+        use std::io::Write as _;
+        match self.error {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl<W: std::io::Write> Write<Ascii> for IoWriter<W> {
+    fn write_slice(&mut self, value: &[Ascii]) {
+        use std::io::Write as _;
+        if self.error.is_some() { return; }
+        let value: &[u8] = <&AsciiStr>::from(value).into();
+        if let Err(error) = self.writer.write_all(value) {
+            self.error = Some(error);
+        }
+    }
+}
+
+/// Adapter of [`std::fmt::Write`] for [`compress_into`],
+/// keeping the first error until [`FmtWriter::finish`].
+pub(crate) struct FmtWriter<W: std::fmt::Write> {
+    writer: W,
+    error: Option<std::fmt::Error>,
+}
+
+impl<W: std::fmt::Write> FmtWriter<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self { writer, error: None }
+    }
+    pub(crate) fn finish(self) -> Result<(), std::fmt::Error> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+impl<W: std::fmt::Write> Write<Ascii> for FmtWriter<W> {
+    fn write_slice(&mut self, value: &[Ascii]) {
+        if self.error.is_some() { return; }
+        let value: &str = <&AsciiStr>::from(value).into();
+        if let Err(error) = self.writer.write_str(value) {
+            self.error = Some(error);
+        }
+    }
+}
+
+/// The zlib stream of the data,
+/// or `None` if the options tell to leave the data uncompressed.
+pub(crate) fn zip_with(body: &[u8], options: &DumpOptions)
+-> Option<Vec<u8>>
+{
+    let zipped = match options.compression {
+        Compression::Never => return None,
+        Compression::Auto | Compression::Always =>
+            match options.compression_chunk_size {
+                Some(chunk_size) if chunk_size < body.len() =>
+                    zip_chunked(body, options.compression_level, chunk_size),
+                _ => zip(body, options.compression_level),
+            },
+    };
+    match options.compression {
+        Compression::Auto if zipped.len() >= body.len() => None,
+        _ => Some(zipped),
+    }
+}
+
+/// Length of the string [`compress`] makes of `len` bytes
+/// when they are left uncompressed.
+pub(crate) fn uncompressed_len(len: usize) -> usize {
+    // prefix, zero length, data, checksum digit
+    3 + encode_base31(0).len() + base62_encoded_len(len) + 1
+}
+
+pub(crate) fn encode_base31(len: usize) -> impl std::ops::Deref<Target=[Ascii]> {
     const MAX_DIGITS: usize = Int31::u32_sufficient_digits();
     if len == 0 {
         return ConstSlice::from_slice(&[ascii::char!('V')]);
@@ -55,14 +152,83 @@ pub(super) fn encode_base31(len: usize) -> impl std::ops::Deref<Target=[Ascii]>
     result
 }
 
-fn zip(data: &[u8]) -> Vec<u8> {
+fn zip(data: &[u8], level: u32) -> Vec<u8> {
     use std::io::Write as _;
     let mut zipper = ZippingWriter::new(
         Vec::<u8>::new(),
-        flate2::Compression::best(),
+        flate2::Compression::new(level.min(9)),
     );
     zipper.write_all(data).unwrap();
     zipper.try_finish().unwrap();
     zipper.finish().unwrap()
 }
 
+/// Deflate the chunks independently, each ending on a byte boundary
+/// (the way `pigz` does), and join them into a single zlib stream.
+fn zip_chunked(data: &[u8], level: u32, chunk_size: usize) -> Vec<u8> {
+    #[cfg(feature = "parallel")]
+    use rayon::prelude::*;
+    let level = level.min(9);
+    let chunks: Vec<&[u8]> = data.chunks(chunk_size.max(1)).collect();
+    let last = chunks.len() - 1;
+    let deflate = |(index, chunk): (usize, &&[u8])|
+        deflate_chunk(chunk, level, index == last);
+    #[cfg(feature = "parallel")]
+    let deflated: Vec<Vec<u8>> = chunks.par_iter().enumerate()
+        .map(deflate).collect();
+    #[cfg(not(feature = "parallel"))]
+    let deflated: Vec<Vec<u8>> = chunks.iter().enumerate()
+        .map(deflate).collect();
+    let mut result = Vec::with_capacity(
+        deflated.iter().map(Vec::len).sum::<usize>() + 6 );
+    // the compression method and the level hint, with a check value
+    result.extend_from_slice(&[0x78, match level {
+        0 ..= 1 => 0x01,
+        2 ..= 5 => 0x5E,
+        6 => 0x9C,
+        _ => 0xDA,
+    }]);
+    for chunk in deflated {
+        result.extend_from_slice(&chunk);
+    }
+    result.extend_from_slice(&adler32(data).to_be_bytes());
+    result
+}
+
+fn deflate_chunk(chunk: &[u8], level: u32, last: bool) -> Vec<u8> {
+    use flate2::{Compress, FlushCompress, Status};
+    let mut compress = Compress::new(flate2::Compression::new(level), false);
+    let flush = if last { FlushCompress::Finish } else { FlushCompress::Sync };
+    let mut output = Vec::with_capacity(chunk.len() / 2 + 64);
+    loop {
+        let consumed = usize::try_from(compress.total_in())
+            .expect("the consumed length should not exceed the chunk");
+        let status = compress.compress_vec(&chunk[consumed..], &mut output, flush)
+            .expect("compression should not fail");
+        let flushed = output.len() < output.capacity() &&
+            usize::try_from(compress.total_in()).ok() == Some(chunk.len());
+        match status {
+            Status::StreamEnd => break,
+            Status::Ok | Status::BufError if !last && flushed => break,
+            Status::Ok | Status::BufError => output.reserve(chunk.len() / 4 + 64),
+        }
+    }
+    output
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    // the largest prime below 2^16
+    const MODULUS: u32 = 0xFFF1;
+    // the largest number of bytes before `b` can overflow
+    const BLOCK_LEN: usize = 5552;
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for block in data.chunks(BLOCK_LEN) {
+        for &byte in block {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    (b << 16) | a
+}
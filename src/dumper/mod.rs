@@ -1,36 +1,291 @@
+//! Encoding of exchange strings.
+//!
+//! The encoded value can be of any type implementing [`Dump`];
+//! see [`crate::blueprint::dump_blueprint`]
+//! for dumping structured blueprints.
+
 use crate::{
     error::DumpError as Error,
     common::{
-        LogSize, iexp2,
+        LogSize, iexp2, Sealed,
         byteseq::Write,
     },
     table_iter::{TableItem, AssocItem},
     dump::{
-        KeyDump, Dump, TableDumpIter,
+        KeyDump, Dump, DynDump, TableDumpIter,
         Dumper as DumperTr, KeyDumper,
     },
-    Exchange,
+    limits::MAX_STRING_LEN,
+    Exchange, Progress,
 };
 
-mod compress;
+pub(crate) mod compress;
+
+const EXCEEDED_LOGLEN: LogSize = crate::limits::MAX_ASSOC_LOGLEN + 1;
+
+/// The zlib implementation used for compression,
+/// chosen with the `zlib-ng` and `zlib-rs` features
+/// (`miniz_oxide` by default).
+/// Backends produce different (equally valid) compressed data,
+/// so exchange strings may differ between them.
+pub const COMPRESSION_BACKEND: &str =
+    if cfg!(feature = "zlib-ng") { "zlib-ng" }
+    else if cfg!(feature = "zlib-rs") { "zlib-rs" }
+    else { "miniz_oxide" };
+
+/// Options for [`dump_blueprint_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DumpOptions {
+    pub compression: Compression,
+    /// The zlib compression level, from `0` (fastest)
+    /// to `9` (smallest, the default).
+    /// Larger values are treated as `9`.
+    /// See also [`COMPRESSION_BACKEND`].
+    pub compression_level: u32,
+    /// Compress the data in independent chunks of this size
+    /// (the default `None` compresses it in one piece).
+    /// The result is still a single zlib stream,
+    /// a little larger since the chunks do not share history.
+    /// With the `parallel` feature the chunks are compressed
+    /// on the `rayon` thread pool.
+    pub compression_chunk_size: Option<usize>,
+    /// What to do with integers that do not fit into 32 bits
+    /// when converting through [`crate::ser`].
+    pub integer_overflow: IntegerOverflow,
+    /// Which of the integer forms to choose.
+    pub integer_width: IntegerWidth,
+    /// What to do with NaN and infinite floats.
+    pub float_policy: FloatPolicy,
+}
+
+impl Default for DumpOptions {
+    fn default() -> Self {
+        Self {
+            compression: Compression::default(),
+            compression_level: 9,
+            compression_chunk_size: None,
+            integer_overflow: IntegerOverflow::default(),
+            integer_width: IntegerWidth::default(),
+            float_policy: FloatPolicy::default(),
+        }
+    }
+}
+
+/// The encoding only supports 32-bit signed integers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IntegerOverflow {
+    /// Fail the conversion (the default).
+    #[default]
+    Error,
+    /// Replace with `i32::MIN` or `i32::MAX`.
+    Saturate,
+    /// Store as a floating point number,
+    /// possibly losing precision.
+    Float,
+}
+
+/// Integers have several forms of different lengths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IntegerWidth {
+    /// The forms the game chooses (the default),
+    /// so that unmodified data is encoded byte for byte the same:
+    /// `-128` and `-32768` take the next wider form.
+    #[default]
+    MatchGame,
+    /// The shortest form for every integer.
+    Minimal,
+}
+
+/// Floats that are NaN or infinite can be encoded,
+/// but they break formats like JSON,
+/// and the game has no use for them
+/// (e.g. as instruction offsets `nx` and `ny`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FloatPolicy {
+    /// Fail the dumping (the default).
+    #[default]
+    Error,
+    /// Encode them as they are.
+    AllowNonFinite,
+    /// Replace them with zero.
+    ClampToZero,
+}
 
-const EXCEEDED_LOGLEN: LogSize = crate::MAX_ASSOC_LOGLEN + 1;
+/// Whether to compress the encoded data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Compress if it makes the string shorter (the default).
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
 
 pub fn dump_blueprint<P, H>(exchange: Exchange<Option<P>, Option<H>>)
 -> Result<String, Error>
 where P: Dump, H: Dump
 {
-    let encoded_body = exchange.map(encode, encode).transpose()?;
-    Ok(compress::compress(encoded_body.as_deref()))
+    dump_blueprint_with(exchange, &DumpOptions::default())
+}
+
+pub fn dump_blueprint_with<P, H>(
+    exchange: Exchange<Option<P>, Option<H>>,
+    options: &DumpOptions,
+) -> Result<String, Error>
+where P: Dump, H: Dump
+{
+    dump_blueprint_with_progress(exchange, options, |_| ())
+}
+
+/// Dump the exchange like [`dump_blueprint_with`],
+/// calling `progress` every now and then while the binary data
+/// is encoded, and once more when it is done.
+///
+/// The data is compressed and base62-encoded after the last call.
+pub fn dump_blueprint_with_progress<P, H, F>(
+    exchange: Exchange<Option<P>, Option<H>>,
+    options: &DumpOptions,
+    progress: F,
+) -> Result<String, Error>
+where P: Dump, H: Dump, F: FnMut(Progress)
+{
+    let encoded_body = match exchange {
+        Exchange::Blueprint(value) => Exchange::Blueprint(
+            encode_with_progress(value, options, progress)? ),
+        Exchange::Behavior(value) => Exchange::Behavior(
+            encode_with_progress(value, options, progress)? ),
+    };
+    Ok(compress::compress(encoded_body.as_deref(), options))
+}
+
+/// Dump the exchange like [`dump_blueprint_with`],
+/// writing the string into `writer` as it is base62-encoded
+/// instead of returning it.
+///
+/// The binary data is still encoded (and compressed) in memory,
+/// but the string, half again as long, is never held whole.
+/// The writer is buffered internally.
+pub fn dump_blueprint_to<P, H, W>(
+    exchange: Exchange<Option<P>, Option<H>>,
+    options: &DumpOptions,
+    writer: W,
+) -> Result<(), Error>
+where P: Dump, H: Dump, W: std::io::Write
+{
+    let encoded_body = encode_exchange(exchange, options)?;
+    compress::compress_into( encoded_body.as_deref(), options,
+        compress::IoWriter::new(writer) ).finish()?;
+    Ok(())
+}
+
+/// Dump the exchange like [`dump_blueprint_to`],
+/// into a [`std::fmt::Write`] (e.g. a [`std::fmt::Formatter`]).
+pub fn dump_blueprint_to_fmt<P, H, W>(
+    exchange: Exchange<Option<P>, Option<H>>,
+    options: &DumpOptions,
+    writer: W,
+) -> Result<(), Error>
+where P: Dump, H: Dump, W: std::fmt::Write
+{
+    let encoded_body = encode_exchange(exchange, options)?;
+    compress::compress_into( encoded_body.as_deref(), options,
+        compress::FmtWriter::new(writer) ).finish()?;
+    Ok(())
+}
+
+fn encode_exchange<P, H>(
+    exchange: Exchange<Option<P>, Option<H>>,
+    options: &DumpOptions,
+) -> Result<Exchange<Vec<u8>>, Error>
+where P: Dump, H: Dump
+{
+    Ok(match exchange {
+        Exchange::Blueprint(value) => Exchange::Blueprint(
+            encode_with_progress(value, options, |_| ())? ),
+        Exchange::Behavior(value) => Exchange::Behavior(
+            encode_with_progress(value, options, |_| ())? ),
+    })
+}
+
+/// Encode the exchange into binary data, compressed according
+/// to the options but without the base62 layer of exchange strings,
+/// for embedding into other binary formats.
+///
+/// Compressed data is a zlib stream (see [`crate::loader::decode_raw`]).
+pub fn encode_raw<P, H>(
+    exchange: Exchange<Option<P>, Option<H>>,
+    options: &DumpOptions,
+) -> Result<Exchange<Vec<u8>>, Error>
+where P: Dump, H: Dump
+{
+    let encoded_body = encode_exchange(exchange, options)?;
+    Ok(encoded_body.map_mono(|body| {
+        compress::zip_with(&body, options).unwrap_or(body)
+    }))
 }
 
 #[inline]
-fn encode<V: Dump>(value: Option<V>) -> Result<Vec<u8>, Error> {
-    let mut dumper = Dumper::new(Vec::with_capacity(128));
+pub(crate) fn encode<V: Dump>(value: Option<V>) -> Result<Vec<u8>, Error> {
+    encode_with_progress(value, &DumpOptions::default(), |_| ())
+}
+
+pub(crate) fn encode_with_progress<V, F>( value: Option<V>,
+    options: &DumpOptions, progress: F,
+) -> Result<Vec<u8>, Error>
+where V: Dump, F: FnMut(Progress)
+{
+    let mut dumper = Dumper::with_progress(
+        Vec::with_capacity(128), progress );
+    dumper.integer_width = options.integer_width;
+    dumper.float_policy = options.float_policy;
     V::dump_option(value.as_ref(), &mut dumper)?;
+    dumper.report_progress();
     Ok(dumper.end())
 }
 
+/// Length of the data [`encode`] would produce, without storing it.
+pub(crate) fn encoded_len<V: Dump>(value: Option<&V>)
+-> Result<usize, Error>
+{
+    let mut dumper = Dumper::new(LenCounter(0));
+    // the length does not depend on the value of a float
+    dumper.float_policy = FloatPolicy::AllowNonFinite;
+    V::dump_option(value, &mut dumper)?;
+    Ok(dumper.end().0)
+}
+
+struct LenCounter(usize);
+
+impl Write<u8> for LenCounter {
+    fn write_slice(&mut self, value: &[u8]) {
+        self.0 += value.len();
+    }
+}
+
+/// The encoder as seen by [`DynDump`]; cannot be used otherwise.
+pub struct DynDumper<'d>(
+    Dumper<DynWriter<'d>, &'d mut dyn FnMut(Progress)>
+);
+
+impl DynDumper<'_> {
+    pub(crate) fn dump<V: Dump>(&mut self, value: &V) -> Result<(), Error> {
+        value.dump(&mut self.0)
+    }
+}
+
+struct DynWriter<'d>(&'d mut dyn FnMut(&[u8]));
+
+impl Write<u8> for DynWriter<'_> {
+    fn write_slice(&mut self, value: &[u8]) {
+        (self.0)(value);
+    }
+}
+
 
 #[inline]
 const fn mask(loglen: u8) -> u32 {
@@ -40,38 +295,74 @@ const fn mask(loglen: u8) -> u32 {
     (1_u32 << loglen) - 1
 }
 
-struct Dumper<W: Write<u8>> {
+struct Dumper<W: Write<u8>, P: FnMut(Progress) = fn(Progress)> {
     writer: W,
+    integer_width: IntegerWidth,
+    float_policy: FloatPolicy,
+    written: usize,
+    values: usize,
+    next_report: usize,
+    progress: P,
 }
 
-impl<W: Write<u8>> Dumper<W> {
+/// Progress is reported whenever this many more bytes are encoded.
+const PROGRESS_STEP: usize = 0x4000;
 
+impl<W: Write<u8>> Dumper<W> {
     fn new(writer: W) -> Self {
-        Self { writer }
+        Self::with_progress(writer, |_| ())
+    }
+}
+
+impl<W: Write<u8>, P: FnMut(Progress)> Dumper<W, P> {
+
+    fn with_progress(writer: W, progress: P) -> Self {
+        Self {
+            writer, integer_width: IntegerWidth::default(),
+            float_policy: FloatPolicy::default(), written: 0,
+            values: 0, next_report: PROGRESS_STEP,
+            progress,
+        }
     }
 
     fn end(self) -> W {
         self.writer
     }
 
+    /// Count an encoded value, reporting progress now and then.
+    fn count_value(&mut self) {
+        self.values += 1;
+        if self.written >= self.next_report {
+            self.report_progress();
+        }
+    }
+
+    fn report_progress(&mut self) {
+        self.next_report = self.written.saturating_add(PROGRESS_STEP);
+        (self.progress)(Progress {
+            bytes: self.written, total_bytes: None, values: self.values });
+    }
+
     #[inline]
     fn write_byte(&mut self, value: u8) {
+        self.written += 1;
         self.writer.write_byte(value);
     }
 
     #[inline]
     fn write_array<const N: usize>(&mut self, value: [u8; N]) {
+        self.written += N;
         self.writer.write_array(value);
     }
 
     #[inline]
     fn write_slice(&mut self, value: &[u8]) {
+        self.written += value.len();
         self.writer.write_slice(value)
     }
 
     #[inline]
     fn write_ext_uint(&mut self, mut value: u32) {
-        assert!(value.checked_ilog2() < Some(21));
         loop {
             let shift = 7;
             let mut byte = (value & mask(shift)) as u8;
@@ -92,7 +383,6 @@ impl<W: Write<u8>> Dumper<W> {
         } else {
             (Some(true), value.wrapping_neg() as u32)
         };
-        assert!(value.checked_ilog2() < Some(20));
         loop {
             let mut shift = 7;
             let negative = negative.take();
@@ -113,6 +403,74 @@ impl<W: Write<u8>> Dumper<W> {
         }
     }
 
+    fn write_integer(&mut self, value: i32) {
+        let minimal = self.integer_width == IntegerWidth::Minimal;
+        match value {
+            -0x80 if minimal => {
+                self.write_byte(0xD0);
+                self.write_array::<1>((value as i8).to_le_bytes());
+            },
+            -0x_8000 if minimal => {
+                self.write_byte(0xD1);
+                self.write_array::<2>((value as i16).to_le_bytes());
+            },
+            -0x20 ..= 0x7F => {
+                self.write_array::<1>((value as i8).to_le_bytes());
+            },
+            0x80 ..= 0xFF => {
+                self.write_byte(0xCC);
+                self.write_array::<1>((value as u8).to_le_bytes());
+            },
+            0x_0100 ..= 0x_FFFF => {
+                self.write_byte(0xCD);
+                self.write_array::<2>((value as u16).to_le_bytes());
+            },
+            0x_0001_0000 ..= 0x_7FFF_FFFF => {
+                self.write_byte(0xCE);
+                self.write_array::<4>(value.to_le_bytes());
+            },
+            -0x7F ..= -0x21 => {
+                self.write_byte(0xD0);
+                self.write_array::<1>((value as i8).to_le_bytes());
+            },
+            -0x_7FFF ..= -0x_0080 => {
+                self.write_byte(0xD1);
+                self.write_array::<2>((value as i16).to_le_bytes());
+            },
+            -0x_8000_0000 ..= -0x_0000_8000 => {
+                self.write_byte(0xD2);
+                self.write_array::<4>(value.to_le_bytes());
+            },
+        }
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<(), Error> {
+        self.write_bytes(value.as_bytes())
+    }
+
+    fn write_bytes(&mut self, value: &[u8]) -> Result<(), Error> {
+        match value.len() {
+            0 ..= 0x1F => {
+                self.write_byte(0xA0 | (value.len() as u8));
+                self.write_slice(value);
+            },
+            0x20 ..= 0xFF => {
+                self.write_byte(0xD9);
+                self.write_byte(value.len() as u8);
+                self.write_slice(value);
+            },
+            0x_0100 ..= MAX_STRING_LEN => {
+                self.write_byte(0xDA);
+                self.write_array::<2>((value.len() as u16).to_le_bytes());
+                self.write_slice(value);
+            },
+            len => return Err(Error::from(format!(
+                "string of {len} bytes is longer \
+                 than {MAX_STRING_LEN} bytes" ))),
+        }
+        Ok(())
+    }
+
     #[inline]
     fn dump_table_header<'v, T>(&mut self, table: &T) -> Result<(), Error>
     where T: TableDumpIter<'v>, T::Key: KeyDump
@@ -129,7 +487,7 @@ impl<W: Write<u8>> Dumper<W> {
                 self.write_byte(0xDD);
                 self.write_array::<4>(len.to_le_bytes());
             },
-            (len @ 0 ..= 0x001F_FFFF, Some(logsize @ 0 ..= 7)) => {
+            (len, Some(logsize @ 0 ..= 7)) => {
                 let has_array = len > 0;
                 self.write_byte(0x80 | u8::from(has_array) | (logsize << 1));
                 if has_array {
@@ -137,7 +495,7 @@ impl<W: Write<u8>> Dumper<W> {
                 }
                 self.write_ext_uint(table.assoc_last_free());
             },
-            (len @ 0 ..= 0x001F_FFFF, Some(logsize @ 8 ..= 20)) => {
+            (len, Some(logsize @ 8 ..= crate::limits::MAX_ASSOC_LOGLEN)) => {
                 self.write_byte(0xDE);
                 let has_array = len > 0;
                 self.write_byte(u8::from(has_array) | (logsize << 1));
@@ -147,7 +505,6 @@ impl<W: Write<u8>> Dumper<W> {
                 }
                 self.write_ext_uint(table.assoc_last_free());
             },
-            (0x0020_0000 ..= u32::MAX, Some(_)) |
             (_, Some(self::EXCEEDED_LOGLEN ..= LogSize::MAX)) =>
                 return Err(Error::from("unsupported table size")),
         }
@@ -163,95 +520,99 @@ impl<W: Write<u8>> Dumper<W> {
 
 }
 
-impl<W: Write<u8>> KeyDumper for &mut Dumper<W> {
+impl<W: Write<u8>, P: FnMut(Progress)> Sealed for &mut Dumper<W, P> {}
+
+impl<W: Write<u8>, P: FnMut(Progress)> KeyDumper for &mut Dumper<W, P> {
     type Ok = <Self as DumperTr>::Ok;
     type Error = <Self as DumperTr>::Error;
 
     #[inline]
     fn dump_integer(self, value: i32) -> Result<Self::Ok, Self::Error> {
-        <Self as DumperTr>::dump_integer(self, value)
+        self.write_integer(value);
+        Ok(())
     }
 
     #[inline]
     fn dump_string(self, value: &str) -> Result<Self::Ok, Self::Error> {
-        <Self as DumperTr>::dump_string(self, value)
+        self.write_string(value)
+    }
+
+    #[inline]
+    fn dump_boolean(self, value: bool) -> Result<Self::Ok, Self::Error> {
+        self.write_byte(0xC2 | u8::from(value));
+        Ok(())
     }
 }
 
-impl<W: Write<u8>> DumperTr for &mut Dumper<W> {
+impl<W: Write<u8>, P: FnMut(Progress)> DumperTr for &mut Dumper<W, P> {
     type Ok = ();
     type Error = Error;
 
     fn dump_nil(self) -> Result<Self::Ok, Error> {
         self.write_byte(0xC0);
+        self.count_value();
         Ok(())
     }
 
     fn dump_boolean(self, value: bool) -> Result<Self::Ok, Error> {
         self.write_byte(0xC2 | u8::from(value));
+        self.count_value();
         Ok(())
     }
 
     fn dump_integer(self, value: i32) -> Result<Self::Ok, Error> {
-        match value {
-            -0x20 ..= 0x7F => {
-                self.write_array::<1>((value as i8).to_le_bytes());
-            },
-            0x80 ..= 0xFF => {
-                self.write_byte(0xCC);
-                self.write_array::<1>((value as u8).to_le_bytes());
-            },
-            0x_0100 ..= 0x_FFFF => {
-                self.write_byte(0xCD);
-                self.write_array::<2>((value as u16).to_le_bytes());
-            },
-            0x_0001_0000 ..= 0x_7FFF_FFFF => {
-                self.write_byte(0xCE);
-                self.write_array::<4>(value.to_le_bytes());
-            },
-            -0x7F ..= -0x21 => {
-                self.write_byte(0xD0);
-                self.write_array::<1>((value as i8).to_le_bytes());
-            },
-            -0x_7FFF ..= -0x_0080 => {
-                self.write_byte(0xD1);
-                self.write_array::<2>((value as i16).to_le_bytes());
-            },
-            -0x_8000_0000 ..= -0x_0000_8000 => {
-                self.write_byte(0xD2);
-                self.write_array::<4>(value.to_le_bytes());
-            },
-        }
+        self.write_integer(value);
+        self.count_value();
         Ok(())
     }
 
-    fn dump_float(self, value: f64) -> Result<Self::Ok, Error> {
+    fn dump_float(self, mut value: f64) -> Result<Self::Ok, Error> {
+        if !value.is_finite() {
+            match self.float_policy {
+                FloatPolicy::Error => return Err(Error::from(format!(
+                    "float {value} is not finite" ))),
+                FloatPolicy::AllowNonFinite => (),
+                FloatPolicy::ClampToZero => value = 0.0,
+            }
+        }
         self.write_byte(0xCB);
         self.write_array::<8>(value.to_le_bytes());
+        self.count_value();
         Ok(())
     }
 
     fn dump_string(self, value: &str) -> Result<Self::Ok, Error> {
-        match value.len() {
-            0 ..= 0x1F => {
-                self.write_byte(0xA0 | (value.len() as u8));
-                self.write_slice(value.as_bytes());
-            },
-            0x20 ..= 0xFF => {
-                self.write_byte(0xD9);
-                self.write_byte(value.len() as u8);
-                self.write_slice(value.as_bytes());
-            },
-            0x_0100 ..= 0x_FFFF => {
-                self.write_byte(0xDA);
-                self.write_array::<2>((value.len() as u16).to_le_bytes());
-                self.write_slice(value.as_bytes());
-            },
-            _ => return Err(Error::from("too long string")),
-        }
+        self.write_string(value)?;
+        self.count_value();
         Ok(())
     }
 
+    fn dump_bytes(self, value: &[u8]) -> Result<Self::Ok, Error> {
+        self.write_bytes(value)?;
+        self.count_value();
+        Ok(())
+    }
+
+    fn dump_dyn(self, value: &dyn DynDump) -> Result<Self::Ok, Error> {
+        let writer = &mut self.writer;
+        let mut write = |slice: &[u8]| writer.write_slice(slice);
+        let mut dumper = DynDumper(Dumper {
+            writer: DynWriter(&mut write),
+            integer_width: self.integer_width,
+            float_policy: self.float_policy,
+            written: self.written,
+            values: self.values,
+            next_report: self.next_report,
+            progress: &mut self.progress,
+        });
+        let result = value.dump_dyn(&mut dumper);
+        let DynDumper(Dumper { written, values, next_report, .. }) = dumper;
+        self.written = written;
+        self.values = values;
+        self.next_report = next_report;
+        result
+    }
+
     fn dump_table<'v, T>(self, table: T) -> Result<Self::Ok, Error>
     where
         T: TableDumpIter<'v>,
@@ -280,6 +641,7 @@ impl<W: Write<u8>> DumperTr for &mut Dumper<W> {
         assert!( array_len == 0 && assoc_len == 0,
             "less than expected number of items" );
         serial.end()?;
+        self.count_value();
         Ok(())
     }
 
@@ -291,19 +653,19 @@ const SERIAL_LEN: usize = {
     len
 };
 
-struct SerialWriter<'v, W, K, V>
-where W: Write<u8>, K: KeyDump, V: Dump
+struct SerialWriter<'v, W, K, V, P>
+where W: Write<u8>, K: KeyDump, V: Dump, P: FnMut(Progress)
 {
-    dumper: &'v mut Dumper<W>,
+    dumper: &'v mut Dumper<W, P>,
     values: [Option<TableItem<K, &'v V>>; SERIAL_LEN],
     len: u8,
     mask: u8,
 }
 
-impl<'v, W, K, V> SerialWriter<'v, W, K, V>
-where W: Write<u8>, K: KeyDump, V: Dump
+impl<'v, W, K, V, P> SerialWriter<'v, W, K, V, P>
+where W: Write<u8>, K: KeyDump, V: Dump, P: FnMut(Progress)
 {
-    fn new(dumper: &'v mut Dumper<W>) -> Self {
+    fn new(dumper: &'v mut Dumper<W, P>) -> Self {
         Self {
             dumper,
             values: [(); SERIAL_LEN].map(|()| None),
@@ -366,3 +728,155 @@ where W: Write<u8>, K: KeyDump, V: Dump
     }
 }
 
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    value::Value,
+    loader::load_blueprint,
+};
+
+use super::{
+    Dumper, IntegerWidth, encode, encode_with_progress,
+    dump_blueprint, dump_blueprint_with, encode_raw,
+    dump_blueprint_to, dump_blueprint_to_fmt,
+    DumpOptions, Compression, COMPRESSION_BACKEND,
+};
+
+#[test]
+fn test_large_varints() {
+    let write_uint = |value| {
+        let mut dumper = Dumper::new(Vec::new());
+        dumper.write_ext_uint(value);
+        dumper.end()
+    };
+    let write_sint = |value| {
+        let mut dumper = Dumper::new(Vec::new());
+        dumper.write_ext_sint(value);
+        dumper.end()
+    };
+    assert_eq!(write_uint(0x20_0000), [0x01, 0x01, 0x01, 0x02]);
+    assert_eq!(write_uint(u32::MAX), [0xFF, 0xFF, 0xFF, 0xFF, 0x1E]);
+    assert_eq!(write_sint(i32::MAX), [0xFD, 0xFF, 0xFF, 0xFF, 0x1E]);
+    assert_eq!(write_sint(i32::MIN), [0x03, 0x01, 0x01, 0x01, 0x20]);
+}
+
+#[test]
+fn test_progress() {
+    let Exchange::Behavior(value) = load_blueprint::<
+        Value, Value, LoadError >(crate::test::EXCHANGE_BEHAVIOR_2)
+        .unwrap()
+    else { panic!("should be a behavior") };
+    let encoded = encode(value.clone()).unwrap();
+    let mut reports = Vec::new();
+    encode_with_progress( value, &DumpOptions::default(),
+        |progress| reports.push(progress) ).unwrap();
+    let last = reports.last().unwrap();
+    assert_eq!(last.bytes, encoded.len());
+    assert_eq!(last.total_bytes, None);
+    assert!(last.values > 0);
+}
+
+#[test]
+fn test_integer_width() {
+    let write = |value, integer_width| {
+        let mut dumper = Dumper::new(Vec::new());
+        dumper.integer_width = integer_width;
+        dumper.write_integer(value);
+        dumper.end()
+    };
+    for (value, game, minimal) in [
+        (-0x80, &[0xD1, 0x80, 0xFF][..], &[0xD0, 0x80][..]),
+        (-0x8000, &[0xD2, 0x00, 0x80, 0xFF, 0xFF], &[0xD1, 0x00, 0x80]),
+        (-0x7F, &[0xD0, 0x81], &[0xD0, 0x81]),
+    ] {
+        assert_eq!(write(value, IntegerWidth::MatchGame), game);
+        assert_eq!(write(value, IntegerWidth::Minimal), minimal);
+        let decoded = crate::loader::decode::<Value>( minimal,
+            crate::loader::DecodeOptions::default(),
+            &mut crate::loader::Warnings::new() ).unwrap();
+        assert_eq!(decoded, Some(Value::Integer(value)));
+    }
+}
+
+#[test]
+fn test_encode_raw() {
+    let exchange = load_blueprint::<Value, Value, LoadError>(
+        crate::test::EXCHANGE_BEHAVIOR_2 ).unwrap();
+    let body = encode_raw(exchange.clone(), &DumpOptions::default())
+        .unwrap();
+    let string = dump_blueprint(exchange.clone()).unwrap();
+    assert!(body.as_ref().unwrap().len() < string.len());
+    let decoded = crate::loader::decode_raw::<Value, Value>(
+        body.as_deref(), crate::loader::DecodeOptions::default(),
+        &mut crate::loader::Warnings::new() ).unwrap();
+    assert_eq!(decoded, exchange);
+}
+
+#[test]
+fn test_dump_to() {
+    let exchange = load_blueprint::<Value, Value, LoadError>(
+        crate::test::EXCHANGE_BEHAVIOR_2 ).unwrap();
+    for compression in [Compression::Auto, Compression::Never] {
+        let options = DumpOptions { compression, ..DumpOptions::default() };
+        let expected = dump_blueprint_with(exchange.clone(), &options)
+            .unwrap();
+        let mut bytes = Vec::new();
+        dump_blueprint_to(exchange.clone(), &options, &mut bytes).unwrap();
+        assert_eq!(bytes, expected.as_bytes());
+        let mut string = String::new();
+        dump_blueprint_to_fmt(exchange.clone(), &options, &mut string)
+            .unwrap();
+        assert_eq!(string, expected);
+    }
+    let mut full = [0_u8; 16];
+    dump_blueprint_to(exchange, &DumpOptions::default(), &mut full[..])
+        .unwrap_err();
+}
+
+#[test]
+fn test_compression_level() {
+    let exchange = load_blueprint::<Value, Value, LoadError>(
+        crate::test::EXCHANGE_BEHAVIOR_2 ).unwrap();
+    let dump = |compression_level| dump_blueprint_with( exchange.clone(),
+        &DumpOptions {
+            compression: Compression::Always, compression_level,
+            ..DumpOptions::default() },
+    ).unwrap();
+    let (fastest, smallest) = (dump(0), dump(9));
+    assert!(fastest.len() > smallest.len(), "{COMPRESSION_BACKEND}");
+    for string in [fastest, smallest, dump(100)] {
+        assert_eq!( load_blueprint::<Value, Value, LoadError>(&string)
+            .unwrap(), exchange );
+    }
+}
+
+#[test]
+fn test_dyn_dump() {
+    use crate::dump::DynDump;
+    use super::dump_blueprint_with_progress;
+    let exchange = load_blueprint::<Value, Value, LoadError>(
+        crate::test::EXCHANGE_BEHAVIOR_2 ).unwrap();
+    let Exchange::Behavior(Some(value)) = exchange
+        else { panic!("should be a behavior") };
+    let values: [Box<dyn DynDump + '_>; 2] =
+        [Box::new(value.clone()), Box::new(&value)];
+    let mut progress = None;
+    let expected = dump_blueprint_with_progress(
+        Exchange::<Option<Value>, _>::Behavior(Some(&value)),
+        &DumpOptions::default(), |report| progress = Some(report) )
+        .unwrap();
+    for value in values {
+        let mut dyn_progress = None;
+        let dumped = dump_blueprint_with_progress(
+            Exchange::<Option<Value>, _>::Behavior(Some(value)),
+            &DumpOptions::default(), |report| dyn_progress = Some(report) )
+            .unwrap();
+        assert_eq!(dumped, expected);
+        assert_eq!(dyn_progress, progress);
+    }
+}
+
+}
@@ -13,28 +13,93 @@ macro_rules! error_from_error {
 #[derive(Debug, Error)]
 #[error("Load error: {reason}")]
 pub struct LoadError {
+    kind: LoadErrorKind,
     reason: String,
 }
 
+/// What stage of loading has failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LoadErrorKind {
+    /// The string does not start with a `DSB` or `DSC` header
+    /// followed by an encoded length.
+    BadPrefix,
+    /// The string contains a character outside of the base62 alphabet
+    /// at the given byte position.
+    BadBase62Char { index: usize },
+    /// The encoded data cannot be split into words
+    /// to compute its checksum;
+    /// usually this means that the string was truncated
+    /// or otherwise damaged.
+    BadChecksum,
+    /// The checksum digit (the last character of the string)
+    /// does not match the encoded data.
+    /// Both digits are base62 values in `0 .. 62`.
+    Checksum { expected: u8, found: u8 },
+    /// The compressed data could not be unpacked.
+    ZlibError,
+    /// The binary data ended in the middle of a value.
+    Eof,
+    /// A byte of the binary data that cannot appear there,
+    /// at the given position of the data
+    /// (after base62 decoding and decompression).
+    UnexpectedByte { offset: usize, byte: u8 },
+    /// A string is not valid UTF-8.
+    Utf8,
+    /// A table header declares an unsupported size,
+    /// or one that the rest of the data cannot hold.
+    TableSize,
+    /// The data was decoded, but does not represent a valid value
+    /// (or a valid blueprint); the reason tells what is wrong.
+    /// Errors made from strings have this kind.
+    ValueError,
+    /// Tables are nested deeper than allowed,
+    /// see [`DecodeOptions::max_depth`].
+    ///
+    /// [`DecodeOptions::max_depth`]: crate::loader::DecodeOptions::max_depth
+    TooDeep,
+}
+
+impl LoadError {
+    pub(crate) fn new(kind: LoadErrorKind, reason: impl Into<String>)
+    -> Self
+    {
+        Self { kind, reason: reason.into() }
+    }
+    #[must_use]
+    pub(crate) fn with_kind(self, kind: LoadErrorKind) -> Self {
+        Self { kind, ..self }
+    }
+    #[must_use]
+    pub fn kind(&self) -> LoadErrorKind {
+        self.kind
+    }
+}
+
 impl crate::load::Error for LoadError {}
 
 impl From<&str> for LoadError {
     fn from(reason: &str) -> Self {
-        Self { reason: String::from(reason) }
+        Self::new(LoadErrorKind::ValueError, reason)
     }
 }
 
 impl From<String> for LoadError {
     fn from(reason: String) -> Self {
-        Self { reason }
+        Self::new(LoadErrorKind::ValueError, reason)
     }
 }
 
 error_from_error!(LoadError: <- crate::common::ascii::AsciiError);
 error_from_error!(LoadError: <- crate::common::intlim::IntLimError);
-error_from_error!(LoadError: <- std::str::Utf8Error);
 error_from_error!(LoadError: <- std::io::Error);
 
+impl From<std::str::Utf8Error> for LoadError {
+    fn from(value: std::str::Utf8Error) -> Self {
+        Self::new(LoadErrorKind::Utf8, value.to_string())
+    }
+}
+
 
 #[derive(Debug, Error)]
 #[error("Dump error: {reason}")]
@@ -56,3 +121,7 @@ impl From<String> for DumpError {
     }
 }
 
+error_from_error!(DumpError: <- std::io::Error);
+error_from_error!(DumpError: <- std::fmt::Error);
+error_from_error!(DumpError: <- crate::blueprint::LabelError);
+
@@ -0,0 +1,679 @@
+//! Operations on exchange strings that leave the encoded value intact,
+//! or change only parts of it.
+
+use thiserror::Error;
+
+use crate::{
+    Exchange, Str,
+    common::u32_to_usize,
+    error::{LoadError, DumpError},
+    value::{Key, Value, path::{Path, PathError}},
+    dumper::{DumpOptions, Compression, encode, compress::compress},
+    loader::{
+        decompress::{decompress, decompress_limited}, decode, item_range,
+        trace::trace,
+        DecodeOptions, Limits, Warnings,
+    },
+    header,
+};
+
+/// Revision of the binary format written by the crate.
+///
+/// Incremented whenever the dumper starts encoding the same value
+/// differently (e.g. to follow a change in the game).
+/// Strings written under earlier revisions still load.
+///
+/// The revision is not recorded in exchange strings,
+/// so it cannot be read back from one;
+/// it names the golden files of the compatibility tests.
+pub const FORMAT_REVISION: u32 = 1;
+
+/// Whether the header at the start of the exchange string
+/// is syntactically valid.
+///
+/// Only the header is read (see [`header::parse`]).
+/// This is a syntax check and nothing more:
+/// exchange strings carry no version marker,
+/// so a string made by a newer game version passes as long as
+/// its header does, and fails later if its data is not understood.
+#[must_use]
+pub fn is_supported(header: &str) -> bool {
+    header::parse(header).is_ok()
+}
+
+/// Whether the exchange string is a blueprint or a behavior.
+///
+/// Only the header is read (see [`header::parse`],
+/// which also tells whether the data is compressed and its length),
+/// so the rest of the string may still turn out to be invalid.
+pub fn kind(exchange: &str) -> Result<Exchange<()>, LoadError> {
+    let (header, _) = header::parse(exchange)?;
+    Ok(header.kind)
+}
+
+/// Decode the base62 and zlib layers of the exchange string
+/// and encode the same binary data again with the given options.
+///
+/// The encoded Lua value is not examined at all,
+/// so this works for any string that passes the checksum.
+/// Useful for fitting a string into a length limit
+/// (or for making it faster to load).
+pub fn recompress(input: &str, options: &DumpOptions)
+-> Result<String, LoadError>
+{
+    let data = decompress(input, true)?;
+    Ok(compress(data.as_deref(), options))
+}
+
+/// A change to the encoded value for [`patch`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Edit {
+    /// Keys leading from the outermost table to the item,
+    /// as they are encoded (e.g. `[Key::Index(3), Key::from("op")]`
+    /// for the operation of the third instruction of a behavior).
+    pub path: Vec<Key>,
+    /// `None` removes the item.
+    pub value: Option<Value>,
+}
+
+impl Edit {
+    #[must_use]
+    pub fn set(path: Vec<Key>, value: Value) -> Self {
+        Self { path, value: Some(value) }
+    }
+    #[must_use]
+    pub fn remove(path: Vec<Key>) -> Self {
+        Self { path, value: None }
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PatchError {
+    #[error(transparent)]
+    Load(#[from] LoadError),
+    #[error(transparent)]
+    Dump(#[from] DumpError),
+    #[error("Edit path is empty")]
+    EmptyPath,
+    /// The item reached by the first `depth` keys of the path
+    /// is not a table.
+    #[error("Item on the edit path at depth {depth} is not a table")]
+    NotTable { depth: usize },
+}
+
+/// Apply the edits to the value encoded in the exchange string,
+/// in order.
+///
+/// The value is not decoded as a whole.
+/// Setting an item that already exists replaces just its encoding;
+/// otherwise the innermost table on the path that contains the item
+/// is encoded anew, along with the tables nested in it
+/// (and missing tables on the path are created).
+/// The rest of the encoded data stays byte-for-byte intact.
+pub fn patch(original: &str, edits: &[Edit]) -> Result<String, PatchError> {
+    let (data, compressed) = {
+        let (header, _) = header::parse(original)?;
+        (decompress(original, true)?, header.data_len.is_some())
+    };
+    let kind = data.as_ref().map_mono(|_| ());
+    let mut data = data.unwrap();
+    for edit in edits {
+        data = patch_data(&data, edit)?;
+    }
+    let mut options = DumpOptions::default();
+    if !compressed {
+        options.compression = Compression::Never;
+    }
+    Ok(compress(kind.with_value(data.as_slice()), &options))
+}
+
+fn patch_data(data: &[u8], edit: &Edit) -> Result<Vec<u8>, PatchError> {
+    let Some((_, table_path)) = edit.path.split_last() else {
+        return Err(PatchError::EmptyPath);
+    };
+    // descend through the tables on the path that are present
+    let mut table_range = 0 .. data.len();
+    let mut depth = 0;
+    for key in &edit.path {
+        let Some(range) = item_range(&data[table_range.clone()], key)
+            .map_err(|_error| PatchError::NotTable { depth })?
+        else {
+            if edit.value.is_none() {
+                // nothing to remove
+                return Ok(data.to_vec());
+            }
+            break;
+        };
+        if depth == table_path.len() {
+            // the item itself is present
+            let Some(ref value) = edit.value else { break };
+            let range = table_range.start + range.start
+                .. table_range.start + range.end;
+            return Ok(splice(data, range, &encode(Some(value.clone()))?));
+        }
+        table_range = table_range.start + range.start
+            .. table_range.start + range.end;
+        depth += 1;
+    }
+    let Some(mut table) = decode::<Value>(
+        &data[table_range.clone()],
+        DecodeOptions::default(), &mut Warnings::new(),
+    )?.filter(|value| matches!(value, Value::Table(_))) else {
+        return Err(PatchError::NotTable { depth });
+    };
+    // the rest of the path is missing, so it leads through new tables
+    let path = Path::from(edit.path[depth..].to_vec());
+    let _old = match edit.value {
+        Some(ref value) => path.set(&mut table, value.clone()),
+        None => path.remove(&mut table),
+    }.map_err(|error| match error {
+        PathError::NotTable { depth: subdepth } =>
+            PatchError::NotTable { depth: depth + subdepth },
+        PathError::Syntax { .. } | PathError::Empty => unreachable!(),
+    })?;
+    Ok(splice(data, table_range, &encode(Some(table))?))
+}
+
+fn splice(data: &[u8], range: std::ops::Range<usize>, encoded: &[u8])
+-> Vec<u8>
+{
+    let mut output =
+        Vec::with_capacity(data.len() - range.len() + encoded.len());
+    output.extend_from_slice(&data[.. range.start]);
+    output.extend_from_slice(encoded);
+    output.extend_from_slice(&data[range.end ..]);
+    output
+}
+
+/// Split the exchange string into parts of at most `max_len` characters
+/// for pasting into chats that truncate long messages.
+///
+/// Each part starts with a frame `[i/n:cccc]`, where `i` is the
+/// 1-based part index, `n` the number of parts and `cccc`
+/// a hexadecimal checksum of the whole string; [`join`] reverses this.
+/// A string that already fits is returned as the only part, unframed.
+///
+/// # Panics
+///
+/// If `max_len` does not leave room for at least one character
+/// after the frame.
+#[must_use]
+pub fn split(exchange: &str, max_len: usize) -> Vec<String> {
+    let chars: Vec<char> = exchange.chars().collect();
+    if chars.len() <= max_len {
+        return vec![exchange.to_owned()];
+    }
+    let checksum = part_checksum(exchange);
+    // the frame grows with the number of digits in the part count
+    let mut count_digits = 1;
+    let chunk_len = loop {
+        let frame_len = format!("[/:{checksum:04x}]").len()
+            + 2 * count_digits;
+        let Some(chunk_len) = max_len.checked_sub(frame_len)
+            .filter(|&len| len > 0) else {
+            panic!("maximum part length {max_len} is too small");
+        };
+        if chars.len().div_ceil(chunk_len).to_string().len()
+            <= count_digits
+        {
+            break chunk_len;
+        }
+        count_digits += 1;
+    };
+    let count = chars.len().div_ceil(chunk_len);
+    chars.chunks(chunk_len).enumerate().map(|(index, chunk)| {
+        let mut part = format!("[{}/{count}:{checksum:04x}]", index + 1);
+        part.extend(chunk);
+        part
+    }).collect()
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum JoinError {
+    #[error("No parts to join")]
+    Empty,
+    #[error("Part {position} does not start with a valid frame")]
+    Frame { position: usize },
+    #[error("Parts do not belong to the same exchange string")]
+    Mismatch,
+    #[error("Part {index} is missing")]
+    Missing { index: usize },
+    #[error("Part {index} is given twice")]
+    Duplicate { index: usize },
+    #[error("Joined string does not match the checksum")]
+    Checksum,
+}
+
+/// Join the parts made by [`split`] back into the exchange string.
+///
+/// The parts may come in any order, and whitespace around them
+/// is ignored.
+/// A single part without a frame is returned as is.
+pub fn join(parts: &[&str]) -> Result<String, JoinError> {
+    let parts: Vec<&str> = parts.iter().map(|part| part.trim()).collect();
+    match *parts.as_slice() {
+        [] => return Err(JoinError::Empty),
+        [part] if !part.starts_with('[') => return Ok(part.to_owned()),
+        _ => (),
+    }
+    let mut frame = None;
+    let mut given = Vec::with_capacity(parts.len());
+    for (position, part) in parts.iter().enumerate() {
+        let Some((index, count, checksum, chunk)) = parse_frame(part)
+        else {
+            return Err(JoinError::Frame { position });
+        };
+        if *frame.get_or_insert((count, checksum)) != (count, checksum) {
+            return Err(JoinError::Mismatch);
+        }
+        given.push((index, chunk));
+    }
+    let count = frame.map_or(0, |(count, _)| count);
+    // the count is not to be trusted; with more parts declared
+    // than given, one of the first `parts.len() + 1` is missing
+    let mut chunks = vec![None; count.min(parts.len() + 1)];
+    for (index, chunk) in given {
+        let Some(slot) = chunks.get_mut(index - 1) else { continue };
+        if slot.replace(chunk).is_some() {
+            return Err(JoinError::Duplicate { index });
+        }
+    }
+    let mut exchange = String::new();
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let Some(chunk) = chunk else {
+            return Err(JoinError::Missing { index: index + 1 });
+        };
+        exchange.push_str(chunk);
+    }
+    match frame {
+        Some((_, checksum)) if checksum == part_checksum(&exchange) =>
+            Ok(exchange),
+        _ => Err(JoinError::Checksum),
+    }
+}
+
+/// Parse `[i/n:cccc]chunk` into `(i, n, cccc, chunk)`,
+/// checking that `1 <= i <= n`.
+fn parse_frame(part: &str) -> Option<(usize, usize, u16, &str)> {
+    let (frame, chunk) = part.strip_prefix('[')?.split_once(']')?;
+    let (index, frame) = frame.split_once('/')?;
+    let (count, checksum) = frame.split_once(':')?;
+    let index: usize = index.parse().ok()?;
+    let count: usize = count.parse().ok()?;
+    if checksum.len() != 4 || !(1 ..= count).contains(&index) {
+        return None;
+    }
+    let checksum = u16::from_str_radix(checksum, 16).ok()?;
+    Some((index, count, checksum, chunk))
+}
+
+/// Fletcher-16 checksum of the string.
+fn part_checksum(exchange: &str) -> u16 {
+    let (sum1, sum2) = exchange.bytes().fold((0_u16, 0_u16),
+        |(sum1, sum2), byte| {
+            let sum1 = (sum1 + u16::from(byte)) % 255;
+            (sum1, (sum2 + sum1) % 255)
+        } );
+    (sum2 << 8) | sum1
+}
+
+/// The binary data of an exchange string, token by token,
+/// as traced by [`inspect`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct InspectReport {
+    pub kind: Exchange<()>,
+    pub compressed: bool,
+    /// Length of the binary data
+    /// (after base62 decoding and decompression).
+    pub data_len: usize,
+    /// Tokens in the order they are encoded.
+    pub tokens: Vec<Token>,
+    /// Number of bytes after the encoded value;
+    /// the loader ignores them.
+    pub trailing_len: usize,
+    /// The error that stopped the trace, right after the last token.
+    pub error: Option<LoadError>,
+}
+
+/// A piece of the binary data with its meaning.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Token {
+    /// Position of the token in the binary data.
+    pub offset: usize,
+    pub len: usize,
+    /// The first byte of the token
+    /// (which tells the type of values and keys).
+    pub byte: u8,
+    /// Nesting level of the table the token belongs to
+    /// (zero for the outermost value).
+    pub depth: u32,
+    pub kind: TokenKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TokenKind {
+    Nil,
+    Boolean(bool),
+    Integer(i32),
+    Float(f64),
+    String(Str),
+    /// Table header; the items follow one level deeper.
+    Table { array_len: u32, assoc_len: u32, last_free: u32 },
+    /// Bits of the next eight slots (lowest first), set for nil items
+    /// of the array part and free slots of the assoc part.
+    Mask(u8),
+    /// Key of an assoc slot, following its value.
+    Key(Key),
+    /// Key marker of a dead assoc slot.
+    DeadKey,
+    /// Link to the next slot in the chain, following the key.
+    Link(i32),
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Nil => write!(f, "nil"),
+            Self::Boolean(value) => write!(f, "boolean {value}"),
+            Self::Integer(value) => write!(f, "integer {value}"),
+            Self::Float(value) => write!(f, "float {value:?}"),
+            Self::String(ref value) => write!(f, "string {:?}", &**value),
+            Self::Table { array_len, assoc_len, last_free } => write!( f,
+                "table: array {array_len}, assoc {assoc_len}, \
+                 last free {last_free}" ),
+            Self::Mask(mask) => write!(f, "mask {mask:08b}"),
+            Self::Key(ref key) => write!(f, "key {key:?}"),
+            Self::DeadKey => write!(f, "dead key"),
+            Self::Link(link) => write!(f, "link {link:+}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let indent = 2 * u32_to_usize(self.depth);
+        write!( f, "{:06X}  {:02X}  {:indent$}{}",
+            self.offset, self.byte, "", self.kind )
+    }
+}
+
+impl std::fmt::Display for InspectReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for token in &self.tokens {
+            writeln!(f, "{token}")?;
+        }
+        if self.trailing_len > 0 {
+            writeln!(f, "{} trailing bytes", self.trailing_len)?;
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Trace the binary data of the exchange string token by token,
+/// with the position, the first byte and the meaning of each token.
+///
+/// Only the layers of the string itself (the header, base62,
+/// the checksum and compression) can fail this;
+/// the trace of the binary data stops at the first error,
+/// which is put into the report.
+/// Useful for finding out why the game rejects a string
+/// that loads fine, or why one does not load.
+///
+/// The limits apply as in [`verify`](crate::loader::verify);
+/// exceeding [`Limits::max_depth`] stops the trace.
+pub fn inspect(exchange: &str, limits: Limits)
+-> Result<InspectReport, LoadError>
+{
+    if exchange.len() > limits.max_exchange_len {
+        return Err(LoadError::from(
+            "exchange string exceeds the length limit" ));
+    }
+    let (data, compressed) =
+        decompress_limited(exchange, limits.max_data_len, true)?;
+    let kind = data.as_ref().map_mono(|_| ());
+    let data = data.unwrap();
+    let mut tokens = Vec::new();
+    let (trailing_len, error) =
+        match trace(&data, limits.max_depth, &mut tokens)
+    {
+        Ok(trailing_len) => (trailing_len, None),
+        Err(error) => (0, Some(error)),
+    };
+    Ok(InspectReport {
+        kind, compressed, data_len: data.len(),
+        tokens, trailing_len, error,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    value::{Key, Value, Table},
+    dumper::{DumpOptions, Compression},
+    loader::{verify, Limits, load_blueprint as load_value},
+    blueprint::load_blueprint,
+    error::LoadError,
+};
+
+use super::{
+    kind, is_supported, recompress, patch, Edit, PatchError,
+    split, join, JoinError,
+    inspect, TokenKind,
+};
+
+#[test]
+fn test_recompress() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let mut options = DumpOptions::default();
+    options.compression = Compression::Never;
+    let uncompressed = recompress(exchange, &options).unwrap();
+    assert!(!verify(&uncompressed, Limits::default()).unwrap().compressed);
+    options.compression = Compression::Auto;
+    let compressed = recompress(&uncompressed, &options).unwrap();
+    assert!(compressed.len() < uncompressed.len());
+    options.compression_level = 0;
+    options.compression = Compression::Always;
+    let stored = recompress(exchange, &options).unwrap();
+    assert!(verify(&stored, Limits::default()).unwrap().compressed);
+    let original = load_blueprint(exchange).unwrap();
+    for recompressed in [&uncompressed, &compressed, &stored] {
+        assert_eq!( format!("{:?}", load_blueprint(recompressed).unwrap()),
+            format!("{original:?}") );
+    }
+    recompress("DSB0garbage", &options).unwrap_err();
+}
+
+
+#[test]
+fn test_chunked_compression() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let original = load_blueprint(exchange).unwrap();
+    let mut options = DumpOptions::default();
+    options.compression = Compression::Always;
+    let whole = recompress(exchange, &options).unwrap();
+    for chunk_size in [1, 7, 64, 1000] {
+        options.compression_chunk_size = Some(chunk_size);
+        let chunked = recompress(exchange, &options).unwrap();
+        assert!(verify(&chunked, Limits::default()).unwrap().compressed);
+        assert_eq!( format!("{:?}", load_blueprint(&chunked).unwrap()),
+            format!("{original:?}") );
+        if chunk_size == 1000 {
+            assert!(chunked.len() < whole.len() * 2);
+        }
+    }
+}
+
+
+#[test]
+fn test_kind() {
+    assert_eq!( kind(crate::test::EXCHANGE_BEHAVIOR_2).unwrap(),
+        crate::Exchange::Behavior(()) );
+    assert_eq!(kind("DSBV").unwrap(), crate::Exchange::Blueprint(()));
+    assert_eq!( kind("DSB0garbage").unwrap(), crate::Exchange::Blueprint(()) );
+    kind("DSX0").unwrap_err();
+}
+
+
+#[test]
+fn test_is_supported() {
+    assert!(is_supported(crate::test::EXCHANGE_BEHAVIOR_2));
+    assert!(is_supported("DSBV"));
+    for unsupported in ["DSX0", "DSD1abc", "DSB", "", "garbage"] {
+        assert!(!is_supported(unsupported));
+    }
+}
+
+
+#[test]
+fn test_patch() {
+    fn data(exchange: &str) -> Vec<u8> {
+        crate::loader::decompress::decompress(exchange, true).unwrap()
+            .unwrap()
+    }
+    fn value(exchange: &str) -> Table {
+        let Exchange::Behavior(Some(Value::Table(table))) =
+            load_value::<Value, Value, LoadError>(exchange).unwrap()
+            else { panic!("should be a behavior table") };
+        table
+    }
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let original = value(exchange);
+    let Some(Value::Table(instruction)) = original.get(&Key::Index(2))
+        else { panic!("should be an instruction table") };
+    let op = Key::from("op");
+    // replacing an item with itself keeps the data intact
+    let same = patch(exchange, &[
+        Edit::set(vec![Key::Index(2), op.clone()],
+            instruction.get(&op).unwrap().clone() ),
+    ]).unwrap();
+    assert_eq!(data(&same), data(exchange));
+    let patched = patch(exchange, &[
+        Edit::set(vec![Key::Index(2), op.clone()],
+            Value::String("nop".into()) ),
+        Edit::set( vec![Key::Index(3), Key::from("new"), Key::Index(1)],
+            Value::Integer(5) ),
+        Edit::remove(vec![Key::Index(1), Key::from("absent")]),
+        Edit::remove(vec![Key::Index(3), op.clone()]),
+    ]).unwrap();
+    let mut expected = original.clone();
+    let Some(Value::Table(instruction)) = expected.get_mut(&Key::Index(2))
+        else { unreachable!() };
+    instruction.insert(op.clone(), Value::String("nop".into()));
+    let Some(Value::Table(instruction)) = expected.get_mut(&Key::Index(3))
+        else { unreachable!() };
+    instruction.insert( Key::from("new"),
+        Value::Table(Table::from_iter([(Key::Index(1), Value::Integer(5))])) );
+    instruction.remove(&op);
+    assert_eq!(value(&patched), expected);
+    // instructions that were not edited are encoded as before
+    let ranges = crate::loader::array_item_ranges(&data(exchange)).unwrap();
+    let patched_ranges =
+        crate::loader::array_item_ranges(&data(&patched)).unwrap();
+    assert_eq!(
+        data(exchange)[ranges[0].clone().unwrap()],
+        data(&patched)[patched_ranges[0].clone().unwrap()] );
+    assert!(matches!(
+        patch(exchange, &[Edit::set(
+            vec![Key::Index(2), op.clone(), Key::Index(1)],
+            Value::Integer(1) )]),
+        Err(PatchError::NotTable { depth: 2 }) ));
+    assert!(matches!(
+        patch(exchange, &[Edit::remove(vec![])]),
+        Err(PatchError::EmptyPath) ));
+}
+
+
+#[test]
+fn test_split_join() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    assert_eq!(split(exchange, exchange.len()), [exchange]);
+    assert_eq!(join(&[exchange]).unwrap(), exchange);
+    for max_len in [24, 40, 200] {
+        let parts = split(exchange, max_len);
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| part.len() <= max_len));
+        let mut parts: Vec<&str> = parts.iter().map(String::as_str)
+            .collect();
+        assert_eq!(join(&parts).unwrap(), exchange);
+        parts.reverse();
+        assert_eq!(join(&parts).unwrap(), exchange);
+        let last = parts.remove(0);
+        assert!(matches!( join(&parts),
+            Err(JoinError::Missing { .. }) ));
+        parts.push(parts[0]);
+        assert!(matches!( join(&parts),
+            Err(JoinError::Duplicate { .. }) ));
+        parts.pop();
+        parts.push(last);
+        parts.push(" [1/1:0000]DSB0 ");
+        assert!(matches!(join(&parts), Err(JoinError::Mismatch)));
+    }
+    let parts = split(exchange, 40);
+    let corrupted = parts[1].replacen(']', "]x", 1);
+    let mut parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+    parts[1] = &corrupted;
+    assert!(matches!(join(&parts), Err(JoinError::Checksum)));
+    assert!(matches!( join(&["[1/2:0000]", "garbage"]),
+        Err(JoinError::Frame { position: 1 }) ));
+    assert!(matches!(join(&[]), Err(JoinError::Empty)));
+    assert!(matches!( join(&["[1/999999999:0000]x"]),
+        Err(JoinError::Missing { index: 2 }) ));
+}
+
+#[test]
+fn test_inspect() {
+    let report = inspect(crate::test::EXCHANGE_BEHAVIOR_2, Limits::default()).unwrap();
+    assert_eq!(report.kind, Exchange::Behavior(()));
+    assert!(report.error.is_none());
+    assert_eq!(report.trailing_len, 0);
+    assert!(matches!(report.tokens[0].kind, TokenKind::Table { .. }));
+    // the tokens cover the data without gaps
+    let mut offset = 0;
+    for token in &report.tokens {
+        assert_eq!(token.offset, offset);
+        offset += token.len;
+    }
+    assert_eq!(offset, report.data_len);
+    assert!(report.tokens.iter().any(|token|
+        token.depth == 1 && token.kind == TokenKind::Key(Key::from("name"))
+    ));
+    assert!(report.to_string().contains(r#"string "Test Behavior 2""#));
+
+    // a table with a single array item, followed by an unknown byte
+    let data: &[u8] = &[0x91, 0x00, 0x0A, 0xC1];
+    let exchange = crate::dumper::compress::compress(
+        Exchange::Blueprint(data), &DumpOptions::default() );
+    let report = inspect(&exchange, Limits::default()).unwrap();
+    assert_eq!(report.trailing_len, 1);
+    let data: &[u8] = &[0x92, 0x00, 0x0A, 0xC1];
+    let exchange = crate::dumper::compress::compress(
+        Exchange::Blueprint(data), &DumpOptions::default() );
+    let report = inspect(&exchange, Limits::default()).unwrap();
+    assert!(report.error.is_some());
+    assert_eq!( report.tokens.iter().map(|token| &token.kind)
+        .collect::<Vec<_>>(), [
+            &TokenKind::Table { array_len: 2, assoc_len: 0, last_free: 0 },
+            &TokenKind::Mask(0),
+            &TokenKind::Integer(10),
+        ] );
+    inspect("DSB0garbage", Limits::default()).unwrap_err();
+    let limits = Limits { max_data_len: 3, ..Limits::default() };
+    inspect(&exchange, limits).unwrap_err();
+    let limits = Limits { max_depth: 0, ..Limits::default() };
+    let report = inspect(&exchange, limits).unwrap();
+    assert!(report.error.is_some());
+    assert_eq!(report.tokens.len(), 1);
+}
+
+}
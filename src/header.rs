@@ -0,0 +1,115 @@
+//! The prefix of an exchange string.
+//!
+//! An exchange string starts with `DSB` (a blueprint) or `DSC`
+//! (a behavior), followed by a base31-encoded length of the data
+//! before compression (zero if the data is not compressed).
+//! The prefix can be read without decoding the rest of the string,
+//! e.g. to tell blueprints from behaviors.
+
+use crate::{
+    Exchange,
+    error::{LoadError as Error, LoadErrorKind as ErrorKind},
+    common::{ascii::{Ascii, AsciiStr}, intlim::Int31},
+    loader::decompress::read_len_base31,
+    dumper::compress::encode_base31,
+};
+
+pub(crate) const PREFIX_LEN: usize = 3;
+
+const BLUEPRINT_PREFIX: &str = "DSB";
+const BEHAVIOR_PREFIX: &str = "DSC";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Header {
+    pub kind: Exchange<()>,
+    /// Length of the data before compression,
+    /// or `None` if the data is not compressed.
+    pub data_len: Option<usize>,
+}
+
+impl Header {
+    #[must_use]
+    pub fn new(kind: Exchange<()>, data_len: Option<usize>) -> Self {
+        Self { kind, data_len }
+    }
+}
+
+pub(crate) fn parse_kind(exchange: &str) -> Result<Exchange<()>, Error> {
+    Ok(match exchange.get(..PREFIX_LEN) {
+        Some(BLUEPRINT_PREFIX) => Exchange::Blueprint(()),
+        Some(BEHAVIOR_PREFIX) => Exchange::Behavior(()),
+        _ => return Err(Error::new( ErrorKind::BadPrefix,
+            "unrecognized blueprint header" )),
+    })
+}
+
+/// Read the header, returning it along with the rest of the string
+/// (the encoded data and the checksum, which are not checked).
+pub fn parse(exchange: &str) -> Result<(Header, &str), Error> {
+    const MAX_DIGITS: usize = Int31::u32_sufficient_digits();
+    let kind = parse_kind(exchange)?;
+    let (_, rest) = exchange.split_at(PREFIX_LEN);
+    let field_len = rest.bytes().take(MAX_DIGITS)
+        .take_while(u8::is_ascii_alphanumeric)
+        .count();
+    let mut field: &[Ascii] =
+        <&AsciiStr>::try_from(&rest.as_bytes()[..field_len])?;
+    let data_len = read_len_base31(&mut field)
+        .map_err(|error| error.with_kind(ErrorKind::BadPrefix))?;
+    let (_, rest) = rest.split_at(field_len - field.len());
+    let data_len = if data_len == 0 { None } else { Some(data_len) };
+    Ok((Header { kind, data_len }, rest))
+}
+
+/// Write the header as it starts an exchange string.
+///
+/// # Panics
+///
+/// Panics if the data length does not fit into 32 bits.
+#[must_use]
+pub fn format(header: &Header) -> String {
+    let mut output = String::from(match header.kind {
+        Exchange::Blueprint(()) => BLUEPRINT_PREFIX,
+        Exchange::Behavior(()) => BEHAVIOR_PREFIX,
+    });
+    output.push_str(<&str>::from(<&AsciiStr>::from(
+        &*encode_base31(header.data_len.unwrap_or(0)) )));
+    output
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    error::LoadErrorKind,
+    loader::{verify, Limits},
+};
+
+use super::{parse, format, Header};
+
+#[test]
+fn test_header() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let (header, rest) = parse(exchange).unwrap();
+    let info = verify(exchange, Limits::default()).unwrap();
+    assert_eq!(header.kind, Exchange::Behavior(()));
+    assert_eq!(header.data_len, Some(info.data_len));
+    assert_eq!(format(&header) + rest, exchange);
+    for header in [
+        Header::new(Exchange::Blueprint(()), None),
+        Header::new(Exchange::Behavior(()), Some(1)),
+        Header::new(Exchange::Blueprint(()), Some(123_456)),
+    ] {
+        let formatted = format(&header);
+        assert_eq!(parse(&formatted).unwrap(), (header, ""));
+        assert_eq!(parse(&(formatted + "x\u{e9}")).unwrap().1, "x\u{e9}");
+    }
+    assert_eq!(format(&Header::new(Exchange::Blueprint(()), None)), "DSBV");
+    for bad in ["", "DSX1", "DSB", "DSB1", "DSB1\u{e9}", "DSB11111111111"] {
+        assert_eq!(parse(bad).unwrap_err().kind(), LoadErrorKind::BadPrefix);
+    }
+}
+
+}
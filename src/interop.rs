@@ -0,0 +1,403 @@
+//! Conversion of generic values from and to other data formats.
+//!
+//! Lua tables do not map onto JSON or RON values directly,
+//! and the following conventions are used:
+//! * a table with only an array part becomes a sequence,
+//!   with holes in the array becoming `null` (`None` in RON);
+//!   any other table becomes a map;
+//! * JSON object keys are strings, so keys that are
+//!   integers written in the canonical decimal form (`"1"`, `"-5"`)
+//!   are taken as integer keys;
+//!   RON map keys can be numbers, and floating point keys
+//!   with integer values are taken as integer keys;
+//! * `null` (`None` in RON) values in maps mean missing entries;
+//! * the exchange is a map with a single `"Blueprint"`
+//!   or `"Behavior"` key, the way `serde_json`
+//!   represents [`Exchange`].
+//!   RON values cannot hold enum variant names,
+//!   so the same map is used for RON;
+//! * [`Value::Bytes`] becomes a map with the single key
+//!   [`BYTES_TAG`](crate::value::BYTES_TAG) holding the bytes
+//!   in base64, the same way serde represents it.
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    value::{Value, Table},
+};
+
+const BLUEPRINT: &str = "Blueprint";
+const BEHAVIOR: &str = "Behavior";
+
+fn exchange_from_entry(tag: &str, value: Option<Value>)
+-> Result<Exchange<Value>, LoadError>
+{
+    let value = value.ok_or_else(|| LoadError::from(
+        "blueprint or behavior should not be represented with nil" ))?;
+    Ok(match tag {
+        BLUEPRINT => Exchange::Blueprint(value),
+        BEHAVIOR => Exchange::Behavior(value),
+        _ => return Err(LoadError::from(format!(
+            "exchange should be tagged as {BLUEPRINT:?} \
+             or {BEHAVIOR:?}, not {tag:?}" ))),
+    })
+}
+
+fn exchange_tag<V>(exchange: &Exchange<V>) -> &'static str {
+    match *exchange {
+        Exchange::Blueprint(_) => BLUEPRINT,
+        Exchange::Behavior(_) => BEHAVIOR,
+    }
+}
+
+fn err_exchange_shape() -> LoadError {
+    LoadError::from(
+        "exchange should be a map with a single blueprint or behavior" )
+}
+
+fn integer_from_i64(value: i64) -> Result<Value, LoadError> {
+    Ok(Value::Integer(i32::try_from(value).map_err(|_err| LoadError::from(
+        format!("integer {value} does not fit into 32 bits") ))?))
+}
+
+/// Table that has only the array part, with holes as `None`.
+fn table_as_array(table: &Table) -> Option<Vec<Option<&Value>>> {
+    if table.is_empty() || table.assoc_part().len() > 0 {
+        return None;
+    }
+    let mut array = Vec::with_capacity(table.array_part_len());
+    for (key, value) in table.array_part() {
+        let Some(Ok(index)) = key.as_index().map(usize::try_from) else {
+            unreachable!("array part keys should be positive indices");
+        };
+        array.resize(index - 1, None);
+        array.push(Some(value));
+    }
+    Some(array)
+}
+
+#[cfg(feature = "json")]
+pub use self::json::{from_json, to_json};
+
+#[cfg(feature = "json")]
+mod json {
+
+use serde_json::{Value as JsonValue, Map, Number};
+
+use crate::{
+    Exchange, Str,
+    error::{LoadError, DumpError},
+    value::{Key, Value, Table, ArrayBuilder, TableBuilder},
+};
+
+use super::{
+    exchange_from_entry, exchange_tag, err_exchange_shape,
+    integer_from_i64, table_as_array,
+};
+
+/// Convert a JSON value of the form `{"Blueprint": …}`
+/// or `{"Behavior": …}`.
+pub fn from_json(json: &JsonValue) -> Result<Exchange<Value>, LoadError> {
+    let JsonValue::Object(map) = json else {
+        return Err(err_exchange_shape());
+    };
+    let mut entries = map.iter();
+    let (Some((tag, value)), None) = (entries.next(), entries.next()) else {
+        return Err(err_exchange_shape());
+    };
+    exchange_from_entry(tag, value_from_json(value)?)
+}
+
+fn value_from_json(json: &JsonValue) -> Result<Option<Value>, LoadError> {
+    Ok(Some(match *json {
+        JsonValue::Null => return Ok(None),
+        JsonValue::Bool(value) => Value::Boolean(value),
+        JsonValue::Number(ref number) => match number.as_f64() {
+            Some(value) if number.is_f64() => Value::Float(value),
+            _ => integer_from_i64(number.as_i64().ok_or_else(||
+                LoadError::from(format!(
+                    "integer {number} does not fit into 32 bits" )))?)?,
+        },
+        JsonValue::String(ref value) => Value::String(Str::from(&**value)),
+        JsonValue::Array(ref items) => {
+            let mut builder = ArrayBuilder::new();
+            for item in items {
+                builder.push_option(value_from_json(item)?);
+            }
+            Value::Table(builder.build())
+        },
+        JsonValue::Object(ref map) => {
+            let mut builder = TableBuilder::new();
+            for (key, value) in map {
+                if let Some(value) = value_from_json(value)? {
+                    builder.insert(key_from_str(key), value);
+                }
+            }
+            Value::from_maybe_tagged(builder.build())
+        },
+    }))
+}
+
+/// Convert to a JSON value of the form `{"Blueprint": …}`
+/// or `{"Behavior": …}`.
+pub fn to_json(exchange: &Exchange<Value>) -> Result<JsonValue, DumpError> {
+    let mut map = Map::new();
+    map.insert( String::from(exchange_tag(exchange)),
+        value_to_json(exchange.as_ref().unwrap())? );
+    Ok(JsonValue::Object(map))
+}
+
+fn value_to_json(value: &Value) -> Result<JsonValue, DumpError> {
+    Ok(match *value {
+        Value::Boolean(value) => JsonValue::Bool(value),
+        Value::Integer(value) => JsonValue::Number(Number::from(value)),
+        Value::Float(value) => JsonValue::Number(Number::from_f64(value)
+            .ok_or_else(|| DumpError::from(format!(
+                "number {value} cannot be represented in JSON" )))?),
+        Value::String(ref value) => JsonValue::String(String::from(&**value)),
+        Value::Bytes(ref value) =>
+            table_to_json(&Value::bytes_to_tagged(value))?,
+        Value::Table(ref table) => table_to_json(table)?,
+    })
+}
+
+/// Integer key written in the canonical form, or a name.
+fn key_from_str(key: &str) -> Key {
+    match key.parse::<i32>() {
+        Ok(index) if index.to_string() == key => Key::Index(index),
+        _ => Key::from_maybe_known(key),
+    }
+}
+
+fn key_to_string(key: &Key) -> Result<String, DumpError> {
+    match *key {
+        Key::Index(index) => Ok(index.to_string()),
+        Key::Name(ref name) => {
+            if let Key::Index(_) = key_from_str(name) {
+                return Err(DumpError::from(format!(
+                    "string key {:?} would be read back as an integer",
+                    &**name )));
+            }
+            Ok(String::from(&**name))
+        },
+        Key::Bool(value) => Err(DumpError::from(format!(
+            "boolean key {value} cannot be represented in JSON" ))),
+    }
+}
+
+fn table_to_json(table: &Table) -> Result<JsonValue, DumpError> {
+    if let Some(array) = table_as_array(table) {
+        return Ok(JsonValue::Array( array.into_iter()
+            .map(|value| value.map_or(Ok(JsonValue::Null), value_to_json))
+            .collect::<Result<_, _>>()? ));
+    }
+    let mut map = Map::new();
+    for (key, value) in table {
+        map.insert(key_to_string(&key)?, value_to_json(value)?);
+    }
+    Ok(JsonValue::Object(map))
+}
+
+}
+
+#[cfg(feature = "ron")]
+pub use self::ron::{from_ron, to_ron};
+
+#[cfg(feature = "ron")]
+mod ron {
+
+use ron::{Value as RonValue, Map, Number, value::Float};
+
+use crate::{
+    Exchange, Str,
+    error::LoadError,
+    value::{Key, Value, Table, ArrayBuilder, TableBuilder},
+};
+
+use super::{
+    exchange_from_entry, exchange_tag, err_exchange_shape,
+    integer_from_i64, table_as_array,
+};
+
+/// Convert a RON value of the form `{"Blueprint": …}`
+/// or `{"Behavior": …}`.
+pub fn from_ron(ron: &RonValue) -> Result<Exchange<Value>, LoadError> {
+    let RonValue::Map(map) = ron else {
+        return Err(err_exchange_shape());
+    };
+    let mut entries = map.iter();
+    let (Some((RonValue::String(tag), value)), None) =
+        (entries.next(), entries.next()) else
+    {
+        return Err(err_exchange_shape());
+    };
+    exchange_from_entry(tag, value_from_ron(value)?)
+}
+
+fn value_from_ron(ron: &RonValue) -> Result<Option<Value>, LoadError> {
+    Ok(Some(match *ron {
+        RonValue::Option(None) => return Ok(None),
+        RonValue::Option(Some(ref value)) => return value_from_ron(value),
+        RonValue::Bool(value) => Value::Boolean(value),
+        RonValue::Number(Number::Integer(value)) => integer_from_i64(value)?,
+        RonValue::Number(Number::Float(value)) => Value::Float(value.get()),
+        RonValue::Char(value) =>
+            Value::String(Str::from(&*value.encode_utf8(&mut [0; 4]))),
+        RonValue::String(ref value) => Value::String(Str::from(&**value)),
+        RonValue::Unit => Value::Table(Table::new()),
+        RonValue::Seq(ref items) => {
+            let mut builder = ArrayBuilder::new();
+            for item in items {
+                builder.push_option(value_from_ron(item)?);
+            }
+            Value::Table(builder.build())
+        },
+        RonValue::Map(ref map) => {
+            let mut builder = TableBuilder::new();
+            for (key, value) in map.iter() {
+                if let Some(value) = value_from_ron(value)? {
+                    builder.insert(key_from_ron(key)?, value);
+                }
+            }
+            Value::from_maybe_tagged(builder.build())
+        },
+    }))
+}
+
+fn key_from_ron(key: &RonValue) -> Result<Key, LoadError> {
+    // an exact round trip through `i32` is the point
+    #![allow(clippy::cast_possible_truncation, clippy::float_cmp)]
+    Ok(match *key {
+        RonValue::Number(Number::Integer(index)) =>
+            Key::Index(i32::try_from(index).map_err(|_err| LoadError::from(
+                format!("integer key {index} does not fit into 32 bits") ))?),
+        RonValue::Number(Number::Float(index)) => {
+            let index = index.get();
+            let int_index = index as i32;
+            if f64::from(int_index) != index {
+                return Err(LoadError::from(format!(
+                    "floating point key {index} is not an integer" )));
+            }
+            Key::Index(int_index)
+        },
+        RonValue::String(ref name) => Key::from_maybe_known(name),
+        RonValue::Bool(value) => Key::Bool(value),
+        _ => return Err(LoadError::from(
+            "only integers, strings and booleans can serve as keys" )),
+    })
+}
+
+/// Convert to a RON value of the form `{"Blueprint": …}`
+/// or `{"Behavior": …}`.
+#[must_use]
+pub fn to_ron(exchange: &Exchange<Value>) -> RonValue {
+    let mut map = Map::new();
+    map.insert( RonValue::String(String::from(exchange_tag(exchange))),
+        value_to_ron(exchange.as_ref().unwrap()) );
+    RonValue::Map(map)
+}
+
+fn value_to_ron(value: &Value) -> RonValue {
+    match *value {
+        Value::Boolean(value) => RonValue::Bool(value),
+        Value::Integer(value) => RonValue::Number(Number::from(value)),
+        Value::Float(value) => RonValue::Number(Number::Float(Float::new(value))),
+        Value::String(ref value) => RonValue::String(String::from(&**value)),
+        Value::Bytes(ref value) =>
+            table_to_ron(&Value::bytes_to_tagged(value)),
+        Value::Table(ref table) => table_to_ron(table),
+    }
+}
+
+fn table_to_ron(table: &Table) -> RonValue {
+    if let Some(array) = table_as_array(table) {
+        return RonValue::Seq( array.into_iter()
+            .map(|value| value.map_or(RonValue::Option(None), value_to_ron))
+            .collect() );
+    }
+    RonValue::Map(table.iter().map(|(key, value)| (
+        match key {
+            Key::Index(index) => RonValue::Number(Number::from(index)),
+            Key::Name(name) => RonValue::String(String::from(&*name)),
+            Key::Bool(flag) => RonValue::Bool(flag),
+        },
+        value_to_ron(value),
+    )).collect())
+}
+
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{Exchange, value::{Key, Value, Table}};
+
+fn sample() -> Exchange<Value> {
+    Exchange::Blueprint(Value::Table(Table::from_iter([
+        (Key::from("name"), Value::String("x".into())),
+        (Key::Index(1), Value::Table(Table::from_iter([
+            (Key::Index(1), Value::Integer(3)),
+            (Key::Index(3), Value::Float(0.5)),
+        ]))),
+        (Key::Index(-1), Value::Boolean(true)),
+    ])))
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json() {
+    use super::{from_json, to_json};
+    let exchange = sample();
+    let json = to_json(&exchange).unwrap();
+    assert_eq!( serde_json::to_string(&json).unwrap(),
+        r#"{"Blueprint":{"-1":true,"1":[3,null,0.5],"name":"x"}}"# );
+    assert_eq!(from_json(&json).unwrap(), exchange);
+    let json: serde_json::Value = serde_json::from_str(
+        r#"{"Behavior":{"01":1,"2":null,"3":1.0}}"# ).unwrap();
+    assert_eq!(from_json(&json).unwrap(), Exchange::Behavior(Value::Table(
+        Table::from_iter([
+            (Key::from("01"), Value::Integer(1)),
+            (Key::Index(3), Value::Float(1.0)),
+        ]) )));
+    assert!(to_json(&Exchange::Behavior(Value::Table(Table::from_iter([
+        (Key::from("1"), Value::Integer(1)),
+    ])))).is_err());
+    assert!(from_json(&serde_json::json!({"Behavior": 1, "x": 2})).is_err());
+    assert!(from_json(&serde_json::json!({"Behavior": 1_i64 << 40}))
+        .is_err());
+    let bytes = Exchange::Behavior(Value::Bytes(vec![b'a', 0xFF]));
+    let json = to_json(&bytes).unwrap();
+    assert_eq!( json,
+        serde_json::json!({"Behavior": {"$bytes": "Yf8="}}) );
+    assert_eq!(from_json(&json).unwrap(), bytes);
+    assert_eq!( serde_json::to_value(bytes.as_ref().unwrap()).unwrap(),
+        json["Behavior"] );
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn test_ron() {
+    use super::{from_ron, to_ron};
+    let exchange = sample();
+    let value = to_ron(&exchange);
+    assert_eq!( ron::to_string(&value).unwrap(),
+        r#"{"Blueprint":{-1:true,1:[3,None,0.5],"name":"x"}}"# );
+    assert_eq!(from_ron(&value).unwrap(), exchange);
+    let value: ron::Value = ron::from_str(
+        r#"{"Behavior": {2.0: 1, 3: None, "4": ()}}"# ).unwrap();
+    assert_eq!(from_ron(&value).unwrap(), Exchange::Behavior(Value::Table(
+        Table::from_iter([
+            (Key::Index(2), Value::Integer(1)),
+            (Key::from("4"), Value::Table(Table::new())),
+        ]) )));
+    let value: ron::Value = ron::from_str(r#"{"Behavior": {2.5: 1}}"#)
+        .unwrap();
+    assert!(from_ron(&value).is_err());
+    let bytes = Exchange::Behavior(Value::Bytes(vec![b'a', 0xFF]));
+    let value = to_ron(&bytes);
+    assert_eq!( ron::to_string(&value).unwrap(),
+        r#"{"Behavior":{"$bytes":"Yf8="}}"# );
+    assert_eq!(from_ron(&value).unwrap(), bytes);
+}
+
+}
@@ -31,6 +31,45 @@
 //!
 //! Tables are Lua's associative arrays. Lua allows keys to be arbitrary
 //! values, but in blueprints keys are always integers and strings.
+//!
+//! ## Modules
+//!
+//! * [`blueprint`] — the structured representation
+//!   of blueprints and behaviors, the usual entry point;
+//! * [`value`] — the generic representation of Lua values,
+//!   with `#[derive(value::FromTable)]` converting tables
+//!   into plain structs (behind the `derive` feature);
+//! * [`loader`] and [`dumper`] — decoding and encoding
+//!   of exchange strings into and from any type
+//!   implementing [`load::Load`] and [`dump::Dump`];
+//! * [`load`] and [`dump`] — the traits themselves.
+//!   The traits on the format side ([`load::Loader`], [`dump::Dumper`]
+//!   and the like) are sealed: the encoding is only implemented
+//!   by this crate. [`dump::DynDump`] and [`load::DynLoad`]
+//!   allow dumping and loading through trait objects.
+//! * [`header`] — the prefix of exchange strings,
+//!   telling blueprints from behaviors without decoding them;
+//! * [`limits`] — what the encoding can hold,
+//!   for checking content before dumping it;
+//! * [`document`] — an exchange held as a string, a value
+//!   and a blueprint at once, each produced on demand;
+//! * `interop` — conversion of generic values from and to
+//!   `serde_json` and `ron` values
+//!   (behind the `json` and `ron` features);
+//! * `schema` — the JSON Schema of the serde representation
+//!   of exchanges (behind the `json` feature);
+//! * `lua` — conversion of generic values from and to
+//!   tables of a Lua runtime (behind the `mlua` feature);
+//! * `metrics` — timing of the phases of loading
+//!   (behind the `metrics` feature);
+//! * `blueprint::graph` — control flow graphs of behaviors
+//!   for Graphviz (behind the `graph` feature);
+//! * `capi` — C interface exchanging blueprints as JSON
+//!   (behind the `capi` feature).
+//!
+//! The serde representation of values and of the blueprint model,
+//! along with `de` and `ser` (serde data formats over generic values),
+//! is behind the `serde` feature, which is on by default.
 
 // LINTS: useful
 #![warn(unused_unsafe)]
@@ -75,7 +114,6 @@
 #![warn(clippy::many_single_char_names)]
 #![warn(clippy::map_unwrap_or)]
 #![warn(clippy::match_bool)]
-#![warn(clippy::match_on_vec_items)]
 #![warn(clippy::match_same_arms)]
 #![warn(clippy::match_wild_err_arm)]
 #![warn(clippy::match_wildcard_for_single_variants)]
@@ -137,7 +175,7 @@
 #![warn(clippy::disallowed_script_idents)]
 #![warn(clippy::doc_link_with_quotes)]
 #![warn(clippy::empty_drop)]
-#![warn(clippy::empty_enum)]
+#![warn(clippy::empty_enums)]
 #![warn(clippy::empty_line_after_outer_attr)]
 #![warn(clippy::empty_structs_with_brackets)]
 #![warn(clippy::equatable_if_let)]
@@ -167,7 +205,6 @@
 #![warn(clippy::string_add)]
 #![warn(clippy::string_add_assign)]
 #![warn(clippy::string_slice)]
-#![warn(clippy::string_to_string)]
 #![warn(clippy::used_underscore_binding)]
 #![warn(clippy::useless_let_if_seq)]
 #![warn(clippy::verbose_bit_mask)]
@@ -175,6 +212,17 @@
 #![warn(clippy::wildcard_imports)]
 #![warn(clippy::zero_sized_map_values)]
 
+// LINTS: tests
+// (tests rebind names freely and prefer terse assertions)
+#![cfg_attr(test, allow(clippy::assertions_on_result_states))]
+#![cfg_attr(test, allow(clippy::field_reassign_with_default))]
+#![cfg_attr(test, allow(clippy::items_after_statements))]
+#![cfg_attr(test, allow(clippy::shadow_unrelated))]
+#![cfg_attr(test, allow(clippy::similar_names))]
+#![cfg_attr(test, allow(clippy::string_add))]
+#![cfg_attr(test, allow(clippy::string_slice))]
+#![cfg_attr(test, allow(clippy::suboptimal_flops))]
+
 // LINTS: development temporary
 // #![allow(dead_code)]
 // #![allow(unreachable_code)]
@@ -194,12 +242,10 @@
 #![warn(clippy::exhaustive_enums)]
 #![warn(clippy::exhaustive_structs)]
 
-use ::serde::{Deserialize, Serialize};
-
 pub mod error;
 
 mod common;
-pub use common::string::Str;
+pub use common::string::{Str, Interner};
 
 pub mod table_iter;
 
@@ -212,14 +258,44 @@ pub mod value;
 
 pub mod blueprint;
 
+pub mod exchange;
+
+pub mod header;
+
+pub mod limits;
+
+pub mod document;
+
+pub mod analysis;
+
+#[cfg(any(feature = "json", feature = "ron"))]
+pub mod interop;
+
+#[cfg(feature = "json")]
+pub mod schema;
+
+#[cfg(feature = "mlua")]
+pub mod lua;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "proptest")]
+pub mod test_support;
+
+#[cfg(feature = "serde")]
 pub mod de;
+#[cfg(feature = "serde")]
 pub mod ser;
 
 mod test;
 
-const MAX_ASSOC_LOGLEN: u8 = 20;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Deserialize, ::serde::Serialize))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[allow(clippy::exhaustive_enums)]
 pub enum Exchange<Blueprint, Behavior = Blueprint> {
     Blueprint(Blueprint),
@@ -295,3 +371,17 @@ impl<P, H, E> Exchange<Result<P, E>, Result<H, E>> {
     }
 }
 
+/// How far decoding or encoding has got,
+/// for showing progress of large exchanges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// Bytes of the binary data decoded or encoded so far.
+    pub bytes: usize,
+    /// Length of the binary data, if known in advance
+    /// (which it is when decoding).
+    pub total_bytes: Option<usize>,
+    /// Values decoded or encoded so far,
+    /// counting nested tables and nil items, but not keys.
+    pub values: usize,
+}
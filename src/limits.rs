@@ -0,0 +1,67 @@
+//! Limits of the encoding, for checking content before dumping it
+//! (the dumper fails on anything beyond them).
+
+use std::ops::RangeInclusive;
+
+/// Lua 5.4 does not allocate hash parts beyond `2^30` slots
+/// (`MAXHBITS`), so no valid table has a larger assoc part.
+/// Array lengths, `last_free` and links are variable-length integers
+/// and can take any value that fits the table.
+pub const MAX_ASSOC_LOGLEN: u8 = 30;
+
+/// Maximum number of items in the assoc part of a table,
+/// which is as many as there are slots.
+pub const MAX_ASSOC_LEN: usize = 1 << MAX_ASSOC_LOGLEN;
+
+/// Maximum length of the array part of a table.
+pub const MAX_ARRAY_LEN: u32 = u32::MAX;
+
+/// Maximum length of a string in bytes.
+pub const MAX_STRING_LEN: usize = 0xFFFF;
+
+/// Values of variable-length unsigned integers
+/// (array lengths of tables with an assoc part, and `last_free`).
+pub const EXT_UINT_RANGE: RangeInclusive<u32> = 0 ..= u32::MAX;
+
+/// Values of variable-length signed integers (links between slots).
+pub const EXT_SINT_RANGE: RangeInclusive<i32> = i32::MIN ..= i32::MAX;
+
+/// Whether a string of `len` bytes can be encoded.
+#[must_use]
+pub const fn fits_string(len: usize) -> bool {
+    len <= MAX_STRING_LEN
+}
+
+/// Whether a table can be encoded with `array_len` items
+/// in the array part and `assoc_len` items in the assoc part.
+#[must_use]
+pub const fn fits_table(array_len: usize, assoc_len: usize) -> bool {
+    array_len <= MAX_ARRAY_LEN as usize && assoc_len <= MAX_ASSOC_LEN
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange, Str,
+    dumper::dump_blueprint,
+    value::Value,
+};
+
+use super::{fits_string, fits_table, MAX_STRING_LEN, MAX_ASSOC_LEN};
+
+#[test]
+fn test_limits() {
+    let dump = |len| dump_blueprint::<Value, Value>(Exchange::Blueprint(
+        Some(Value::String(Str::from(&*"x".repeat(len)))) ));
+    assert!(fits_string(MAX_STRING_LEN));
+    dump(MAX_STRING_LEN).unwrap();
+    assert!(!fits_string(MAX_STRING_LEN + 1));
+    let error = dump(MAX_STRING_LEN + 1).unwrap_err();
+    assert!(error.to_string().contains("65536"), "{error}");
+    assert!(fits_table(0, 0));
+    assert!(fits_table(1000, MAX_ASSOC_LEN));
+    assert!(!fits_table(0, MAX_ASSOC_LEN + 1));
+}
+
+}
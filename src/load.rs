@@ -1,6 +1,11 @@
 //! A specialized imitation of `serde::ser`.
 
-use crate::table_iter::{TableItem, TableSize};
+use crate::{
+    common::Sealed,
+    error::LoadError,
+    loader::{DuplicateKeys, DynLoader},
+    table_iter::{TableItem, TableSize},
+};
 
 pub trait Error : std::error::Error + for<'s> From<&'s str> {}
 
@@ -12,10 +17,36 @@ pub trait Load : Sized {
     fn load<L: Loader>(loader: L) -> Result<Option<Self>, L::Error>;
 }
 
+/// An object-safe counterpart of [`Load`].
+///
+/// Loading makes a new value, which a trait object cannot do,
+/// so `dyn DynLoad` is a slot that receives the value instead.
+/// `Option<T>` is such a slot for every type implementing [`Load`];
+/// [`crate::loader::load_blueprint_dyn`] fills slots
+/// of types chosen at runtime (e.g. `Box<dyn DynLoad>`).
+pub trait DynLoad {
+    /// Called by the decoder; the argument cannot be made elsewhere.
+    fn load_dyn(&mut self, loader: &mut DynLoader<'_>)
+    -> Result<(), LoadError>;
+}
+
+impl<T: Load> DynLoad for Option<T> {
+    fn load_dyn(&mut self, loader: &mut DynLoader<'_>)
+    -> Result<(), LoadError>
+    {
+        *self = loader.load()?;
+        Ok(())
+    }
+}
+
 pub trait KeyBuilder : Sized {
     type Output;
     fn build_integer<E: Error>(self, value: i32) -> Result<Self::Output, E>;
     fn build_string<E: Error>(self, value: &str) -> Result<Self::Output, E>;
+    /// A boolean key; Lua allows them, though the game does not write them.
+    fn build_boolean<E: Error>(self, _value: bool) -> Result<Self::Output, E> {
+        Err(E::from("boolean keys are not supported"))
+    }
 }
 
 pub trait Builder : Sized {
@@ -29,13 +60,21 @@ pub trait Builder : Sized {
     fn build_integer<E: Error>(self, value: i32) -> Result<Option<Self::Output>, E>;
     fn build_float<E: Error>(self, value: f64) -> Result<Option<Self::Output>, E>;
     fn build_string<E: Error>(self, value: &str) -> Result<Option<Self::Output>, E>;
+    /// A string that is not valid UTF-8; only called with
+    /// [`InvalidUtf8::Bytes`](crate::loader::InvalidUtf8::Bytes).
+    fn build_bytes<E: Error>(self, _value: &[u8])
+    -> Result<Option<Self::Output>, E>
+    {
+        Err(E::from("string is not valid UTF-8"))
+    }
     fn build_table<T>(self, items: T) -> Result<Option<Self::Output>, T::Error>
     where
         T: TableLoader<Key=Self::Key, Value=Self::Value>,
         T::Error : Error;
 }
 
-pub trait Loader {
+/// Implemented by the decoder; sealed.
+pub trait Loader : Sealed {
     type Error: Error;
     fn load_value<B: Builder>( self,
         builder: B,
@@ -45,11 +84,14 @@ pub trait Loader {
     ) -> Result<Option<KB::Output>, Self::Error>;
 }
 
-pub trait TableLoader : TableSize + Iterator<
+/// Implemented by the decoder; sealed.
+pub trait TableLoader : Sealed + TableSize + Iterator<
     Item = Result<Option<TableItem<Self::Key, Self::Value>>, Self::Error>
 > {
     type Key : KeyLoad;
     type Value : Load;
     type Error : Error;
+    /// How the table should treat keys that are encoded twice.
+    fn duplicate_keys(&self) -> DuplicateKeys;
 }
 
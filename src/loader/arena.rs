@@ -0,0 +1,405 @@
+//! Decoding into values allocated in a bump arena.
+//!
+//! Tables and strings of an [`ArenaValue`] live in the [`Arena`]
+//! and are freed all at once when it is dropped or reset,
+//! which is much cheaper than freeing a [`Value`] node by node
+//! when many exchanges are decoded in a row.
+
+use std::cell::Cell;
+
+use bumpalo::collections::Vec as ArenaVec;
+
+use crate::{
+    Exchange,
+    common::u32_to_usize,
+    error::LoadError as Error,
+    load::{
+        Error as LoadErrorTr,
+        KeyLoad, Load, KeyBuilder, Builder,
+        Loader, TableLoader,
+    },
+    table_iter::{TableItem, AssocItem},
+    value::{Key, Value, TableBuilder},
+};
+
+use super::{DecodeOptions, DuplicateKeys, Warnings};
+
+pub use bumpalo::Bump as Arena;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum ArenaValue<'a> {
+    Boolean(bool),
+    Integer(i32),
+    Float(f64),
+    String(&'a str),
+    /// A string that is not valid UTF-8; only loaded with
+    /// [`InvalidUtf8::Bytes`](super::InvalidUtf8::Bytes).
+    Bytes(&'a [u8]),
+    Table(ArenaTable<'a>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ArenaKey<'a> {
+    Index(i32),
+    Name(&'a str),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct ArenaTable<'a> {
+    /// Items with the indices of the array part, starting with `1`,
+    /// wherever they were encoded; trailing `nil` items are dropped.
+    pub array: &'a [Option<ArenaValue<'a>>],
+    /// Items with all other keys, sorted by key.
+    pub assoc: &'a [(ArenaKey<'a>, ArenaValue<'a>)],
+}
+
+impl<'a> ArenaTable<'a> {
+    #[must_use]
+    pub fn get(&self, key: ArenaKey<'_>) -> Option<&ArenaValue<'a>> {
+        if let ArenaKey::Index(index) = key {
+            let found = usize::try_from(index).ok()
+                .and_then(|index| index.checked_sub(1))
+                .and_then(|index| self.array.get(index));
+            if let Some(value) = found {
+                return value.as_ref();
+            }
+        }
+        let position = self.assoc
+            .binary_search_by(|&(item_key, _)| item_key.cmp(&key))
+            .ok()?;
+        Some(&self.assoc[position].1)
+    }
+}
+
+impl From<ArenaKey<'_>> for Key {
+    fn from(key: ArenaKey<'_>) -> Self {
+        match key {
+            ArenaKey::Index(index) => Self::Index(index),
+            ArenaKey::Name(name) => Self::from_maybe_known(name),
+            ArenaKey::Bool(value) => Self::Bool(value),
+        }
+    }
+}
+
+impl From<&ArenaValue<'_>> for Value {
+    /// Copy the value out of the arena.
+    fn from(value: &ArenaValue<'_>) -> Self {
+        match *value {
+            ArenaValue::Boolean(value) => Self::Boolean(value),
+            ArenaValue::Integer(value) => Self::Integer(value),
+            ArenaValue::Float(value) => Self::Float(value),
+            ArenaValue::String(value) => Self::String(value.into()),
+            ArenaValue::Bytes(value) => Self::Bytes(value.to_vec()),
+            ArenaValue::Table(table) => {
+                let mut builder = TableBuilder::new();
+                for (index, item) in (1 ..).zip(table.array) {
+                    let Some(item) = item else { continue };
+                    builder.insert(Key::Index(index), Self::from(item));
+                }
+                for &(key, ref item) in table.assoc {
+                    builder.insert(Key::from(key), Self::from(item));
+                }
+                Self::Table(builder.build())
+            },
+        }
+    }
+}
+
+/// Load the exchange string into the arena.
+///
+/// This is [`load_into_arena_with`] with the default options,
+/// dropping the warnings.
+pub fn load_into_arena<'a>(arena: &'a Arena, exchange: &str)
+-> Result<Exchange<Option<ArenaValue<'a>>, Option<ArenaValue<'a>>>, Error>
+{
+    load_into_arena_with( arena, exchange,
+        DecodeOptions::default(), &mut Warnings::new() )
+}
+
+/// Load the exchange string into the arena
+/// like [`super::load_blueprint_with_warnings`].
+///
+/// Unlike [`super::load_blueprint_with_warnings`], this does not check
+/// how the keys are placed in the hash part.
+pub fn load_into_arena_with<'a>( arena: &'a Arena, exchange: &str,
+    options: DecodeOptions, warnings: &mut Warnings,
+) -> Result<Exchange<Option<ArenaValue<'a>>, Option<ArenaValue<'a>>>, Error>
+{
+    let _installed = Installed::new(arena);
+    let loaded = super::load_blueprint_with_warnings::<
+        ArenaLoad<'a>, ArenaLoad<'a> >(exchange, options, warnings)?;
+    Ok(loaded.map( |value| value.map(|ArenaLoad(value)| value),
+        |value| value.map(|ArenaLoad(value)| value) ))
+}
+
+thread_local! {
+    static CURRENT_ARENA: Cell<*const Arena> =
+        const { Cell::new(std::ptr::null()) };
+}
+
+/// Makes the arena current on this thread while alive,
+/// and restores the previous one when dropped.
+struct Installed(*const Arena);
+
+impl Installed {
+    fn new(arena: &Arena) -> Self {
+        Self(CURRENT_ARENA.with(|current| current.replace(arena)))
+    }
+}
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        CURRENT_ARENA.with(|current| current.set(self.0));
+    }
+}
+
+/// The arena installed by [`load_into_arena_with`].
+fn current_arena<'a>() -> &'a Arena {
+    let arena = CURRENT_ARENA.with(Cell::get);
+    assert!(!arena.is_null(), "arena should be installed");
+    // SAFETY: `ArenaLoad<'a>` is only loaded inside
+    // `load_into_arena_with`, while an arena living for `'a`
+    // is installed, and nothing else can name the type.
+    unsafe { &*arena }
+}
+
+/// The shared loader reaches the arena through the thread-local,
+/// as [`Load`] takes no state.
+struct ArenaLoad<'a>(ArenaValue<'a>);
+
+struct ArenaKeyLoad<'a>(ArenaKey<'a>);
+
+impl<'a> Load for ArenaLoad<'a> {
+    fn load<L: Loader>(loader: L) -> Result<Option<Self>, L::Error> {
+        loader.load_value(ArenaBuilder { arena: current_arena() })
+    }
+}
+
+impl<'a> KeyLoad for ArenaKeyLoad<'a> {
+    fn load_key<L: Loader>(loader: L) -> Result<Option<Self>, L::Error> {
+        let key = loader.load_key(ArenaBuilder { arena: current_arena() })?;
+        Ok(key.map(Self))
+    }
+}
+
+struct ArenaBuilder<'a> {
+    arena: &'a Arena,
+}
+
+impl<'a> KeyBuilder for ArenaBuilder<'a> {
+    type Output = ArenaKey<'a>;
+    fn build_integer<E: LoadErrorTr>(self, value: i32)
+    -> Result<Self::Output, E>
+    {
+        Ok(ArenaKey::Index(value))
+    }
+    fn build_string<E: LoadErrorTr>(self, value: &str)
+    -> Result<Self::Output, E>
+    {
+        Ok(ArenaKey::Name(self.arena.alloc_str(value)))
+    }
+    fn build_boolean<E: LoadErrorTr>(self, value: bool)
+    -> Result<Self::Output, E>
+    {
+        Ok(ArenaKey::Bool(value))
+    }
+}
+
+impl<'a> Builder for ArenaBuilder<'a> {
+    type Output = ArenaLoad<'a>;
+    type Key = ArenaKeyLoad<'a>;
+    type Value = ArenaLoad<'a>;
+    fn build_boolean<E: LoadErrorTr>(self, value: bool)
+    -> Result<Option<Self::Output>, E>
+    {
+        Ok(Some(ArenaLoad(ArenaValue::Boolean(value))))
+    }
+    fn build_integer<E: LoadErrorTr>(self, value: i32)
+    -> Result<Option<Self::Output>, E>
+    {
+        Ok(Some(ArenaLoad(ArenaValue::Integer(value))))
+    }
+    fn build_float<E: LoadErrorTr>(self, value: f64)
+    -> Result<Option<Self::Output>, E>
+    {
+        Ok(Some(ArenaLoad(ArenaValue::Float(value))))
+    }
+    fn build_string<E: LoadErrorTr>(self, value: &str)
+    -> Result<Option<Self::Output>, E>
+    {
+        Ok(Some(ArenaLoad(ArenaValue::String(self.arena.alloc_str(value)))))
+    }
+    fn build_bytes<E: LoadErrorTr>(self, value: &[u8])
+    -> Result<Option<Self::Output>, E>
+    {
+        Ok(Some(ArenaLoad(ArenaValue::Bytes(
+            self.arena.alloc_slice_copy(value) ))))
+    }
+    fn build_table<T>(self, items: T) -> Result<Option<Self::Output>, T::Error>
+    where
+        T: TableLoader<Key=Self::Key, Value=Self::Value>,
+        T::Error : LoadErrorTr,
+    {
+        let array_len = items.array_len();
+        let duplicate_keys = items.duplicate_keys();
+        // both parts go into one list, so that an index
+        // encoded in both of them counts as a duplicate
+        let mut pairs = ArenaVec::new_in(self.arena);
+        let mut array_rest = array_len;
+        let mut array_index = 0;
+        for item in items {
+            match item? {
+                Some(TableItem::Array(ArenaLoad(value))) => {
+                    array_rest -= 1;
+                    array_index += 1;
+                    pairs.push((ArenaKey::Index(array_index), value));
+                },
+                None if array_rest > 0 => {
+                    array_rest -= 1;
+                    array_index += 1;
+                },
+                Some(TableItem::Assoc(AssocItem::Live {
+                    key: ArenaKeyLoad(key),
+                    value: Some(ArenaLoad(value)), ..
+                })) => pairs.push((key, value)),
+                _ => (),
+            }
+        }
+        // the sort is stable, so items with the same key
+        // stay in the order they were encoded
+        pairs.sort_by_key(|&(key, _)| key);
+        match duplicate_keys {
+            DuplicateKeys::Error => {
+                if let Some(pair) = pairs.windows(2)
+                    .find(|pair| pair[0].0 == pair[1].0)
+                {
+                    return Err(T::Error::from(&*format!(
+                        "table key {:?} should not be encoded twice",
+                        Key::from(pair[0].0) )));
+                }
+            },
+            DuplicateKeys::FirstWins =>
+                pairs.dedup_by(|(k1, _), (k2, _)| k1 == k2),
+            DuplicateKeys::LastWins =>
+                pairs.dedup_by(|later, earlier| {
+                    let same = later.0 == earlier.0;
+                    if same {
+                        std::mem::swap(later, earlier);
+                    }
+                    same
+                }),
+        }
+        // indices within the array part are kept there,
+        // wherever they were encoded
+        let array_position = |key: &ArenaKey<'_>| match *key {
+            ArenaKey::Index(index) => u32::try_from(index).ok()
+                .filter(|&index| index >= 1 && index <= array_len),
+            _ => None,
+        };
+        let start = pairs.partition_point(|(key, _)|
+            *key < ArenaKey::Index(1) );
+        let end = start + pairs[start ..].partition_point(|(key, _)|
+            array_position(key).is_some() );
+        let mut array = ArenaVec::new_in(self.arena);
+        for (key, value) in pairs.drain(start .. end) {
+            let Some(index) = array_position(&key) else { continue };
+            array.resize(u32_to_usize(index), None);
+            array[u32_to_usize(index) - 1] = Some(value);
+        }
+        Ok(Some(ArenaLoad(ArenaValue::Table(ArenaTable {
+            array: array.into_bump_slice(),
+            assoc: pairs.into_bump_slice(),
+        }))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    dumper::DumpOptions,
+    loader::{DecodeOptions, DuplicateKeys, Warnings},
+    value::Value,
+};
+
+use super::{
+    Arena, ArenaKey, ArenaValue,
+    load_into_arena, load_into_arena_with,
+};
+
+#[test]
+fn test_load_into_arena() {
+    let mut arena = Arena::new();
+    for exchange in [
+        crate::test::EXCHANGE_BEHAVIOR_1_UNIT,
+        crate::test::EXCHANGE_BEHAVIOR_2,
+        crate::test::EXCHANGE_BEHAVIOR_3_PARAM,
+        crate::test::EXCHANGE_BEHAVIOR_4_SUB,
+    ] {
+        let expected = crate::loader::load_blueprint::<
+            Value, Value, LoadError >(exchange).unwrap();
+        let loaded = load_into_arena(&arena, exchange).unwrap();
+        assert_eq!( loaded.map( |value| value.as_ref().map(Value::from),
+            |value| value.as_ref().map(Value::from) ), expected );
+        arena.reset();
+    }
+
+    let Exchange::Behavior(Some(ArenaValue::Table(table))) =
+        load_into_arena(&arena, crate::test::EXCHANGE_BEHAVIOR_2).unwrap()
+        else { panic!("should be a behavior") };
+    assert_eq!( table.get(ArenaKey::Name("name")),
+        Some(&ArenaValue::String("Test Behavior 2")) );
+
+    // `1 = 10` in the array part and `1 = 20` in the assoc part
+    let array_assoc: &[u8] = &[
+        0x81, 0x02, 0x00, 0x00,
+        0x0A,
+        0x14, 0x01, 0x00 ];
+    // `"a" = 1` in its main position linked to `"a" = 2`
+    let assoc_assoc: &[u8] = &[
+        0x82, 0x00, 0x00,
+        0x01, 0xA1, b'a', 0x04,
+        0x02, 0xA1, b'a', 0x00 ];
+    let load = |data, duplicate_keys| {
+        let exchange = crate::dumper::compress::compress(
+            Exchange::Blueprint(data), &DumpOptions::default() );
+        let loaded = load_into_arena_with( &arena, &exchange,
+            DecodeOptions { duplicate_keys, ..DecodeOptions::default() },
+            &mut Warnings::new() );
+        loaded.map(|loaded| match loaded {
+            Exchange::Blueprint(Some(ArenaValue::Table(loaded))) => loaded,
+            _ => panic!("should be a table"),
+        })
+    };
+    for data in [array_assoc, assoc_assoc] {
+        let error = load(data, DuplicateKeys::Error).unwrap_err();
+        assert!(error.to_string().contains("encoded twice"), "{error}");
+    }
+    let index_first = load(array_assoc, DuplicateKeys::FirstWins).unwrap();
+    assert_eq!(index_first.array, &[Some(ArenaValue::Integer(10))]);
+    assert_eq!(index_first.assoc, &[]);
+    let index_last = load(array_assoc, DuplicateKeys::LastWins).unwrap();
+    assert_eq!(index_last.array, &[Some(ArenaValue::Integer(20))]);
+    assert_eq!(index_last.assoc, &[]);
+    let name_first = load(assoc_assoc, DuplicateKeys::FirstWins).unwrap();
+    assert_eq!( name_first.get(ArenaKey::Name("a")),
+        Some(&ArenaValue::Integer(1)) );
+    let name_last = load(assoc_assoc, DuplicateKeys::LastWins).unwrap();
+    assert_eq!( name_last.get(ArenaKey::Name("a")),
+        Some(&ArenaValue::Integer(2)) );
+
+    // the same table with the array item masked
+    let masked = load(&[
+        0x81, 0x02, 0x00, 0x01,
+        0x14, 0x01, 0x00 ], DuplicateKeys::Error).unwrap();
+    assert_eq!(masked.get(ArenaKey::Index(1)), Some(&ArenaValue::Integer(20)));
+    assert_eq!(masked.assoc, &[]);
+}
+
+}
@@ -1,13 +1,14 @@
 use flate2::read::ZlibDecoder as UnZippingReader;
 
 use crate::{
-    error::LoadError as Error,
+    error::{LoadError as Error, LoadErrorKind as ErrorKind},
     common::{
         ascii::{Ascii, AsciiStr},
         byteseq::Read,
         intlim::{Int62, Int31, decode_base62, Base62Decode},
     },
     Exchange,
+    header::{parse_kind, PREFIX_LEN},
 };
 
 #[cold]
@@ -17,39 +18,70 @@ fn error_eof() -> Error {
 
 pub(crate) fn decompress(
     body: &str,
+    verify_checksum: bool,
 ) -> Result<Exchange<Vec<u8>>, Error> {
-    let mut body: &[Ascii] = <&AsciiStr>::try_from(body)?;
-    let kind = match body.read_slice(3)
-        .map(|s| <&AsciiStr>::from(s).into())
-        .ok_or_else(error_eof)?
+    let (data, _compressed) =
+        decompress_limited(body, usize::MAX, verify_checksum)?;
+    Ok(data)
+}
+
+/// Also returns whether the data was compressed.
+///
+/// The header and the alphabet are checked before anything is decoded,
+/// and the checksum is checked before anything is unpacked,
+/// so that the error kind tells what is wrong with the string itself.
+pub(crate) fn decompress_limited(
+    body: &str,
+    max_data_len: usize,
+    verify_checksum: bool,
+) -> Result<(Exchange<Vec<u8>>, bool), Error> {
+    let kind = parse_kind(body)?;
+    // `parse_kind` has checked that the prefix is ASCII
+    let (_, body) = body.split_at(PREFIX_LEN);
+    if let Some((index, _)) = body.char_indices()
+        .find(|&(_, c)| !c.is_ascii_alphanumeric())
     {
-        "DSB" => Exchange::Blueprint(()),
-        "DSC" => Exchange::Behavior(()),
-        _ => return Err(Error::from("unrecognized blueprint header")),
-    };
-    let encoded_len = read_len_base31(&mut body)?;
+        let index = PREFIX_LEN + index;
+        return Err(Error::new( ErrorKind::BadBase62Char { index },
+            format!("unexpected character at position {index}") ));
+    }
+    let mut body: &[Ascii] = <&AsciiStr>::try_from(body)?;
+    let encoded_len = read_len_base31(&mut body)
+        .map_err(|error| error.with_kind(ErrorKind::BadPrefix))?;
     let encoded_checksum = decode_base62(
         body.read_end_byte()
-            .ok_or_else(error_eof)?
+            .ok_or_else(|| error_eof().with_kind(ErrorKind::BadChecksum))?
     )?;
     let mut decoder = Base62Decode::new(Vec::new(), std::num::Wrapping(0));
-    decoder.write_slice(body)?;
     #[allow(clippy::shadow_unrelated)]
-    let (body, checksum) = decoder.end()?;
-    if Int62::divrem(checksum.0).1 != encoded_checksum {
-        return Err(Error::from("checksum does not match"));
+    let (body, checksum) = decoder.write_slice(body)
+        .and_then(|()| decoder.end())
+        .map_err(|error| error.with_kind(ErrorKind::BadChecksum))?;
+    let expected_checksum = Int62::divrem(checksum.0).1;
+    if verify_checksum && expected_checksum != encoded_checksum {
+        let (expected, found) =
+            (u8::from(expected_checksum), u8::from(encoded_checksum));
+        return Err(Error::new( ErrorKind::Checksum { expected, found },
+            format!( "checksum does not match \
+                (expected {expected}, found {found})" ) ));
     }
-    let body: Vec<u8> = if encoded_len == 0 { body } else {
-        let unzipped = unzip(&body)?;
+    if encoded_len > max_data_len || body.len() > max_data_len {
+        return Err(Error::from("data length exceeds the limit"));
+    }
+    let compressed = encoded_len != 0;
+    let body: Vec<u8> = if !compressed { body } else {
+        let unzipped = unzip(&body, encoded_len)
+            .map_err(|error| error.with_kind(ErrorKind::ZlibError))?;
         if encoded_len != unzipped.len() {
-            return Err(Error::from("length does not match"));
+            return Err(Error::new( ErrorKind::ZlibError,
+                "length does not match" ));
         }
         unzipped
     };
-    Ok(kind.map_mono(|()| body))
+    Ok((kind.map_mono(|()| body), compressed))
 }
 
-fn read_len_base31(mut reader: impl Read<Ascii>) -> Result<usize, Error> {
+pub(crate) fn read_len_base31(mut reader: impl Read<Ascii>) -> Result<usize, Error> {
     const MAX_DIGITS: usize = Int31::u32_sufficient_digits();
     let mut digits = [Int31::zero(); MAX_DIGITS];
     let mut digits_mut: &mut [_] = &mut digits;
@@ -76,11 +108,102 @@ fn read_len_base31(mut reader: impl Read<Ascii>) -> Result<usize, Error> {
         as usize )
 }
 
-fn unzip(data: &[u8]) -> Result<Vec<u8>, Error> {
+/// Unzip the data if it starts with a zlib header.
+///
+/// Encoded values never start that way:
+/// the header would be the integer `120` followed by more data.
+pub(crate) fn unzip_raw(data: &[u8])
+-> Result<std::borrow::Cow<'_, [u8]>, Error>
+{
+    unzip_raw_limited(data, usize::MAX)
+}
+
+/// Unzip the data like [`unzip_raw`],
+/// failing if the result would exceed `max_len`.
+pub(crate) fn unzip_raw_limited(data: &[u8], max_len: usize)
+-> Result<std::borrow::Cow<'_, [u8]>, Error>
+{
+    use std::io::Read as _;
+    let is_zlib = match *data {
+        [method @ 0x78, flags, ..] =>
+            (u16::from(method) << 8 | u16::from(flags)) % 31 == 0,
+        _ => false,
+    };
+    if !is_zlib {
+        return Ok(std::borrow::Cow::Borrowed(data));
+    }
+    let mut result = Vec::new();
+    UnZippingReader::new(data)
+        .take((max_len as u64).saturating_add(1))
+        .read_to_end(&mut result)
+        .map_err(|error| Error::from(error).with_kind(ErrorKind::ZlibError))?;
+    if result.len() > max_len {
+        return Err(Error::from("data length exceeds the limit"));
+    }
+    Ok(std::borrow::Cow::Owned(result))
+}
+
+/// Read the exchange string from the reader,
+/// checking the prefix, the alphabet and the length limits
+/// as the data arrives, so that a bad string is rejected
+/// without reading it to the end.
+pub(crate) fn read_exchange<R: std::io::Read>(
+    mut reader: R,
+    max_exchange_len: usize,
+    max_data_len: usize,
+) -> Result<String, Error> {
+    const HEADER_LEN: usize = PREFIX_LEN + Int31::u32_sufficient_digits();
+    let mut exchange = Vec::new();
+    let mut chunk = [0_u8; 4096];
+    let mut header_checked = false;
+    loop {
+        let len = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted =>
+                continue,
+            Err(error) => return Err(error.into()),
+        };
+        let start = exchange.len();
+        if start + len > max_exchange_len {
+            return Err(Error::from(
+                "exchange string exceeds the length limit" ));
+        }
+        exchange.extend_from_slice(&chunk[..len]);
+        if let Some(prefix) = exchange.get(..PREFIX_LEN) {
+            parse_kind(std::str::from_utf8(prefix).unwrap_or(""))?;
+        }
+        let checked = start.max(PREFIX_LEN);
+        if let Some(index) = exchange.get(checked..)
+            .and_then(|data| data.iter()
+                .position(|byte| !byte.is_ascii_alphanumeric()) )
+        {
+            let index = checked + index;
+            return Err(Error::new( ErrorKind::BadBase62Char { index },
+                format!("unexpected character at position {index}") ));
+        }
+        if !header_checked && exchange.len() >= HEADER_LEN {
+            let header = <&AsciiStr>::try_from(&exchange[..HEADER_LEN])?;
+            let (header, _) = crate::header::parse(header.into())?;
+            if header.data_len.unwrap_or(0) > max_data_len {
+                return Err(Error::from("data length exceeds the limit"));
+            }
+            header_checked = true;
+        }
+    }
+    // only the prefix can be anything but ASCII at this point,
+    // and a short one is rejected along with the rest of the string
+    Ok(String::from_utf8(exchange).unwrap_or_else(|error|
+        String::from_utf8_lossy(error.as_bytes()).into_owned() ))
+}
+
+fn unzip(data: &[u8], expected_len: usize) -> Result<Vec<u8>, Error> {
     use std::io::Read as _;
+    // Anything beyond the expected length is an error anyway,
+    // so there is no need to unzip more than one extra byte.
     let mut unzipper = UnZippingReader::new(
         data,
-    );
+    ).take((expected_len as u64).saturating_add(1));
     let mut result = Vec::new();
     unzipper.read_to_end(&mut result)?;
     Ok(result)
@@ -1,9 +1,15 @@
-use std::marker::PhantomData;
+//! Decoding of exchange strings.
+//!
+//! The decoded value can be of any type implementing [`Load`];
+//! see [`crate::blueprint::load_blueprint`]
+//! for loading structured blueprints.
+
+use std::{borrow::Cow, marker::PhantomData, ops::Range};
 
 use crate::{
-    error::LoadError as Error,
+    error::{LoadError as Error, LoadErrorKind as ErrorKind},
     common::{
-        u32_to_usize, LogSize, iexp2,
+        u32_to_usize, LogSize, iexp2, Sealed,
         byteseq::Read,
     },
     table_iter::{
@@ -11,53 +17,604 @@ use crate::{
         TableSize,
     },
     load::{
-        KeyLoad, Load,
+        KeyLoad, Load, DynLoad,
         KeyBuilder, Builder,
         Loader as LoaderTr, TableLoader
     },
-    Exchange
+    value::{Key, Value, Table},
+    dumper::{DumpOptions, FloatPolicy, encode_with_progress},
+    Exchange, Progress,
+};
+
+pub(crate) mod decompress;
+mod skip;
+pub(crate) mod trace;
+
+#[cfg(feature = "arena")]
+mod arena;
+#[cfg(feature = "arena")]
+pub use arena::{
+    Arena, ArenaValue, ArenaKey, ArenaTable,
+    load_into_arena, load_into_arena_with,
 };
 
-mod decompress;
+mod warnings;
+pub use warnings::{Warning, Warnings, MAX_INSTRUCTION_OFFSET};
+
+use self::skip::Skip;
+
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::{Observer, PhaseMetrics};
 
 pub fn load_blueprint<P, B, E>(exchange: &str)
 -> Result<Exchange<Option<P>, Option<B>>, Error>
 where P: Load, B: Load,
 {
-    let encoded_data = decompress::decompress(exchange)?;
-    encoded_data.as_deref().map(decode, decode).transpose()
+    load_blueprint_with::<P, B>(exchange, DecodeOptions::default())
+}
+
+/// Options for [`load_blueprint_with`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// Check the checksum digit of the exchange string (the default).
+    /// Skipping the check allows recovering data from strings
+    /// with a damaged last character.
+    pub verify_checksum: bool,
+    /// Reject variable-length integers (table sizes and links)
+    /// that are not encoded with the minimal number of bytes.
+    /// With this set, distinct encoded data never decodes
+    /// to the same value through different integer encodings.
+    /// The dumper always writes the minimal encoding.
+    pub canonical_varints: bool,
+    /// Applied to every table key, array indices included
+    /// (e.g. to rename keys written by older versions of the game).
+    /// The data is first loaded as a [`Value`], and its tables
+    /// are rebuilt from the mapped keys before anything else
+    /// sees them, so a key can be mapped to any other key.
+    /// Mapping two keys of a table to the same key fails the loading.
+    pub key_map: Option<KeyMap>,
+    /// Maximum nesting level of tables.
+    /// Tables are decoded recursively, so this keeps
+    /// untrusted strings from exhausting the stack.
+    pub max_depth: u32,
+    /// What to do with a key encoded more than once in a table.
+    pub duplicate_keys: DuplicateKeys,
+    /// What to do with a string that is not valid UTF-8.
+    pub utf8: InvalidUtf8,
+}
+
+pub(crate) const DEFAULT_MAX_DEPTH: u32 = 64;
+
+/// A key mapping for [`DecodeOptions::key_map`].
+///
+/// This is a plain function rather than a closure,
+/// so that the options stay `Copy`.
+pub type KeyMap = fn(Key) -> Key;
+
+/// What to do with a key that is encoded in a table more than once,
+/// either in both the array and the assoc part,
+/// or twice in the assoc part.
+///
+/// The game never produces such tables.
+/// Keys with nil values are not counted.
+/// Items are ordered the way they are encoded:
+/// the array part first, then the assoc part slot by slot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateKeys {
+    /// Fail the loading.
+    #[default]
+    Error,
+    /// Keep the value of the first occurrence.
+    FirstWins,
+    /// Keep the value of the last occurrence.
+    LastWins,
+}
+
+/// What to do with a string that is not valid UTF-8.
+///
+/// Lua strings are byte strings,
+/// so nothing keeps the game from encoding such a string.
+/// Table keys are always loaded as text:
+/// with [`InvalidUtf8::Bytes`] an invalid key fails the loading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidUtf8 {
+    /// Fail the loading.
+    #[default]
+    Strict,
+    /// Replace invalid sequences with `U+FFFD`.
+    /// The string is not dumped back the same way.
+    Lossy,
+    /// Pass the bytes to [`Builder::build_bytes`],
+    /// so that the string can be dumped back byte-exactly
+    /// (see [`Value::Bytes`]).
+    Bytes,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            verify_checksum: true,
+            canonical_varints: false,
+            key_map: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            duplicate_keys: DuplicateKeys::default(),
+            utf8: InvalidUtf8::default(),
+        }
+    }
+}
+
+pub fn load_blueprint_with<P, B>(exchange: &str, options: DecodeOptions)
+-> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load,
+{
+    load_blueprint_with_warnings(exchange, options, &mut Warnings::new())
+}
+
+/// Load the exchange string like [`load_blueprint_with`],
+/// recording non-fatal oddities of the encoding in `warnings`.
+pub fn load_blueprint_with_warnings<P, B>( exchange: &str,
+    options: DecodeOptions, warnings: &mut Warnings,
+) -> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load,
+{
+    load_blueprint_with_progress(exchange, options, warnings, |_| ())
+}
+
+/// Load the exchange string like [`load_blueprint_with_warnings`],
+/// calling `progress` every now and then while the binary data
+/// is decoded, and once more when it is done.
+///
+/// The string is unpacked (base62 and zlib) before the first call.
+pub fn load_blueprint_with_progress<P, B, F>( exchange: &str,
+    options: DecodeOptions, warnings: &mut Warnings, progress: F,
+) -> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load, F: FnMut(Progress),
+{
+    let encoded_data = decompress::decompress(
+        exchange, options.verify_checksum )?;
+    Ok(match encoded_data.as_deref() {
+        Exchange::Blueprint(data) => Exchange::Blueprint(
+            decode_with_progress(data, options, warnings, progress)? ),
+        Exchange::Behavior(data) => Exchange::Behavior(
+            decode_with_progress(data, options, warnings, progress)? ),
+    })
+}
+
+/// Load the exchange string like [`load_blueprint_with_warnings`],
+/// into `blueprint` or `behavior` depending on its kind,
+/// so that the types can be chosen at runtime.
+///
+/// Returns the kind of the exchange; the other slot is left as is.
+pub fn load_blueprint_dyn( exchange: &str,
+    options: DecodeOptions, warnings: &mut Warnings,
+    blueprint: &mut dyn DynLoad, behavior: &mut dyn DynLoad,
+) -> Result<Exchange<()>, Error>
+{
+    let encoded_data = decompress::decompress(
+        exchange, options.verify_checksum )?;
+    match encoded_data.as_deref() {
+        Exchange::Blueprint(data) =>
+            decode_dyn(data, options, warnings, blueprint)?,
+        Exchange::Behavior(data) =>
+            decode_dyn(data, options, warnings, behavior)?,
+    }
+    Ok(encoded_data.as_ref().map_mono(|_| ()))
+}
+
+/// Load the exchange string like [`load_blueprint_with_warnings`],
+/// reporting how long unpacking and decoding take to `observer`.
+#[cfg(feature = "metrics")]
+pub fn load_blueprint_with_observer<P, B>( exchange: &str,
+    options: DecodeOptions, warnings: &mut Warnings,
+    observer: &mut dyn Observer,
+) -> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load,
+{
+    let decompress_start = Instant::now();
+    let encoded_data = decompress::decompress(
+        exchange, options.verify_checksum )?;
+    let data_len = match encoded_data {
+        Exchange::Blueprint(ref data) | Exchange::Behavior(ref data) =>
+            data.len(),
+    };
+    observer.decompress(PhaseMetrics {
+        input_bytes: Some(exchange.len()),
+        output_bytes: Some(data_len),
+        ..PhaseMetrics::new(decompress_start.elapsed())
+    });
+    let decode_start = Instant::now();
+    let mut values = 0;
+    let progress = |progress: Progress| values = progress.values;
+    let value = match encoded_data.as_deref() {
+        Exchange::Blueprint(data) => Exchange::Blueprint(
+            decode_with_progress(data, options, warnings, progress)? ),
+        Exchange::Behavior(data) => Exchange::Behavior(
+            decode_with_progress(data, options, warnings, progress)? ),
+    };
+    observer.decode(PhaseMetrics {
+        input_bytes: Some(data_len),
+        values: Some(values),
+        ..PhaseMetrics::new(decode_start.elapsed())
+    });
+    Ok(value)
+}
+
+/// Load the binary data of an exchange, as found in save files
+/// and such, without the base62 layer of exchange strings.
+///
+/// The data may be a zlib stream (see [`crate::dumper::encode_raw`]);
+/// it is unzipped in full, so its size should be checked beforehand
+/// if it comes from an untrusted source.
+pub fn decode_raw<P, B>( data: Exchange<&[u8]>,
+    options: DecodeOptions, warnings: &mut Warnings,
+) -> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load,
+{
+    Ok(match data {
+        Exchange::Blueprint(data) => Exchange::Blueprint(decode(
+            &decompress::unzip_raw(data)?, options, warnings )?),
+        Exchange::Behavior(data) => Exchange::Behavior(decode(
+            &decompress::unzip_raw(data)?, options, warnings )?),
+    })
+}
+
+/// Load the exchange string read from `reader`,
+/// as in server pipelines that receive uploads as streams.
+///
+/// The prefix, the alphabet and the lengths are checked
+/// as the string arrives, so that a bad one is rejected early,
+/// without reading it to the end.
+/// The lower of [`Limits::max_depth`] and [`DecodeOptions::max_depth`]
+/// applies.
+pub fn load_blueprint_from<P, B, R>( reader: R,
+    options: DecodeOptions, limits: Limits, warnings: &mut Warnings,
+) -> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load, R: std::io::Read,
+{
+    let exchange = decompress::read_exchange( reader,
+        limits.max_exchange_len, limits.max_data_len )?;
+    let (encoded_data, _) = decompress::decompress_limited(
+        &exchange, limits.max_data_len, options.verify_checksum )?;
+    let options = DecodeOptions {
+        max_depth: options.max_depth.min(limits.max_depth),
+        ..options
+    };
+    Ok(match encoded_data.as_deref() {
+        Exchange::Blueprint(data) => Exchange::Blueprint(
+            decode(data, options, warnings)? ),
+        Exchange::Behavior(data) => Exchange::Behavior(
+            decode(data, options, warnings)? ),
+    })
+}
+
+/// Load the binary data of an exchange like [`decode_raw`],
+/// reading it from `reader`.
+///
+/// [`Limits::max_data_len`] applies to the data both as it is read
+/// and after it is unzipped; [`Limits::max_exchange_len`] is not used.
+pub fn decode_raw_from<P, B, R>( data: Exchange<R>,
+    options: DecodeOptions, limits: Limits, warnings: &mut Warnings,
+) -> Result<Exchange<Option<P>, Option<B>>, Error>
+where P: Load, B: Load, R: std::io::Read,
+{
+    use std::io::Read as _;
+    let kind = data.as_ref().map_mono(|_| ());
+    let mut buffer = Vec::new();
+    data.unwrap()
+        .take((limits.max_data_len as u64).saturating_add(1))
+        .read_to_end(&mut buffer)?;
+    if buffer.len() > limits.max_data_len {
+        return Err(Error::from("data length exceeds the limit"));
+    }
+    let unzipped =
+        decompress::unzip_raw_limited(&buffer, limits.max_data_len)?;
+    let options = DecodeOptions {
+        max_depth: options.max_depth.min(limits.max_depth),
+        ..options
+    };
+    Ok(match kind {
+        Exchange::Blueprint(()) => Exchange::Blueprint(
+            decode(&unzipped, options, warnings)? ),
+        Exchange::Behavior(()) => Exchange::Behavior(
+            decode(&unzipped, options, warnings)? ),
+    })
+}
+
+/// Limits for [`verify`] and [`load_blueprint_from`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// Maximum length of the exchange string itself.
+    pub max_exchange_len: usize,
+    /// Maximum length of the binary data
+    /// (after base62 decoding and decompression).
+    pub max_data_len: usize,
+    /// Maximum nesting level of tables.
+    pub max_depth: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_exchange_len: usize::MAX,
+            max_data_len: usize::MAX,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+/// Information gathered by [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExchangeInfo {
+    pub kind: Exchange<()>,
+    pub compressed: bool,
+    /// Length of the binary data
+    /// (after base62 decoding and decompression).
+    pub data_len: usize,
+    /// Maximum nesting level of tables
+    /// (zero if the value is not a table).
+    pub depth: u32,
+}
+
+/// Check that the exchange string can be loaded,
+/// without actually building the value.
+///
+/// Everything is validated except for the placement of keys
+/// in the assoc parts of tables
+/// (checking which would require remembering the keys).
+pub fn verify(exchange: &str, limits: Limits) -> Result<ExchangeInfo, Error> {
+    if exchange.len() > limits.max_exchange_len {
+        return Err(Error::from("exchange string exceeds the length limit"));
+    }
+    let (encoded_data, compressed) =
+        decompress::decompress_limited(exchange, limits.max_data_len, true)?;
+    let kind = encoded_data.as_ref().map_mono(|_| ());
+    let data = encoded_data.unwrap();
+    let mut loader = Loader::new(data.as_slice());
+    loader.max_depth = limits.max_depth;
+    Skip::load(&mut loader)?;
+    Ok(ExchangeInfo {
+        kind,
+        compressed,
+        data_len: data.len(),
+        depth: loader.max_seen_depth,
+    })
+}
+
+/// Byte ranges of the items in the array part of the outermost table
+/// (`None` for nil items).
+/// Items are encoded independently of each other,
+/// so an item can be replaced by splicing another encoded value
+/// into its range.
+pub(crate) fn array_item_ranges(data: &[u8])
+-> Result<Vec<Option<Range<usize>>>, Error>
+{
+    let mut loader = Loader::new(data);
+    let head = loader.read_byte()?;
+    let TableHeader { array_len, .. } = loader.load_table_header(head)?;
+    let mut ranges = Vec::new();
+    let mut mask = 0;
+    for index in 0 .. array_len {
+        if index % 8 == 0 {
+            mask = loader.read_byte()?;
+        }
+        let is_masked = (mask & 0x01) > 0;
+        mask >>= 1;
+        if is_masked {
+            ranges.push(None);
+            continue;
+        }
+        let start = data.len() - loader.reader.len();
+        Skip::load(&mut loader)?;
+        let end = data.len() - loader.reader.len();
+        ranges.push(Some(start .. end));
+    }
+    Ok(ranges)
 }
 
-fn decode<V: Load>(data: &[u8]) -> Result<Option<V>, Error>
+/// Byte range of the value stored under the key
+/// in the outermost table (`None` if there is no such value).
+pub(crate) fn item_range(data: &[u8], key: &Key)
+-> Result<Option<Range<usize>>, Error>
 {
-    V::load(&mut Loader::new(data))
+    let mut loader = Loader::new(data);
+    let head = loader.read_byte()?;
+    let TableHeader { array_len, assoc_loglen, .. } =
+        loader.load_table_header(head)?;
+    let position = |this: &Loader<&[u8]>| data.len() - this.reader.len();
+    let mut mask = 0;
+    let mut mask_len = 0;
+    let mut next_is_masked = |this: &mut Loader<&[u8]>| {
+        if mask_len == 0 {
+            mask = this.read_byte()?;
+            mask_len = 8;
+        }
+        let is_masked = (mask & 0x01) > 0;
+        mask >>= 1;
+        mask_len -= 1;
+        Ok::<_, Error>(is_masked)
+    };
+    for index in 1 ..= array_len {
+        let is_masked = next_is_masked(&mut loader)?;
+        let is_key = i32::try_from(index).ok()
+            .is_some_and(|index| *key == Key::Index(index));
+        if is_masked {
+            if is_key { return Ok(None); }
+            continue;
+        }
+        let start = position(&loader);
+        Skip::load(&mut loader)?;
+        if is_key {
+            return Ok(Some(start .. position(&loader)));
+        }
+    }
+    for _ in 0 .. iexp2(assoc_loglen) {
+        if next_is_masked(&mut loader)? {
+            continue;
+        }
+        let start = position(&loader);
+        Skip::load(&mut loader)?;
+        let end = position(&loader);
+        let item_key = Key::load_key(&mut loader)?;
+        loader.read_ext_sint()?;
+        if item_key.as_ref() == Some(key) {
+            return Ok(Some(start .. end));
+        }
+    }
+    Ok(None)
 }
 
+pub(crate) fn decode<V: Load>( data: &[u8],
+    options: DecodeOptions, warnings: &mut Warnings,
+) -> Result<Option<V>, Error>
+{
+    decode_with_progress(data, options, warnings, |_| ())
+}
 
-struct Loader<R: Read<u8>> {
+fn decode_with_progress<V, F>( data: &[u8],
+    options: DecodeOptions, warnings: &mut Warnings, progress: F,
+) -> Result<Option<V>, Error>
+where V: Load, F: FnMut(Progress)
+{
+    if let Some(key_map) = options.key_map {
+        let data = map_keys(data, key_map, options, warnings, progress)?;
+        return decode( &data,
+            DecodeOptions { key_map: None, ..options }, &mut Warnings::new() );
+    }
+    let mut loader = Loader::with_progress(data, progress);
+    loader.set_options(options);
+    let value = V::load(&mut loader)?;
+    loader.report_progress();
+    warnings.extend(loader.warnings);
+    Ok(value)
+}
+fn decode_dyn( data: &[u8],
+    options: DecodeOptions, warnings: &mut Warnings,
+    slot: &mut dyn DynLoad,
+) -> Result<(), Error>
+{
+    let mapped_data;
+    let (data, options) = match options.key_map {
+        Some(key_map) => {
+            mapped_data = map_keys(data, key_map, options, warnings, |_| ())?;
+            (&*mapped_data, DecodeOptions { key_map: None, ..options })
+        },
+        None => (data, options),
+    };
+    let mut loader = DynLoader(Loader::new(data));
+    loader.0.set_options(options);
+    slot.load_dyn(&mut loader)?;
+    let DynLoader(loader) = loader;
+    warnings.extend(loader.warnings);
+    Ok(())
+}
+
+
+/// Decode the data as a [`Value`], apply `key_map` to its table keys
+/// and encode it back, so that the tables are laid out anew
+/// for the mapped keys.
+fn map_keys<F>( data: &[u8], key_map: KeyMap,
+    options: DecodeOptions, warnings: &mut Warnings, progress: F,
+) -> Result<Vec<u8>, Error>
+where F: FnMut(Progress)
+{
+    let mut value = decode_with_progress::<Value, F>( data,
+        DecodeOptions { key_map: None, ..options }, warnings, progress )?;
+    let mut duplicate = None;
+    if let Some(ref mut value) = value {
+        value.for_each_mut(|value| {
+            let Value::Table(ref mut table) = *value else { return };
+            let mut mapped = Table::new();
+            for (key, item) in std::mem::take(table) {
+                if mapped.insert(key_map(key), item).is_some() {
+                    duplicate.get_or_insert_with(|| Error::from(
+                        "mapped keys of a table should be distinct" ));
+                }
+            }
+            *table = mapped;
+        });
+    }
+    if let Some(duplicate) = duplicate {
+        return Err(duplicate);
+    }
+    // the floats were loaded, so they are to be encoded as they are
+    let dump_options = DumpOptions {
+        float_policy: FloatPolicy::AllowNonFinite,
+        ..DumpOptions::default()
+    };
+    encode_with_progress(value, &dump_options, |_| ())
+        .map_err(|error| Error::from(error.to_string()))
+}
+
+/// The decoder as seen by [`DynLoad`]; cannot be used otherwise.
+pub struct DynLoader<'d>(Loader<&'d [u8]>);
+
+impl DynLoader<'_> {
+    pub(crate) fn load<V: Load>(&mut self) -> Result<Option<V>, Error> {
+        V::load(&mut self.0)
+    }
+}
+
+struct Loader<R: Read<u8>, P: FnMut(Progress) = fn(Progress)> {
     reader: R,
     max_array_len: u32,
+    depth: u32,
+    max_depth: u32,
+    max_seen_depth: u32,
+    canonical_varints: bool,
+    duplicate_keys: DuplicateKeys,
+    utf8: InvalidUtf8,
+    warnings: Warnings,
+    // for telling the positions of errors
+    data_len: usize,
+    values: usize,
+    next_report: usize,
+    progress: P,
 }
 
+/// Progress is reported whenever this many more bytes are decoded.
+const PROGRESS_STEP: usize = 0x4000;
+
 #[cold]
-fn error_unexpected(byte: u8) -> Error {
-    Error::from(format!("unexpected byte {byte:X}"))
+fn error_eof() -> Error {
+    Error::new(ErrorKind::Eof, "unexpected end of data")
 }
 
 #[cold]
-fn error_eof() -> Error {
-    Error::from("unexpected end of data")
+fn error_non_canonical() -> Error {
+    Error::from("integer encoding is not minimal")
+}
+
+#[cold]
+fn error_large_varint() -> Error {
+    Error::from("unexpectedly large index")
+}
+
+/// Place the bits of a variable-length integer byte at the shift,
+/// failing if they do not fit into 32 bits.
+fn shift_varint_bits(byte: u8, shift: u32) -> Result<u32, Error> {
+    let bits = u32::from(byte);
+    bits.checked_shl(shift)
+        .filter(|&shifted| shifted >> shift == bits)
+        .ok_or_else(error_large_varint)
 }
 
 #[cold]
 fn error_bad_size() -> Error {
-    Error::from(
+    Error::new( ErrorKind::TableSize,
         "Table size is too large to be correct" )
 }
 
 #[cold]
 fn error_unsupported_size() -> Error {
-    Error::from(
+    Error::new( ErrorKind::TableSize,
         "Table size is unsupported" )
 }
 
@@ -78,20 +635,69 @@ impl TableHeader {
 }
 
 impl<R: Read<u8>> Loader<R> {
-
     #[must_use]
     fn new(reader: R) -> Self {
+        Self::with_progress(reader, |_| ())
+    }
+}
+
+impl<R: Read<u8>, P: FnMut(Progress)> Loader<R, P> {
+
+    #[must_use]
+    fn with_progress(reader: R, progress: P) -> Self {
         // The most compact representation of an array element
         // is bitmask, which is eight (nil) elements per one byte.
         let max_array_len = u32::try_from(reader.len())
             .unwrap_or(u32::MAX)
             .saturating_mul(8);
+        let data_len = reader.len();
         Self {
             reader,
             max_array_len,
+            depth: 0,
+            max_depth: u32::MAX,
+            max_seen_depth: 0,
+            canonical_varints: false,
+            duplicate_keys: DuplicateKeys::default(),
+            utf8: InvalidUtf8::default(),
+            warnings: Warnings::new(),
+            data_len,
+            values: 0,
+            next_report: PROGRESS_STEP,
+            progress,
+        }
+    }
+
+    fn set_options(&mut self, options: DecodeOptions) {
+        self.canonical_varints = options.canonical_varints;
+        self.max_depth = options.max_depth;
+        self.duplicate_keys = options.duplicate_keys;
+        self.utf8 = options.utf8;
+    }
+
+    /// Count a decoded value, reporting progress now and then.
+    fn count_value(&mut self) {
+        self.values += 1;
+        if self.data_len - self.reader.len() >= self.next_report {
+            self.report_progress();
         }
     }
 
+    fn report_progress(&mut self) {
+        let bytes = self.data_len - self.reader.len();
+        self.next_report = bytes.saturating_add(PROGRESS_STEP);
+        (self.progress)(Progress {
+            bytes, total_bytes: Some(self.data_len), values: self.values });
+    }
+
+    /// The error for the byte just read.
+    #[cold]
+    fn error_unexpected(&self, byte: u8) -> Error {
+        let offset = self.data_len - self.reader.len() - 1;
+        Error::new( ErrorKind::UnexpectedByte { offset, byte },
+            format!("unexpected byte {byte:X} at position {offset}") )
+    }
+
     fn read_byte(&mut self) -> Result<u8, Error> {
         self.reader.read_byte()
             .ok_or_else(error_eof)
@@ -115,11 +721,11 @@ impl<R: Read<u8>> Loader<R> {
             let mut byte = self.read_byte()?;
             let continued = (byte & 0x01) > 0;
             byte >>= 1; next_shift -= 1;
-            if next_shift > 21 {
-                return Err(Error::from("unexpectedly large index"));
-            }
-            value += u32::from(byte) << shift;
+            value += shift_varint_bits(byte, shift)?;
             if !continued {
+                if self.canonical_varints && shift > 0 && byte == 0 {
+                    return Err(error_non_canonical());
+                }
                 break;
             }
             shift = next_shift;
@@ -140,34 +746,41 @@ impl<R: Read<u8>> Loader<R> {
                 negative = Some(byte & 0x01 > 0);
                 byte >>= 1; next_shift -= 1;
             }
-            if next_shift > 20 {
-                return Err(Error::from("unexpectedly large index"));
-            }
-            value += u32::from(byte) << shift;
+            value += shift_varint_bits(byte, shift)?;
             if !continued {
+                if self.canonical_varints && shift > 0 && byte == 0 {
+                    return Err(error_non_canonical());
+                }
                 break;
             }
             shift = next_shift;
         }
         let Some(negative) = negative else { unreachable!() };
-        let value = value as i32;
-        Ok(if !negative { value } else { -value })
+        if self.canonical_varints && negative && value == 0 {
+            return Err(error_non_canonical());
+        }
+        if !negative {
+            i32::try_from(value).map_err(|_err| error_large_varint())
+        } else {
+            // the magnitude of `i32::MIN` wraps onto itself
+            (value <= i32::MIN.unsigned_abs())
+                .then(|| (value as i32).wrapping_neg())
+                .ok_or_else(error_large_varint)
+        }
     }
 
     fn load_nil(&mut self, head: u8) -> Result<(), Error> {
-        #![allow(clippy::unused_self)]
         match head {
             0xC0 => Ok(()),
-            _ => Err(error_unexpected(head)),
+            _ => Err(self.error_unexpected(head)),
         }
     }
 
     fn load_boolean(&mut self, head: u8) -> Result<bool, Error> {
-        #![allow(clippy::unused_self)]
         match head {
             0xC2 => Ok(false),
             0xC3 => Ok(true),
-            _ => Err(error_unexpected(head)),
+            _ => Err(self.error_unexpected(head)),
         }
     }
 
@@ -180,37 +793,46 @@ impl<R: Read<u8>> Loader<R> {
                 Ok(u8::from_le_bytes(self.read_array::<1>()?) as i32),
             0xCD =>
                 Ok(u16::from_le_bytes(self.read_array::<2>()?) as i32),
-            0xCE =>
+            0xCE | 0xD2 =>
                 Ok(i32::from_le_bytes(self.read_array::<4>()?)),
             0xD0 =>
                 Ok(i8::from_le_bytes(self.read_array::<1>()?) as i32),
             0xD1 =>
                 Ok(i16::from_le_bytes(self.read_array::<2>()?) as i32),
-            0xD2 =>
-                Ok(i32::from_le_bytes(self.read_array::<4>()?)),
-            _ => Err(error_unexpected(head)),
+            _ => Err(self.error_unexpected(head)),
         }
     }
 
     fn load_float(&mut self, head: u8) -> Result<f64, Error> {
         match head {
             0xCB => Ok(f64::from_le_bytes(self.read_array::<8>()?)),
-            _ => Err(error_unexpected(head)),
+            _ => Err(self.error_unexpected(head)),
         }
     }
 
     fn load_string( &mut self,
         head: u8,
-    ) -> Result<&str, Error> {
+    ) -> Result<Cow<'_, str>, Error> {
+        let lossy = self.utf8 == InvalidUtf8::Lossy;
+        let bytes = self.load_string_bytes(head)?;
+        if lossy {
+            return Ok(String::from_utf8_lossy(bytes));
+        }
+        Ok(Cow::Borrowed(std::str::from_utf8(bytes)?))
+    }
+
+    fn load_string_bytes( &mut self,
+        head: u8,
+    ) -> Result<&[u8], Error> {
         #![allow(clippy::cast_lossless)]
         let len = match head {
             head @ 0xA0 ..= 0xBF => (head & 0x1F) as u32,
             0xD9 => u8::from_le_bytes(self.read_array::<1>()?) as u32,
             0xDA => u16::from_le_bytes(self.read_array::<2>()?) as u32,
-            _ => return Err(error_unexpected(head)),
+            _ => return Err(self.error_unexpected(head)),
         };
         let len = u32_to_usize(len);
-        Ok(std::str::from_utf8(self.read_slice(len)?)?)
+        self.read_slice(len)
     }
 
     fn load_table_header( &mut self,
@@ -245,7 +867,7 @@ impl<R: Read<u8>> Loader<R> {
                     (byte & 0x01 > 0, Some(byte >> 1))
                 };
                 if let byte @ 0x01.. = self.read_byte()? {
-                    return Err(error_unexpected(byte));
+                    return Err(self.error_unexpected(byte));
                 };
                 let array_len = if has_array_part {
                     self.read_ext_uint()?
@@ -257,16 +879,11 @@ impl<R: Read<u8>> Loader<R> {
                     assoc_last_free,
                 }
             },
-            _ => return Err(error_unexpected(head)),
+            _ => return Err(self.error_unexpected(head)),
         })
     }
 
-}
-
-impl<R: Read<u8>> LoaderTr for &mut Loader<R> {
-    type Error = Error;
-
-    fn load_value<B>(self, builder: B)
+    fn build_value<B>(&mut self, builder: B)
     -> Result<Option<B::Output>, Error>
     where B: Builder
     {
@@ -285,8 +902,17 @@ impl<R: Read<u8>> LoaderTr for &mut Loader<R> {
                 self.load_integer(head)? ),
             0xCB => builder.build_float(
                 self.load_float(head)? ),
+            0xA0 ..= 0xBF | 0xD9 | 0xDA
+                if self.utf8 == InvalidUtf8::Bytes =>
+            {
+                let bytes = self.load_string_bytes(head)?;
+                match std::str::from_utf8(bytes) {
+                    Ok(value) => builder.build_string(value),
+                    Err(_err) => builder.build_bytes(bytes),
+                }
+            },
             0xA0 ..= 0xBF | 0xD9 | 0xDA => {
-                builder.build_string(self.load_string(head)?)
+                builder.build_string(&self.load_string(head)?)
             },
             0x80 ..= 0x8F | 0x90 ..= 0x9F | 0xDC | 0xDE => {
                 let TableHeader { array_len, assoc_loglen, assoc_last_free } =
@@ -298,7 +924,7 @@ impl<R: Read<u8>> LoaderTr for &mut Loader<R> {
                     Some(rest) => rest,
                 };
                 if let Some(assoc_loglen) = assoc_loglen {
-                    if assoc_loglen > crate::MAX_ASSOC_LOGLEN {
+                    if assoc_loglen > crate::limits::MAX_ASSOC_LOGLEN {
                         return Err(error_unsupported_size());
                     }
                     self.max_array_len = match
@@ -308,16 +934,40 @@ impl<R: Read<u8>> LoaderTr for &mut Loader<R> {
                         Some(rest) => rest,
                     };
                 }
-                builder.build_table(SerialReader::new(
-                    self,
+                if self.depth >= self.max_depth {
+                    return Err(Error::new( ErrorKind::TooDeep,
+                        "tables are nested too deep" ));
+                }
+                self.depth += 1;
+                self.max_seen_depth = self.max_seen_depth.max(self.depth);
+                let result = builder.build_table(SerialReader::new(
+                    &mut *self,
                     array_len,
                     assoc_loglen, assoc_last_free,
-                ))
+                ));
+                self.depth -= 1;
+                result
             },
-            _ => Err(error_unexpected(head)),
+            _ => Err(self.error_unexpected(head)),
         }
     }
 
+}
+
+impl<R: Read<u8>, P: FnMut(Progress)> Sealed for &mut Loader<R, P> {}
+
+impl<R: Read<u8>, P: FnMut(Progress)> LoaderTr for &mut Loader<R, P> {
+    type Error = Error;
+
+    fn load_value<B>(self, builder: B)
+    -> Result<Option<B::Output>, Error>
+    where B: Builder
+    {
+        let value = self.build_value(builder)?;
+        self.count_value();
+        Ok(value)
+    }
+
     fn load_key<KB>(self, builder: KB)
     -> Result<Option<KB::Output>, Error>
     where KB: KeyBuilder
@@ -331,31 +981,36 @@ impl<R: Read<u8>> LoaderTr for &mut Loader<R> {
                 builder.build_integer::<Error>(self.load_integer(head)?)?
             )),
             0xA0 ..= 0xBF | 0xD9 | 0xDA => Ok(Some(
-                builder.build_string::<Error>(self.load_string(head)?)?
+                builder.build_string::<Error>(&self.load_string(head)?)?
+            )),
+            0xC2 | 0xC3 => Ok(Some(
+                builder.build_boolean::<Error>(self.load_boolean(head)?)?
             )),
-            _ => Err(error_unexpected(head)),
+            _ => Err(self.error_unexpected(head)),
         }
     }
 
 }
 
-struct SerialReader<'l, R, K, V>
-where R: Read<u8>, K: KeyLoad, V: Load
+struct SerialReader<'l, R, K, V, P>
+where R: Read<u8>, K: KeyLoad, V: Load, P: FnMut(Progress)
 {
-    loader: &'l mut Loader<R>,
+    loader: &'l mut Loader<R, P>,
     array_len: u32,
     assoc_loglen: Option<LogSize>,
     assoc_last_free: u32,
     assoc_len: u32,
+    dead_keys: u32,
+    linked_position: Option<u32>,
     mask: u8, mask_len: u8,
     output: PhantomData<TableItem<K, V>>,
 }
 
-impl<'l, R, K, V> SerialReader<'l, R, K, V>
-where R: Read<u8>, K: KeyLoad, V: Load
+impl<'l, R, K, V, P> SerialReader<'l, R, K, V, P>
+where R: Read<u8>, K: KeyLoad, V: Load, P: FnMut(Progress)
 {
     fn new(
-        loader: &'l mut Loader<R>,
+        loader: &'l mut Loader<R, P>,
         array_len: u32,
         assoc_loglen: Option<LogSize>, assoc_last_free: u32,
     ) -> Self {
@@ -364,6 +1019,8 @@ where R: Read<u8>, K: KeyLoad, V: Load
             array_len,
             assoc_loglen, assoc_last_free,
             assoc_len: iexp2(assoc_loglen),
+            dead_keys: 0,
+            linked_position: None,
             mask: 0, mask_len: 0,
             output: PhantomData,
         }
@@ -386,13 +1043,32 @@ where R: Read<u8>, K: KeyLoad, V: Load
         let value = V::load(&mut *self.loader)?;
         Ok(value.map(TableItem::Array))
     }
-    fn read_assoc_item(&mut self) -> Result<Option<TableItem<K, V>>, Error> {
+    fn warn_on_end(&mut self) {
+        let warnings = &mut self.loader.warnings;
+        if let Some(linked_position) = self.linked_position {
+            warnings.push(Warning::LastFreeTooLarge {
+                last_free: self.assoc_last_free, linked_position });
+        }
+        if self.dead_keys > 0 && self.loader.depth == 1 {
+            warnings.push(Warning::RootDeadKey);
+        }
+    }
+    fn read_assoc_item(&mut self, position: u32)
+    -> Result<Option<TableItem<K, V>>, Error>
+    {
         if self.next_is_masked()? {
             return Ok(None);
         }
         let value = V::load(&mut *self.loader)?;
         let key = K::load_key(&mut *self.loader)?;
         let link = self.loader.read_ext_sint()?;
+        // Nodes reached by links were placed at free positions,
+        // and free positions are taken below `last_free`.
+        if let Some(linked) = position.checked_add_signed(link)
+            .filter(|&linked| link != 0 && linked < self.assoc_last_free)
+        {
+            self.linked_position.get_or_insert(linked);
+        }
         if let Some(key) = key {
             Ok(Some(TableItem::Assoc(AssocItem::Live { value, key, link })))
         } else {
@@ -400,13 +1076,14 @@ where R: Read<u8>, K: KeyLoad, V: Load
                 return Err(Error::from(
                     "empty key should correspond to nil value" ))
             }
+            self.dead_keys += 1;
             Ok(Some(TableItem::Assoc(AssocItem::Dead { link })))
         }
     }
 }
 
-impl<'l, R, K, V> TableSize for SerialReader<'l, R, K, V>
-where R: Read<u8>, K: KeyLoad, V: Load
+impl<'l, R, K, V, P> TableSize for SerialReader<'l, R, K, V, P>
+where R: Read<u8>, K: KeyLoad, V: Load, P: FnMut(Progress)
 {
     fn array_len(&self) -> u32 {
         self.array_len
@@ -419,8 +1096,8 @@ where R: Read<u8>, K: KeyLoad, V: Load
     }
 }
 
-impl<'l, R, K, V> Iterator for SerialReader<'l, R, K, V>
-where R: Read<u8>, K: KeyLoad, V: Load
+impl<'l, R, K, V, P> Iterator for SerialReader<'l, R, K, V, P>
+where R: Read<u8>, K: KeyLoad, V: Load, P: FnMut(Progress)
 {
     type Item = Result<Option<TableItem<K, V>>, Error>;
     fn next(&mut self) -> Option<Self::Item> {
@@ -428,19 +1105,495 @@ where R: Read<u8>, K: KeyLoad, V: Load
             self.array_len -= 1;
             return Some(self.read_array_item());
         }
+        let position = iexp2(self.assoc_loglen) - self.assoc_len;
         if self.assoc_len > 0 {
             self.assoc_len -= 1;
-            return Some(self.read_assoc_item());
+            let item = self.read_assoc_item(position);
+            if self.assoc_len == 0 {
+                self.warn_on_end();
+            }
+            return Some(item);
         }
         None
     }
 }
 
-impl<'l, R, K, V> TableLoader for SerialReader<'l, R, K, V>
-where R: Read<u8>, K: KeyLoad, V: Load
+impl<'l, R, K, V, P> Sealed for SerialReader<'l, R, K, V, P>
+where R: Read<u8>, K: KeyLoad, V: Load, P: FnMut(Progress)
+{}
+
+impl<'l, R, K, V, P> TableLoader for SerialReader<'l, R, K, V, P>
+where R: Read<u8>, K: KeyLoad, V: Load, P: FnMut(Progress)
 {
     type Key = K;
     type Value = V;
     type Error = Error;
+    fn duplicate_keys(&self) -> DuplicateKeys {
+        self.loader.duplicate_keys
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange, Str,
+    error::{LoadError, LoadErrorKind},
+    dumper::{DumpOptions, Compression},
+    value::{Key, Value},
+};
+
+use super::{
+    verify, Limits, DecodeOptions, DuplicateKeys, InvalidUtf8,
+    Warning, Warnings,
+    load_blueprint, load_blueprint_with, load_blueprint_with_warnings,
+    load_blueprint_with_progress, load_blueprint_dyn,
+    decode, decode_raw,
+    load_blueprint_from, decode_raw_from,
+    skip::Skip, Loader,
+};
+
+#[test]
+fn test_verify() {
+    let info = verify(crate::test::EXCHANGE_BEHAVIOR_2, Limits::default())
+        .unwrap();
+    assert_eq!(info.kind, Exchange::Behavior(()));
+    assert!(info.compressed);
+    assert!(info.depth > 1);
+    verify( crate::test::EXCHANGE_BEHAVIOR_2,
+        Limits { max_depth: info.depth - 1, ..Limits::default() },
+    ).unwrap_err();
+    verify( crate::test::EXCHANGE_BEHAVIOR_2,
+        Limits { max_data_len: info.data_len - 1, ..Limits::default() },
+    ).unwrap_err();
+    let mut corrupted = String::from(crate::test::EXCHANGE_BEHAVIOR_2);
+    corrupted.insert(20, '0');
+    verify(&corrupted, Limits::default()).unwrap_err();
 }
 
+#[test]
+fn test_error_kinds() {
+    fn load_kind(exchange: &str) -> LoadErrorKind {
+        load_blueprint::<Value, Value, LoadError>(exchange)
+            .unwrap_err().kind()
+    }
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    assert_eq!(load_kind(""), LoadErrorKind::BadPrefix);
+    assert_eq!(load_kind(&exchange.replacen("DSC", "DSX", 1)),
+        LoadErrorKind::BadPrefix );
+    let mut corrupted = String::from(exchange);
+    corrupted.insert(20, '-');
+    assert_eq!( load_kind(&corrupted),
+        LoadErrorKind::BadBase62Char { index: 20 } );
+    assert_eq!( load_kind(&exchange[..exchange.len() - 7]),
+        LoadErrorKind::BadChecksum );
+    let (body, checksum) = exchange.split_at(exchange.len() - 1);
+    let other = if checksum == "0" { "1" } else { "0" };
+    let corrupted = format!("{body}{other}");
+    let LoadErrorKind::Checksum { expected, found } = load_kind(&corrupted)
+        else { panic!("should be a checksum mismatch") };
+    assert_eq!(found, other.parse::<u8>().unwrap());
+    assert_ne!(expected, found);
+    assert_eq!(
+        load_blueprint_with::<Value, Value>( &corrupted, DecodeOptions {
+                verify_checksum: false, ..DecodeOptions::default() }
+        ).unwrap(),
+        load_blueprint::<Value, Value, LoadError>(exchange).unwrap() );
+    // the last digit of the encoded length is in `V ..= z` range
+    let len_end = exchange.find(|c: char| c >= 'V').unwrap();
+    let mut corrupted = String::from(exchange);
+    let digit = if &exchange[len_end ..= len_end] == "z" { "y" } else { "z" };
+    corrupted.replace_range(len_end ..= len_end, digit);
+    assert_eq!(load_kind(&corrupted), LoadErrorKind::ZlibError);
+    let integer = crate::dumper::dump_blueprint::<Value, Value>(
+        Exchange::Blueprint(Some(Value::Integer(1))) ).unwrap();
+    let error = crate::blueprint::load_blueprint(&integer).unwrap_err();
+    assert_eq!(error.kind(), LoadErrorKind::ValueError);
+    let decode_kind = |data: &[u8]| decode::<Value>( data,
+        DecodeOptions::default(), &mut Warnings::new() )
+        .unwrap_err().kind();
+    // an array of two items, the second one missing or invalid
+    assert_eq!(decode_kind(&[0x92, 0x00, 0x01]), LoadErrorKind::Eof);
+    assert_eq!( decode_kind(&[0x92, 0x00, 0x01, 0xC1]),
+        LoadErrorKind::UnexpectedByte { offset: 3, byte: 0xC1 } );
+    assert_eq!( decode_kind(&[0xA2, b'a', 0xFF]), LoadErrorKind::Utf8 );
+    // an array of 255 items
+    assert_eq!( decode_kind(&[0xDC, 0xFF, 0x00, 0x00]),
+        LoadErrorKind::TableSize );
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let decode = |data: &[u8], utf8| decode::<Value>( data,
+        DecodeOptions { utf8, ..DecodeOptions::default() },
+        &mut Warnings::new() );
+    let data = [0xA2, b'a', 0xFF];
+    assert_eq!( decode(&data, InvalidUtf8::Lossy).unwrap(),
+        Some(Value::String(Str::from("a\u{FFFD}"))) );
+    let value = decode(&data, InvalidUtf8::Bytes).unwrap();
+    assert_eq!(value, Some(Value::Bytes(vec![b'a', 0xFF])));
+    assert_eq!(crate::dumper::encode(value).unwrap(), data);
+    assert_eq!( decode(&[0xA1, b'a'], InvalidUtf8::Bytes).unwrap(),
+        Some(Value::String(Str::from("a"))) );
+    // keys are always text; a table with a single `"a\xFF" = true` item
+    let table = [0x80, 0x00, 0x00, 0xC3, 0xA2, b'a', 0xFF, 0x00];
+    decode(&table, InvalidUtf8::Bytes).unwrap_err();
+    assert!(decode(&table, InvalidUtf8::Lossy).is_ok());
+}
+
+#[test]
+fn test_boolean_keys() {
+    let decode = |data: &[u8]| decode::<Value>( data,
+        DecodeOptions::default(), &mut Warnings::new() ).unwrap();
+    // a table with a single `true = 1` item
+    let data = [0x80, 0x02, 0x00, 0x01, 0xC3, 0x00];
+    let value = decode(&data);
+    assert_eq!( value, Some(Value::Table(
+        [(Key::Bool(true), Value::Integer(1))].into_iter().collect() )) );
+    assert_eq!(crate::dumper::encode(value).unwrap(), data);
+    let value = Value::Table([
+        (Key::Bool(false), Value::Integer(1)),
+        (Key::Bool(true), Value::Integer(2)),
+        (Key::Index(1), Value::Integer(3)),
+        (Key::from("name"), Value::Integer(4)),
+    ].into_iter().collect());
+    let data = crate::dumper::encode(Some(value.clone())).unwrap();
+    assert_eq!(decode(&data), Some(value));
+}
+
+#[test]
+fn test_max_depth() {
+    // arrays of a single item, nested `depth` times around an integer
+    let nested = |depth: usize| -> Vec<u8> {
+        let mut data = [0x91, 0x00].repeat(depth);
+        data.push(0x01);
+        data
+    };
+    let decode = |data: &[u8], options|
+        decode::<Value>(data, options, &mut Warnings::new());
+    let value = decode(&nested(3), DecodeOptions::default()).unwrap();
+    let shallow = DecodeOptions { max_depth: 2, ..DecodeOptions::default() };
+    assert_eq!(
+        decode(&nested(3), shallow).unwrap_err().kind(),
+        LoadErrorKind::TooDeep );
+    decode(&nested(2), shallow).unwrap();
+    // would overflow the stack without the limit
+    assert_eq!(
+        decode(&nested(1_000_000), DecodeOptions::default())
+            .unwrap_err().kind(),
+        LoadErrorKind::TooDeep );
+    let exchange = crate::dumper::dump_blueprint::<Value, Value>(
+        Exchange::Blueprint(value) ).unwrap();
+    assert_eq!(
+        verify(&exchange, Limits { max_depth: 2, ..Limits::default() })
+            .unwrap_err().kind(),
+        LoadErrorKind::TooDeep );
+}
+
+#[test]
+fn test_canonical_varints() {
+    let decode = |data, options|
+        decode::<Value>(data, options, &mut Warnings::new());
+    let strict = DecodeOptions {
+        canonical_varints: true, ..DecodeOptions::default() };
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let value = load_blueprint::<Value, Value, LoadError>(exchange).unwrap();
+    let dumped = crate::dumper::dump_blueprint::<Value, Value>(
+        value.clone() ).unwrap();
+    assert_eq!(
+        load_blueprint_with::<Value, Value>(&dumped, strict).unwrap(),
+        value );
+    // a table with a single assoc slot and `last_free` of zero
+    let canonical: &[u8] = &[0x80, 0x00, 0x01];
+    let padded: &[u8] = &[0x80, 0x01, 0x00, 0x01];
+    // a table with a single `1 = "a"` item and a link of negative zero
+    let negative_zero: &[u8] = &[0x80, 0x00, 0x00, 0xA1, b'a', 0x01, 0x02];
+    for data in [canonical, padded, negative_zero] {
+        decode(data, DecodeOptions::default()).unwrap();
+    }
+    assert_eq!(
+        decode(padded, DecodeOptions::default()).unwrap(),
+        decode(canonical, strict).unwrap() );
+    decode(padded, strict).unwrap_err();
+    decode(negative_zero, strict).unwrap_err();
+}
+
+#[test]
+fn test_key_map() {
+    fn lowercase(key: Key) -> Key {
+        match key {
+            Key::Name(name) if name.chars().any(char::is_uppercase) =>
+                Key::Name(Str::from(name.to_lowercase().as_str())),
+            Key::Index(index) => Key::Index(index + 10),
+            key => key,
+        }
+    }
+    let table = |keys: Vec<Key>| Exchange::Blueprint(Some(Value::Table(
+        keys.into_iter().zip(1 ..).map(|(key, value)|
+            (key, Value::Integer(value)) ).collect() )));
+    let options = DecodeOptions {
+        key_map: Some(lowercase), ..DecodeOptions::default() };
+    let load = |keys| load_blueprint_with::<Value, Value>(
+        &crate::dumper::dump_blueprint::<Value, Value>(table(keys)).unwrap(),
+        options );
+    // array indices are mapped as well
+    assert_eq!(
+        load(vec![
+            Key::from("Frame"), Key::from("name"), Key::Index(-1),
+            Key::Index(1), Key::Index(2) ]).unwrap(),
+        table(vec![
+            Key::from("frame"), Key::from("name"), Key::Index(9),
+            Key::Index(11), Key::Index(12) ]) );
+    // the renamed key is placed in a slot of its own
+    let mut keys = ('a' ..= 'i')
+        .map(|name| Key::Name(Str::from(name.to_string().as_str())))
+        .collect::<Vec<_>>();
+    keys.push(Key::from("Renamed"));
+    let mut mapped_keys = keys.clone();
+    mapped_keys[9] = Key::from("renamed");
+    assert_eq!(load(keys).unwrap(), table(mapped_keys));
+    assert_eq!( load(vec![Key::from("Name"), Key::from("name")])
+        .unwrap_err().kind(), LoadErrorKind::ValueError );
+}
+
+#[test]
+fn test_large_varints() {
+    let read_uint = |data: &[u8]| Loader::new(data).read_ext_uint();
+    let read_sint = |data: &[u8]| Loader::new(data).read_ext_sint();
+    assert_eq!(read_uint(&[0x01, 0x01, 0x01, 0x02]).unwrap(), 0x20_0000);
+    assert_eq!( read_uint(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1E]).unwrap(),
+        u32::MAX );
+    read_uint(&[0xFF, 0xFF, 0xFF, 0xFF, 0x3E]).unwrap_err();
+    read_uint(&[0x01, 0x01, 0x01, 0x01, 0x01, 0x00]).unwrap_err();
+    assert_eq!( read_sint(&[0xFD, 0xFF, 0xFF, 0xFF, 0x1E]).unwrap(),
+        i32::MAX );
+    assert_eq!( read_sint(&[0x03, 0x01, 0x01, 0x01, 0x20]).unwrap(),
+        i32::MIN );
+    read_sint(&[0x01, 0x01, 0x01, 0x01, 0x20]).unwrap_err();
+    read_sint(&[0x07, 0x01, 0x01, 0x01, 0x20]).unwrap_err();
+    // a table with `0x20_0000` nil items in the array part
+    // and `"a" = 1` in the assoc part
+    let mut data = vec![0x81, 0x01, 0x01, 0x01, 0x02, 0x00];
+    data.extend(std::iter::repeat(0xFF).take(0x20_0000 / 8));
+    data.extend([0x00, 0x01, 0xA1, b'a', 0x00]);
+    let Some(Value::Table(table)) = decode::<Value>( &data,
+        DecodeOptions::default(), &mut Warnings::new() ).unwrap()
+    else { panic!("should be a table") };
+    assert_eq!( table.into_iter().collect::<Vec<_>>(),
+        [(Key::from("a"), Value::Integer(1))] );
+}
+
+#[test]
+fn test_duplicate_keys() {
+    let decode = |data, duplicate_keys| decode::<Value>( data,
+        DecodeOptions { duplicate_keys, ..DecodeOptions::default() },
+        &mut Warnings::new() );
+    let single = |key: Key, value| Some(Value::Table(
+        [(key, Value::Integer(value))].into_iter().collect() ));
+    // a table with `1 = 10` in the array part and `1 = 20` in the assoc part
+    let array_assoc: &[u8] = &[
+        0x81, 0x02, 0x00, 0x00,
+        0x0A,
+        0x14, 0x01, 0x00 ];
+    // a table with `"a" = 1` in its main position
+    // linked to `"a" = 2`
+    let assoc_assoc: &[u8] = &[
+        0x82, 0x00, 0x00,
+        0x01, 0xA1, b'a', 0x04,
+        0x02, 0xA1, b'a', 0x00 ];
+    for data in [array_assoc, assoc_assoc] {
+        decode(data, DuplicateKeys::Error).unwrap_err();
+        load_blueprint::<Value, Value, LoadError>(
+            &crate::dumper::compress::compress(
+                Exchange::Blueprint(data), &DumpOptions::default() )
+        ).unwrap_err();
+    }
+    assert_eq!( decode(array_assoc, DuplicateKeys::FirstWins).unwrap(),
+        single(Key::Index(1), 10) );
+    assert_eq!( decode(array_assoc, DuplicateKeys::LastWins).unwrap(),
+        single(Key::Index(1), 20) );
+    assert_eq!( decode(assoc_assoc, DuplicateKeys::FirstWins).unwrap(),
+        single(Key::from("a"), 1) );
+    assert_eq!( decode(assoc_assoc, DuplicateKeys::LastWins).unwrap(),
+        single(Key::from("a"), 2) );
+}
+
+#[test]
+fn test_decode_raw() {
+    let Exchange::Behavior(Some(value)) = load_blueprint::<
+        Value, Value, LoadError >(crate::test::EXCHANGE_BEHAVIOR_2)
+        .unwrap() else { panic!("should be a behavior") };
+    for compression in [Compression::Never, Compression::Always] {
+        let data = crate::dumper::encode_raw::<Value, Value>(
+            Exchange::Behavior(Some(value.clone())),
+            &DumpOptions { compression, ..DumpOptions::default() },
+        ).unwrap();
+        assert_eq!(data.as_ref().unwrap()[0] == 0x78,
+            compression == Compression::Always);
+        let decoded = decode_raw::<Value, Value>( data.as_deref(),
+            DecodeOptions::default(), &mut Warnings::new() ).unwrap();
+        assert_eq!(decoded, Exchange::Behavior(Some(value.clone())));
+    }
+    decode_raw::<Value, Value>( Exchange::Blueprint(&[0x78, 0x9C, 0x00]),
+        DecodeOptions::default(), &mut Warnings::new() ).unwrap_err();
+}
+
+#[test]
+fn test_load_from() {
+    use std::io::Read as _;
+    /// Fails the test if read at all.
+    struct Unreachable;
+    impl std::io::Read for Unreachable {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            panic!("the rest of the stream should not be read")
+        }
+    }
+    fn load_from(data: impl std::io::Read, limits: Limits)
+    -> Result<Exchange<Option<Value>, Option<Value>>, LoadError>
+    {
+        load_blueprint_from( data,
+            DecodeOptions::default(), limits, &mut Warnings::new() )
+    }
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let expected = load_blueprint::<Value, Value, LoadError>(exchange)
+        .unwrap();
+    assert_eq!( load_from(exchange.as_bytes(), Limits::default()).unwrap(),
+        expected );
+    assert_eq!( load_from(b"DSX0".chain(Unreachable), Limits::default())
+        .unwrap_err().kind(), LoadErrorKind::BadPrefix );
+    assert_eq!( load_from(b"DSC0ab-".chain(Unreachable), Limits::default())
+        .unwrap_err().kind(), LoadErrorKind::BadBase62Char { index: 6 } );
+    let info = verify(exchange, Limits::default()).unwrap();
+    load_from(exchange.as_bytes().chain(Unreachable), Limits {
+        max_exchange_len: exchange.len() - 1, ..Limits::default() })
+        .unwrap_err();
+    load_from(exchange.as_bytes(), Limits {
+        max_data_len: info.data_len - 1, ..Limits::default() })
+        .unwrap_err();
+    load_from(exchange.as_bytes(), Limits {
+        max_depth: info.depth - 1, ..Limits::default() })
+        .unwrap_err();
+    assert_eq!( load_from(&b"DS"[..], Limits::default())
+        .unwrap_err().kind(), LoadErrorKind::BadPrefix );
+
+    let encode = |compression| crate::dumper::encode_raw::<Value, Value>(
+        expected.clone(),
+        &DumpOptions { compression, ..DumpOptions::default() },
+    ).unwrap();
+    let data_len = encode(Compression::Never).unwrap().len();
+    let zipped = encode(Compression::Always);
+    let decode_from = |max_data_len| decode_raw_from::<Value, Value, _>(
+        zipped.as_deref(), DecodeOptions::default(),
+        Limits { max_data_len, ..Limits::default() },
+        &mut Warnings::new() );
+    assert_eq!(decode_from(data_len).unwrap(), expected);
+    decode_from(data_len - 1).unwrap_err();
+}
+
+#[test]
+fn test_part_equivalence() {
+    let decode = |data| decode::<Value>( data,
+        DecodeOptions::default(), &mut Warnings::new() ).unwrap();
+    // `{10}`, encoded with `1 = 10` in the array part,
+    // in the assoc part, and next to a dead key in either part
+    let encodings: [&[u8]; 4] = [
+        &[0x91, 0x00, 0x0A],
+        &[0x80, 0x00, 0x00, 0x0A, 0x01, 0x00],
+        &[0x81, 0x02, 0x00, 0x00, 0x0A, 0xC0, 0xC5, 0x00],
+        &[0x82, 0x00, 0x00, 0x0A, 0x01, 0x00, 0xC0, 0xC5, 0x00],
+    ];
+    let expected = decode(encodings[0]);
+    assert_eq!( expected, Some(Value::Table(
+        [(Key::Index(1), Value::Integer(10))].into_iter().collect() )) );
+    for data in encodings {
+        assert_eq!(decode(data), expected, "{data:02X?}");
+    }
+}
+
+#[test]
+fn test_declared_array_len() {
+    // `{10, nil, nil}`, then the same with `2 = 20` in the assoc part
+    let data: &[u8] = &[0x93, 0x06, 0x0A];
+    let Some(Value::Table(mut table)) = decode::<Value>( data,
+        DecodeOptions::default(), &mut Warnings::new() ).unwrap()
+        else { panic!("should be a table") };
+    assert_eq!(table.declared_array_len(), 3);
+    assert_eq!(table.array_part_len(), 3);
+    assert_eq!( crate::dumper::encode(Some(Value::Table(table.clone())))
+        .unwrap(), data );
+    table.insert(Key::Index(20), Value::Integer(20));
+    assert_eq!(table.array_part_len(), 3);
+    table.insert(Key::Index(2), Value::Integer(2));
+    assert_eq!(table.array_part_len(), 3);
+    table.set_declared_array_len(0);
+    assert_eq!(table.array_part_len(), 2);
+}
+
+#[test]
+fn test_warnings() {
+    let mut warnings = Warnings::new();
+    // a table with `"a" = 1` linked to `"b" = 2`,
+    // with `last_free` of `2` and `1`
+    let linked = |last_free| [
+        0x82, last_free, 0x00,
+        0x01, 0xA1, b'a', 0x04,
+        0x02, 0xA1, b'b', 0x00 ];
+    decode::<Skip>(&linked(0x04), DecodeOptions::default(), &mut warnings)
+        .unwrap();
+    decode::<Skip>(&linked(0x02), DecodeOptions::default(), &mut warnings)
+        .unwrap();
+    // a table with a dead key and a `"a" = 1` item
+    let dead_key: &[u8] = &[
+        0x82, 0x00, 0x00,
+        0xC0, 0xC5, 0x00,
+        0x01, 0xA1, b'a', 0x00 ];
+    decode::<Skip>(dead_key, DecodeOptions::default(), &mut warnings)
+        .unwrap();
+    assert_eq!(warnings.into_iter().collect::<Vec<_>>(), [
+        Warning::LastFreeTooLarge { last_free: 2, linked_position: 1 },
+        Warning::RootDeadKey,
+    ]);
+    let mut warnings = Warnings::new();
+    load_blueprint_with_warnings::<Value, Value>(
+        crate::test::EXCHANGE_BEHAVIOR_2,
+        DecodeOptions::default(), &mut warnings ).unwrap();
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn test_load_dyn() {
+    let expected = load_blueprint::<Value, Value, LoadError>(
+        crate::test::EXCHANGE_BEHAVIOR_2 ).unwrap();
+    let mut blueprint = None::<Value>;
+    let mut behavior = None::<Value>;
+    let kind = load_blueprint_dyn( crate::test::EXCHANGE_BEHAVIOR_2,
+        DecodeOptions::default(), &mut Warnings::new(),
+        &mut blueprint, &mut behavior ).unwrap();
+    assert_eq!(kind, Exchange::Behavior(()));
+    assert_eq!(blueprint, None);
+    assert_eq!(Exchange::Behavior(behavior), expected);
+    let mut skipped = None::<Skip>;
+    let error = load_blueprint_dyn( crate::test::EXCHANGE_BEHAVIOR_2,
+        DecodeOptions { max_depth: 0, ..DecodeOptions::default() },
+        &mut Warnings::new(), &mut blueprint, &mut skipped ).unwrap_err();
+    assert_eq!(error.kind(), LoadErrorKind::TooDeep);
+}
+
+#[test]
+fn test_progress() {
+    let mut reports = Vec::new();
+    let Exchange::Behavior(Some(_)) = load_blueprint_with_progress::<
+        Value, Value, _ >( crate::test::EXCHANGE_BEHAVIOR_2,
+        DecodeOptions::default(), &mut Warnings::new(),
+        |progress| reports.push(progress) ).unwrap()
+    else { panic!("should be a behavior") };
+    let last = reports.last().unwrap();
+    assert_eq!(Some(last.bytes), last.total_bytes);
+    assert!(last.values > 0);
+    assert!(reports.windows(2).all(|pair| {
+        pair[0].bytes <= pair[1].bytes && pair[0].values <= pair[1].values
+    }));
+}
+
+}
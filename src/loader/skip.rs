@@ -0,0 +1,108 @@
+//! Loading values only to throw them away.
+
+use crate::{
+    common::iexp2,
+    table_iter::{TableItem, AssocItem},
+    load::{
+        Error, KeyLoad, Load,
+        KeyBuilder, Builder,
+        Loader, TableLoader,
+    },
+};
+
+/// A value that was read and then discarded.
+///
+/// Loading it goes through the whole structure of the value
+/// (including nested tables), but never allocates anything.
+pub(super) struct Skip;
+
+impl KeyLoad for Skip {
+    fn load_key<L: Loader>(loader: L) -> Result<Option<Self>, L::Error> {
+        loader.load_key(SkipBuilder)
+    }
+}
+
+impl Load for Skip {
+    fn load<L: Loader>(loader: L) -> Result<Option<Self>, L::Error> {
+        loader.load_value(SkipBuilder)
+    }
+}
+
+struct SkipBuilder;
+
+impl KeyBuilder for SkipBuilder {
+    type Output = Skip;
+
+    fn build_integer<E: Error>(self, _value: i32) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn build_string<E: Error>(self, _value: &str) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn build_boolean<E: Error>(self, _value: bool) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+}
+
+impl Builder for SkipBuilder {
+    type Key = Skip;
+    type Value = Skip;
+    type Output = Skip;
+
+    fn build_boolean<E: Error>(self, _value: bool) -> Result<Option<Skip>, E> {
+        Ok(Some(Skip))
+    }
+
+    fn build_integer<E: Error>(self, _value: i32) -> Result<Option<Skip>, E> {
+        Ok(Some(Skip))
+    }
+
+    fn build_float<E: Error>(self, _value: f64) -> Result<Option<Skip>, E> {
+        Ok(Some(Skip))
+    }
+
+    fn build_string<E: Error>(self, _value: &str) -> Result<Option<Skip>, E> {
+        Ok(Some(Skip))
+    }
+
+    fn build_bytes<E: Error>(self, _value: &[u8]) -> Result<Option<Skip>, E> {
+        Ok(Some(Skip))
+    }
+
+    /// Consume all the items, checking what can be checked
+    /// without remembering the keys
+    /// (that is, everything except the placement of keys
+    /// in the assoc part).
+    fn build_table<T>(self, items: T) -> Result<Option<Skip>, T::Error>
+    where
+        T: TableLoader<Key=Skip, Value=Skip>,
+        T::Error : Error,
+    {
+        let array_len = items.array_len();
+        let assoc_len = iexp2(items.assoc_loglen());
+        if items.assoc_last_free() > assoc_len {
+            return Err(T::Error::from(
+                "last free index should not exceed table size" ));
+        }
+        for (index, item) in (0_u32 ..).zip(items) {
+            let Some(TableItem::Assoc(assoc_item)) = item? else {
+                continue;
+            };
+            let (AssocItem::Live { link, .. } | AssocItem::Dead { link }) =
+                assoc_item;
+            if link == 0 { continue; }
+            let position = index - array_len;
+            if position.checked_add_signed(link)
+                .filter(|&next| next < assoc_len).is_none()
+            {
+                return Err(T::Error::from(
+                    "assoc node link should lead within bounds" ));
+            }
+        }
+        Ok(Some(Skip))
+    }
+
+}
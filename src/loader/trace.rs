@@ -0,0 +1,127 @@
+//! Walking the binary data token by token, for [`crate::exchange::inspect`].
+
+use crate::{
+    common::iexp2,
+    error::{LoadError as Error, LoadErrorKind as ErrorKind},
+    exchange::{Token, TokenKind},
+    limits::MAX_ASSOC_LOGLEN,
+    value::Key,
+    Str,
+};
+
+use super::{Loader, TableHeader, error_bad_size, error_unsupported_size};
+
+/// Record the tokens of the encoded value,
+/// stopping at the first error.
+/// Returns the number of bytes left after the value.
+pub(crate) fn trace(data: &[u8], max_depth: u32, tokens: &mut Vec<Token>)
+-> Result<usize, Error>
+{
+    let mut tracer = Tracer { loader: Loader::new(data), data, tokens };
+    tracer.loader.max_depth = max_depth;
+    tracer.trace_value()?;
+    Ok(tracer.loader.reader.len())
+}
+
+struct Tracer<'d, 't> {
+    loader: Loader<&'d [u8]>,
+    data: &'d [u8],
+    tokens: &'t mut Vec<Token>,
+}
+
+impl Tracer<'_, '_> {
+
+    fn offset(&self) -> usize {
+        self.data.len() - self.loader.reader.len()
+    }
+
+    /// Record the token read since the offset.
+    fn push(&mut self, offset: usize, kind: TokenKind) {
+        self.tokens.push(Token {
+            offset, len: self.offset() - offset,
+            byte: self.data[offset], depth: self.loader.depth, kind,
+        });
+    }
+
+    fn trace_value(&mut self) -> Result<(), Error> {
+        let offset = self.offset();
+        let head = self.loader.read_byte()?;
+        let kind = match head {
+            0xC0 => TokenKind::Nil,
+            0xC2 | 0xC3 => TokenKind::Boolean(self.loader.load_boolean(head)?),
+            0x00 ..= 0x7F | 0xE0 ..= 0xFF |
+            0xCC | 0xCD | 0xCE |
+            0xD0 | 0xD1 | 0xD2 =>
+                TokenKind::Integer(self.loader.load_integer(head)?),
+            0xCB => TokenKind::Float(self.loader.load_float(head)?),
+            0xA0 ..= 0xBF | 0xD9 | 0xDA =>
+                TokenKind::String(Str::from(&*self.loader.load_string(head)?)),
+            0x80 ..= 0x8F | 0x90 ..= 0x9F | 0xDC | 0xDE =>
+                return self.trace_table(offset, head),
+            _ => return Err(self.loader.error_unexpected(head)),
+        };
+        self.push(offset, kind);
+        Ok(())
+    }
+
+    fn trace_table(&mut self, offset: usize, head: u8) -> Result<(), Error> {
+        let TableHeader { array_len, assoc_loglen, assoc_last_free } =
+            self.loader.load_table_header(head)?;
+        if assoc_loglen.is_some_and(|loglen| loglen > MAX_ASSOC_LOGLEN)
+        {
+            return Err(error_unsupported_size());
+        }
+        let assoc_len = iexp2(assoc_loglen);
+        self.loader.max_array_len = self.loader.max_array_len
+            .checked_sub(array_len)
+            .and_then(|rest| rest.checked_sub(assoc_len))
+            .ok_or_else(error_bad_size)?;
+        self.push(offset, TokenKind::Table {
+            array_len, assoc_len, last_free: assoc_last_free });
+        if self.loader.depth >= self.loader.max_depth {
+            return Err(Error::new( ErrorKind::TooDeep,
+                "tables are nested too deep" ));
+        }
+        self.loader.depth += 1;
+        let mut mask = 0;
+        for index in 0 .. array_len + assoc_len {
+            if index % 8 == 0 {
+                let mask_offset = self.offset();
+                mask = self.loader.read_byte()?;
+                self.push(mask_offset, TokenKind::Mask(mask));
+            }
+            let is_masked = (mask & 0x01) > 0;
+            mask >>= 1;
+            if is_masked { continue; }
+            self.trace_value()?;
+            if index >= array_len {
+                self.trace_key()?;
+                let link_offset = self.offset();
+                let link = self.loader.read_ext_sint()?;
+                self.push(link_offset, TokenKind::Link(link));
+            }
+        }
+        self.loader.depth -= 1;
+        Ok(())
+    }
+
+    fn trace_key(&mut self) -> Result<(), Error> {
+        let offset = self.offset();
+        let head = self.loader.read_byte()?;
+        let kind = match head {
+            0xC5 => TokenKind::DeadKey,
+            0x00 ..= 0x7F | 0xE0 ..= 0xFF |
+            0xCC | 0xCD | 0xCE |
+            0xD0 | 0xD1 | 0xD2 =>
+                TokenKind::Key(Key::Index(self.loader.load_integer(head)?)),
+            0xA0 ..= 0xBF | 0xD9 | 0xDA => TokenKind::Key(Key::Name(
+                Str::from(&*self.loader.load_string(head)?) )),
+            0xC2 | 0xC3 =>
+                TokenKind::Key(Key::Bool(self.loader.load_boolean(head)?)),
+            _ => return Err(self.loader.error_unexpected(head)),
+        };
+        self.push(offset, kind);
+        Ok(())
+    }
+
+}
@@ -0,0 +1,98 @@
+//! Non-fatal oddities noticed while loading.
+
+use crate::blueprint::OpRef;
+
+/// Something unusual that does not prevent loading,
+/// but suggests the data was not written by the game as is.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// `last_free` of a table is above the position of a node
+    /// reached by a link; such nodes are placed at free positions,
+    /// which are taken below `last_free` as it decreases.
+    LastFreeTooLarge { last_free: u32, linked_position: u32 },
+    /// A removed entry left in the assoc part of the outermost table.
+    /// Nested tables often have them, but the outermost one
+    /// is built anew on every export.
+    RootDeadKey,
+    /// An instruction operation that is not in the catalog.
+    UnknownOperation { op: OpRef },
+    /// An instruction offset (`nx`, `ny`) that is not finite
+    /// or is farther than [`MAX_INSTRUCTION_OFFSET`] from the origin.
+    OffsetOutOfRange { x: f64, y: f64 },
+}
+
+/// Instruction offsets beyond this distance on either axis
+/// are reported with [`Warning::OffsetOutOfRange`].
+pub const MAX_INSTRUCTION_OFFSET: f64 = 100_000.0;
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::LastFreeTooLarge { last_free, linked_position } => write!( f,
+                "Table has last free index {last_free} \
+                 above linked position {linked_position}" ),
+            Self::RootDeadKey => write!( f,
+                "Outermost table has a dead key" ),
+            Self::UnknownOperation { ref op } => write!( f,
+                "Unknown operation {op:?}" ),
+            Self::OffsetOutOfRange { x, y } => write!( f,
+                "Instruction offset ({x}, {y}) is out of range" ),
+        }
+    }
+}
+
+/// A collector of [`Warning`]s, in the order they were noticed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Warnings {
+    warnings: Vec<Warning>,
+}
+
+impl Warnings {
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Warning> {
+        self.warnings.iter()
+    }
+
+}
+
+impl Extend<Warning> for Warnings {
+    fn extend<I: IntoIterator<Item=Warning>>(&mut self, iter: I) {
+        self.warnings.extend(iter);
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.into_iter()
+    }
+}
+
+impl<'s> IntoIterator for &'s Warnings {
+    type Item = &'s Warning;
+    type IntoIter = std::slice::Iter<'s, Warning>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
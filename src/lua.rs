@@ -0,0 +1,208 @@
+//! Conversion of generic values from and to tables of a Lua runtime.
+//!
+//! Values are converted the obvious way, with the following caveats:
+//! * Lua integers that do not fit into 32 bits, non-UTF-8 strings,
+//!   functions, userdata and other runtime objects cannot be converted
+//!   from Lua, as well as table keys other than integers and strings;
+//! * tables nested too deep (including reference cycles)
+//!   cannot be converted from Lua;
+//! * tables made by [`to_lua`] have their array and hash parts
+//!   preallocated to the sizes of the encoded table,
+//!   and [`dead_keys_to_lua`] reproduces a [`DeadKeyTable`]
+//!   as an empty table with dead keys in its hash part.
+//!   The positions of keys in the hash part depend on the string hashing
+//!   of the runtime and are not reproduced.
+
+use mlua::{Lua, Table as LuaTable, Value as LuaValue};
+
+use crate::{
+    Str,
+    loader::DEFAULT_MAX_DEPTH,
+    value::{Key, Value, Table, TableBuilder, DeadKeyTable},
+};
+
+/// Make a Lua value out of the value.
+pub fn to_lua<'lua>(lua: &'lua Lua, value: &Value)
+-> mlua::Result<LuaValue<'lua>>
+{
+    Ok(match *value {
+        Value::Boolean(value) => LuaValue::Boolean(value),
+        Value::Integer(value) => LuaValue::Integer(value.into()),
+        Value::Float(value) => LuaValue::Number(value),
+        Value::String(ref value) =>
+            LuaValue::String(lua.create_string(&**value)?),
+        Value::Bytes(ref value) =>
+            LuaValue::String(lua.create_string(value)?),
+        Value::Table(ref table) => LuaValue::Table(table_to_lua(lua, table)?),
+    })
+}
+
+fn table_to_lua<'lua>(lua: &'lua Lua, table: &Table)
+-> mlua::Result<LuaTable<'lua>>
+{
+    let lua_table = lua.create_table_with_capacity(
+        table.array_part_len(), table.assoc_part().len() )?;
+    for (key, value) in table {
+        let value = to_lua(lua, value)?;
+        match key {
+            Key::Index(index) => lua_table.raw_set(index, value)?,
+            Key::Name(name) => lua_table.raw_set(&*name, value)?,
+            Key::Bool(key) => lua_table.raw_set(key, value)?,
+        }
+    }
+    Ok(lua_table)
+}
+
+/// Make an empty Lua table with as many dead keys
+/// and as large a hash part as the table has.
+pub fn dead_keys_to_lua<'lua>(lua: &'lua Lua, table: &DeadKeyTable)
+-> mlua::Result<LuaTable<'lua>>
+{
+    let links = table.links();
+    let lua_table = lua.create_table_with_capacity(0, links.len())?;
+    let dead_len = links.iter().filter(|link| link.is_some()).count();
+    // keys set to nil stay in the hash part as dead keys
+    for index in 1 ..= dead_len {
+        lua_table.raw_set(format!("dead_{index}"), true)?;
+    }
+    for index in 1 ..= dead_len {
+        lua_table.raw_set(format!("dead_{index}"), LuaValue::Nil)?;
+    }
+    Ok(lua_table)
+}
+
+/// Make a value out of the Lua value;
+/// `nil` results in `None`.
+pub fn from_lua(value: LuaValue<'_>) -> mlua::Result<Option<Value>> {
+    value_from_lua(value, DEFAULT_MAX_DEPTH)
+}
+
+fn value_from_lua(value: LuaValue<'_>, depth: u32)
+-> mlua::Result<Option<Value>>
+{
+    Ok(Some(match value {
+        LuaValue::Nil => return Ok(None),
+        LuaValue::Boolean(value) => Value::Boolean(value),
+        LuaValue::Integer(value) => Value::Integer(
+            i32::try_from(value).map_err(|_err| conversion_error(
+                "integer", format!("integer {value} does not fit into 32 bits")
+            ))? ),
+        LuaValue::Number(value) => Value::Float(value),
+        LuaValue::String(ref value) => match value.to_str() {
+            Ok(value) => Value::String(Str::from(value)),
+            Err(_err) => Value::Bytes(value.as_bytes().to_vec()),
+        },
+        LuaValue::Table(table) => Value::Table(table_from_lua(table, depth)?),
+        ref value => return Err(conversion_error(
+            value.type_name(), "only plain data can be converted" )),
+    }))
+}
+
+fn table_from_lua(table: LuaTable<'_>, depth: u32) -> mlua::Result<Table> {
+    let Some(depth) = depth.checked_sub(1) else {
+        return Err(conversion_error(
+            "table", "tables are nested too deep" ));
+    };
+    let mut builder = TableBuilder::new();
+    for pair in table.pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        let key = match key {
+            LuaValue::Integer(index) => Key::Index(
+                i32::try_from(index).map_err(|_err| conversion_error(
+                    "integer", format!("key {index} does not fit into 32 bits")
+                ))? ),
+            LuaValue::String(ref name) => Key::from_maybe_known(
+                name.to_str().map_err(|_err| conversion_error(
+                    "string", "key is not valid UTF-8" ))? ),
+            LuaValue::Boolean(key) => Key::Bool(key),
+            ref key => return Err(conversion_error( key.type_name(),
+                "keys should be integers, strings or booleans" )),
+        };
+        if let Some(value) = value_from_lua(value, depth)? {
+            builder.insert(key, value);
+        }
+    }
+    Ok(builder.build())
+}
+
+fn conversion_error(from: &'static str, message: impl Into<String>)
+-> mlua::Error
+{
+    mlua::Error::FromLuaConversionError {
+        from, to: "Value", message: Some(message.into()) }
+}
+
+#[cfg(test)]
+mod test {
+
+use mlua::{Lua, Value as LuaValue};
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    loader::load_blueprint,
+    value::{Key, Value, DeadKeyTable},
+};
+
+use super::{to_lua, from_lua, dead_keys_to_lua};
+
+#[test]
+fn test_lua_round_trip() {
+    let Exchange::Behavior(Some(value)) = load_blueprint::<
+        Value, Value, LoadError >(crate::test::EXCHANGE_BEHAVIOR_2)
+        .unwrap() else { panic!("should be a behavior") };
+    let lua = Lua::new();
+    let lua_value = to_lua(&lua, &value).unwrap();
+    assert_eq!(from_lua(lua_value.clone()).unwrap().as_ref(), Some(&value));
+    lua.globals().set("behavior", lua_value).unwrap();
+    lua.load(r#"behavior.name = behavior.name .. "!""#).exec().unwrap();
+    let Some(Value::Table(table)) =
+        from_lua(lua.globals().get("behavior").unwrap()).unwrap()
+        else { panic!("should be a table") };
+    assert_eq!( table.get(&Key::from("name")),
+        Some(&Value::String("Test Behavior 2!".into())) );
+
+    lua.load("cycle = {}; cycle.self = cycle").exec().unwrap();
+    from_lua(lua.globals().get("cycle").unwrap()).unwrap_err();
+    from_lua(lua.load("{ [2^40] = 1 }").eval().unwrap()).unwrap_err();
+    from_lua(lua.load("{ print }").eval().unwrap()).unwrap_err();
+    assert_eq!(from_lua(LuaValue::Nil).unwrap(), None);
+}
+
+#[test]
+fn test_dead_keys_to_lua() {
+    let mut links = vec![None; 8];
+    links[0] = Some(0);
+    let table = DeadKeyTable::new(links, 8).unwrap();
+    let lua = Lua::new();
+    let lua_table = dead_keys_to_lua(&lua, &table).unwrap();
+    // The hash part should hold all eight nodes, one of them taken by
+    // a dead key: seven new keys fit in without a rehash, nine do not.
+    lua.gc_stop();
+    let keys = (1 ..= 9)
+        .map(|index| lua.create_string(format!("key_{index}")).unwrap())
+        .collect::<Vec<_>>();
+    let scratch = lua.create_table().unwrap();
+    for key in &keys {
+        scratch.raw_set(key.clone(), true).unwrap();
+    }
+    let used_memory = lua.used_memory();
+    for key in &keys[.. 7] {
+        lua_table.raw_set(key.clone(), true).unwrap();
+    }
+    assert_eq!(lua.used_memory(), used_memory);
+    for key in &keys[7 ..] {
+        lua_table.raw_set(key.clone(), true).unwrap();
+    }
+    assert!(lua.used_memory() > used_memory);
+    for key in keys {
+        lua_table.raw_set(key, LuaValue::Nil).unwrap();
+    }
+    lua.gc_restart();
+    lua.globals().set("dead", lua_table).unwrap();
+    assert!(lua.load("next(dead) == nil").eval::<bool>().unwrap());
+    assert_eq!( from_lua(lua.globals().get("dead").unwrap()).unwrap(),
+        Some(Value::Table(crate::value::Table::new())) );
+}
+
+}
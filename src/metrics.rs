@@ -0,0 +1,124 @@
+//! Timing of the phases of loading, for monitoring decode latency.
+//!
+//! Pass an [`Observer`] to [`loader::load_blueprint_with_observer`]
+//! or [`blueprint::load_blueprint_with_observer`].
+//!
+//! This is behind the `metrics` feature, since [`std::time::Instant`]
+//! is not available on every target (e.g. `wasm32-unknown-unknown`).
+//!
+//! [`loader::load_blueprint_with_observer`]:
+//!     crate::loader::load_blueprint_with_observer
+//! [`blueprint::load_blueprint_with_observer`]:
+//!     crate::blueprint::load_blueprint_with_observer
+
+use std::time::Duration;
+
+/// Called once each phase of loading is complete.
+///
+/// A phase that fails is not reported, nor are the phases after it.
+pub trait Observer {
+    /// The exchange string is unpacked (base62 and zlib)
+    /// into binary data.
+    fn decompress(&mut self, _metrics: PhaseMetrics) {}
+    /// The binary data is decoded into values.
+    fn decode(&mut self, _metrics: PhaseMetrics) {}
+    /// The values (tables) are built into a blueprint or a behavior.
+    /// Only reported by [`blueprint::load_blueprint_with_observer`].
+    ///
+    /// [`blueprint::load_blueprint_with_observer`]:
+    ///     crate::blueprint::load_blueprint_with_observer
+    fn build(&mut self, _metrics: PhaseMetrics) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PhaseMetrics {
+    /// Time the phase took.
+    pub elapsed: Duration,
+    /// Length of the input of the phase in bytes:
+    /// of the exchange string when decompressing,
+    /// of the binary data when decoding.
+    pub input_bytes: Option<usize>,
+    /// Length of the output of the phase in bytes:
+    /// of the binary data when decompressing.
+    pub output_bytes: Option<usize>,
+    /// Number of values decoded (see [`Progress::values`]),
+    /// when decoding.
+    ///
+    /// [`Progress::values`]: crate::Progress::values
+    pub values: Option<usize>,
+}
+
+impl PhaseMetrics {
+    pub(crate) fn new(elapsed: Duration) -> Self {
+        Self {
+            elapsed,
+            input_bytes: None,
+            output_bytes: None,
+            values: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    loader::{DecodeOptions, Warnings},
+    blueprint::LoadOptions,
+    value::Value,
+};
+
+use super::{Observer, PhaseMetrics};
+
+#[derive(Default)]
+struct Recorder {
+    phases: Vec<(&'static str, PhaseMetrics)>,
+}
+
+impl Observer for Recorder {
+    fn decompress(&mut self, metrics: PhaseMetrics) {
+        self.phases.push(("decompress", metrics));
+    }
+    fn decode(&mut self, metrics: PhaseMetrics) {
+        self.phases.push(("decode", metrics));
+    }
+    fn build(&mut self, metrics: PhaseMetrics) {
+        self.phases.push(("build", metrics));
+    }
+}
+
+#[test]
+fn test_observer() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let mut recorder = Recorder::default();
+    let Exchange::Behavior(_) =
+        crate::blueprint::load_blueprint_with_observer(
+            exchange, LoadOptions::default(), &mut recorder ).unwrap()
+        else { panic!("should be a behavior") };
+    let names: Vec<_> = recorder.phases.iter()
+        .map(|&(name, _)| name).collect();
+    assert_eq!(names, ["decompress", "decode", "build"]);
+    let (_, decompress) = recorder.phases[0];
+    let (_, decode) = recorder.phases[1];
+    assert_eq!(decompress.input_bytes, Some(exchange.len()));
+    assert_eq!(decode.input_bytes, decompress.output_bytes);
+    assert!(decode.values.is_some_and(|values| values > 0));
+    assert_eq!(recorder.phases[2].1.input_bytes, None);
+
+    recorder.phases.clear();
+    crate::loader::load_blueprint_with_observer::<Value, Value>(
+        exchange, DecodeOptions::default(), &mut Warnings::new(),
+        &mut recorder ).unwrap();
+    assert_eq!(recorder.phases.len(), 2);
+    recorder.phases.clear();
+    let mut damaged = exchange.to_owned();
+    damaged.pop();
+    crate::loader::load_blueprint_with_observer::<Value, Value>(
+        &damaged, DecodeOptions::default(),
+        &mut Warnings::new(), &mut recorder ).unwrap_err();
+    assert!(recorder.phases.is_empty());
+}
+
+}
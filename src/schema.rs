@@ -0,0 +1,339 @@
+//! JSON Schema of the serde representation of exchanges.
+//!
+//! The schema describes `Exchange<Blueprint, Behavior>`
+//! (see [`crate::blueprint`]) the way `serde_json` writes it,
+//! so that editors can validate user-edited JSON
+//! before trying to encode it.
+//! Keys that deserialization ignores are allowed by the schema,
+//! except for instructions, which reject unknown keys.
+//! Generic Lua values (the `extra` tables and such)
+//! are only checked to be non-null.
+
+use serde_json::{json, Value};
+
+/// The JSON Schema (draft 2020-12) of an exchange.
+#[must_use]
+pub fn exchange_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Desynced exchange",
+        "oneOf": [
+            variant("Blueprint", reference("Blueprint")),
+            variant("Behavior", reference("Behavior")),
+        ],
+        "$defs": {
+            "Blueprint": blueprint(),
+            "Component": component(),
+            "Behavior": behavior(),
+            "Parameter": parameter(),
+            "Instruction": instruction(),
+            "Operand": operand(),
+            "Jump": jump(),
+            "Place": place(),
+            "Value": value(),
+            "Coord": coord(),
+            "Int32": {
+                "type": "integer",
+                "minimum": i32::MIN,
+                "maximum": i32::MAX,
+            },
+            "LuaValue": {
+                "type": ["boolean", "number", "string", "array", "object"],
+            },
+        },
+    })
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{name}") })
+}
+
+/// An externally tagged enum variant with contents.
+fn variant(name: &str, contents: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": { name: contents },
+        "required": [name],
+        "additionalProperties": false,
+    })
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn pair(first: Value, second: Value) -> Value {
+    json!({
+        "type": "array",
+        "prefixItems": [first, second],
+        "items": false,
+        "minItems": 2,
+    })
+}
+
+fn lua_table() -> Value {
+    json!({ "type": ["object", "array"] })
+}
+
+fn blueprint() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "frame": { "type": "string" },
+            "powered": { "type": "boolean" },
+            "connected": { "type": "boolean" },
+            "logistics": {
+                "type": "object",
+                "additionalProperties": reference("LuaValue"),
+            },
+            "components": array_of(reference("Component")),
+            "registers": array_of(register()),
+            "links": array_of(pair(reference("Int32"), reference("Int32"))),
+            "locks": array_of(json!({ "type": ["string", "null"] })),
+            "extra": lua_table(),
+        },
+        "required": ["frame"],
+    })
+}
+
+fn component() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "item": { "type": "string" },
+            "index": reference("Int32"),
+            "behavior": reference("Behavior"),
+            "registers": array_of(register()),
+            "extra": lua_table(),
+        },
+        "required": ["item", "index"],
+    })
+}
+
+/// A register slot of a blueprint or a component.
+fn register() -> Value {
+    json!({
+        "oneOf": [
+            reference("Value"),
+            variant("Register", json!({
+                "type": "object",
+                "properties": {
+                    "value": reference("Value"),
+                    "extra": lua_table(),
+                },
+                "additionalProperties": false,
+            })),
+            { "type": "null" },
+        ],
+    })
+}
+
+fn behavior() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "description": { "type": "string" },
+            "parameters": array_of(reference("Parameter")),
+            "instructions": array_of(reference("Instruction")),
+            "subroutines": array_of(reference("Behavior")),
+            "extra": lua_table(),
+            "extra_indexed": array_of(pair(
+                reference("Int32"), reference("LuaValue") )),
+            "stale_parameter_names": array_of(pair(
+                reference("Int32"), json!({ "type": "string" }) )),
+            "empty_tables": array_of(json!({ "enum": [
+                "Parameters", "ParameterNames", "Subroutines",
+            ] })),
+        },
+        "required": ["instructions"],
+    })
+}
+
+fn parameter() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "is_output": { "type": "boolean" },
+        },
+        "required": ["is_output"],
+    })
+}
+
+fn instruction() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "op": { "oneOf": [{ "type": "string" }, reference("Int32")] },
+            "args": array_of(reference("Operand")),
+            "arg_count": { "type": "integer", "minimum": 0 },
+            "next": reference("Jump"),
+            "comment": { "type": "string" },
+            "label": { "type": "string" },
+            "offset": pair(
+                json!({ "type": "number" }), json!({ "type": "number" }) ),
+            "extra": {
+                "type": "object",
+                "additionalProperties": reference("LuaValue"),
+            },
+        },
+        "required": ["op"],
+        "additionalProperties": false,
+    })
+}
+
+fn operand() -> Value {
+    json!({
+        "oneOf": [
+            { "enum": ["Unset", "Skipped"] },
+            variant("Index", reference("Int32")),
+            reference("Jump"),
+            reference("Place"),
+            reference("Value"),
+        ],
+    })
+}
+
+fn jump() -> Value {
+    json!({
+        "oneOf": [
+            { "enum": ["Return", "Next"] },
+            variant("Jump", reference("Int32")),
+            variant("Label", json!({ "type": "string" })),
+        ],
+    })
+}
+
+fn place() -> Value {
+    json!({
+        "oneOf": [
+            { "const": "SkippedPlace" },
+            variant("Parameter", reference("Int32")),
+            variant("Register", json!({ "enum": [
+                "Goto", "Store", "Visual", "Signal",
+            ] })),
+            variant("Variable", json!({ "type": "string" })),
+        ],
+    })
+}
+
+fn value() -> Value {
+    json!({
+        "oneOf": [
+            { "const": "SkippedValue" },
+            variant("Number", reference("Int32")),
+            variant("Item", json!({ "type": "string" })),
+            variant("ItemCount", pair(
+                json!({ "type": "string" }), reference("Int32") )),
+            variant("Coord", reference("Coord")),
+            variant("CoordCount", pair(
+                reference("Coord"), reference("Int32") )),
+            variant("CoordList", array_of(reference("Coord"))),
+            variant("Area", json!({
+                "type": "object",
+                "properties": {
+                    "min": reference("Coord"),
+                    "max": reference("Coord"),
+                },
+                "required": ["min", "max"],
+                "additionalProperties": false,
+            })),
+        ],
+    })
+}
+
+fn coord() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "x": reference("Int32"),
+            "y": reference("Int32"),
+        },
+        "required": ["x", "y"],
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+use serde_json::Value;
+
+use super::exchange_schema;
+
+/// Check that the value has no keys the schema does not describe
+/// and all keys it requires, following object properties by name.
+/// Only the definitions whose shape is an object are examined.
+fn check_keys(defs: &Value, name: &str, value: &Value) {
+    let def = &defs[name];
+    let Some(object) = value.as_object() else { return };
+    let properties = def["properties"].as_object().unwrap();
+    for key in object.keys() {
+        assert!(properties.contains_key(key), "{name} has no {key:?}");
+    }
+    for key in def["required"].as_array().unwrap() {
+        assert!( object.contains_key(key.as_str().unwrap()),
+            "{name} misses {key}" );
+    }
+    let nested: &[(&str, &str)] = match name {
+        "Blueprint" => &[("components", "Component")],
+        "Component" => &[("behavior", "Behavior")],
+        "Behavior" => &[
+            ("instructions", "Instruction"),
+            ("subroutines", "Behavior"),
+            ("parameters", "Parameter"),
+        ],
+        _ => &[],
+    };
+    for &(key, nested_name) in nested {
+        match object.get(key) {
+            Some(Value::Array(items)) => for item in items {
+                check_keys(defs, nested_name, item);
+            },
+            Some(item) => check_keys(defs, nested_name, item),
+            None => (),
+        }
+    }
+}
+
+fn collect_refs<'v>(value: &'v Value, refs: &mut Vec<&'v str>) {
+    match value {
+        Value::Object(object) => for (key, value) in object {
+            match (key.as_str(), value) {
+                ("$ref", Value::String(reference)) => refs.push(reference),
+                _ => collect_refs(value, refs),
+            }
+        },
+        Value::Array(items) => for item in items {
+            collect_refs(item, refs);
+        },
+        _ => (),
+    }
+}
+
+#[test]
+fn test_schema() {
+    let schema = exchange_schema();
+    let defs = &schema["$defs"];
+    let mut refs = Vec::new();
+    collect_refs(&schema, &mut refs);
+    assert!(!refs.is_empty());
+    for reference in refs {
+        let name = reference.strip_prefix("#/$defs/").unwrap();
+        assert!(defs.get(name).is_some(), "dangling reference {reference}");
+    }
+    for exchange in [
+        crate::test::EXCHANGE_BEHAVIOR_2,
+        crate::test::EXCHANGE_BEHAVIOR_3_PARAM,
+        crate::test::EXCHANGE_BEHAVIOR_4_SUB,
+    ] {
+        let exchange = crate::blueprint::load_blueprint(exchange).unwrap();
+        let Value::Object(json) = serde_json::to_value(&exchange).unwrap()
+        else { panic!("exchange should be an object") };
+        let (tag, value) = json.iter().next().unwrap();
+        check_keys(defs, tag, value);
+    }
+}
+
+}
@@ -3,9 +3,11 @@ use serde::ser::{self, Error as _};
 use crate::{
     error::DumpError as Error,
     Str,
+    dumper::{DumpOptions, IntegerOverflow},
     value::{
         Key, Value,
         Table, ArrayBuilder, TableBuilder,
+        maybe_known_str,
     },
     Exchange,
 };
@@ -16,12 +18,18 @@ impl ser::Error for Error {
     }
 }
 
-pub struct Serializer(());
+pub struct Serializer {
+    options: DumpOptions,
+}
 
 impl Serializer {
     #[must_use]
     pub fn new() -> Self {
-        Self(())
+        Self::with_options(DumpOptions::default())
+    }
+    #[must_use]
+    pub fn with_options(options: DumpOptions) -> Self {
+        Self { options }
     }
     fn err_invalid() -> Error {
         Error::from(
@@ -57,13 +65,15 @@ impl ser::Serializer for Serializer {
     ) -> Result<Self::Ok, Self::Error>
     where T: ?Sized + ser::Serialize
     {
-        crate::dumper::dump_blueprint(match variant {
+        let kind = match variant {
             "Blueprint" => Exchange::Blueprint(()),
             "Behavior" => Exchange::Behavior(()),
             _ => return Err(Self::err_invalid()),
-        }.with_value(
-            value.serialize(ValueSerializer::new())?
-        ))
+        };
+        let value = value.serialize(
+            ValueSerializer::with_overflow(self.options.integer_overflow) )?;
+        crate::dumper::dump_blueprint_with(
+            kind.with_value(value), &self.options )
     }
 
     fn serialize_bool(self, _: bool) -> Result<Self::Ok, Self::Error>
@@ -177,11 +187,31 @@ impl ser::Serializer for Serializer {
 
 }
 
-struct ValueSerializer(());
+struct ValueSerializer {
+    overflow: IntegerOverflow,
+}
 
 impl ValueSerializer {
+    #[cfg(test)]
     fn new() -> Self {
-        Self(())
+        Self::with_overflow(IntegerOverflow::default())
+    }
+    fn with_overflow(overflow: IntegerOverflow) -> Self {
+        Self { overflow }
+    }
+    fn serialize_wide(self, v: i128) -> Result<Option<Value>, Error> {
+        use ser::Serializer as _;
+        if let Ok(v) = i32::try_from(v) {
+            return self.serialize_i32(v);
+        }
+        match self.overflow {
+            IntegerOverflow::Error => Err(Error::custom(format!(
+                "integer {v} does not fit into 32 bits" ))),
+            IntegerOverflow::Saturate => self.serialize_i32(
+                if v < 0 { i32::MIN } else { i32::MAX } ),
+            #[allow(clippy::cast_precision_loss)]
+            IntegerOverflow::Float => self.serialize_f64(v as f64),
+        }
     }
 }
 
@@ -212,7 +242,10 @@ impl ser::Serializer for ValueSerializer {
     { self.serialize_i32(v.into()) }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error>
-    { self.serialize_i32(v.try_into().map_err(Error::custom)?) }
+    { self.serialize_wide(v.into()) }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error>
+    { self.serialize_wide(v) }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error>
     { self.serialize_i32(v.into()) }
@@ -221,10 +254,13 @@ impl ser::Serializer for ValueSerializer {
     { self.serialize_i32(v.into()) }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error>
-    { self.serialize_i32(v.try_into().map_err(Error::custom)?) }
+    { self.serialize_wide(v.into()) }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error>
-    { self.serialize_i32(v.try_into().map_err(Error::custom)?) }
+    { self.serialize_wide(v.into()) }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error>
+    { self.serialize_wide(i128::try_from(v).unwrap_or(i128::MAX)) }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
         Ok(Some(Value::Float(v)))
@@ -238,11 +274,11 @@ impl ser::Serializer for ValueSerializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        Ok(Some(Value::String(Str::from(v))))
+        Ok(Some(Value::String(maybe_known_str(v))))
     }
 
-    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Err(Self::Error::custom("bytes are not supported"))
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::Bytes(v.to_vec())))
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
@@ -273,7 +309,7 @@ impl ser::Serializer for ValueSerializer {
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error>
     {
-        self.serialize_str(variant)
+        Ok(Some(Value::String(Str::known(variant))))
     }
 
     fn serialize_newtype_struct<T>( self,
@@ -293,20 +329,20 @@ impl ser::Serializer for ValueSerializer {
     where T: ?Sized + ser::Serialize
     {
         Ok(Some(Value::Table([
-            (variant, value.serialize(Self::new())?)
+            (variant, value.serialize(Self::with_overflow(self.overflow))?)
         ].into_iter().filter_map(|(k, v)| Some((k, v?))).collect())))
     }
 
     fn serialize_seq(self, _len: Option<usize>)
     -> Result<Self::SerializeSeq, Self::Error>
     {
-        Ok(TableArraySerializer::new())
+        Ok(TableArraySerializer::new(self.overflow))
     }
 
     fn serialize_tuple(self, _len: usize)
     -> Result<Self::SerializeTuple, Self::Error>
     {
-        Ok(TableArraySerializer::new())
+        Ok(TableArraySerializer::new(self.overflow))
     }
 
     fn serialize_tuple_struct(
@@ -315,7 +351,7 @@ impl ser::Serializer for ValueSerializer {
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error>
     {
-        Ok(TableArraySerializer::new())
+        Ok(TableArraySerializer::new(self.overflow))
     }
 
     fn serialize_tuple_variant(
@@ -326,14 +362,14 @@ impl ser::Serializer for ValueSerializer {
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
         Ok(TableArraySerializer::new_with_finisher(
-            VariantFinisher(variant)
+            VariantFinisher(variant), self.overflow
         ))
     }
 
     fn serialize_map(self, _len: Option<usize>)
     -> Result<Self::SerializeMap, Self::Error>
     {
-        Ok(TableSerializer::new())
+        Ok(TableSerializer::new(self.overflow))
     }
 
     fn serialize_struct(
@@ -341,7 +377,7 @@ impl ser::Serializer for ValueSerializer {
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        Ok(TableSerializer::new())
+        Ok(TableSerializer::new(self.overflow))
     }
 
     fn serialize_struct_variant(
@@ -351,7 +387,8 @@ impl ser::Serializer for ValueSerializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Ok(TableSerializer::new_with_finisher(VariantFinisher(variant)))
+        Ok(TableSerializer::new_with_finisher(
+            VariantFinisher(variant), self.overflow ))
     }
 
 }
@@ -383,21 +420,22 @@ impl ValueFinisher for VariantFinisher {
 struct TableArraySerializer<F: ValueFinisher> {
     f: F,
     array: ArrayBuilder<Value>,
+    overflow: IntegerOverflow,
 }
 
 impl TableArraySerializer<TrivialFinisher> {
-    fn new() -> Self {
-        Self::new_with_finisher(TrivialFinisher)
+    fn new(overflow: IntegerOverflow) -> Self {
+        Self::new_with_finisher(TrivialFinisher, overflow)
     }
 }
 
 impl<F: ValueFinisher> TableArraySerializer<F> {
-    fn new_with_finisher(f: F) -> Self {
-        Self { f, array: ArrayBuilder::new() }
+    fn new_with_finisher(f: F, overflow: IntegerOverflow) -> Self {
+        Self { f, array: ArrayBuilder::new(), overflow }
     }
     fn push<V: ser::Serialize>(&mut self, value: V) -> Result<(), Error> {
         self.array.push_option(
-            value.serialize(ValueSerializer::new())?
+            value.serialize(ValueSerializer::with_overflow(self.overflow))?
         );
         Ok(())
     }
@@ -411,24 +449,26 @@ struct TableSerializer<F: ValueFinisher> {
     f: F,
     table: TableBuilder<Value>,
     next_key: Option<Key>,
+    overflow: IntegerOverflow,
 }
 
 impl TableSerializer<TrivialFinisher> {
-    fn new() -> Self {
-        Self::new_with_finisher(TrivialFinisher)
+    fn new(overflow: IntegerOverflow) -> Self {
+        Self::new_with_finisher(TrivialFinisher, overflow)
     }
 }
 
 impl<F: ValueFinisher> TableSerializer<F> {
-    fn new_with_finisher(f: F) -> Self {
-        Self { f, table: TableBuilder::new(), next_key: None }
+    fn new_with_finisher(f: F, overflow: IntegerOverflow) -> Self {
+        Self { f, table: TableBuilder::new(), next_key: None, overflow }
     }
     fn serialize_value_with_key<V>( &mut self,
         key: Key, value: &V,
     ) -> Result<(), Error>
     where V: ?Sized + ser::Serialize
     {
-        let Some(value) = value.serialize(ValueSerializer::new())? else {
+        let Some(value) = value.serialize(
+            ValueSerializer::with_overflow(self.overflow) )? else {
             // we can't store nil values, so we just drop it
             return Ok(());
         };
@@ -510,7 +550,8 @@ impl<F: ValueFinisher> ser::SerializeMap for TableSerializer<F> {
     where T: ?Sized + ser::Serialize
     {
         let old_key = self.next_key.replace(
-            key.serialize(ValueSerializer::new())?.try_into()? );
+            key.serialize(ValueSerializer::with_overflow(self.overflow))?
+                .try_into()? );
         assert!( old_key.is_none(),
             "consequent `serialize_key` calls" );
         Ok(())
@@ -526,7 +567,10 @@ impl<F: ValueFinisher> ser::SerializeMap for TableSerializer<F> {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Some(self.end()))
+        Ok(Some(match self.end() {
+            Value::Table(table) => Value::from_maybe_tagged(table),
+            value => value,
+        }))
     }
 
 }
@@ -576,6 +620,8 @@ use crate::common::{
     serde::OptionSerdeWrap,
 };
 
+use crate::dumper::IntegerOverflow;
+
 use super::{Value, ValueSerializer};
 
 #[test]
@@ -585,6 +631,44 @@ fn test_value_ser() {
         .unwrap().into_inner();
     let value2 = value1.serialize(ValueSerializer::new()).unwrap();
     assert_eq!(value1, value2);
+    let bytes = Some(Value::Bytes(vec![b'a', 0xFF]));
+    assert_eq!(bytes.serialize(ValueSerializer::new()).unwrap(), bytes);
+}
+
+#[test]
+fn test_integer_overflow() {
+    let serialize = |v: i64, overflow| v.serialize(
+        ValueSerializer::with_overflow(overflow) );
+    assert_eq!( serialize(-7, IntegerOverflow::Error).unwrap(),
+        Some(Value::Integer(-7)) );
+    serialize(1 << 40, IntegerOverflow::Error).unwrap_err();
+    assert_eq!( serialize(1 << 40, IntegerOverflow::Saturate).unwrap(),
+        Some(Value::Integer(i32::MAX)) );
+    assert_eq!( serialize(-(1 << 40), IntegerOverflow::Saturate).unwrap(),
+        Some(Value::Integer(i32::MIN)) );
+    assert_eq!( serialize(1 << 40, IntegerOverflow::Float).unwrap(),
+        Some(Value::Float((1_i64 << 40) as f64)) );
+    assert_eq!( u64::MAX.serialize(
+            ValueSerializer::with_overflow(IntegerOverflow::Saturate) )
+        .unwrap(), Some(Value::Integer(i32::MAX)) );
+}
+
+#[test]
+fn test_known_strings() {
+    use std::collections::BTreeMap;
+    use crate::{Str, value::Key};
+    let is_static = |s: &Str| matches!(*s, Str::Static(_));
+    let map = BTreeMap::from([("op", "next"), ("unusual", "unusual")]);
+    let Some(Value::Table(table)) =
+        map.serialize(ValueSerializer::new()).unwrap()
+    else { panic!("table expected") };
+    for (key, value) in table {
+        let (Key::Name(key), Value::String(value)) = (key, value) else {
+            panic!("string keys and values expected");
+        };
+        assert_eq!(is_static(&key), &*key == "op");
+        assert_eq!(is_static(&value), &*value == "next");
+    }
 }
 
 }
@@ -2,9 +2,15 @@
 //! between `load::Load` and `load::Loader` and
 //! between `dump::Dump` and `dump::Dumper`.
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::common::LogSize;
+use crate::{
+    common::{LogSize, ilog2_ceil},
+    limits::MAX_ASSOC_LOGLEN,
+    value::Key,
+};
 
 #[derive(Debug, Clone)]
 #[allow(clippy::exhaustive_enums)]
@@ -13,7 +19,8 @@ pub enum TableItem<K, V> {
     Assoc(AssocItem<K, V>),
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[allow(clippy::exhaustive_enums)]
 pub enum AssocItem<K, V> {
     Dead { link: i32 },
@@ -70,3 +77,110 @@ pub trait TableSize {
     fn assoc_last_free(&self) -> u32;
 }
 
+
+/// Sizes of the parts of an encoded table,
+/// chosen the same way as when dumping [`crate::value::Table`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableCapacity {
+    /// Length of the array part, including holes.
+    pub array_len: u32,
+    pub assoc_loglen: Option<LogSize>,
+}
+
+#[derive(Debug, Error)]
+#[error("The table is too large to be encoded")]
+#[non_exhaustive]
+pub struct UnsupportedTableSize;
+
+impl TableCapacity {
+
+    /// Capacity for `n` entries that all go into the assoc part
+    /// (e.g. string keys).
+    /// The assoc part is the smallest power of two that fits them all.
+    pub fn for_entries(n: usize) -> Result<Self, UnsupportedTableSize> {
+        Self { array_len: 0, assoc_loglen: ilog2_ceil(n) }.checked()
+    }
+
+    /// Capacity for a table with the given (distinct) keys.
+    ///
+    /// Positive integer keys `1 ..= k` go into the array part,
+    /// where `k` is the largest key such that at least half of
+    /// the keys `1 ..= k` are present;
+    /// all other keys go into the assoc part.
+    pub fn for_keys<'k, I>(keys: I) -> Result<Self, UnsupportedTableSize>
+    where I: IntoIterator<Item=&'k Key>
+    {
+        let mut indices = Vec::new();
+        let mut other_count: usize = 0;
+        for key in keys {
+            match *key {
+                Key::Index(index) if index > 0 => indices.push(index),
+                _ => other_count += 1,
+            }
+        }
+        indices.sort_unstable();
+        let array_count = array_part_count(indices.len(), |i| indices[i]);
+        let array_len = array_count.checked_sub(1)
+            .map_or(0, |last| indices[last].unsigned_abs());
+        Self {
+            array_len,
+            assoc_loglen: ilog2_ceil(
+                other_count + (indices.len() - array_count) ),
+        }.checked()
+    }
+
+    fn checked(self) -> Result<Self, UnsupportedTableSize> {
+        match self.assoc_loglen {
+            None => Ok(self),
+            Some(loglen) if loglen <= MAX_ASSOC_LOGLEN => Ok(self),
+            Some(_) => Err(UnsupportedTableSize),
+        }
+    }
+
+}
+
+/// Number of leading keys (out of `len` sorted positive integer keys,
+/// accessed with `key_at`) that go into the array part.
+pub(crate) fn array_part_count<F>(len: usize, key_at: F) -> usize
+where F: Fn(usize) -> i32
+{
+    let mut count = len;
+    while let Some(last) = count.checked_sub(1) {
+        let max_index = count.saturating_mul(2)
+            .try_into().unwrap_or(i32::MAX);
+        if key_at(last) <= max_index {
+            break;
+        }
+        count = last;
+    }
+    count
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::value::{Key, Table, Value};
+
+use super::TableCapacity;
+
+#[test]
+fn test_table_capacity() {
+    let capacity = TableCapacity::for_entries(5).unwrap();
+    assert_eq!((capacity.array_len, capacity.assoc_loglen), (0, Some(3)));
+    assert_eq!(TableCapacity::for_entries(0).unwrap().assoc_loglen, None);
+    TableCapacity::for_entries(1 << 30).unwrap();
+    TableCapacity::for_entries((1 << 30) + 1).unwrap_err();
+    let keys = [
+        Key::Index(1), Key::Index(2), Key::Index(4), Key::Index(100),
+        Key::Index(-1), Key::from("a"),
+    ];
+    let capacity = TableCapacity::for_keys(&keys).unwrap();
+    let table: Table = keys.iter().cloned()
+        .map(|key| (key, Value::Boolean(true))).collect();
+    assert_eq!(capacity.array_len, 4);
+    assert_eq!(capacity.array_len as usize, table.array_part_len());
+    assert_eq!(capacity.assoc_loglen, Some(2));
+}
+
+}
@@ -0,0 +1,3 @@
+//! Helpers for testing code built on top of this crate.
+
+pub mod strategies;
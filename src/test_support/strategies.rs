@@ -0,0 +1,137 @@
+//! [`proptest`] strategies generating values that this crate
+//! can dump and load back.
+//!
+//! Tables get both an array part (with holes)
+//! and an assoc part (with keys that may also fall into
+//! the range of the array part), and can be nested.
+//! Dead keys cannot be represented by [`Table`] at all,
+//! so they are generated separately, see [`dead_key_table`].
+
+use proptest::{
+    prelude::{Strategy, Just, any, prop_oneof},
+    collection::vec,
+    option,
+};
+
+use crate::{
+    Str,
+    value::{Key, Value, Table, ArrayBuilder, DeadKeyTable},
+};
+
+/// Table keys: small positive integers (the usual array indices),
+/// arbitrary integers, names known to the game, and arbitrary names.
+pub fn key() -> impl Strategy<Value=Key> + Clone {
+    prop_oneof![
+        (1 ..= 16_i32).prop_map(Key::Index),
+        any::<i32>().prop_map(Key::Index),
+        prop_oneof![
+            Just("name"), Just("op"), Just("next"), Just("components"),
+        ].prop_map(Key::from),
+        "\\PC{0,12}".prop_map(|name| Key::from_maybe_known(&name)),
+    ]
+}
+
+/// Non-table values.
+/// Floats are never NaN, so that generated values
+/// are equal to themselves.
+pub fn scalar() -> impl Strategy<Value=Value> + Clone {
+    prop_oneof![
+        any::<bool>().prop_map(Value::Boolean),
+        any::<i32>().prop_map(Value::Integer),
+        any::<f64>()
+            .prop_filter("NaN is not equal to itself", |x| !x.is_nan())
+            .prop_map(Value::Float),
+        "\\PC{0,24}".prop_map(|string| Value::String(Str::from(&*string))),
+    ]
+}
+
+/// Values with tables nested up to `depth` levels.
+pub fn value(depth: u32) -> impl Strategy<Value=Value> + Clone {
+    scalar().prop_recursive(depth, 64, 8, |inner|
+        table_of(inner).prop_map(Value::Table) )
+}
+
+/// Tables with values nested up to `depth` levels.
+pub fn table(depth: u32) -> impl Strategy<Value=Table> {
+    table_of(value(depth))
+}
+
+/// Tables with the given values.
+pub fn table_of<S>(value: S) -> impl Strategy<Value=Table>
+where S: Strategy<Value=Value> + Clone
+{
+    (
+        vec(option::weighted(0.8, value.clone()), 0 .. 12),
+        vec((key(), value), 0 .. 12),
+    ).prop_map(|(array, assoc)| {
+        let mut table = ArrayBuilder::from_iter(array).build().into_builder();
+        table.extend(assoc);
+        table.build()
+    })
+}
+
+/// Tables consisting of dead keys only, with up to `2^max_loglen` slots.
+///
+/// Links only lead forward, so that chains never form loops.
+pub fn dead_key_table(max_loglen: u8) -> impl Strategy<Value=DeadKeyTable> {
+    (0 ..= max_loglen).prop_flat_map(|loglen| {
+        let len = 1_usize << loglen;
+        let slots = (0 .. len).map(|index| option::of(
+            (index .. len).prop_map(move |target| {
+                let Ok(link) = i32::try_from(target - index) else {
+                    unreachable!("table size is limited");
+                };
+                link
+            })
+        )).collect::<Vec<_>>();
+        let Ok(len) = u32::try_from(len) else {
+            unreachable!("table size is limited");
+        };
+        (slots, 0 ..= len)
+    }).prop_map(|(links, last_free)| {
+        let Ok(table) = DeadKeyTable::new(links, last_free) else {
+            unreachable!("the layout should be valid");
+        };
+        table
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+use proptest::{prelude::ProptestConfig, proptest};
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    dumper::dump_blueprint,
+    loader::load_blueprint,
+    value::{Value, DeadKeyTable},
+};
+
+use super::{value, dead_key_table};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_value_round_trip(value in value(4)) {
+        let exchange = dump_blueprint::<_, Value>(
+            Exchange::Blueprint(Some(value.clone())) ).unwrap();
+        let reloaded = load_blueprint::<Value, Value, LoadError>(&exchange)
+            .unwrap();
+        assert_eq!(reloaded, Exchange::Blueprint(Some(value)));
+    }
+
+    #[test]
+    fn test_dead_key_table_round_trip(table in dead_key_table(5)) {
+        let exchange = dump_blueprint::<_, DeadKeyTable>(
+            Exchange::Blueprint(Some(table.clone())) ).unwrap();
+        let reloaded = load_blueprint::<DeadKeyTable, DeadKeyTable, LoadError>(
+            &exchange ).unwrap();
+        assert_eq!(reloaded, Exchange::Blueprint(Some(table)));
+    }
+
+}
+
+}
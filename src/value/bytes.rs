@@ -0,0 +1,116 @@
+//! Representation of [`Value::Bytes`] in text formats.
+//!
+//! Text formats have no byte strings that read back as such,
+//! so the bytes are written as a table with the single key
+//! [`BYTES_TAG`] holding them in base64
+//! (standard alphabet, padded), e.g. `{"$bytes": "Yf8="}`.
+//! Serde and the `interop` conversions all write this form,
+//! and read it back as [`Value::Bytes`].
+//!
+//! A table of exactly this shape that was not written for bytes
+//! reads back as bytes (or a string, if they are valid UTF-8) as well.
+
+use crate::Str;
+
+use super::{Key, Value, Table};
+
+/// The key of the table representing [`Value::Bytes`].
+pub const BYTES_TAG: &str = "$bytes";
+
+const ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut word = [0; 3];
+        word[.. chunk.len()].copy_from_slice(chunk);
+        let word = u32::from_be_bytes([0, word[0], word[1], word[2]]);
+        for position in 0 .. 4 {
+            if position > chunk.len() {
+                text.push('=');
+                continue;
+            }
+            let digit = (word >> (18 - 6 * position)) & 0x3F;
+            text.push(char::from(ALPHABET[digit as usize]));
+        }
+    }
+    text
+}
+
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if text.len() % 4 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let chunk_count = text.len() / 4;
+    for (chunk_index, chunk) in text.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && chunk_index + 1 != chunk_count) {
+            return None;
+        }
+        let mut word = 0_u32;
+        for &c in &chunk[.. 4 - padding] {
+            let digit = ALPHABET.iter().position(|&a| a == c)?;
+            word = (word << 6) | u32::try_from(digit).ok()?;
+        }
+        word <<= 6 * padding;
+        let [_, first, second, third] = word.to_be_bytes();
+        bytes.extend_from_slice(&[first, second, third][.. 3 - padding]);
+    }
+    Some(bytes)
+}
+
+impl Value {
+
+    /// The table representing bytes (see the [module documentation](self)).
+    pub(crate) fn bytes_to_tagged(bytes: &[u8]) -> Table {
+        Table::from_iter([(
+            Key::from(BYTES_TAG),
+            Self::String(Str::from(&*encode_base64(bytes))),
+        )])
+    }
+
+    /// Read back the table if it represents bytes.
+    pub(crate) fn from_maybe_tagged(table: Table) -> Self {
+        let mut items = table.iter();
+        let (Some((Key::Name(key), Self::String(text))), None) =
+            (items.next(), items.next()) else { return Self::Table(table) };
+        if &*key != BYTES_TAG {
+            return Self::Table(table);
+        }
+        let Some(bytes) = decode_base64(text) else {
+            return Self::Table(table);
+        };
+        match String::from_utf8(bytes) {
+            Ok(string) => Self::String(Str::from(&*string)),
+            Err(err) => Self::Bytes(err.into_bytes()),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+
+use super::{encode_base64, decode_base64};
+
+#[test]
+fn test_base64() {
+    for (bytes, text) in [
+        (&b""[..], ""),
+        (b"a", "YQ=="),
+        (b"a\xFF", "Yf8="),
+        (b"abc", "YWJj"),
+        (b"\xFB\xFF\xBF", "+/+/"),
+    ] {
+        assert_eq!(encode_base64(bytes), text);
+        assert_eq!(decode_base64(text).as_deref(), Some(bytes));
+    }
+    for text in ["Y", "YQ=a", "YQ==YQ==", "Y===", "Y!=="] {
+        assert_eq!(decode_base64(text), None, "{text}");
+    }
+}
+
+}
@@ -0,0 +1,235 @@
+//! Sharing of equal strings of decoded values, and finding
+//! tables that repeat, e.g. in large blueprint books.
+//!
+//! Strings decoded without an [`Interner`] installed
+//! each have an allocation of their own;
+//! [`Value::deduplicate_strings`] makes equal ones share it
+//! after the fact, so that the value takes less memory
+//! while it is kept around or dumped.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{Str, Interner};
+
+use super::{Key, Value, Table, path::Path};
+
+/// Returned by [`Value::deduplicate_strings`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DedupStats {
+    /// Strings and key names replaced with a copy
+    /// shared with an equal one.
+    pub replaced: usize,
+    /// Bytes of the replaced strings that were freed
+    /// (that is, held by nothing else).
+    pub freed_bytes: usize,
+}
+
+/// A table that occurs more than once in a value;
+/// see [`Value::duplicate_subtrees`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DuplicateSubtree {
+    /// Path to the first occurrence, in key order.
+    pub path: Path,
+    /// Number of occurrences.
+    pub count: usize,
+    /// Number of values in the table, nested ones
+    /// and the table itself included.
+    pub values: usize,
+}
+
+impl Value {
+
+    /// Make equal strings of the value, and of all values
+    /// nested in it, share one allocation; key names as well.
+    ///
+    /// Known names (see [`Key::from_maybe_known`]) are not allocated,
+    /// and are left as they are.
+    pub fn deduplicate_strings(&mut self) -> DedupStats {
+        let mut sharing = Sharing::default();
+        self.for_each_mut(|value| match *value {
+            Self::String(ref mut string) => sharing.share(string),
+            Self::Table(ref mut table) => {
+                for (key, _) in table.items_mut() {
+                    if let Key::Name(ref mut name) = *key {
+                        sharing.share(name);
+                    }
+                }
+            },
+            Self::Boolean(_) | Self::Integer(_) |
+            Self::Float(_) | Self::Bytes(_) => (),
+        });
+        sharing.stats
+    }
+
+    /// Tables that occur in the value more than once,
+    /// with the most values in repeated occurrences first.
+    ///
+    /// Tables nested in a repeated table are reported as well,
+    /// if they repeat.
+    /// Floats are compared bitwise.
+    #[must_use]
+    pub fn duplicate_subtrees(&self) -> Vec<DuplicateSubtree> {
+        let Self::Table(ref root) = *self else { return Vec::new() };
+        // shapes of tables, in which nested tables are replaced
+        // with their ids, so that equal tables get the same id
+        let mut ids = HashMap::<Vec<(Key, Shape)>, usize>::new();
+        let mut table_ids = HashMap::<*const Table, usize>::new();
+        let mut subtrees = Vec::<DuplicateSubtree>::new();
+        // not recursive (see `Value::for_each_mut`)
+        let mut stack = vec![(root, Vec::new(), false)];
+        while let Some((table, path, visited)) = stack.pop() {
+            if !visited {
+                let nested: Vec<_> = table.iter()
+                    .filter_map(|(key, value)| match *value {
+                        Self::Table(ref nested) => {
+                            let mut path = path.clone();
+                            path.push(key);
+                            Some((nested, path, false))
+                        },
+                        _ => None,
+                    })
+                    .collect();
+                stack.push((table, path, true));
+                stack.extend(nested.into_iter().rev());
+                continue;
+            }
+            let mut values = 1;
+            let shape = table.iter()
+                .map(|(key, item)| (key, match *item {
+                    Self::Boolean(value) => Shape::Boolean(value),
+                    Self::Integer(value) => Shape::Integer(value),
+                    Self::Float(value) => Shape::Float(value.to_bits()),
+                    Self::String(ref value) => Shape::String(value.clone()),
+                    Self::Bytes(ref value) => Shape::Bytes(value.clone()),
+                    Self::Table(ref nested) => {
+                        let nested: *const _ = nested;
+                        let id = table_ids[&nested];
+                        values += subtrees[id].values - 1;
+                        Shape::Table(id)
+                    },
+                }))
+                .collect::<Vec<_>>();
+            values += shape.len();
+            let id = *ids.entry(shape).or_insert_with(|| {
+                subtrees.push(DuplicateSubtree {
+                    path: Path::from(path), count: 0, values });
+                subtrees.len() - 1
+            });
+            subtrees[id].count += 1;
+            let table: *const _ = table;
+            table_ids.insert(table, id);
+        }
+        subtrees.retain(|subtree| subtree.count > 1);
+        subtrees.sort_by_key(|subtree| std::cmp::Reverse(
+            (subtree.count - 1).saturating_mul(subtree.values) ));
+        subtrees
+    }
+
+}
+
+#[derive(Default)]
+struct Sharing {
+    interner: Interner,
+    stats: DedupStats,
+}
+
+impl Sharing {
+    fn share(&mut self, string: &mut Str) {
+        let Str::Shared(ref old) = *string else { return };
+        let shared = self.interner.intern_shared(old);
+        if Rc::ptr_eq(&shared, old) {
+            return;
+        }
+        self.stats.replaced += 1;
+        if Rc::strong_count(old) == 1 {
+            self.stats.freed_bytes += old.len();
+        }
+        *string = Str::shared(shared);
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum Shape {
+    Boolean(bool),
+    Integer(i32),
+    Float(u64),
+    String(Str),
+    Bytes(Vec<u8>),
+    Table(usize),
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Str,
+    common::string::SharedStr,
+};
+
+use super::{
+    super::{Key, Value, Table},
+    DedupStats,
+};
+
+fn string(value: &str) -> Value {
+    Value::String(Str::shared(SharedStr::from(value)))
+}
+
+fn component() -> Value {
+    Value::Table(Table::from_iter([
+        (Key::Index(1), string("c_small_storage")),
+        (Key::Index(2), Value::Integer(1)),
+        (Key::Name(Str::from("note")), string("the same")),
+    ]))
+}
+
+#[test]
+fn test_deduplicate_strings() {
+    let book = || Value::Table(Table::from_iter([
+        (Key::Index(1), component()),
+        (Key::Index(2), component()),
+        (Key::Index(3), string("the same")),
+        (Key::Name(Str::from("note")), Value::String(Str::known("note"))),
+    ]));
+    let mut value = book();
+    let stats = value.deduplicate_strings();
+    assert_eq!(value, book());
+    assert_eq!(stats, DedupStats {
+        replaced: 5,
+        freed_bytes: 2 * "the same".len() + "c_small_storage".len()
+            + 2 * "note".len(),
+    });
+    let Value::Table(ref table) = value else { unreachable!() };
+    let (Some(Value::Table(first)), Some(Value::Table(second))) =
+        (table.get(&Key::Index(1)), table.get(&Key::Index(2)))
+        else { panic!("should be tables") };
+    let (Some(Value::String(Str::Shared(first))),
+        Some(Value::String(Str::Shared(second)))) =
+        (first.get(&Key::Index(1)), second.get(&Key::Index(1)))
+        else { panic!("should be shared strings") };
+    assert!(SharedStr::ptr_eq(first, second));
+    assert_eq!(value.deduplicate_strings(), DedupStats::default());
+}
+
+#[test]
+fn test_duplicate_subtrees() {
+    let value = Value::Table(Table::from_iter([
+        (Key::Index(1), component()),
+        (Key::Index(2), Value::Table(Table::from_iter([
+            (Key::Index(1), component()),
+            (Key::Index(2), component()),
+        ]))),
+        (Key::Index(3), Value::Table(Table::new())),
+    ]));
+    let subtrees = value.duplicate_subtrees();
+    assert_eq!(subtrees.len(), 1);
+    assert_eq!(subtrees[0].count, 3);
+    assert_eq!(subtrees[0].values, 4);
+    assert_eq!(subtrees[0].path.to_string(), "[1]");
+    assert!(Value::Integer(1).duplicate_subtrees().is_empty());
+    assert!(component().duplicate_subtrees().is_empty());
+}
+
+}
@@ -1,15 +1,49 @@
 use crate::Str;
 
 mod table;
-pub use table::{ArrayBuilder, TableBuilder};
+pub use table::{
+    ArrayBuilder, TableBuilder, DeadKeyTable, TableStats,
+    FaithfulValue, FaithfulTable,
+    hash,
+};
+#[cfg(feature = "serde")]
 pub(crate) use table::ArrayIntoIter;
 
+mod normalize;
+pub use normalize::NormalizeOptions;
+
+mod dedup;
+pub use dedup::{DedupStats, DuplicateSubtree};
+
+#[cfg(feature = "serde")]
+mod bytes;
+#[cfg(feature = "serde")]
+pub use bytes::BYTES_TAG;
+
+pub mod path;
+
+mod typed;
+pub use typed::{TryFromTable, TableField};
+#[doc(hidden)]
+pub use typed::error_in_field;
+#[cfg(feature = "derive")]
+pub use desynced_exchange_derive::FromTable;
+
+/// Table key.
+///
+/// Keys are ordered with all indices before all names,
+/// and all names before booleans;
+/// indices are ordered as numbers,
+/// names are ordered byte-wise, and `false` goes before `true`.
 #[derive( Clone,
     PartialEq, Eq, PartialOrd, Ord, Hash )]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[allow(clippy::exhaustive_enums)]
 pub enum Key {
     Index(i32),
     Name(Str),
+    /// Lua allows boolean keys, though the game does not write them.
+    Bool(bool),
 }
 
 impl std::fmt::Debug for Key {
@@ -17,6 +51,7 @@ impl std::fmt::Debug for Key {
         match self {
             Self::Index(index) => index.fmt(f),
             Self::Name(name) => name.fmt(f),
+            Self::Bool(value) => value.fmt(f),
         }
     }
 }
@@ -27,23 +62,30 @@ impl Key {
     pub fn as_index(&self) -> Option<i32> {
         match *self {
             Self::Index(index) => Some(index),
-            Self::Name(_) => None,
+            Self::Name(_) | Self::Bool(_) => None,
         }
     }
     #[must_use]
     #[inline]
     pub fn as_name(&self) -> Option<&str> {
         match *self {
-            Self::Index(_) => None,
+            Self::Index(_) | Self::Bool(_) => None,
             Self::Name(ref name) => Some(name),
         }
     }
     #[must_use]
     pub fn from_maybe_known(name: &str) -> Self {
-        Self::Name(match find_known_name(name) {
-            Some(name) => Str::known(name),
-            None => Str::from(name),
-        })
+        Self::Name(maybe_known_str(name))
+    }
+}
+
+/// Make a string, borrowing a static copy if the name is known
+/// instead of allocating (see also [`crate::Interner`]).
+#[must_use]
+pub(crate) fn maybe_known_str(name: &str) -> Str {
+    match find_known_name(name) {
+        Some(name) => Str::known(name),
+        None => crate::common::string::intern(name),
     }
 }
 
@@ -102,16 +144,24 @@ impl From<Str> for Key {
 
 fn err_key_from_value() -> crate::error::DumpError {
     crate::error::DumpError::from(
-        "only integers ans strings can serve as keys")
+        "only integers, strings and booleans can serve as keys")
 }
 
 #[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
 #[allow(clippy::exhaustive_enums)]
 pub enum Value {
     Boolean(bool),
     Integer(i32),
     Float(f64),
     String(Str),
+    /// A string that is not valid UTF-8, loaded with
+    /// [`InvalidUtf8::Bytes`](crate::loader::InvalidUtf8::Bytes).
+    ///
+    /// Text formats represent it as a table with the single key
+    /// `"$bytes"` holding the bytes in base64, e.g. `{"$bytes": "Yf8="}`,
+    /// and read such a table back as bytes.
+    Bytes(Vec<u8>),
     Table(Table),
 }
 
@@ -124,11 +174,47 @@ impl std::fmt::Debug for Value {
             Self::Integer(value) => value.fmt(f),
             Self::Float  (value) => value.fmt(f),
             Self::String (value) => value.fmt(f),
+            Self::Bytes  (value) =>
+                write!(f, "b\"{}\"", value.escape_ascii()),
             Self::Table  (table) => table.fmt(f),
         }
     }
 }
 
+impl Value {
+    /// Items of the table ordered by key
+    /// (as `Table::sorted_iter` orders them),
+    /// or nothing if the value is not a table.
+    pub fn sorted_iter(&self) -> impl Iterator<Item=(Key, &Self)> + '_ {
+        let table = match *self {
+            Self::Table(ref table) => Some(table),
+            _ => None,
+        };
+        table.into_iter().flat_map(Table::sorted_iter)
+    }
+
+    /// Call `f` for the value and all values nested in it,
+    /// each before the values nested in it.
+    ///
+    /// Only loading limits the nesting depth of tables
+    /// (see [`DecodeOptions::max_depth`]), and values built
+    /// in memory can be nested arbitrarily deep,
+    /// so walkers go through this instead of recursing.
+    ///
+    /// [`DecodeOptions::max_depth`]: crate::loader::DecodeOptions::max_depth
+    pub(crate) fn for_each_mut<F>(&mut self, mut f: F)
+    where F: FnMut(&mut Self)
+    {
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            f(value);
+            if let Self::Table(ref mut table) = *value {
+                stack.extend(table.values_mut());
+            }
+        }
+    }
+}
+
 #[allow(clippy::use_self)]
 impl TryFrom<Value> for Key {
     type Error = crate::error::DumpError;
@@ -136,7 +222,8 @@ impl TryFrom<Value> for Key {
         Ok(match value {
             Value::Integer(number) => Key::Index(number),
             Value::String(string) => Key::Name(string),
-            Value::Boolean(_) | Value::Float(_) | Value::Table(_)
+            Value::Boolean(value) => Key::Bool(value),
+            Value::Float(_) | Value::Bytes(_) | Value::Table(_)
                 => return Err(err_key_from_value()),
         })
     }
@@ -155,6 +242,7 @@ impl From<Key> for Value {
         match value {
             Key::Index(index) => Value::Integer(index),
             Key::Name(name) => Value::String(name),
+            Key::Bool(value) => Value::Boolean(value),
         }
     }
 }
@@ -163,7 +251,7 @@ impl From<Key> for Value {
 mod load {
 
 use crate::{
-    Str,
+    common::string::intern,
     load::{
         Error,
         KeyLoad, KeyBuilder as KeyBuilderTr,
@@ -193,6 +281,10 @@ impl KeyBuilderTr for KeyBuilder {
         Ok(Key::from_maybe_known(value))
     }
 
+    fn build_boolean<E: Error>(self, value: bool) -> Result<Self::Output, E> {
+        Ok(Key::Bool(value))
+    }
+
 }
 
 impl Load for Value {
@@ -233,7 +325,14 @@ impl BuilderTr for Builder {
     fn build_string<E: Error>(self, value: &str)
     -> Result<Option<Value>, E>
     {
-        Ok(Some(Value::String(Str::from(value))))
+        Ok(Some(Value::String(intern(value))))
+    }
+
+    #[inline]
+    fn build_bytes<E: Error>(self, value: &[u8])
+    -> Result<Option<Value>, E>
+    {
+        Ok(Some(Value::Bytes(value.to_vec())))
     }
 
     fn build_table<T>(self, items: T) -> Result<Option<Value>, T::Error>
@@ -251,19 +350,35 @@ impl BuilderTr for Builder {
 
 mod dump {
 
-use crate::dump::{
-    KeyDump, Dump,
-    KeyDumper, Dumper,
+use crate::{
+    dump::{
+        KeyDump, Dump,
+        KeyDumper, Dumper,
+    },
+    dumper::{encoded_len, compress::uncompressed_len},
 };
 
 use super::{Key, Value};
 
+impl Value {
+    /// Length of the exchange string carrying the value
+    /// without compression, or `usize::MAX` if the value
+    /// is too large to be encoded.
+    /// Compression usually makes the string shorter,
+    /// so this is an upper estimate of what dumping produces.
+    #[must_use]
+    pub fn estimated_encoded_len(&self) -> usize {
+        encoded_len(Some(self)).map_or(usize::MAX, uncompressed_len)
+    }
+}
+
 impl KeyDump for Key {
     fn dump_key<D: KeyDumper>(&self, dumper: D)
     -> Result<D::Ok, D::Error> {
         match *self {
             Self::Index(index) => dumper.dump_integer(index),
             Self::Name(ref name) => dumper.dump_string(name),
+            Self::Bool(value) => dumper.dump_boolean(value),
         }
     }
 
@@ -280,6 +395,8 @@ impl Dump for Value {
                 dumper.dump_float(value),
             Self::String(ref value) =>
                 dumper.dump_string(value),
+            Self::Bytes(ref value) =>
+                dumper.dump_bytes(value),
             Self::Table(ref table) =>
                 dumper.dump_table(table.dump_iter()),
         }
@@ -289,6 +406,7 @@ impl Dump for Value {
 }
 
 
+#[cfg(feature = "serde")]
 mod de {
 
 use serde::{Deserialize, de};
@@ -316,7 +434,13 @@ impl<'de> de::Visitor<'de> for KeyVisitor {
     type Value = Key;
 
     fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "an integer or a string")
+        write!(fmt, "an integer, a string or a boolean")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        Ok(Key::Bool(v))
     }
 
     common_serde::visit_forward_to_i32!();
@@ -382,6 +506,15 @@ impl<'de> de::Visitor<'de> for ValueVisitor {
         Ok(Some(Value::String(Str::from(v))))
     }
 
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        Ok(Some(match std::str::from_utf8(v) {
+            Ok(v) => Value::String(Str::from(v)),
+            Err(_err) => Value::Bytes(v.to_vec()),
+        }))
+    }
+
     fn visit_none<E>(self) -> Result<Self::Value, E>
     where E: de::Error
     {
@@ -411,7 +544,7 @@ impl<'de> de::Visitor<'de> for ValueVisitor {
     fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
     where A: de::MapAccess<'de>
     {
-        Ok(Some(Value::Table(
+        Ok(Some(Value::from_maybe_tagged(
             super::table::de::TableVisitor::new().visit_map(map)? )))
     }
 
@@ -420,6 +553,7 @@ impl<'de> de::Visitor<'de> for ValueVisitor {
 }
 
 
+#[cfg(feature = "serde")]
 mod ser {
 
 use ::serde::{Serialize, ser};
@@ -435,6 +569,7 @@ impl Serialize for Key {
         match self {
             Self::Index(index) => index.serialize(ser),
             Self::Name (name)  => name .serialize(ser),
+            Self::Bool (value) => value.serialize(ser),
         }
     }
 }
@@ -448,6 +583,8 @@ impl Serialize for Value {
             Self::Integer(value) => value.serialize(ser),
             Self::Float  (value) => value.serialize(ser),
             Self::String (value) => value.serialize(ser),
+            Self::Bytes  (value) =>
+                Self::bytes_to_tagged(value).serialize(ser),
             Self::Table  (table) => table.serialize(ser),
         }
     }
@@ -461,6 +598,7 @@ common_serde::impl_flat_se_option!(Value);
 #[cfg(test)]
 mod test {
 
+#[cfg(feature = "serde")]
 use crate::common::{
     TransparentRef,
     serde::{OptionSerdeWrap, OptionRefSerdeWrap},
@@ -468,6 +606,7 @@ use crate::common::{
 
 use super::Value;
 
+#[cfg(feature = "serde")]
 #[test]
 fn test_value_serde() {
     let value: Option<Value> =
@@ -478,5 +617,80 @@ fn test_value_serde() {
     assert_eq!(ron_again.as_str(), crate::test::RON_VALUE_1_COMPACT);
 }
 
+#[cfg(feature = "serde")]
+#[test]
+fn test_bytes_serde() {
+    let bytes = Value::Bytes(vec![b'a', 0xFF]);
+    let json = serde_json::to_string(&bytes).unwrap();
+    assert_eq!(json, r#"{"$bytes":"Yf8="}"#);
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), bytes);
+    let ron = ron::to_string(&bytes).unwrap();
+    assert_eq!(ron::from_str::<Value>(&ron).unwrap(), bytes);
+    // valid UTF-8 and other shapes are not bytes
+    assert_eq!( serde_json::from_str::<Value>(r#"{"$bytes":"YQ=="}"#)
+        .unwrap(), Value::String("a".into()) );
+    for json in [r#"{"$bytes":"Y"}"#, r#"{"$bytes":"YQ==","x":1}"#] {
+        let Value::Table(_) = serde_json::from_str::<Value>(json).unwrap()
+            else { panic!("should be a table") };
+    }
+}
+
+#[test]
+fn test_size_estimate() {
+    use crate::{
+        Exchange,
+        error::LoadError,
+        loader::load_blueprint,
+        dumper::{
+            DumpOptions, Compression,
+            dump_blueprint_with, encode,
+        },
+    };
+    use super::{Key, Table, TableStats};
+    let table = Table::from_iter([
+        (Key::Index(1), Value::Integer(1)),
+        (Key::Index(2), Value::Integer(2)),
+        (Key::Index(4), Value::Integer(4)),
+        (Key::from("name"), Value::Boolean(true)),
+    ]);
+    assert_eq!(table.size_stats(), TableStats {
+        array_len: 4, assoc_len: 1, assoc_free: 0,
+        encoded_len: encode(Some(Value::Table(table.clone())))
+            .unwrap().len(),
+    });
+    let Exchange::Behavior(Some(value)) = load_blueprint::<
+        Value, Value, LoadError >(crate::test::EXCHANGE_BEHAVIOR_2)
+        .unwrap() else { panic!("should be a behavior") };
+    let mut options = DumpOptions::default();
+    options.compression = Compression::Never;
+    let uncompressed = dump_blueprint_with(
+        Exchange::<Option<Value>, _>::Behavior(Some(value.clone())),
+        &options ).unwrap();
+    assert_eq!(value.estimated_encoded_len(), uncompressed.len());
+    let Value::Table(ref table) = value else { panic!("should be a table") };
+    assert_eq!( table.size_stats().encoded_len,
+        encode(Some(value.clone())).unwrap().len() );
+}
+
+#[test]
+fn test_sorted_iter() {
+    use super::{Key, Table};
+    let value = Value::Table(Table::from_iter([
+        (Key::from("b"), Value::Integer(1)),
+        (Key::Index(2), Value::Integer(2)),
+        (Key::from("a"), Value::Integer(3)),
+        (Key::Index(-1), Value::Integer(4)),
+        (Key::Index(0), Value::Integer(5)),
+    ]));
+    assert_eq!(
+        value.sorted_iter().map(|(key, _)| key).collect::<Vec<_>>(),
+        [ Key::Index(-1), Key::Index(0), Key::Index(2),
+          Key::from("a"), Key::from("b") ] );
+    #[cfg(feature = "serde")]
+    assert_eq!( ron::to_string(&value).unwrap(),
+        r#"{-1:4,0:5,2:2,"a":3,"b":1}"# );
+    assert_eq!(Value::Integer(1).sorted_iter().count(), 0);
+}
+
 }
 
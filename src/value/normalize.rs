@@ -0,0 +1,104 @@
+//! Canonical form of decoded values.
+//!
+//! A [`Table`](super::Table) already keeps its items sorted by key,
+//! and the split into the array and assoc parts is derived from the keys
+//! when the table is encoded; tables consisting of dead keys
+//! are loaded as empty tables.
+//! So two values decoded from differently laid out tables compare equal
+//! as long as their items do.
+//! What is left to normalize are the scalars themselves,
+//! e.g. the same number may be encoded either as an integer
+//! or as a float.
+
+use super::Value;
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NormalizeOptions {
+    /// Replace floats holding exact 32-bit integers with integers.
+    /// Negative zero is kept as a float.
+    pub float_integers: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self { float_integers: true }
+    }
+}
+
+impl Value {
+    /// Bring the value, and all values nested in it, to canonical form.
+    ///
+    /// Should be applied before comparing or hashing decoded values
+    /// that could have been encoded by different means.
+    pub fn normalize(&mut self, options: &NormalizeOptions) {
+        self.for_each_mut(|value| match *value {
+            Self::Float(number) if options.float_integers => {
+                if let Some(number) = float_integer(number) {
+                    *value = Self::Integer(number);
+                }
+            },
+            Self::Boolean(_) | Self::Integer(_) | Self::Float(_) |
+            Self::String(_) | Self::Bytes(_) | Self::Table(_) => (),
+        });
+    }
+}
+
+fn float_integer(number: f64) -> Option<i32> {
+    // an exact round trip through `i32` is the point
+    #![allow(clippy::cast_possible_truncation, clippy::float_cmp)]
+    let integer = number as i32;
+    if f64::from(integer) != number {
+        return None;
+    }
+    if integer == 0 && number.is_sign_negative() {
+        return None;
+    }
+    Some(integer)
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::Str;
+
+use super::{
+    super::{Key, Value, Table},
+    NormalizeOptions,
+};
+
+#[test]
+fn test_normalize() {
+    let mut value = Value::Table(Table::from_iter([
+        (Key::Index(1), Value::Float(3.0)),
+        (Key::Index(2), Value::Float(-0.0)),
+        (Key::Index(3), Value::Float(0.5)),
+        (Key::Index(4), Value::Float(1e10)),
+        (Key::Index(5), Value::Float(f64::NAN)),
+        (Key::Name(Str::from("x")), Value::Table(Table::from_iter([
+            (Key::Index(-7), Value::Float(-7.0)),
+            (Key::Name(Str::from("y")), Value::String(Str::from("1.0"))),
+        ]))),
+    ]));
+    let mut unchanged = value.clone();
+    unchanged.normalize(&NormalizeOptions { float_integers: false });
+    value.normalize(&NormalizeOptions::default());
+    let Value::Table(ref table) = value else { unreachable!() };
+    let Value::Table(ref unchanged) = unchanged else { unreachable!() };
+    assert_eq!(unchanged.get(&Key::Index(1)), Some(&Value::Float(3.0)));
+    assert_eq!(table.get(&Key::Index(1)), Some(&Value::Integer(3)));
+    assert!(matches!( table.get(&Key::Index(2)),
+        Some(&Value::Float(zero)) if zero.is_sign_negative() ));
+    assert_eq!(table.get(&Key::Index(3)), Some(&Value::Float(0.5)));
+    assert_eq!(table.get(&Key::Index(4)), Some(&Value::Float(1e10)));
+    assert!(matches!( table.get(&Key::Index(5)),
+        Some(&Value::Float(nan)) if nan.is_nan() ));
+    assert_eq!(
+        table.get(&Key::Name(Str::from("x"))),
+        Some(&Value::Table(Table::from_iter([
+            (Key::Index(-7), Value::Integer(-7)),
+            (Key::Name(Str::from("y")), Value::String(Str::from("1.0"))),
+        ]))) );
+}
+
+}
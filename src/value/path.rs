@@ -0,0 +1,236 @@
+//! Addressing values nested in tables.
+//!
+//! A path is a sequence of keys, written as names separated by dots
+//! and integer indices in brackets, e.g. `components[2].behavior[5].op`;
+//! boolean keys are written in brackets as well (`[true]`).
+//! Keys are the ones in the encoded tables (so instructions
+//! of a behavior are `[1]`, `[2]`, … of the behavior itself).
+//! Names cannot contain `.`, `[` or `]`.
+
+use thiserror::Error;
+
+use super::{Key, Value, Table};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Path {
+    keys: Vec<Key>,
+}
+
+#[derive(Debug, Clone, Copy, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathError {
+    #[error("Path is malformed at byte {position}")]
+    Syntax { position: usize },
+    #[error("Path is empty")]
+    Empty,
+    /// The value reached by the first `depth` keys
+    /// is not a table.
+    #[error("Value on the path at depth {depth} is not a table")]
+    NotTable { depth: usize },
+}
+
+/// The value at the path, if there is one.
+pub fn get<'v>(value: &'v Value, path: &str)
+-> Result<Option<&'v Value>, PathError>
+{
+    Ok(path.parse::<Path>()?.get(value))
+}
+
+impl Path {
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn name(mut self, name: &str) -> Self {
+        self.keys.push(Key::from_maybe_known(name));
+        self
+    }
+
+    #[must_use]
+    pub fn index(mut self, index: i32) -> Self {
+        self.keys.push(Key::Index(index));
+        self
+    }
+
+    #[must_use]
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    #[must_use]
+    pub fn get<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.keys.iter().try_fold(value, |value, key| match *value {
+            Value::Table(ref table) => table.get(key),
+            _ => None,
+        })
+    }
+
+    /// Store the value at the path, returning the value it replaces.
+    ///
+    /// Missing tables on the path are created;
+    /// other values on the path are not replaced with tables.
+    pub fn set(&self, root: &mut Value, value: Value)
+    -> Result<Option<Value>, PathError>
+    {
+        let Some((last, keys)) = self.keys.split_last() else {
+            return Ok(Some(std::mem::replace(root, value)));
+        };
+        let mut current = root;
+        for (depth, key) in keys.iter().enumerate() {
+            let Value::Table(ref mut table) = *current else {
+                return Err(PathError::NotTable { depth });
+            };
+            if table.get(key).is_none() {
+                table.insert(key.clone(), Value::Table(Table::new()));
+            }
+            let Some(next) = table.get_mut(key) else { unreachable!() };
+            current = next;
+        }
+        let Value::Table(ref mut table) = *current else {
+            return Err(PathError::NotTable { depth: keys.len() });
+        };
+        Ok(table.insert(last.clone(), value))
+    }
+
+    /// Remove the value at the path, as assigning `nil` in Lua does.
+    ///
+    /// The table keeps no trace of the key
+    /// (it is not kept as a dead key when encoded),
+    /// and other items keep their keys,
+    /// so removing an item of an array leaves a hole.
+    /// Removing a missing value does nothing.
+    pub fn remove(&self, root: &mut Value)
+    -> Result<Option<Value>, PathError>
+    {
+        let Some((last, keys)) = self.keys.split_last() else {
+            return Err(PathError::Empty);
+        };
+        let mut current = root;
+        for (depth, key) in keys.iter().enumerate() {
+            let Value::Table(ref mut table) = *current else {
+                return Err(PathError::NotTable { depth });
+            };
+            let Some(next) = table.get_mut(key) else { return Ok(None) };
+            current = next;
+        }
+        let Value::Table(ref mut table) = *current else {
+            return Err(PathError::NotTable { depth: keys.len() });
+        };
+        Ok(table.remove(last))
+    }
+
+}
+
+impl From<Vec<Key>> for Path {
+    fn from(keys: Vec<Key>) -> Self {
+        Self { keys }
+    }
+}
+
+impl std::str::FromStr for Path {
+    type Err = PathError;
+    fn from_str(path: &str) -> Result<Self, PathError> {
+        let mut keys = Vec::new();
+        let mut rest = path;
+        while !rest.is_empty() {
+            let position = path.len() - rest.len();
+            let error = PathError::Syntax { position };
+            if let Some(tail) = rest.strip_prefix('[') {
+                let end = tail.find(']').ok_or(error)?;
+                let (index, tail) = tail.split_at(end);
+                keys.push(match index {
+                    "true" => Key::Bool(true),
+                    "false" => Key::Bool(false),
+                    _ => Key::Index(index.parse().map_err(|_err| error)?),
+                });
+                (_, rest) = tail.split_at(1);
+                continue;
+            }
+            let name = if keys.is_empty() { rest } else {
+                rest.strip_prefix('.').ok_or(error)?
+            };
+            let end = name.find(['.', '[', ']']).unwrap_or(name.len());
+            let (name, tail) = name.split_at(end);
+            if name.is_empty() {
+                return Err(error);
+            }
+            keys.push(Key::from_maybe_known(name));
+            rest = tail;
+        }
+        Ok(Self { keys })
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (position, key) in self.keys.iter().enumerate() {
+            match *key {
+                Key::Index(index) => write!(f, "[{index}]")?,
+                Key::Name(ref name) if position == 0 =>
+                    f.write_str(name)?,
+                Key::Name(ref name) => write!(f, ".{}", &**name)?,
+                Key::Bool(value) => write!(f, "[{value}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Path> for Vec<Key> {
+    fn from(path: Path) -> Self {
+        path.keys
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::value::{Key, Value, Table};
+
+use super::{get, Path, PathError};
+
+#[test]
+fn test_path() {
+    let path: Path = "components[2].behavior[-5].op".parse().unwrap();
+    assert_eq!(path, Path::new()
+        .name("components").index(2).name("behavior").index(-5).name("op") );
+    assert_eq!(path.to_string(), "components[2].behavior[-5].op");
+    assert_eq!( "[1][2]".parse::<Path>().unwrap(),
+        Path::new().index(1).index(2) );
+    assert_eq!("".parse::<Path>().unwrap(), Path::new());
+    for (bad, position) in [
+        (".a", 0), ("a..b", 1), ("a[x]", 1), ("a[1", 1),
+        ("a]", 1), ("a[1]b", 4),
+    ] {
+        assert_eq!( bad.parse::<Path>().unwrap_err(),
+            PathError::Syntax { position }, "{bad}" );
+    }
+
+    let mut value = Value::Table(Table::new());
+    let path = Path::new().name("a").index(2);
+    assert_eq!(path.set(&mut value, Value::Integer(1)), Ok(None));
+    assert_eq!( path.set(&mut value, Value::Integer(2)),
+        Ok(Some(Value::Integer(1))) );
+    assert_eq!(get(&value, "a[2]"), Ok(Some(&Value::Integer(2))));
+    assert_eq!(get(&value, "a[1]"), Ok(None));
+    assert_eq!(get(&value, "a[2].b"), Ok(None));
+    assert_eq!(
+        Path::new().name("a").index(2).name("b")
+            .set(&mut value, Value::Integer(3)),
+        Err(PathError::NotTable { depth: 2 }) );
+    Path::new().name("a").index(1).set(&mut value, Value::Integer(1)).unwrap();
+    assert_eq!( Path::new().name("a").index(1).remove(&mut value),
+        Ok(Some(Value::Integer(1))) );
+    assert_eq!(Path::new().name("b").index(1).remove(&mut value), Ok(None));
+    assert_eq!(Path::new().remove(&mut value), Err(PathError::Empty));
+    assert_eq!(value, Value::Table(Table::from_iter([
+        (Key::from("a"), Value::Table(Table::from_iter([
+            (Key::Index(2), Value::Integer(2)),
+        ]))),
+    ])));
+}
+
+}
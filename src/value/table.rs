@@ -1,18 +1,34 @@
-use std::{iter::FusedIterator, ops::Range};
+use std::{collections::BTreeMap, iter::FusedIterator, ops::Range};
 
 use thiserror::Error;
 
+use crate::table_iter::array_part_count;
+
 use super::Key;
 
 mod assoc;
 
+pub mod hash;
+
+mod dead;
+pub use dead::DeadKeyTable;
+
+mod faithful;
+pub use faithful::{FaithfulValue, FaithfulTable};
+
 #[derive(Clone)]
 pub struct Table<V> {
     items: Vec<(Key, V)>,
     // the range of positive integer keys
     indices: Range<usize>,
+    // the array length to encode at least, including trailing holes
+    declared_array_len: usize,
 }
 
+/// Tables are equal if they have equal values under equal keys.
+/// Whether an item came from the array or the assoc part
+/// of the encoding does not matter, and dead keys are not kept;
+/// the declared array length is ignored as well.
 impl<V: PartialEq> PartialEq for Table<V> {
     fn eq(&self, other: &Self) -> bool {
         self.items == other.items
@@ -30,7 +46,7 @@ impl<V: std::fmt::Debug> std::fmt::Debug for Table<V> {
 impl<V> Table<V> {
     #[must_use]
     pub fn new() -> Self {
-        Self { items: Vec::new(), indices: 0..0 }
+        Self { items: Vec::new(), indices: 0..0, declared_array_len: 0 }
     }
     #[must_use]
     pub fn len(&self) -> usize {
@@ -46,8 +62,64 @@ impl<V> Table<V> {
             Err(_) => None,
         }
     }
+    pub fn get_mut(&mut self, key: &Key) -> Option<&mut V> {
+        match self.find_item(key) {
+            Ok(index) => Some(&mut self.items[index].1),
+            Err(_) => None,
+        }
+    }
+    /// Returns the value previously stored under the key.
+    pub fn insert(&mut self, key: Key, value: V) -> Option<V> {
+        match self.find_item(&key) {
+            Ok(index) =>
+                Some(std::mem::replace(&mut self.items[index].1, value)),
+            Err(index) => {
+                self.items.insert(index, (key, value));
+                self.indices = 0 .. 0;
+                self.indices_fix();
+                None
+            },
+        }
+    }
+    pub fn remove(&mut self, key: &Key) -> Option<V> {
+        let index = self.find_item(key).ok()?;
+        let (_, value) = self.items.remove(index);
+        self.indices = 0 .. 0;
+        self.indices_fix();
+        Some(value)
+    }
+    /// Values of all items, ordered by key.
+    pub fn values_mut(&mut self)
+    -> impl ExactSizeIterator<Item=&mut V> + '_
+    {
+        self.items.iter_mut().map(|(_, value)| value)
+    }
+    /// Items with mutable keys, ordered by key.
+    /// A key may only be replaced with an equal one
+    /// (e.g. a name with a shared copy of it).
+    pub(super) fn items_mut(&mut self)
+    -> impl ExactSizeIterator<Item=(&mut Key, &mut V)> + '_
+    {
+        self.items.iter_mut().map(|(key, value)| (key, value))
+    }
+    /// The array length declared on load (trailing `nil` items
+    /// included), or set with
+    /// [`set_declared_array_len`](Self::set_declared_array_len).
+    #[must_use]
+    pub fn declared_array_len(&self) -> usize {
+        self.declared_array_len
+    }
+    /// Encode the array part with at least `len` items,
+    /// padding it with `nil`.
+    /// Positive keys that go into the assoc part limit the padding.
+    pub fn set_declared_array_len(&mut self, len: usize) {
+        self.declared_array_len = len;
+    }
+    /// Continue building the table; pushed values are appended
+    /// after its largest positive index.
     pub fn into_builder(self) -> TableBuilder<V> {
-        TableBuilder { table: self }
+        let last_index = self.last_index_key();
+        TableBuilder { table: self, last_index, dead: Vec::new() }
     }
 }
 
@@ -107,7 +179,7 @@ impl<V> Table<V> {
                     )
                 ),
             Key::Index(_) => 0,
-            Key::Name(_) => self.indices.end,
+            Key::Name(_) | Key::Bool(_) => self.indices.end,
         };
         while index < self.items.len() {
             let Some((k, _)) = self.items.get(index) else {
@@ -125,6 +197,19 @@ impl<V> Table<V> {
     fn push_item(&mut self, key: Key, value: V) {
         self.items.push((key, value));
     }
+    /// Remove the items inserted before their key was declared dead;
+    /// each dead key is paired with the number of items
+    /// inserted before it.
+    fn remove_dead(&mut self, dead: Vec<(Key, usize)>) {
+        if dead.is_empty() { return; }
+        // later declarations override earlier ones
+        let dead: BTreeMap<Key, usize> = dead.into_iter().collect();
+        let mut index = 0;
+        self.items.retain(|(key, _)| {
+            index += 1;
+            dead.get(key).map_or(true, |&count| index > count)
+        });
+    }
     fn sort_items(&mut self) {
         self.items.sort_by(|(k1, _), (k2, _)| Key::cmp(k1, k2));
         dedup_assign(&mut self.items, |(k1, _), (k2, _)| k1 == k2);
@@ -149,7 +234,6 @@ impl<V> Table<V> {
             Some(&(Key::Index(index), _)) if index <= 0 => {
                 self.indices.start += 1;
                 self.indices.end += 1;
-                continue;
             },
             _ => break,
         } }
@@ -157,7 +241,6 @@ impl<V> Table<V> {
             self.items.get(self.indices.end)
         {
             self.indices.end += 1;
-            continue;
         }
     }
 }
@@ -166,6 +249,23 @@ impl<V> Default for Table<V> {
     fn default() -> Self { Self::new() }
 }
 
+#[cfg(feature = "fuzz")]
+impl<'a, V> arbitrary::Arbitrary<'a> for Table<V>
+where V: arbitrary::Arbitrary<'a>
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>)
+    -> arbitrary::Result<Self>
+    {
+        u.arbitrary_iter::<(Key, V)>()?.collect()
+    }
+}
+
+/// Builder of tables that have only the array part.
+///
+/// Unlike [`TableBuilder`], it does not sort the items when built.
+/// Both builders share [`push`](Self::push),
+/// [`push_option`](Self::push_option)
+/// and [`with_capacity`](Self::with_capacity).
 pub struct ArrayBuilder<V> {
     table: Table<V>,
     last_index: i32,
@@ -178,6 +278,17 @@ impl<V> ArrayBuilder<V> {
     pub fn new() -> Self {
         Self { table: Table::new(), last_index: 0 }
     }
+    /// Make a builder expecting about `len` values.
+    #[must_use]
+    pub fn with_capacity(len: usize) -> Self {
+        Self {
+            table: Table {
+                items: Vec::with_capacity(len), indices: 0 .. 0,
+                declared_array_len: 0,
+            },
+            last_index: 0,
+        }
+    }
     #[must_use]
     #[inline]
     pub fn build(self) -> Table<V> {
@@ -224,8 +335,15 @@ impl<V> Extend<Option<V>> for ArrayBuilder<V> {
     }
 }
 
+/// Builder of arbitrary tables.
+///
+/// Items can be added in any order;
+/// if a key is inserted more than once, the last insertion wins.
 pub struct TableBuilder<V> {
     table: Table<V>,
+    last_index: i32,
+    // keys declared dead, each with the number of items before it
+    dead: Vec<(Key, usize)>,
 }
 
 impl<V> TableBuilder<V> {
@@ -233,12 +351,23 @@ impl<V> TableBuilder<V> {
     #[must_use]
     #[inline]
     pub fn new() -> Self {
-        Self { table: Table::new() }
+        Self { table: Table::new(), last_index: 0, dead: Vec::new() }
+    }
+    /// Make a builder expecting about `len` items.
+    #[must_use]
+    pub fn with_capacity(len: usize) -> Self {
+        let mut this = Self::new();
+        this.reserve(len);
+        this
+    }
+    pub fn reserve(&mut self, additional: usize) {
+        self.table.items.reserve(additional);
     }
     #[must_use]
     #[inline]
     pub fn build(self) -> Table<V> {
         let mut table = self.table;
+        table.remove_dead(self.dead);
         table.sort_items();
         table
     }
@@ -246,6 +375,26 @@ impl<V> TableBuilder<V> {
     pub fn insert(&mut self, key: Key, value: V) {
         self.table.push_item(key, value)
     }
+    /// Insert `nil` under the key, removing the value
+    /// inserted under it before, if any.
+    pub fn insert_dead(&mut self, key: Key) {
+        self.dead.push((key, self.table.items.len()));
+    }
+    /// Insert the value under the index following
+    /// the last pushed one (starting with `1`).
+    #[inline]
+    pub fn push(&mut self, value: V) {
+        self.push_option(Some(value))
+    }
+    /// Like [`push`](Self::push), but `None` only takes the index.
+    pub fn push_option(&mut self, value: Option<V>) {
+        self.last_index += 1;
+        let key = Key::Index(self.last_index);
+        match value {
+            Some(value) => self.insert(key, value),
+            None => self.insert_dead(key),
+        }
+    }
 }
 
 impl<V, K> FromIterator<K> for TableBuilder<V>
@@ -452,17 +601,30 @@ where
 
 
 impl<V> Table<V> {
+    /// Same as [`sorted_iter`](Self::sorted_iter).
     #[must_use]
     pub fn iter(&self) -> ClonedKeysSliceIter<'_, V> {
         <&Self as IntoIterator>::into_iter(self)
     }
+    /// Items ordered by key: integer keys first, in numeric order,
+    /// then names, in byte-wise order,
+    /// then boolean keys, `false` before `true` (see [`Key`]).
+    ///
+    /// The order only depends on the keys,
+    /// not on how the table was built or encoded,
+    /// and is not going to change between versions.
+    /// Serialization goes in this order as well.
+    #[must_use]
+    pub fn sorted_iter(&self) -> ClonedKeysSliceIter<'_, V> {
+        ClonedKeysIter::new(self.items.iter())
+    }
 }
 
 impl<'s, V> IntoIterator for &'s Table<V> {
     type Item = (Key, &'s V);
     type IntoIter = ClonedKeysSliceIter<'s, V>;
     fn into_iter(self) -> Self::IntoIter {
-        ClonedKeysIter::new(self.items.iter())
+        self.sorted_iter()
     }
 }
 
@@ -549,27 +711,21 @@ impl<V> Table<V> {
         }
         ArrayIter::new(array_keys, iter)
     }
+    /// The range of items that go into the array part when encoded
+    fn array_range(&self) -> Range<usize> {
+        let start = self.indices.start;
+        let count = array_part_count( self.indices.len(),
+            |index| self.items[start + index].0.as_index().unwrap() );
+        start .. start + count
+    }
     /// Split the map into array and assoc parts
     fn array_assoc_iter(&self) -> (
         ArrayRefIter<'_, V>,
         ClonedKeysIter<'_, V, ChainSliceIter<'_, V>>,
     ) {
-        let mut indices = self.indices.clone();
-        loop {
-            let Some(end) = indices.end.checked_sub(1) else { break; };
-            if end < indices.start { break; }
-            let max_index = usize::saturating_sub(indices.end, indices.start)
-                .saturating_mul(2)
-                .try_into().unwrap_or(i32::MAX);
-            let key = self.items[end].0.as_index().unwrap();
-            if key <= max_index {
-                break;
-            }
-            let Some(_) = indices.next_back() else { break; };
-        }
+        let indices = self.array_range();
         let array_items = &self.items[indices.clone()];
-        let array_keys = 1 .. array_items.last()
-            .map_or(1, |(k, _)| 1 + k.as_index().unwrap());
+        let array_keys = 1 .. self.array_len_in(indices.clone()) + 1;
         (
             ArrayIter::new(array_keys, TupleRefIter::new(array_items)),
             ClonedKeysIter::new(ChainIter::new(
@@ -580,6 +736,81 @@ impl<V> Table<V> {
     }
 }
 
+impl<V> Table<V> {
+    /// Items that go into the array part when encoded,
+    /// ordered by key.
+    /// These are all positive integer keys, except for those
+    /// that are too sparse to be worth storing in the array.
+    #[must_use]
+    pub fn array_part(&self) -> &[(Key, V)] {
+        &self.items[self.array_range()]
+    }
+    /// Length of the array part when encoded, including holes
+    /// (and the padding up to [`Self::declared_array_len`]).
+    #[must_use]
+    pub fn array_part_len(&self) -> usize {
+        let Ok(len) = usize::try_from(self.array_len_in(self.array_range()))
+            else { unreachable!("array length should not be negative") };
+        len
+    }
+    /// Length of the array part made of the items in the range,
+    /// padded up to the declared length
+    /// as long as the padding does not reach the assoc part.
+    fn array_len_in(&self, range: Range<usize>) -> i32 {
+        let index_of = |item: &(Key, V)| match item.0 {
+            Key::Index(index) if index > 0 => index,
+            _ => unreachable!("array part keys should be positive indices"),
+        };
+        let len = self.items[range.clone()].last().map_or(0, index_of);
+        let limit = self.items[range.end .. self.indices.end].first()
+            .map_or(i32::MAX, |item| index_of(item) - 1);
+        let declared = i32::try_from(self.declared_array_len)
+            .unwrap_or(i32::MAX).min(limit);
+        len.max(declared)
+    }
+    pub fn array_part_mut(&mut self)
+    -> impl ExactSizeIterator<Item=(&Key, &mut V)> + '_
+    {
+        let range = self.array_range();
+        self.items[range].iter_mut().map(|(key, value)| (&*key, value))
+    }
+    /// Items that go into the assoc part when encoded,
+    /// ordered by key.
+    pub fn assoc_part(&self)
+    -> impl ExactSizeIterator<Item=(&Key, &V)> + '_
+    {
+        let range = self.array_range();
+        ChainIter::new(
+            TupleRefIter::new(&self.items[..range.start]),
+            TupleRefIter::new(&self.items[range.end..]),
+        )
+    }
+    pub fn assoc_part_mut(&mut self)
+    -> impl ExactSizeIterator<Item=(&Key, &mut V)> + '_
+    {
+        let range = self.array_range();
+        let (before, after) = self.items.split_at_mut(range.start);
+        ChainIter::new(before.iter_mut(), after[range.len()..].iter_mut())
+            .map(|(key, value)| (&*key, value))
+    }
+}
+
+/// How the table is laid out when encoded; see `Table::size_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableStats {
+    /// Length of the array part, including holes.
+    pub array_len: usize,
+    /// Number of items in the assoc part.
+    pub assoc_len: usize,
+    /// Number of unused slots in the assoc part.
+    /// Tables do not keep dead keys, so all of these are free.
+    pub assoc_free: usize,
+    /// Length of the encoded table in bytes (before base62 and zlib),
+    /// or `usize::MAX` if the table is too large to be encoded.
+    pub encoded_len: usize,
+}
+
 #[derive(Debug, Error)]
 #[error("The sequence cannot contain None")]
 pub struct NonContinuousError;
@@ -627,7 +858,7 @@ impl<V> Table<V> {
     /// The first found non-conforming key will result in an `Err` item.
     /// Iterator fuses after an error is encountered.
     pub fn into_continuous_iter(self)
-    -> impl Iterator<Item=Result<V, NonContinuousError>> + FusedIterator
+    -> impl FusedIterator<Item=Result<V, NonContinuousError>>
     {
         let mut next_index = 1;
         HaltingIter::new(self.into_iter()
@@ -645,12 +876,13 @@ impl<V> Table<V> {
 pub(super) mod load {
 
 use crate::{
-    common::iexp2,
+    common::{iexp2, u32_to_usize},
     table_iter::TableItem,
     load::{Error, TableLoader},
+    loader::DuplicateKeys,
 };
 
-use super::{Key, Table, ArrayBuilder};
+use super::{Key, Table, TableBuilder, ArrayBuilder, dedup_assign};
 
 use super::assoc::Table as AssocTable;
 
@@ -663,6 +895,7 @@ impl<V> Table<V> {
         let array_len = items.array_len();
         let assoc_loglen = items.assoc_loglen();
         let assoc_len = iexp2(assoc_loglen);
+        let duplicate_keys = items.duplicate_keys();
         let mut array = ArrayBuilder::new();
         let mut assoc = AssocTable::new_load_builder(assoc_loglen);
         assoc.set_last_free(items.assoc_last_free());
@@ -695,7 +928,37 @@ impl<V> Table<V> {
         }
         let mut table = array.build().into_builder();
         table.extend(assoc.build::<T::Error>()?.into_map_iter());
-        Ok(table.build())
+        let mut table = table.build_loaded::<T::Error>(duplicate_keys)?;
+        table.declared_array_len = u32_to_usize(array_len);
+        Ok(table)
+    }
+}
+
+impl<V> TableBuilder<V> {
+    fn build_loaded<E: Error>(self, duplicate_keys: DuplicateKeys)
+    -> Result<Table<V>, E>
+    {
+        let mut table = self.table;
+        // the sort is stable, so items with the same key
+        // stay in the order they were encoded
+        table.items.sort_by(|(k1, _), (k2, _)| Key::cmp(k1, k2));
+        match duplicate_keys {
+            DuplicateKeys::Error => {
+                if let Some(pair) = table.items.windows(2)
+                    .find(|pair| pair[0].0 == pair[1].0)
+                {
+                    return Err(E::from(&*format!(
+                        "table key {:?} should not be encoded twice",
+                        pair[0].0 )));
+                }
+            },
+            DuplicateKeys::FirstWins =>
+                table.items.dedup_by(|(k1, _), (k2, _)| k1 == k2),
+            DuplicateKeys::LastWins =>
+                dedup_assign(&mut table.items, |(k1, _), (k2, _)| k1 == k2),
+        }
+        table.indices_fix();
+        Ok(table)
     }
 }
 
@@ -705,13 +968,14 @@ impl<V> Table<V> {
 pub(super) mod dump {
 
 use crate::{
-    common::LogSize,
-    dump::{Dump, TableDumpIter as TableDumpIterTr},
+    common::{LogSize, iexp2},
+    dump::{Dump, Dumper, TableDumpIter as TableDumpIterTr},
+    dumper::encoded_len,
     table_iter::{TableItem, TableSize},
 };
 
 use super::{
-    Key, Table,
+    Key, Table, TableStats,
     add_size_hint,
 };
 
@@ -726,6 +990,29 @@ impl<V: Dump> Table<V> {
         let (array_iter, assoc_iter) = self.array_assoc_iter();
         TableDumpIter::from_array_assoc_iter(array_iter, assoc_iter)
     }
+    /// Sizes of the parts of the table when encoded,
+    /// to tell in advance whether it fits into a length limit.
+    #[must_use]
+    pub fn size_stats(&self) -> TableStats {
+        let dump_iter = self.dump_iter();
+        let assoc_len = self.len() - self.array_part().len();
+        let assoc_size = iexp2(dump_iter.assoc_loglen()) as usize;
+        TableStats {
+            array_len: dump_iter.array_len() as usize,
+            assoc_len,
+            assoc_free: assoc_size - assoc_len,
+            encoded_len: encoded_len(Some(&TableDump(self)))
+                .unwrap_or(usize::MAX),
+        }
+    }
+}
+
+struct TableDump<'s, V>(&'s Table<V>);
+
+impl<V: Dump> Dump for TableDump<'_, V> {
+    fn dump<D: Dumper>(&self, dumper: D) -> Result<D::Ok, D::Error> {
+        dumper.dump_table(self.0.dump_iter())
+    }
 }
 
 struct TableDumpIter<'s, V, I>
@@ -833,6 +1120,7 @@ where
 }
 
 
+#[cfg(feature = "serde")]
 pub(super) mod de {
 
 use std::marker::PhantomData;
@@ -900,6 +1188,7 @@ where V: DeserializeOption<'de>
 }
 
 
+#[cfg(feature = "serde")]
 pub(super) mod ser {
 
 use serde::{Serialize, ser};
@@ -918,7 +1207,7 @@ where V: SerializeOption
         if assoc_iter.len() == 0 && array_iter.len() > 0 {
             ser.collect_seq(array_iter.map(OptionRefSerdeWrap))
         } else {
-            ser.collect_map(self.iter())
+            ser.collect_map(self.sorted_iter())
         }
     }
 }
@@ -931,7 +1220,7 @@ mod test {
 
 use crate::Str;
 
-use super::{Key, TableBuilder};
+use super::{Key, Table, TableBuilder, ArrayBuilder};
 
 use super::dedup_assign;
 
@@ -989,5 +1278,53 @@ fn test_insert_remove() {
         == test_keys );
 }
 
+#[test]
+fn test_parts() {
+    let mut table = Table::from_iter([
+        (Key::Index(-1), 0),
+        (Key::Index(1), 1), (Key::Index(2), 2), (Key::Index(4), 4),
+        (Key::Index(100), 100),
+        (Key::from("name"), 5),
+    ]);
+    assert_eq!( table.array_part(),
+        [(Key::Index(1), 1), (Key::Index(2), 2), (Key::Index(4), 4)] );
+    assert_eq!(table.array_part_len(), 4);
+    assert_eq!( table.assoc_part().map(|(k, _)| k.clone()).collect::<Vec<_>>(),
+        [Key::Index(-1), Key::Index(100), Key::from("name")] );
+    for (_, value) in table.array_part_mut() { *value += 10; }
+    for (_, value) in table.assoc_part_mut() { *value *= 2; }
+    assert_eq!( table.into_iter().map(|(_, v)| v).collect::<Vec<_>>(),
+        [0, 11, 12, 14, 200, 10] );
+}
+
+#[test]
+fn test_builders() {
+    let mut builder = TableBuilder::with_capacity(6);
+    builder.push(1);
+    builder.push_option(None);
+    builder.push(3);
+    builder.insert(Key::from("name"), 4);
+    builder.insert_dead(Key::from("name"));
+    builder.insert(Key::from("other"), 5);
+    builder.insert_dead(Key::Index(1));
+    builder.insert(Key::Index(1), 6);
+    let table = builder.build();
+    assert_eq!( table.iter().collect::<Vec<_>>(), [
+        (Key::Index(1), &6), (Key::Index(3), &3), (Key::from("other"), &5),
+    ] );
+    let mut builder = table.into_builder();
+    builder.push(7);
+    builder.insert_dead(Key::Index(3));
+    assert_eq!( builder.build().array_part(),
+        [(Key::Index(1), 6), (Key::Index(4), 7)] );
+
+    let mut builder = ArrayBuilder::with_capacity(3);
+    builder.push(1);
+    builder.push_option(None);
+    builder.push(3);
+    assert_eq!( builder.build(),
+        Table::from_iter([(Key::Index(1), 1), (Key::Index(3), 3)]) );
+}
+
 }
 
@@ -2,92 +2,56 @@ use crate::
     common::{LogSize, iexp2, ilog2_ceil, ilog2_exact}
 ;
 
-use super::Key;
-
-
-#[inline]
-const fn mask(loglen: LogSize) -> u32 {
-    iexp2(Some(loglen)) - 1
-}
-
-// https://www.lua.org/source/5.4/lstring.c.html#luaS_hash
-const fn str_table_hash_with_seed<const SEED: u32>(value: &str) -> u32 {
-    let value = value.as_bytes();
-    let mut index = value.len();
-    assert!(u32::BITS <= usize::BITS && index <= u32::MAX as usize);
-    let mut hash = SEED ^ (index as u32);
-    let step = (index >> 5) + 1;
-    while index >= step {
-        let j = match index.checked_sub(1) {
-            Some(j) if j < value.len() => j,
-            // SAFETY: i dare you
-            _ => unsafe { std::hint::unreachable_unchecked() },
-        };
-        hash ^= u32::wrapping_add(
-            u32::wrapping_add(hash << 5, hash >> 2),
-            value[j] as u32 );
-        index -= step;
-    }
-    hash
-}
+use super::{
+    Key,
+    hash::{key_position, link},
+};
 
-pub(crate) const fn str_table_hash(value: &str) -> u32 {
-    str_table_hash_with_seed::<0x_645D_BFCD>(value)
-}
+#[cfg(test)]
+use super::hash::mask;
 
-// https://www.lua.org/source/5.4/ltable.c.html#hashint
-pub(crate) const fn int_table_hash(value: i32, loglen: LogSize) -> u32 {
-    if loglen == 0 { return 0; }
-    if value >= 0 {
-        (value % (mask(loglen) as i32)) as u32
-    } else {
-        (value as u32) % mask(loglen)
-    }
-}
 
 impl Key {
     #[inline]
     fn position(&self, loglen: LogSize) -> u32 {
-        match *self {
-            Self::Index(index) => int_table_hash(index, loglen),
-            Self::Name(ref value) => str_table_hash(value) & mask(loglen),
-        }
+        key_position(self, loglen)
     }
 }
 
 
 pub(crate) type Item<V> = crate::table_iter::AssocItem<Key, V>;
 
-impl<V> Item<V> {
-    #[inline]
-    fn main_position(&self, loglen: LogSize) -> Option<u32> {
-        match self {
-            Self::Dead { .. } => None,
-            Self::Live { key, .. } => Some(key.position(loglen))
-        }
-    }
-}
-
 
+/// Hash part of a Lua table, laid out in parallel arrays.
+///
+/// A slot is free unless its bit in `occupied` is set.
+/// An occupied slot with no key is dead.
 #[derive(Debug, Clone)]
 pub(super) struct Table<V> {
     // Invariant:
-    // if `items` is `Some` than `items.len()`` is a power of two
-    items: Option<Box<[Option<Item<V>>]>>,
+    // `keys`, `values`, `links` and `positions` have the same length,
+    // which is either zero or a power of two;
+    // `occupied` has a bit for each of them.
+    keys: Box<[Option<Key>]>,
+    values: Box<[Option<V>]>,
+    links: Box<[i32]>,
+    // main positions of the keys, calculated once on insertion
+    // (dead slots are considered to be in their main position)
+    positions: Box<[u32]>,
+    occupied: Box<[u64]>,
     last_free: u32,
 }
 
 impl<V> Table<V> {
     fn new(loglen: Option<LogSize>) -> Self {
-        let size = iexp2(loglen);
-        let items = (size > 0).then(|| {
-            let mut items = Vec::with_capacity(size as usize);
-            items.resize_with(size as usize, || None);
-            items
-        });
+        let size = iexp2(loglen) as usize;
         Self {
-            items: items.map(Box::from),
-            last_free: size,
+            keys: std::iter::repeat_with(|| None).take(size).collect(),
+            values: std::iter::repeat_with(|| None).take(size).collect(),
+            links: vec![0; size].into_boxed_slice(),
+            positions: vec![0; size].into_boxed_slice(),
+            occupied: vec![0; size.div_ceil(64)].into_boxed_slice(),
+            last_free: size as u32,
         }
     }
     pub(super) fn new_load_builder(loglen: Option<LogSize>)
@@ -96,9 +60,7 @@ impl<V> Table<V> {
         load::TableLoadBuilder::new(loglen)
     }
     pub(super) fn len(&self) -> usize {
-        if let Some(items) = self.items.as_ref() {
-            items.len()
-        } else { 0 }
+        self.keys.len()
     }
     pub(super) fn loglen(&self) -> Option<LogSize> {
         let Ok(loglen) = ilog2_exact(self.len()) else {
@@ -109,19 +71,58 @@ impl<V> Table<V> {
     pub(super) fn last_free(&self) -> u32 {
         self.last_free
     }
+
+    #[inline]
+    fn is_occupied(&self, index: u32) -> bool {
+        self.occupied[(index / 64) as usize] & (1 << (index % 64)) != 0
+    }
+
+    /// Main position of the key in the occupied slot.
+    #[inline]
+    fn main_position(&self, index: u32) -> u32 {
+        self.positions[index as usize]
+    }
+
+    #[inline]
+    fn put( &mut self, index: u32, position: u32,
+        key: Option<Key>, value: Option<V>, link: i32,
+    ) {
+        let slot = index as usize;
+        self.keys[slot] = key;
+        self.values[slot] = value;
+        self.links[slot] = link;
+        self.positions[slot] = position;
+        self.occupied[(index / 64) as usize] |= 1 << (index % 64);
+    }
+
+    /// Move the slot contents, adjusting the link.
+    fn relocate(&mut self, old_index: u32, new_index: u32) {
+        let key = self.keys[old_index as usize].take();
+        let value = self.values[old_index as usize].take();
+        let position = self.positions[old_index as usize];
+        let mut link = self.links[old_index as usize];
+        if link != 0 {
+            link += old_index as i32 - new_index as i32;
+        }
+        self.put(new_index, position, key, value, link);
+    }
+
+    /// Adjust the link after its target was moved.
+    fn relocate_link(&mut self, index: u32, old_index: u32, new_index: u32) {
+        let link = &mut self.links[index as usize];
+        if *link != 0 {
+            *link += new_index as i32 - old_index as i32;
+        }
+    }
 }
 
 impl<V> Table<V> {
     pub(super) fn into_map_iter(self)
     -> impl Iterator<Item=(Key, V)>
     {
-        let items = self.items
-            .map(Vec::from).map(Vec::into_iter)
-            .unwrap_or_default();
-        items.filter_map(|item| match item? {
-            Item::Dead { .. } => None,
-            Item::Live { key, value, .. } => Some((key, value?)),
-        })
+        Vec::from(self.keys).into_iter()
+            .zip(Vec::from(self.values))
+            .filter_map(|(key, value)| Some((key?, value?)))
     }
 }
 
@@ -152,33 +153,11 @@ impl<V> ItemBuilder<V> {
         }
     }
     #[inline]
-    fn into_item(self, link: i32) -> Item<V> {
+    fn into_key_value(self) -> (Option<Key>, Option<V>) {
         match self {
             #[cfg(test)]
-            Self::Dead { .. } => Item::Dead { link },
-            Self::Live { key, value } => Item::Live { value, key, link },
-        }
-    }
-}
-
-impl<V> Item<V> {
-    #[inline]
-    fn relocate(mut self, old_index: u32, new_index: u32) -> Self {
-        let link_mut = match &mut self {
-            Self::Live { link, .. } | Self::Dead { link } => link,
-        };
-        if *link_mut != 0 {
-            *link_mut += old_index as i32 - new_index as i32;
-        }
-        self
-    }
-    #[inline]
-    fn relocate_link(&mut self, old_index: u32, new_index: u32) {
-        let link_mut = match self {
-            Self::Live { link, .. } | Self::Dead { link } => link,
-        };
-        if *link_mut != 0 {
-            *link_mut += new_index as i32 - old_index as i32;
+            Self::Dead { .. } => (None, None),
+            Self::Live { key, value } => (Some(key), value),
         }
     }
 }
@@ -204,20 +183,10 @@ impl<V> TableBuilder<V> {
         self.insert_item(ItemBuilder::dead_from_key(key, loglen))
     }
 
-    fn get(&self, index: u32) -> &Option<Item<V>> {
-        let items = self.table.items.as_ref().unwrap();
-        &items[index as usize]
-    }
-
-    fn get_mut(&mut self, index: u32) -> &mut Option<Item<V>> {
-        let items = self.table.items.as_mut().unwrap();
-        &mut items[index as usize]
-    }
-
     fn find_free_index(&mut self) -> Option<u32> {
         while self.table.last_free > 0 {
             self.table.last_free -= 1;
-            if self.get(self.table.last_free).is_none() {
+            if !self.table.is_occupied(self.table.last_free) {
                 return Some(self.table.last_free);
             }
         }
@@ -230,36 +199,28 @@ impl<V> TableBuilder<V> {
         let loglen = self.table.loglen()
             .expect("the table should have some space");
         let main_index = item.position(loglen);
-        if let free @ &mut None = self.get_mut(main_index) {
+        let (key, value) = item.into_key_value();
+        if !self.table.is_occupied(main_index) {
             // Lua here would fill dead position as well as free.
             // But we are not Lua: we do not normally make dead positions,
             // and even when we do, we don't want to overwrite them.
-            *free = Some(item.into_item(0));
+            self.table.put(main_index, main_index, key, value, 0);
             return;
         }
         let free_index = self.find_free_index()
             .expect("the table should have free space");
-        let other_index = self.get_mut(main_index)
-            .as_ref().unwrap()
-            .main_position(loglen)
-            .unwrap_or(main_index);
+        let other_index = self.table.main_position(main_index);
         if other_index == main_index {
-            let link = free_index as i32 - main_index as i32;
-            *self.get_mut(free_index) = Some(
-                self.get_mut(main_index)
-                    .replace(item.into_item(link))
-                    .unwrap()
-                    .relocate(main_index, free_index)
-            );
+            let link = link(main_index, free_index);
+            self.table.relocate(main_index, free_index);
+            self.table.put(main_index, main_index, key, value, link);
             return;
         }
         let mut prev_index = other_index;
         loop {
-            let link = match self.get_mut(prev_index)
-                .as_ref().unwrap()
-            {
-                &Item::Dead { link } | &Item::Live { link, .. }
-                    if link != 0 => link,
+            let link = match self.table.links[prev_index as usize] {
+                link if link != 0 && self.table.is_occupied(prev_index)
+                    => link,
                 _ => unreachable!("table structure is broken"),
             };
             let Some(next_index) = prev_index.checked_add_signed(link) else {
@@ -270,14 +231,9 @@ impl<V> TableBuilder<V> {
             }
             prev_index = next_index;
         }
-        *self.get_mut(free_index) = Some(
-            self.get_mut(main_index)
-                .replace(item.into_item(0))
-                .unwrap()
-                .relocate(main_index, free_index)
-        );
-        self.get_mut(prev_index).as_mut().unwrap()
-            .relocate_link(main_index, free_index);
+        self.table.relocate(main_index, free_index);
+        self.table.put(main_index, main_index, key, value, 0);
+        self.table.relocate_link(prev_index, main_index, free_index);
     }
 
 }
@@ -335,10 +291,18 @@ impl<V> TableLoadBuilder<V> {
     }
 
     pub(crate) fn insert(&mut self, index: u32, item: Item<V>) {
-        let items = self.table.items.as_mut().unwrap();
-        let index = u32_to_usize(index);
-        let old_item = items[index].replace(item);
-        assert!(old_item.is_none());
+        assert!(!self.table.is_occupied(index));
+        match item {
+            Item::Dead { link } =>
+                self.table.put(index, index, None, None, link),
+            Item::Live { key, value, link } => {
+                let Some(loglen) = self.table.loglen() else {
+                    unreachable!("the slot should exist");
+                };
+                let position = key.position(loglen);
+                self.table.put(index, position, Some(key), value, link);
+            },
+        }
     }
 
     pub(crate) fn set_last_free(&mut self, last_free: u32) {
@@ -349,19 +313,14 @@ impl<V> TableLoadBuilder<V> {
 
 impl<V> Table<V> {
     pub(super) fn validate_positions<E: Error>(&self) -> Result<(), E> {
-        let Some(items) = self.items.as_deref() else {
-            return Ok(());
-        };
         let Some(loglen) = self.loglen() else {
-            unreachable!();
+            return Ok(());
         };
         let len = iexp2(Some(loglen));
-        let mut unvalidated: Vec<Option<u32>> = items.iter()
-            .enumerate().map( |(index, item)| {
-                let index: u32 = index.try_into().unwrap();
-                let item = item.as_ref()?;
-                Some(item.main_position(loglen).unwrap_or(index))
-            }).collect();
+        let mut unvalidated: Vec<Option<u32>> = (0 .. len)
+            .map( |index| self.is_occupied(index)
+                .then(|| self.main_position(index)) )
+            .collect();
         for main_position in 0 .. len {
             let mut position = main_position;
             let mut steps = 0;
@@ -375,12 +334,10 @@ impl<V> Table<V> {
                 if unvalidated[index] == Some(main_position) {
                     unvalidated[index] = None;
                 }
-                let link = match items[index] {
-                    Some(Item::Dead { link } | Item::Live { link, .. })
-                        if link != 0
-                        => link,
-                    _ => break,
-                };
+                let link = self.links[index];
+                if link == 0 || !self.is_occupied(position) {
+                    break;
+                }
                 let Some(next_position) = position.checked_add_signed(link)
                     .filter(|&pos| pos < len)
                 else {
@@ -414,35 +371,49 @@ use super::{Item, Table};
 
 impl<'v, V> Table<&'v V> {
     pub(crate) fn dump_iter(self) -> TableDumpIter<'v, V> {
-        TableDumpIter {
-            items: self.items.map_or_else(
-                Default::default,
-                |items| Vec::from(items).into_iter() ),
-        }
+        TableDumpIter { table: self, index: 0 }
     }
 }
 
 pub(in super::super) struct TableDumpIter<'s, V> {
-    items: std::vec::IntoIter<Option<Item<&'s V>>>,
+    table: Table<&'s V>,
+    index: u32,
 }
 
 impl<'s, V> Iterator for TableDumpIter<'s, V> {
     type Item = Option<Item<&'s V>>;
     fn next(&mut self) -> Option<Self::Item> {
-        let item = self.items.next()?;
-        Some(item)
+        let index = self.index;
+        if index as usize >= self.table.len() {
+            return None;
+        }
+        self.index += 1;
+        if !self.table.is_occupied(index) {
+            return Some(None);
+        }
+        let slot = index as usize;
+        let link = self.table.links[slot];
+        Some(Some(match self.table.keys[slot].take() {
+            None => Item::Dead { link },
+            Some(key) => Item::Live {
+                key, value: self.table.values[slot].take(), link },
+        }))
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.items.size_hint()
+        let len = self.len();
+        (len, Some(len))
     }
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
-        self.items.nth(n)
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        self.index = self.index.saturating_add(n)
+            .min(self.table.len() as u32);
+        self.next()
     }
 }
 
 impl<'s, V> ExactSizeIterator for TableDumpIter<'s, V> {
     fn len(&self) -> usize {
-        self.items.len()
+        self.table.len() - self.index as usize
     }
 }
 
@@ -462,4 +433,3 @@ fn test_dead_insert() {
 }
 
 }
-
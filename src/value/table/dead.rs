@@ -0,0 +1,245 @@
+//! Tables consisting solely of dead keys.
+
+use crate::{
+    common::{LogSize, iexp2, ilog2_exact},
+    error::LoadError,
+    limits::MAX_ASSOC_LOGLEN,
+    table_iter::{TableItem, AssocItem, TableSize},
+    load::{
+        Error, Load,
+        Builder, Loader, TableLoader,
+    },
+    dump::{Dump, Dumper, TableDumpIter},
+};
+
+use super::{
+    super::{Key, Value},
+    assoc::Table as AssocTable,
+};
+
+/// A table with no array part and only dead keys in the assoc part.
+///
+/// The game occasionally emits such degenerate tables.
+/// [`Value`] loads them as empty tables, losing the layout;
+/// this type keeps the layout as is, so that it can be examined
+/// and encoded back exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadKeyTable {
+    // `None` for a free slot, `Some(link)` for a dead key
+    links: Vec<Option<i32>>,
+    last_free: u32,
+}
+
+impl DeadKeyTable {
+
+    /// The length of `links` is the size of the assoc part
+    /// and should be either zero or a power of two.
+    /// The layout is checked the same way it is checked on load.
+    pub fn new(links: Vec<Option<i32>>, last_free: u32)
+    -> Result<Self, LoadError>
+    {
+        Self::from_parts(links, last_free)
+    }
+
+    fn from_parts<E: Error>(links: Vec<Option<i32>>, last_free: u32)
+    -> Result<Self, E>
+    {
+        let Ok(loglen) = ilog2_exact(links.len()) else {
+            return Err(E::from(
+                "assoc part size should be a power of two" ));
+        };
+        if loglen.is_some_and(|loglen| loglen > MAX_ASSOC_LOGLEN) {
+            return Err(E::from("assoc part should not be that large"));
+        }
+        let mut assoc = AssocTable::<()>::new_load_builder(loglen);
+        assoc.set_last_free(last_free);
+        for (index, &link) in (0_u32 ..).zip(&links) {
+            let Some(link) = link else { continue };
+            assoc.insert(index, AssocItem::Dead { link });
+        }
+        assoc.build::<E>()?;
+        Ok(Self { links, last_free })
+    }
+
+    /// Links of the dead keys, `None` for free slots.
+    #[must_use]
+    pub fn links(&self) -> &[Option<i32>] {
+        &self.links
+    }
+
+    #[must_use]
+    pub fn last_free(&self) -> u32 {
+        self.last_free
+    }
+
+    fn loglen(&self) -> Option<LogSize> {
+        let Ok(loglen) = ilog2_exact(self.links.len()) else {
+            unreachable!("struct invariant");
+        };
+        loglen
+    }
+
+}
+
+impl Load for DeadKeyTable {
+    fn load<L: Loader>(loader: L) -> Result<Option<Self>, L::Error> {
+        loader.load_value(DeadKeyTableBuilder)
+    }
+}
+
+struct DeadKeyTableBuilder;
+
+impl DeadKeyTableBuilder {
+    fn err_not_table<E: Error>() -> E {
+        E::from("dead key table should be represented by a table")
+    }
+}
+
+impl Builder for DeadKeyTableBuilder {
+    type Key = Key;
+    type Value = Value;
+    type Output = DeadKeyTable;
+
+    fn build_boolean<E: Error>(self, _value: bool)
+    -> Result<Option<DeadKeyTable>, E>
+    {
+        Err(Self::err_not_table())
+    }
+
+    fn build_integer<E: Error>(self, _value: i32)
+    -> Result<Option<DeadKeyTable>, E>
+    {
+        Err(Self::err_not_table())
+    }
+
+    fn build_float<E: Error>(self, _value: f64)
+    -> Result<Option<DeadKeyTable>, E>
+    {
+        Err(Self::err_not_table())
+    }
+
+    fn build_string<E: Error>(self, _value: &str)
+    -> Result<Option<DeadKeyTable>, E>
+    {
+        Err(Self::err_not_table())
+    }
+
+    fn build_bytes<E: Error>(self, _value: &[u8])
+    -> Result<Option<DeadKeyTable>, E>
+    {
+        Err(Self::err_not_table())
+    }
+
+    fn build_table<T>(self, items: T)
+    -> Result<Option<DeadKeyTable>, T::Error>
+    where
+        T: TableLoader<Key=Key, Value=Value>,
+        T::Error : Error,
+    {
+        if items.array_len() > 0 {
+            return Err(T::Error::from(
+                "dead key table should not have an array part" ));
+        }
+        let last_free = items.assoc_last_free();
+        let mut links = Vec::with_capacity(
+            iexp2(items.assoc_loglen()) as usize );
+        for item in items {
+            links.push(match item? {
+                None => None,
+                Some(TableItem::Assoc(AssocItem::Dead { link })) =>
+                    Some(link),
+                Some(TableItem::Assoc(AssocItem::Live { .. })) =>
+                    return Err(T::Error::from(
+                        "dead key table should not have live keys" )),
+                Some(TableItem::Array(_)) =>
+                    unreachable!("array part should be empty"),
+            });
+        }
+        DeadKeyTable::from_parts(links, last_free).map(Some)
+    }
+
+}
+
+impl Dump for DeadKeyTable {
+    fn dump<D: Dumper>(&self, dumper: D) -> Result<D::Ok, D::Error> {
+        dumper.dump_table(DeadKeyDumpIter {
+            links: self.links.iter(),
+            loglen: self.loglen(),
+            last_free: self.last_free,
+        })
+    }
+}
+
+struct DeadKeyDumpIter<'s> {
+    links: std::slice::Iter<'s, Option<i32>>,
+    loglen: Option<LogSize>,
+    last_free: u32,
+}
+
+impl<'s> Iterator for DeadKeyDumpIter<'s> {
+    type Item = Option<TableItem<Key, &'s Value>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let &link = self.links.next()?;
+        Some(link.map(|link| TableItem::Assoc(AssocItem::Dead { link })))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.links.size_hint()
+    }
+}
+
+impl<'s> ExactSizeIterator for DeadKeyDumpIter<'s> {}
+
+impl<'s> TableSize for DeadKeyDumpIter<'s> {
+    fn array_len(&self) -> u32 { 0 }
+    fn assoc_loglen(&self) -> Option<LogSize> { self.loglen }
+    fn assoc_last_free(&self) -> u32 { self.last_free }
+}
+
+impl<'s> TableDumpIter<'s> for DeadKeyDumpIter<'s> {
+    type Key = Key;
+    type Value = Value;
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    dumper::dump_blueprint,
+    loader::load_blueprint,
+    value::{Value, Table},
+};
+
+use super::DeadKeyTable;
+
+#[test]
+fn test_dead_key_table_round_trip() {
+    let table = DeadKeyTable::new(
+        vec![Some(2), None, Some(0), Some(-3)], 1 ).unwrap();
+    let exchange = dump_blueprint::<_, DeadKeyTable>(
+        Exchange::Blueprint(Some(table.clone())) ).unwrap();
+    let reloaded = load_blueprint::<DeadKeyTable, DeadKeyTable, LoadError>(
+        &exchange ).unwrap();
+    let Exchange::Blueprint(Some(reloaded)) = reloaded
+        else { panic!("should be a blueprint") };
+    assert_eq!(reloaded, table);
+    assert_eq!(
+        dump_blueprint::<_, DeadKeyTable>(
+            Exchange::Blueprint(Some(reloaded)) ).unwrap(),
+        exchange );
+    let value = load_blueprint::<Value, Value, LoadError>(&exchange).unwrap();
+    let Exchange::Blueprint(Some(Value::Table(value))) = value
+        else { panic!("should be a table") };
+    assert_eq!(value, Table::new());
+}
+
+#[test]
+fn test_dead_key_table_invalid() {
+    DeadKeyTable::new(vec![None; 3], 0).unwrap_err();
+    DeadKeyTable::new(vec![None; 2], 3).unwrap_err();
+    DeadKeyTable::new(vec![Some(2), None], 0).unwrap_err();
+    DeadKeyTable::new(vec![Some(1), Some(-1)], 0).unwrap_err();
+}
+
+}
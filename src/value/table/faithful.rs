@@ -0,0 +1,340 @@
+//! Values that keep the layout of their tables as encoded.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Str,
+    common::{u32_to_usize, iexp2, ilog2_exact, LogSize},
+    error::{LoadError, DumpError},
+    limits::MAX_ASSOC_LOGLEN,
+    table_iter::{TableItem, AssocItem, TableSize},
+    load::{
+        Error, Load,
+        Builder, Loader, TableLoader,
+    },
+    dump::{Dump, Dumper, TableDumpIter},
+    loader::{DecodeOptions, InvalidUtf8, Warnings, decode},
+    dumper::encode,
+};
+
+use super::{
+    super::{Key, Value},
+    assoc::Table as AssocTable,
+};
+
+/// A value whose tables keep the layout they are encoded with
+/// (the “lua-faithful” representation).
+///
+/// Unlike [`Value`], this keeps the holes of array parts,
+/// and the free slots, dead keys and links of assoc parts,
+/// so that its serde representation shows exactly
+/// what is encoded, tombstones included.
+/// It is dumped back byte for byte the same.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub enum FaithfulValue {
+    Boolean(bool),
+    Integer(i32),
+    Float(f64),
+    String(Str),
+    /// See [`Value::Bytes`].
+    Bytes(Vec<u8>),
+    Table(FaithfulTable),
+}
+
+/// A table of [`FaithfulValue`], laid out as encoded.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[non_exhaustive]
+pub struct FaithfulTable {
+    /// Items of the array part, `None` for holes.
+    pub array: Vec<Option<FaithfulValue>>,
+    /// Slots of the assoc part, `None` for free ones.
+    /// Their number is either zero or a power of two.
+    pub assoc: Vec<Option<AssocItem<Key, FaithfulValue>>>,
+    pub last_free: u32,
+}
+
+impl FaithfulValue {
+
+    /// The value as the dumper would encode it.
+    pub fn from_dump<V: Dump>(value: &V) -> Result<Self, DumpError> {
+        let data = encode(Some(value))?;
+        let options = DecodeOptions {
+            utf8: InvalidUtf8::Bytes, ..DecodeOptions::default() };
+        decode::<Self>(&data, options, &mut Warnings::new())
+            .map_err(|error| DumpError::from(error.to_string()))?
+            .ok_or_else(|| DumpError::from("value should not be nil"))
+    }
+
+    /// The value as [`Value`] would load it
+    /// (e.g. without dead keys).
+    pub fn to_value(&self) -> Result<Value, LoadError> {
+        let data = encode(Some(self))
+            .map_err(|error| LoadError::from(error.to_string()))?;
+        let options = DecodeOptions {
+            utf8: InvalidUtf8::Bytes, ..DecodeOptions::default() };
+        decode::<Value>(&data, options, &mut Warnings::new())?
+            .ok_or_else(|| LoadError::from("value should not be nil"))
+    }
+
+}
+
+impl FaithfulTable {
+
+    /// The layout is checked the same way it is checked on load.
+    pub fn new(
+        array: Vec<Option<FaithfulValue>>,
+        assoc: Vec<Option<AssocItem<Key, FaithfulValue>>>,
+        last_free: u32,
+    ) -> Result<Self, LoadError> {
+        let this = Self { array, assoc, last_free };
+        this.validate::<LoadError>()?;
+        Ok(this)
+    }
+
+    fn validate<E: Error>(&self) -> Result<(), E> {
+        if u32::try_from(self.array.len()).is_err() {
+            return Err(E::from("array part should not be that large"));
+        }
+        let loglen = self.loglen::<E>()?;
+        let mut assoc = AssocTable::<()>::new_load_builder(loglen);
+        assoc.set_last_free(self.last_free);
+        for (index, slot) in (0_u32 ..).zip(&self.assoc) {
+            let Some(item) = slot else { continue };
+            assoc.insert(index, match *item {
+                AssocItem::Dead { link } => AssocItem::Dead { link },
+                AssocItem::Live { ref key, ref value, link } =>
+                    AssocItem::Live {
+                        key: key.clone(),
+                        value: value.as_ref().map(|_| ()),
+                        link,
+                    },
+            });
+        }
+        assoc.build::<E>()?;
+        Ok(())
+    }
+
+    fn loglen<E: Error>(&self) -> Result<Option<LogSize>, E> {
+        let Ok(loglen) = ilog2_exact(self.assoc.len()) else {
+            return Err(E::from(
+                "assoc part size should be a power of two" ));
+        };
+        if loglen.is_some_and(|loglen| loglen > MAX_ASSOC_LOGLEN) {
+            return Err(E::from("assoc part should not be that large"));
+        }
+        Ok(loglen)
+    }
+
+}
+
+impl Load for FaithfulValue {
+    fn load<L: Loader>(loader: L) -> Result<Option<Self>, L::Error> {
+        loader.load_value(FaithfulBuilder)
+    }
+}
+
+struct FaithfulBuilder;
+
+impl Builder for FaithfulBuilder {
+    type Key = Key;
+    type Value = FaithfulValue;
+    type Output = FaithfulValue;
+
+    fn build_boolean<E: Error>(self, value: bool)
+    -> Result<Option<FaithfulValue>, E>
+    {
+        Ok(Some(FaithfulValue::Boolean(value)))
+    }
+
+    fn build_integer<E: Error>(self, value: i32)
+    -> Result<Option<FaithfulValue>, E>
+    {
+        Ok(Some(FaithfulValue::Integer(value)))
+    }
+
+    fn build_float<E: Error>(self, value: f64)
+    -> Result<Option<FaithfulValue>, E>
+    {
+        Ok(Some(FaithfulValue::Float(value)))
+    }
+
+    fn build_string<E: Error>(self, value: &str)
+    -> Result<Option<FaithfulValue>, E>
+    {
+        Ok(Some(FaithfulValue::String(Str::from(value))))
+    }
+
+    fn build_bytes<E: Error>(self, value: &[u8])
+    -> Result<Option<FaithfulValue>, E>
+    {
+        Ok(Some(FaithfulValue::Bytes(value.to_vec())))
+    }
+
+    fn build_table<T>(self, items: T)
+    -> Result<Option<FaithfulValue>, T::Error>
+    where
+        T: TableLoader<Key=Key, Value=FaithfulValue>,
+        T::Error : Error,
+    {
+        let array_len = u32_to_usize(items.array_len());
+        let mut table = FaithfulTable {
+            array: Vec::with_capacity(array_len),
+            assoc: Vec::with_capacity(
+                u32_to_usize(iexp2(items.assoc_loglen())) ),
+            last_free: items.assoc_last_free(),
+        };
+        for item in items {
+            match item? {
+                None if table.array.len() < array_len =>
+                    table.array.push(None),
+                None => table.assoc.push(None),
+                Some(TableItem::Array(value)) =>
+                    table.array.push(Some(value)),
+                Some(TableItem::Assoc(item)) =>
+                    table.assoc.push(Some(item)),
+            }
+        }
+        table.validate::<T::Error>()?;
+        Ok(Some(FaithfulValue::Table(table)))
+    }
+
+}
+
+impl Dump for FaithfulValue {
+    fn dump<D: Dumper>(&self, dumper: D) -> Result<D::Ok, D::Error> {
+        match *self {
+            Self::Boolean(value) => dumper.dump_boolean(value),
+            Self::Integer(value) => dumper.dump_integer(value),
+            Self::Float(value) => dumper.dump_float(value),
+            Self::String(ref value) => dumper.dump_string(value),
+            Self::Bytes(ref value) => dumper.dump_bytes(value),
+            Self::Table(ref table) => {
+                let loglen = table.loglen::<LoadError>()
+                    .and_then(|loglen| {
+                        table.validate::<LoadError>()?;
+                        Ok(loglen)
+                    })
+                    .map_err(|error| D::Error::from(&error.to_string()))?;
+                dumper.dump_table(FaithfulDumpIter {
+                    array: table.array.iter(),
+                    assoc: table.assoc.iter(),
+                    #[allow(clippy::cast_possible_truncation)]
+                    array_len: table.array.len() as u32,
+                    loglen,
+                    last_free: table.last_free,
+                })
+            },
+        }
+    }
+}
+
+struct FaithfulDumpIter<'s> {
+    array: std::slice::Iter<'s, Option<FaithfulValue>>,
+    assoc: std::slice::Iter<'s, Option<AssocItem<Key, FaithfulValue>>>,
+    array_len: u32,
+    loglen: Option<LogSize>,
+    last_free: u32,
+}
+
+impl<'s> Iterator for FaithfulDumpIter<'s> {
+    type Item = Option<TableItem<Key, &'s FaithfulValue>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(value) = self.array.next() {
+            return Some(value.as_ref().map(TableItem::Array));
+        }
+        let slot = self.assoc.next()?;
+        Some(slot.as_ref().map(|item|
+            TableItem::Assoc(item.as_value_ref()) ))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.array.len() + self.assoc.len();
+        (len, Some(len))
+    }
+}
+
+impl<'s> ExactSizeIterator for FaithfulDumpIter<'s> {}
+
+impl<'s> TableSize for FaithfulDumpIter<'s> {
+    fn array_len(&self) -> u32 { self.array_len }
+    fn assoc_loglen(&self) -> Option<LogSize> { self.loglen }
+    fn assoc_last_free(&self) -> u32 { self.last_free }
+}
+
+impl<'s> TableDumpIter<'s> for FaithfulDumpIter<'s> {
+    type Key = Key;
+    type Value = FaithfulValue;
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    Exchange,
+    error::LoadError,
+    table_iter::AssocItem,
+    dumper::dump_blueprint,
+    loader::load_blueprint,
+    value::{Key, Value, DeadKeyTable},
+    blueprint::Behavior,
+};
+
+use super::{FaithfulValue, FaithfulTable};
+
+#[test]
+fn test_faithful_round_trip() {
+    let exchange = crate::test::EXCHANGE_BEHAVIOR_2;
+    let Exchange::Behavior(Some(faithful)) = load_blueprint::<
+        FaithfulValue, FaithfulValue, LoadError >(exchange).unwrap()
+        else { panic!("should be a behavior") };
+    let Exchange::Behavior(Some(value)) = load_blueprint::<
+        Value, Value, LoadError >(exchange).unwrap()
+        else { panic!("should be a behavior") };
+    assert_eq!(faithful.to_value().unwrap(), value);
+    // the exchange has dead keys, which are not in the value
+    assert_ne!(FaithfulValue::from_dump(&value).unwrap(), faithful);
+    assert_eq!(FaithfulValue::from_dump(&faithful).unwrap(), faithful);
+    #[cfg(feature = "serde")] {
+        let json = serde_json::to_string(&faithful).unwrap();
+        let faithful_again: FaithfulValue =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(faithful_again, faithful);
+    }
+    let dumped = dump_blueprint::<FaithfulValue, FaithfulValue>(
+        Exchange::Behavior(Some(faithful.clone())) ).unwrap();
+    assert_eq!( load_blueprint::<FaithfulValue, FaithfulValue, LoadError>(
+        &dumped ).unwrap(), Exchange::Behavior(Some(faithful.clone())) );
+    let behavior = Behavior::from_faithful(&faithful).unwrap();
+    assert_eq!( Value::try_from(behavior.clone()).unwrap(),
+        Value::try_from(Behavior::try_from(value).unwrap()).unwrap() );
+    assert_eq!( Behavior::from_faithful(&behavior.to_faithful().unwrap())
+        .unwrap().instructions.len(), behavior.instructions.len() );
+}
+
+#[test]
+fn test_faithful_dead_keys() {
+    let dead = DeadKeyTable::new(vec![Some(0), None], 1).unwrap();
+    let FaithfulValue::Table(table) = FaithfulValue::from_dump(&dead)
+        .unwrap() else { panic!("should be a table") };
+    assert_eq!( table.assoc,
+        [Some(AssocItem::Dead { link: 0 }), None] );
+    assert_eq!(table.last_free, 1);
+    FaithfulTable::new(Vec::new(), vec![None; 3], 0).unwrap_err();
+    FaithfulTable::new(Vec::new(), vec![None; 2], 3).unwrap_err();
+    let table = FaithfulTable::new(vec![None], vec![
+        Some(AssocItem::Live {
+            key: Key::from("x"),
+            value: Some(FaithfulValue::Integer(1)),
+            link: 0,
+        }),
+    ], 0).unwrap();
+    let value = FaithfulValue::Table(table).to_value().unwrap();
+    assert_eq!( value,
+        Value::Table([(Key::from("x"), Value::Integer(1))].into_iter()
+            .collect()) );
+}
+
+}
@@ -0,0 +1,114 @@
+//! Placement of keys in the assoc part of a table,
+//! computed the same way as in the Lua implementation used by the game.
+//!
+//! The assoc part of size `2^loglen` is an array of slots.
+//! A key is stored in its main position ([`key_position`]) if that slot
+//! is free; otherwise it goes to a free slot, found by scanning down
+//! from the last free index, and is chained to the other keys
+//! with the same main position.
+//! The link of a slot is the offset of the next slot in its chain
+//! ([`link`]), or zero at the end of the chain.
+//! Building an assoc part with the same key order and the same
+//! placement rules gives a byte-compatible encoding.
+
+use crate::common::{LogSize, iexp2};
+
+use super::super::Key;
+
+#[inline]
+pub(super) const fn mask(loglen: LogSize) -> u32 {
+    iexp2(Some(loglen)) - 1
+}
+
+// https://www.lua.org/source/5.4/lstring.c.html#luaS_hash
+const fn str_table_hash_with_seed<const SEED: u32>(value: &str) -> u32 {
+    let value = value.as_bytes();
+    let mut index = value.len();
+    assert!(u32::BITS <= usize::BITS && index <= u32::MAX as usize);
+    let mut hash = SEED ^ (index as u32);
+    let step = (index >> 5) + 1;
+    while index >= step {
+        let j = match index.checked_sub(1) {
+            Some(j) if j < value.len() => j,
+            // SAFETY: i dare you
+            _ => unsafe { std::hint::unreachable_unchecked() },
+        };
+        hash ^= u32::wrapping_add(
+            u32::wrapping_add(hash << 5, hash >> 2),
+            value[j] as u32 );
+        index -= step;
+    }
+    hash
+}
+
+/// Hash of a string key, with the seed used by the game.
+#[must_use]
+pub const fn str_table_hash(value: &str) -> u32 {
+    str_table_hash_with_seed::<0x_645D_BFCD>(value)
+}
+
+/// Main position of an integer key in the assoc part of size `2^loglen`.
+// https://www.lua.org/source/5.4/ltable.c.html#hashint
+#[must_use]
+pub const fn int_table_hash(value: i32, loglen: LogSize) -> u32 {
+    if loglen == 0 { return 0; }
+    if value >= 0 {
+        (value % (mask(loglen) as i32)) as u32
+    } else {
+        (value as u32) % mask(loglen)
+    }
+}
+
+/// Main position of the key in the assoc part of size `2^loglen`.
+#[must_use]
+pub fn key_position(key: &Key, loglen: LogSize) -> u32 {
+    match *key {
+        Key::Index(index) => int_table_hash(index, loglen),
+        Key::Name(ref value) => str_table_hash(value) & mask(loglen),
+        // https://www.lua.org/source/5.4/ltable.c.html#hashboolean
+        Key::Bool(value) => u32::from(value) & mask(loglen),
+    }
+}
+
+/// Link from the slot at `from` to the next slot of the chain at `to`.
+#[must_use]
+pub const fn link(from: u32, to: u32) -> i32 {
+    to as i32 - from as i32
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::{
+    table_iter::AssocItem,
+    value::Key,
+};
+
+use super::{super::assoc::Table as AssocTable, key_position, link};
+
+#[test]
+fn test_chains() {
+    let keys: Vec<Key> = (0 .. 6).map(|index| Key::Index(index * 5 - 7))
+        .chain(["a", "b", "c", "name", "frame", "links"].map(Key::from))
+        .collect();
+    let table = AssocTable::from_map_iter(
+        keys.iter().cloned().map(|key| (key, &())).collect::<Vec<_>>() );
+    let loglen = table.loglen().unwrap();
+    let slots: Vec<_> = table.dump_iter().map(|item| match item {
+        Some(AssocItem::Live { key, link, .. }) => (Some(key), link),
+        Some(AssocItem::Dead { link }) => (None, link),
+        None => (None, 0),
+    }).collect();
+    // every key is reachable from its main position
+    for key in &keys {
+        let mut index = key_position(key, loglen);
+        while slots[index as usize].0.as_ref() != Some(key) {
+            let next = slots[index as usize].1;
+            assert_ne!(next, 0, "{key:?} is not in its chain");
+            index = index.checked_add_signed(next).unwrap();
+        }
+    }
+    assert_eq!(link(3, 1), -2);
+}
+
+}
@@ -0,0 +1,161 @@
+//! Conversion of tables into plain structs, field by field.
+//!
+//! Structs with named fields get [`TryFromTable`]
+//! from `#[derive(FromTable)]` (behind the `derive` feature):
+//! every field is taken from the string key of the same name,
+//! unless renamed with `#[table(key = "...")]`.
+//! A missing key is an error, except for `Option` fields.
+//! Keys not matching any field are an error as well,
+//! unless a field marked `#[table(extra)]` collects them
+//! (its type should implement `FromIterator<(Key, Value)>`,
+//! e.g. [`Table`]).
+//!
+//! Only string keys are matched, so the derive is meant
+//! for user-defined types: tables with integer keys
+//! or an array part are out of its reach.
+//! The blueprint structures ([`crate::blueprint`]) are not
+//! converted this way either; they need such keys,
+//! and the crate is built without the `derive` feature.
+
+use crate::{
+    Str,
+    error::LoadError,
+};
+
+use super::{Value, Table};
+
+/// A type that can be made out of a table.
+pub trait TryFromTable: Sized {
+    fn try_from_table(table: Table) -> Result<Self, LoadError>;
+}
+
+/// A type that can be made out of a table item.
+pub trait TableField: Sized {
+    fn from_value(value: Value) -> Result<Self, LoadError>;
+    /// The field value in case the key is missing,
+    /// or `None` if the key is required.
+    #[must_use]
+    fn missing() -> Option<Self> { None }
+}
+
+impl Table {
+    /// Convert the table into the struct.
+    pub fn into_struct<T: TryFromTable>(self) -> Result<T, LoadError> {
+        T::try_from_table(self)
+    }
+}
+
+impl<T: TryFromTable> TableField for T {
+    fn from_value(value: Value) -> Result<Self, LoadError> {
+        match value {
+            Value::Table(table) => T::try_from_table(table),
+            _ => Err(error_expected("a table")),
+        }
+    }
+}
+
+impl<T: TableField> TableField for Option<T> {
+    fn from_value(value: Value) -> Result<Self, LoadError> {
+        T::from_value(value).map(Some)
+    }
+    fn missing() -> Option<Self> { Some(None) }
+}
+
+impl TableField for Value {
+    fn from_value(value: Value) -> Result<Self, LoadError> {
+        Ok(value)
+    }
+}
+
+impl TryFromTable for Table {
+    fn try_from_table(table: Table) -> Result<Self, LoadError> {
+        Ok(table)
+    }
+}
+
+macro_rules! impl_table_field {
+    ($type:ty, $variant:ident, $expected:literal) => {
+        impl TableField for $type {
+            fn from_value(value: Value) -> Result<Self, LoadError> {
+                match value {
+                    Value::$variant(value) => Ok(value.into()),
+                    _ => Err(error_expected($expected)),
+                }
+            }
+        }
+    };
+}
+
+impl_table_field!(bool, Boolean, "a boolean");
+impl_table_field!(i32, Integer, "an integer");
+impl_table_field!(f64, Float, "a float");
+impl_table_field!(Str, String, "a string");
+
+impl TableField for String {
+    fn from_value(value: Value) -> Result<Self, LoadError> {
+        Str::from_value(value).map(|value| Self::from(&*value))
+    }
+}
+
+fn error_expected(expected: &str) -> LoadError {
+    LoadError::from(format!("the value should be {expected}"))
+}
+
+/// Prefix the error with the key of the field.
+#[doc(hidden)]
+#[must_use]
+pub fn error_in_field(key: &str, error: &LoadError) -> LoadError {
+    LoadError::from(format!("field {key:?}: {error}"))
+        .with_kind(error.kind())
+}
+
+#[cfg(test)]
+mod test {
+
+use crate::value::{Key, Value, Table};
+
+use super::{TryFromTable, TableField};
+
+#[derive(Debug, PartialEq)]
+struct Point { x: i32, y: Option<i32> }
+
+impl TryFromTable for Point {
+    fn try_from_table(table: Table) -> Result<Self, crate::error::LoadError> {
+        let mut x = None;
+        let mut y = None;
+        for (key, value) in table {
+            match key.as_name() {
+                Some("x") => x = Some(i32::from_value(value)?),
+                Some("y") => y = Some(TableField::from_value(value)?),
+                _ => return Err("unexpected key".into()),
+            }
+        }
+        Ok(Self {
+            x: x.or_else(i32::missing).ok_or("missing x")?,
+            y: y.or_else(Option::missing).ok_or("missing y")?,
+        })
+    }
+}
+
+#[test]
+fn test_into_struct() {
+    let table = Table::from_iter([("x", Value::Integer(1))]);
+    assert_eq!( table.into_struct::<Point>().unwrap(),
+        Point { x: 1, y: None } );
+    let table = Table::from_iter([
+        (Key::from("x"), Value::Integer(1)),
+        (Key::from("y"), Value::Integer(2)) ]);
+    assert_eq!( table.into_struct::<Point>().unwrap(),
+        Point { x: 1, y: Some(2) } );
+    Table::from_iter([("y", Value::Integer(2))])
+        .into_struct::<Point>().unwrap_err();
+    Table::from_iter([("x", Value::Boolean(true))])
+        .into_struct::<Point>().unwrap_err();
+    let nested = Table::from_iter([("p", Value::Table(
+        Table::from_iter([("x", Value::Integer(3))]) ))]);
+    assert_eq!( <Option<Point>>::from_value(
+        nested.get(&Key::from("p")).unwrap().clone() ).unwrap(),
+        Some(Point { x: 3, y: None }) );
+}
+
+}
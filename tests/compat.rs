@@ -0,0 +1,78 @@
+//! Compatibility of the output with stored exchange strings.
+//!
+//! `tests/golden/r<N>/` holds the corpus strings from `tests/corpus/`
+//! as dumped (without compression) under format revision `N`.
+//! If the output changes, this fails until
+//! `exchange::FORMAT_REVISION` is incremented and a directory
+//! for the new revision is added;
+//! the directories of earlier revisions stay, and must keep loading.
+
+use std::path::{Path, PathBuf};
+
+use desynced_exchange::{
+    blueprint::{load_blueprint, dump_blueprint},
+    dumper::{DumpOptions, Compression},
+    exchange::{FORMAT_REVISION, is_supported, recompress},
+};
+
+fn corpus() -> Vec<(String, String)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut corpus: Vec<_> = std::fs::read_dir(dir).unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap()
+                .to_string_lossy().into_owned();
+            let exchange = std::fs::read_to_string(&path).unwrap();
+            (name, exchange.trim().to_owned())
+        })
+        .collect();
+    corpus.sort();
+    corpus
+}
+
+fn golden_dir(revision: u32) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join(format!("tests/golden/r{revision}"))
+}
+
+fn read_golden(revision: u32, name: &str) -> String {
+    let path = golden_dir(revision).join(name);
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("{}: {error}", path.display()))
+        .trim().to_owned()
+}
+
+fn dump_uncompressed(exchange: &str) -> String {
+    let dumped = dump_blueprint(load_blueprint(exchange).unwrap()).unwrap();
+    let mut options = DumpOptions::default();
+    options.compression = Compression::Never;
+    recompress(&dumped, &options).unwrap()
+}
+
+#[test]
+fn test_golden_current() {
+    for (name, exchange) in corpus() {
+        assert!(is_supported(&exchange), "{name}");
+        assert_eq!( dump_uncompressed(&exchange),
+            read_golden(FORMAT_REVISION, &name),
+            "{name}: output differs from format revision \
+             {FORMAT_REVISION}" );
+    }
+}
+
+#[test]
+fn test_golden_previous() {
+    for revision in 1 ..= FORMAT_REVISION {
+        if !golden_dir(revision).exists() {
+            continue;
+        }
+        for (name, exchange) in corpus() {
+            let golden = read_golden(revision, &name);
+            assert!(is_supported(&golden), "r{revision}/{name}");
+            assert_eq!(
+                format!("{:?}", load_blueprint(&golden).unwrap()),
+                format!("{:?}", load_blueprint(&exchange).unwrap()),
+                "r{revision}/{name}" );
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! `#[derive(FromTable)]`, as used from outside the crate.
+
+#![cfg(feature = "derive")]
+
+use desynced_exchange::{
+    Str,
+    error::LoadError,
+    value::{Key, Value, Table, FromTable},
+};
+
+#[derive(Debug, PartialEq, FromTable)]
+struct Parameter {
+    name: Str,
+    #[table(key = "cmt")]
+    comment: Option<String>,
+}
+
+#[derive(Debug, PartialEq, FromTable)]
+struct Behavior {
+    name: Option<Str>,
+    parameter: Parameter,
+    count: i32,
+    #[table(extra)]
+    rest: Table,
+}
+
+fn table<const N: usize>(items: [(&'static str, Value); N]) -> Table {
+    Table::from_iter(items)
+}
+
+#[test]
+fn test_derive_from_table() {
+    let behavior: Behavior = table([
+        ("parameter", Value::Table(table([
+            ("name", Value::String(Str::from("p"))),
+            ("cmt", Value::String(Str::from("c"))),
+        ]))),
+        ("count", Value::Integer(2)),
+        ("other", Value::Boolean(true)),
+    ]).into_struct().unwrap();
+    assert_eq!(behavior, Behavior {
+        name: None,
+        parameter: Parameter {
+            name: Str::from("p"), comment: Some(String::from("c")) },
+        count: 2,
+        rest: table([("other", Value::Boolean(true))]),
+    });
+    assert_eq!( behavior.rest.get(&Key::from("other")),
+        Some(&Value::Boolean(true)) );
+
+    let error: LoadError = table([("count", Value::Integer(2))])
+        .into_struct::<Behavior>().unwrap_err();
+    assert!(error.to_string().contains("parameter"), "{error}");
+    let error = table([
+        ("name", Value::String(Str::from("p"))),
+        ("unknown", Value::Integer(1)),
+    ]).into_struct::<Parameter>().unwrap_err();
+    assert!(error.to_string().contains("unknown"), "{error}");
+    let error = table([("name", Value::Integer(1))])
+        .into_struct::<Parameter>().unwrap_err();
+    assert!(error.to_string().contains("\"name\""), "{error}");
+}
@@ -0,0 +1,190 @@
+//! A compile-time snapshot of the stable public API.
+//!
+//! Every item below is named by its public path, and functions
+//! are coerced to their exact signatures, so that renaming,
+//! moving or changing any of them fails to compile.
+//! A change to this file is a breaking change of the crate.
+
+#![allow(unused_imports)]
+// signatures are spelled out in full on purpose
+#![allow(clippy::type_complexity)]
+
+use desynced_exchange::{
+    Exchange, Str, Interner, Progress,
+    error::{LoadError, LoadErrorKind, DumpError},
+    load::{Load, DynLoad, KeyLoad, Builder, KeyBuilder, Loader, TableLoader},
+    dump::{Dump, DynDump, KeyDump, TableDumpIter, Dumper, KeyDumper},
+    loader::{
+        DecodeOptions, DuplicateKeys, InvalidUtf8, KeyMap, Limits,
+        ExchangeInfo,
+        Warning, Warnings,
+    },
+    dumper::{
+        DumpOptions, Compression, IntegerOverflow, IntegerWidth, FloatPolicy,
+    },
+    value::{
+        Key, Value, Table, TableBuilder, ArrayBuilder,
+        NormalizeOptions, TableStats, TryFromTable, TableField,
+        DedupStats, DuplicateSubtree,
+        FaithfulValue, FaithfulTable,
+    },
+    document::Document,
+    blueprint::{
+        Blueprint, Behavior, Component, Parameter, Instruction,
+        Operand, Jump, Place, Register, RegisterValue,
+        LoadOptions, UnknownKeys, EmptyTable, Logistics, LogisticsValue, Locks,
+        OpCatalog, OpInfo, ArgInfo, ArgKind, OpRef,
+        RegisterLayout, RegisterAddress,
+        MergeError, ReorderError, Patcher, ValidationError, BehaviorStats,
+        StripOptions, StripReport, LabelError,
+        lint::{Lint, LintConfig, LintRule, Severity},
+        edit::{EditOp, EditError},
+        optimize::{OptimizeOptions, OptimizeReport, OptimizeError},
+    },
+};
+
+#[test]
+fn test_entry_points() {
+    let _: fn(&str) -> Result<Exchange<Blueprint, Behavior>, LoadError> =
+        desynced_exchange::blueprint::load_blueprint;
+    let _: fn(&str, LoadOptions)
+        -> Result<Exchange<Blueprint, Behavior>, LoadError> =
+        desynced_exchange::blueprint::load_blueprint_with;
+    let _: fn(&str, LoadOptions, fn(Progress))
+        -> Result<Exchange<Blueprint, Behavior>, LoadError> =
+        desynced_exchange::blueprint::load_blueprint_with_progress;
+    let _: fn(Exchange<Blueprint, Behavior>) -> Result<String, DumpError> =
+        desynced_exchange::blueprint::dump_blueprint;
+    let _: fn(&str) -> bool = desynced_exchange::exchange::is_supported;
+    let _: fn(usize) -> bool = desynced_exchange::limits::fits_string;
+    let _: fn(usize, usize) -> bool = desynced_exchange::limits::fits_table;
+    let _: fn(&str)
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::load_blueprint::<Value, Value, LoadError>;
+    let _: fn(&str, DecodeOptions)
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::load_blueprint_with::<Value, Value>;
+    let _: fn(&str, DecodeOptions, &mut Warnings)
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::load_blueprint_with_warnings::<Value, Value>;
+    let _: fn(&str, DecodeOptions, &mut Warnings, fn(Progress))
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::load_blueprint_with_progress;
+    let _: fn(&str, DecodeOptions, &mut Warnings,
+        &mut dyn DynLoad, &mut dyn DynLoad)
+        -> Result<Exchange<()>, LoadError> =
+        desynced_exchange::loader::load_blueprint_dyn;
+    let _: fn(&str, Limits) -> Result<ExchangeInfo, LoadError> =
+        desynced_exchange::loader::verify;
+    let _: fn(std::fs::File, DecodeOptions, Limits, &mut Warnings)
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::load_blueprint_from;
+    let _: fn(&Behavior) -> BehaviorStats = Behavior::stats;
+    let _: fn(Str) -> Blueprint = Blueprint::frame_only;
+    let _: fn(&Blueprint) -> bool = Blueprint::is_frame_only;
+    let _: fn(&mut Behavior, &StripOptions) -> StripReport =
+        Behavior::strip_metadata;
+    let _: fn(&mut Behavior) -> Result<(), LabelError> =
+        Behavior::resolve_labels;
+    let _: fn(&mut Blueprint) -> Result<(), LabelError> =
+        Blueprint::resolve_labels;
+    let _: fn(&Behavior, &LintConfig) -> Vec<Lint> =
+        desynced_exchange::blueprint::lint::lint;
+    let _: fn(LintRule) -> Severity = LintRule::default_severity;
+    let _: fn(&Blueprint) -> Result<FaithfulValue, DumpError> =
+        Blueprint::to_faithful;
+    let _: fn(&FaithfulValue) -> Result<Behavior, LoadError> =
+        Behavior::from_faithful;
+    let _: fn(&Value) -> Result<FaithfulValue, DumpError> =
+        FaithfulValue::from_dump;
+    let _: fn(Vec<Option<FaithfulValue>>, Vec<Option<_>>, u32)
+        -> Result<FaithfulTable, LoadError> = FaithfulTable::new;
+    let _: fn(Value, LoadOptions) -> Result<RegisterValue, LoadError> =
+        RegisterValue::from_value_with;
+    let _: fn(&str, DumpOptions, LoadOptions) -> Result<Patcher, LoadError> =
+        Patcher::with_options;
+    let _: fn(&mut Value) -> DedupStats = Value::deduplicate_strings;
+    let _: fn(&Value) -> Vec<DuplicateSubtree> = Value::duplicate_subtrees;
+    let _: fn(Exchange<Option<Box<dyn DynDump>>, Option<Box<dyn DynDump>>>)
+        -> Result<String, DumpError> =
+        desynced_exchange::dumper::dump_blueprint;
+    let _: fn(&mut Blueprint, &std::collections::HashMap<Str, Str>) -> usize =
+        Blueprint::rename_item_ids;
+    let _: fn(Exchange<&[u8]>, DecodeOptions, &mut Warnings)
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::decode_raw::<Value, Value>;
+    let _: fn(Exchange<std::fs::File>, DecodeOptions, Limits, &mut Warnings)
+        -> Result<Exchange<Option<Value>, Option<Value>>, LoadError> =
+        desynced_exchange::loader::decode_raw_from;
+    let _: fn(Exchange<Option<Value>, Option<Value>>)
+        -> Result<String, DumpError> =
+        desynced_exchange::dumper::dump_blueprint::<Value, Value>;
+    let _: fn(Exchange<Option<Value>, Option<Value>>, &DumpOptions,
+        fn(Progress)) -> Result<String, DumpError> =
+        desynced_exchange::dumper::dump_blueprint_with_progress;
+    let _: fn(Exchange<Option<Value>, Option<Value>>, &DumpOptions,
+        std::fs::File) -> Result<(), DumpError> =
+        desynced_exchange::dumper::dump_blueprint_to;
+    let _: fn(Exchange<Option<Value>, Option<Value>>, &DumpOptions,
+        String) -> Result<(), DumpError> =
+        desynced_exchange::dumper::dump_blueprint_to_fmt;
+    let _: fn(Exchange<Option<Value>, Option<Value>>, &DumpOptions)
+        -> Result<Exchange<Vec<u8>>, DumpError> =
+        desynced_exchange::dumper::encode_raw::<Value, Value>;
+    let _: fn(Exchange<Blueprint, Behavior>) -> Result<String, DumpError> =
+        desynced_exchange::blueprint::dump_blueprint_validated;
+    let _: fn(&Blueprint) -> Result<(), Vec<ValidationError>> =
+        Blueprint::validate;
+    let _: fn(&str, &DumpOptions) -> Result<String, LoadError> =
+        desynced_exchange::exchange::recompress;
+    let _: fn(&str, &[desynced_exchange::exchange::Edit])
+        -> Result<String, desynced_exchange::exchange::PatchError> =
+        desynced_exchange::exchange::patch;
+    let _: fn(&str, usize) -> Vec<String> =
+        desynced_exchange::exchange::split;
+    let _: fn(&[&str])
+        -> Result<String, desynced_exchange::exchange::JoinError> =
+        desynced_exchange::exchange::join;
+    let _: fn(&str, Limits) -> Result<
+        desynced_exchange::exchange::InspectReport, LoadError > =
+        desynced_exchange::exchange::inspect;
+    let _: fn(Table) -> Result<Table, LoadError> = Table::into_struct;
+    let _: fn(&mut Behavior, EditOp) -> Result<EditOp, EditError> =
+        desynced_exchange::blueprint::edit::apply;
+    let _: fn(&mut Behavior, &OpCatalog, &OptimizeOptions)
+        -> Result<OptimizeReport, OptimizeError> =
+        desynced_exchange::blueprint::optimize::optimize;
+}
+
+#[test]
+fn test_defaults() {
+    let _ = DecodeOptions::default();
+    let _ = InvalidUtf8::default();
+    let _ = DumpOptions::default();
+    let _ = LintConfig::default();
+    let _ = FloatPolicy::default();
+    let _: &str = desynced_exchange::dumper::COMPRESSION_BACKEND;
+    let _: u32 = desynced_exchange::exchange::FORMAT_REVISION;
+    let _: (u8, usize, u32, usize) = (
+        desynced_exchange::limits::MAX_ASSOC_LOGLEN,
+        desynced_exchange::limits::MAX_ASSOC_LEN,
+        desynced_exchange::limits::MAX_ARRAY_LEN,
+        desynced_exchange::limits::MAX_STRING_LEN,
+    );
+    let _: std::ops::RangeInclusive<u32> =
+        desynced_exchange::limits::EXT_UINT_RANGE;
+    let _: std::ops::RangeInclusive<i32> =
+        desynced_exchange::limits::EXT_SINT_RANGE;
+    let _ = LoadOptions::default();
+    let _ = Limits::default();
+    let _ = NormalizeOptions::default();
+    let _ = StripOptions::default();
+    let _ = Interner::default();
+    let _ = Blueprint::default();
+    let exchange = desynced_exchange::blueprint::dump_blueprint(
+        Exchange::Blueprint(Blueprint::default()) ).unwrap();
+    let Exchange::Blueprint(blueprint) =
+        desynced_exchange::blueprint::load_blueprint(&exchange).unwrap()
+        else { panic!("should be a blueprint") };
+    assert!(blueprint.components.is_empty());
+}
@@ -9,6 +9,7 @@ use desynced_exchange::{
     dumper::dump_blueprint as dump,
     loader::load_blueprint as load,
     value::Value,
+    interop::{from_json, to_json},
     blueprint::{
         dump_blueprint, load_blueprint,
         Exchange,
@@ -43,6 +44,15 @@ extern "C" {
 
 }
 
+#[wasm_bindgen]
+extern "C" {
+    pub type ObjectParameters;
+
+    #[wasm_bindgen(method, getter, js_name="interRepr")]
+    fn inter_repr_s(this: &ObjectParameters) -> String;
+
+}
+
 enum DecodeFormat {
     Ron,
     Json,
@@ -118,6 +128,12 @@ impl EncodeParameters {
     }
 }
 
+impl ObjectParameters {
+    fn inter_repr(&self) -> Result<InterRepr, JsError> {
+        self.inter_repr_s().as_str().try_into()
+    }
+}
+
 #[wasm_bindgen]
 pub fn decode(encoded: &str, params: &DecodeParameters)
 -> Result<String, JsError>
@@ -132,7 +148,14 @@ pub fn decode(encoded: &str, params: &DecodeParameters)
                 .transpose().ok_or_else(|| JsError::new(
                     "Blueprint or behavior should not \
                     be represented with nil" ))?;
-            serialize::<Exchange<Value>>(value, params)
+            match params.decode_format()? {
+                // JSON object keys are strings,
+                // so integer keys need special care
+                DecodeFormat::Json =>
+                    serialize_into_json(to_json(&value)?, params),
+                DecodeFormat::Ron =>
+                    serialize::<Exchange<Value>>(value, params),
+            }
         }
     }
 }
@@ -180,10 +203,13 @@ pub fn encode(decoded: &str, params: &EncodeParameters)
         InterRepr::Struct =>
             dump_blueprint(deserialise(decoded, params)?)?,
         InterRepr::MapTree => {
-            dump(
-                deserialise::<Exchange<Value>>(decoded, params)?
-                    .map_mono(Some)
-            )?
+            let value = match params.decode_format()? {
+                DecodeFormat::Json =>
+                    from_json(&deserialise(decoded, params)?)?,
+                DecodeFormat::Ron =>
+                    deserialise::<Exchange<Value>>(decoded, params)?,
+            };
+            dump(value.map_mono(Some))?
         },
     })
 }
@@ -212,3 +238,41 @@ where V: Deserialize<'de>,
     Ok(json::from_str(decoded)?)
 }
 
+
+/// Like [`decode`], but produce a JS object instead of a string.
+/// Maps become plain objects, with the same treatment
+/// of integer keys as in JSON.
+#[wasm_bindgen]
+pub fn decode_to_object(encoded: &str, params: &ObjectParameters)
+-> Result<JsValue, JsError>
+{
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(match params.inter_repr()? {
+        InterRepr::Struct =>
+            load_blueprint(encoded)?.serialize(&serializer)?,
+        InterRepr::MapTree => {
+            let value = load::<_,_,LoadError>(encoded)?
+                .transpose().ok_or_else(|| JsError::new(
+                    "Blueprint or behavior should not \
+                    be represented with nil" ))?;
+            to_json(&value)?.serialize(&serializer)?
+        },
+    })
+}
+
+/// Like [`encode`], but take a JS object
+/// (as produced by [`decode_to_object`]) instead of a string.
+#[wasm_bindgen]
+pub fn encode_from_object(decoded: JsValue, params: &ObjectParameters)
+-> Result<String, JsError>
+{
+    Ok(match params.inter_repr()? {
+        InterRepr::Struct =>
+            dump_blueprint(serde_wasm_bindgen::from_value(decoded)?)?,
+        InterRepr::MapTree => {
+            let value = from_json(
+                &serde_wasm_bindgen::from_value(decoded)? )?;
+            dump(value.map_mono(Some))?
+        },
+    })
+}
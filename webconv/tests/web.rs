@@ -0,0 +1,99 @@
+//! Run with `wasm-pack test --node`.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use desynced_exchange_web::{
+    decode, encode, decode_to_object, encode_from_object,
+    DecodeParameters, EncodeParameters, ObjectParameters,
+};
+
+const CORPUS: &[&str] = &[
+    include_str!("../../tests/corpus/behavior_1_unit.txt"),
+    include_str!("../../tests/corpus/behavior_2.txt"),
+    include_str!("../../tests/corpus/behavior_3_param.txt"),
+    include_str!("../../tests/corpus/behavior_4_sub.txt"),
+];
+
+const FORMATS: &[&str] = &["ron", "json"];
+const STYLES: &[&str] = &["pretty", "compact"];
+const INTER_REPRS: &[&str] = &["struct", "map_tree"];
+
+#[wasm_bindgen(inline_js = "
+    export function decode_parameters(format, style, repr) {
+        return { decodeFormat: format, decodeStyle: style, interRepr: repr };
+    }
+    export function encode_parameters(format, repr) {
+        return { decodeFormat: format, interRepr: repr };
+    }
+    export function object_parameters(repr) {
+        return { interRepr: repr };
+    }
+")]
+extern "C" {
+    fn decode_parameters(format: &str, style: &str, repr: &str)
+    -> DecodeParameters;
+    fn encode_parameters(format: &str, repr: &str) -> EncodeParameters;
+    fn object_parameters(repr: &str) -> ObjectParameters;
+}
+
+fn unwrap<T>(result: Result<T, JsError>, context: &str) -> T {
+    let Ok(value) = result else { panic!("{context}") };
+    value
+}
+
+#[wasm_bindgen_test]
+fn test_round_trip() {
+    for (index, exchange) in CORPUS.iter().enumerate() {
+        for &repr in INTER_REPRS {
+            for &format in FORMATS {
+                for &style in STYLES {
+                    let context = format!(
+                        "exchange {index}, {repr}, {format}, {style}" );
+                    let decoded = unwrap( decode( exchange,
+                        &decode_parameters(format, style, repr) ),
+                        &context );
+                    let encoded = unwrap( encode( &decoded,
+                        &encode_parameters(format, repr) ),
+                        &context );
+                    let redecoded = unwrap( decode( &encoded,
+                        &decode_parameters(format, style, repr) ),
+                        &context );
+                    assert_eq!(decoded, redecoded, "{context}");
+                }
+            }
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_object_round_trip() {
+    for (index, exchange) in CORPUS.iter().enumerate() {
+        for &repr in INTER_REPRS {
+            let context = format!("exchange {index}, {repr}");
+            let object = unwrap( decode_to_object( exchange,
+                &object_parameters(repr) ), &context );
+            assert!(object.is_object(), "{context}");
+            let encoded = unwrap( encode_from_object( object,
+                &object_parameters(repr) ), &context );
+            let params = decode_parameters("json", "compact", repr);
+            assert_eq!(
+                unwrap(decode(&encoded, &params), &context),
+                unwrap(decode(exchange, &params), &context),
+                "{context}" );
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_bad_parameters() {
+    let exchange = CORPUS[0];
+    assert!(decode(exchange, &decode_parameters("xml", "pretty", "struct"))
+        .is_err());
+    assert!(decode(exchange, &decode_parameters("ron", "tiny", "struct"))
+        .is_err());
+    assert!(decode(exchange, &decode_parameters("ron", "pretty", "tree"))
+        .is_err());
+    assert!(encode("{}", &encode_parameters("ron", "struct")).is_err());
+    assert!(decode_to_object(exchange, &object_parameters("tree")).is_err());
+}